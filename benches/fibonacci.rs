@@ -0,0 +1,40 @@
+// cargo bench で評価器と VM の速さを再帰的なフィボナッチ数の計算で比べる
+use monkey_rust::engine::{Engine, Interpreter};
+use monkey_rust::lexer::Lexer;
+use monkey_rust::parser::Parser;
+use std::time::Instant;
+
+const INPUT: &str = "
+let fibonacci = fn(x) {
+    if (x == 0) {
+        0
+    } else {
+        if (x == 1) {
+            return 1;
+        } else {
+            fibonacci(x - 1) + fibonacci(x - 2);
+        }
+    }
+};
+fibonacci(25);
+";
+
+fn main() {
+    let mut l = Lexer::new(INPUT);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+    for engine in [Engine::Eval, Engine::Vm] {
+        let start = Instant::now();
+        let result = Interpreter::new(engine).run(&program);
+        let duration = start.elapsed();
+
+        println!(
+            "engine={}, result={}, duration={:?}",
+            engine,
+            result.map_or("none".to_string(), |r| r.to_string()),
+            duration
+        );
+    }
+}
@@ -1,9 +1,12 @@
+pub mod json;
+pub mod sexp;
 #[cfg(test)]
 mod test;
 mod util;
 
 use crate::ast::util::define_node_enum;
-use crate::token::Token;
+use crate::lexer::quote;
+use crate::token::{Span, Token};
 use std::fmt::Debug;
 
 pub trait Node: Debug + PartialEq + Eq {
@@ -17,6 +20,12 @@ define_node_enum!(
     ReturnStatement,
     ExpressionStatement,
     BlockStatement,
+    WhileStatement,
+    ForStatement,
+    BreakStatement,
+    ContinueStatement,
+    ImportStatement,
+    ExportStatement,
 );
 
 define_node_enum!(
@@ -27,8 +36,113 @@ define_node_enum!(
     InfixExpression,
     Boolean,
     IfExpression,
+    FunctionLiteral,
+    CallExpression,
+    StringLiteral,
+    ArrayLiteral,
+    IndexExpression,
+    HashLiteral,
+    RangeExpression,
+    AssignExpression,
+    MatchExpression,
+    MemberExpression,
 );
 
+// match の腕や let、関数の引数で値と照らし合わせる形。リテラルは同じ型の等しい値にだけ一致する
+define_node_enum!(
+    Pattern,
+    WildcardPattern,
+    Identifier,
+    IntegerLiteral,
+    StringLiteral,
+    Boolean,
+    ArrayPattern,
+    HashPattern,
+);
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::LetStatement(s) => s.token.span,
+            Statement::ReturnStatement(s) => s.token.span,
+            Statement::ExpressionStatement(s) => s.token.span,
+            Statement::BlockStatement(s) => s.token.span,
+            Statement::WhileStatement(s) => s.token.span,
+            Statement::ForStatement(s) => s.token.span,
+            Statement::BreakStatement(s) => s.token.span,
+            Statement::ContinueStatement(s) => s.token.span,
+            Statement::ImportStatement(s) => s.token.span,
+            Statement::ExportStatement(s) => s.token.span,
+        }
+    }
+}
+
+impl Expression {
+    // 中置式と範囲と代入は演算子、呼び出しは '('、添字は '['、メンバ参照は '.' の位置になる
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(e) => e.token.span,
+            Expression::IntegerLiteral(e) => e.token.span,
+            Expression::Boolean(e) => e.token.span,
+            Expression::PrefixExpression(e) => e.token.span,
+            Expression::InfixExpression(e) => e.token.span,
+            Expression::IfExpression(e) => e.token.span,
+            Expression::FunctionLiteral(e) => e.token.span,
+            Expression::CallExpression(e) => e.token.span,
+            Expression::StringLiteral(e) => e.token.span,
+            Expression::ArrayLiteral(e) => e.token.span,
+            Expression::IndexExpression(e) => e.token.span,
+            Expression::HashLiteral(e) => e.token.span,
+            Expression::RangeExpression(e) => e.token.span,
+            Expression::AssignExpression(e) => e.token.span,
+            Expression::MatchExpression(e) => e.token.span,
+            Expression::MemberExpression(e) => e.token.span,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::WildcardPattern(p) => p.token.span,
+            Pattern::Identifier(p) => p.token.span,
+            Pattern::IntegerLiteral(p) => p.token.span,
+            Pattern::StringLiteral(p) => p.token.span,
+            Pattern::Boolean(p) => p.token.span,
+            Pattern::ArrayPattern(p) => p.token.span,
+            Pattern::HashPattern(p) => p.token.span,
+        }
+    }
+
+    // ただの名前なら、その名前
+    pub fn as_identifier(&self) -> Option<&Identifier> {
+        match self {
+            Pattern::Identifier(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    // どんな値にも一致する
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::WildcardPattern(_) | Pattern::Identifier(_))
+    }
+
+    // 束縛する名前。左から順に並べる
+    pub fn bindings(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Identifier(p) => vec![p],
+            Pattern::ArrayPattern(p) => p
+                .elements
+                .iter()
+                .flat_map(Pattern::bindings)
+                .chain(p.rest.as_ref().and_then(|r| r.name.as_ref()))
+                .collect(),
+            Pattern::HashPattern(p) => p.pairs.iter().flat_map(|(_, v)| v.bindings()).collect(),
+            _ => vec![],
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Program {
     pub statements: Vec<Statement>,
@@ -37,25 +151,43 @@ pub struct Program {
 impl Node for Program {
     fn token_literal(&self) -> String {
         self.statements
-            .iter()
-            .nth(0)
+            .first()
             .map(|s| s.token_literal())
-            .unwrap_or(String::new())
+            .unwrap_or_default()
     }
 
     fn to_string(&self) -> String {
-        self.statements
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-            .join("")
+        statements_to_string(&self.statements)
+    }
+}
+
+// 式文の後ろに文が続く場合は、続けて読まれないようにセミコロンで区切る
+fn statements_to_string(statements: &[Statement]) -> String {
+    let mut s = String::new();
+    for (i, stmt) in statements.iter().enumerate() {
+        s.push_str(&stmt.to_string());
+        if i + 1 < statements.len() && matches!(stmt, Statement::ExpressionStatement(_)) {
+            s.push(';');
+        }
+    }
+    s
+}
+
+// if と while の条件。前置・中置式は自身で括弧を出力する
+fn condition_to_string(condition: Option<&Expression>) -> String {
+    match condition {
+        Some(c @ (Expression::PrefixExpression(_) | Expression::InfixExpression(_))) => {
+            c.to_string()
+        }
+        Some(c) => format!("({})", c.to_string()),
+        None => "()".to_string(),
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct LetStatement {
     pub token: Token,
-    pub name: Box<Identifier>,
+    pub pattern: Box<Pattern>,
     pub value: Option<Expression>,
 }
 
@@ -68,17 +200,21 @@ impl Node for LetStatement {
         format!(
             "{} {} = {};",
             self.token_literal(),
-            self.name.value,
+            self.pattern.to_string(),
             self.value.as_ref().map_or(String::new(), |v| v.to_string())
         )
     }
 }
 
 impl LetStatement {
-    pub fn new(token: Token, name: Identifier, value: Option<Expression>) -> LetStatement {
+    pub fn new(
+        token: Token,
+        pattern: impl Into<Pattern>,
+        value: Option<Expression>,
+    ) -> LetStatement {
         LetStatement {
             token,
-            name: Box::new(name),
+            pattern: Box::new(pattern.into()),
             value,
         }
     }
@@ -151,11 +287,10 @@ impl Node for BlockStatement {
     }
 
     fn to_string(&self) -> String {
-        self.statements
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-            .join("")
+        if self.statements.is_empty() {
+            return "{ }".to_string();
+        }
+        format!("{{ {} }}", statements_to_string(&self.statements))
     }
 }
 
@@ -165,6 +300,188 @@ impl BlockStatement {
     }
 }
 
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct WhileStatement {
+    pub token: Token,
+    pub condition: Option<Expression>,
+    pub body: Box<BlockStatement>,
+}
+
+impl Node for WhileStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "while {} {}",
+            condition_to_string(self.condition.as_ref()),
+            self.body.to_string()
+        )
+    }
+}
+
+impl WhileStatement {
+    pub fn new(
+        token: Token,
+        condition: Option<Expression>,
+        body: BlockStatement,
+    ) -> WhileStatement {
+        WhileStatement {
+            token,
+            condition,
+            body: Box::new(body),
+        }
+    }
+}
+
+// 変数が二つなら添字 (ハッシュはキー) と要素を束縛する
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ForStatement {
+    pub token: Token,
+    pub variables: Vec<Identifier>,
+    pub iterable: Option<Expression>,
+    pub body: Box<BlockStatement>,
+}
+
+impl Node for ForStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "for ({} in {}) {}",
+            self.variables
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.iterable
+                .as_ref()
+                .map_or(String::new(), |i| i.to_string()),
+            self.body.to_string()
+        )
+    }
+}
+
+impl ForStatement {
+    pub fn new(
+        token: Token,
+        variables: Vec<Identifier>,
+        iterable: Option<Expression>,
+        body: BlockStatement,
+    ) -> ForStatement {
+        ForStatement {
+            token,
+            variables,
+            iterable,
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct BreakStatement {
+    pub token: Token,
+}
+
+impl Node for BreakStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!("{};", self.token_literal())
+    }
+}
+
+impl BreakStatement {
+    pub fn new(token: Token) -> BreakStatement {
+        BreakStatement { token }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ContinueStatement {
+    pub token: Token,
+}
+
+impl Node for ContinueStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!("{};", self.token_literal())
+    }
+}
+
+impl ContinueStatement {
+    pub fn new(token: Token) -> ContinueStatement {
+        ContinueStatement { token }
+    }
+}
+
+// import "path" as alias; パスは読み込むファイルからの相対パスか、検索パスからの相対パス
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub path: String,
+    pub alias: Identifier,
+}
+
+impl Node for ImportStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "{} {} as {};",
+            self.token_literal(),
+            quote(&self.path),
+            self.alias.to_string()
+        )
+    }
+}
+
+impl ImportStatement {
+    pub fn new(token: Token, path: impl ToString, alias: Identifier) -> ImportStatement {
+        ImportStatement {
+            token,
+            path: path.to_string(),
+            alias,
+        }
+    }
+}
+
+// export let ...; 束縛した名前をモジュールの外から参照できるようにする
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ExportStatement {
+    pub token: Token,
+    pub statement: Box<LetStatement>,
+}
+
+impl Node for ExportStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!("{} {}", self.token_literal(), self.statement.to_string())
+    }
+}
+
+impl ExportStatement {
+    pub fn new(token: Token, statement: LetStatement) -> ExportStatement {
+        ExportStatement {
+            token,
+            statement: Box::new(statement),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Identifier {
     pub token: Token,
@@ -322,16 +639,13 @@ impl Node for IfExpression {
     }
 
     fn to_string(&self) -> String {
-        let mut s = format!(
-            "if {} {}",
-            self.condition
-                .as_ref()
-                .map_or(String::new(), |c| c.to_string()),
-            self.consequence.to_string()
-        );
+        let condition = condition_to_string(self.condition.as_deref());
+        let mut s = format!("if {} {}", condition, self.consequence.to_string());
 
-        if let Some(alt) = &self.alternative {
-            s.push_str(&format!("else {}", alt.to_string()));
+        match (self.else_if(), &self.alternative) {
+            (Some(next), _) => s.push_str(&format!(" else {}", next.to_string())),
+            (None, Some(alt)) => s.push_str(&format!(" else {}", alt.to_string())),
+            (None, None) => {}
         }
 
         s
@@ -352,4 +666,516 @@ impl IfExpression {
             alternative: alternative.map(Box::new),
         }
     }
+
+    // else if は、続く if だけを入れたブロックを else の側に持つ
+    pub fn into_else_block(self) -> BlockStatement {
+        let token = self.token.clone();
+        let stmt = ExpressionStatement::new(token.clone(), Some(self.into()));
+        BlockStatement::new(token, vec![stmt.into()])
+    }
+
+    // else の側が if だけのブロックなら、その if を返す
+    pub fn else_if(&self) -> Option<&IfExpression> {
+        match self.alternative.as_deref()?.statements.as_slice() {
+            [Statement::ExpressionStatement(ExpressionStatement {
+                expression: Some(Expression::IfExpression(next)),
+                ..
+            })] => Some(next),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub parameters: Vec<Pattern>,
+    pub body: Box<BlockStatement>,
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "{}({}) {}",
+            self.token_literal(),
+            self.parameters
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.body.to_string()
+        )
+    }
+}
+
+impl FunctionLiteral {
+    pub fn new(token: Token, parameters: Vec<Pattern>, body: BlockStatement) -> FunctionLiteral {
+        FunctionLiteral {
+            token,
+            parameters,
+            body: Box::new(body),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct CallExpression {
+    pub token: Token,
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "{}({})",
+            self.function.to_string(),
+            self.arguments
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl CallExpression {
+    pub fn new(token: Token, function: Expression, arguments: Vec<Expression>) -> CallExpression {
+        CallExpression {
+            token,
+            function: Box::new(function),
+            arguments,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        quote(&self.value)
+    }
+}
+
+impl StringLiteral {
+    pub fn new(token: Token, value: impl ToString) -> StringLiteral {
+        StringLiteral {
+            token,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ArrayLiteral {
+    pub token: Token,
+    pub elements: Vec<Expression>,
+}
+
+impl Node for ArrayLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "[{}]",
+            self.elements
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl ArrayLiteral {
+    pub fn new(token: Token, elements: Vec<Expression>) -> ArrayLiteral {
+        ArrayLiteral { token, elements }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!("({}[{}])", self.left.to_string(), self.index.to_string())
+    }
+}
+
+impl IndexExpression {
+    pub fn new(token: Token, left: Expression, index: Expression) -> IndexExpression {
+        IndexExpression {
+            token,
+            left: Box::new(left),
+            index: Box::new(index),
+        }
+    }
+}
+
+// object.property
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MemberExpression {
+    pub token: Token,
+    pub object: Box<Expression>,
+    pub property: Identifier,
+}
+
+impl Node for MemberExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "({}.{})",
+            self.object.to_string(),
+            self.property.to_string()
+        )
+    }
+}
+
+impl MemberExpression {
+    pub fn new(token: Token, object: Expression, property: Identifier) -> MemberExpression {
+        MemberExpression {
+            token,
+            object: Box::new(object),
+            property,
+        }
+    }
+}
+
+// キーと値の組はソースに書かれた順に持つ
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct HashLiteral {
+    pub token: Token,
+    pub pairs: Vec<(Expression, Expression)>,
+}
+
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "{{{}}}",
+            self.pairs
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key.to_string(), value.to_string()))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl HashLiteral {
+    pub fn new(token: Token, pairs: Vec<(Expression, Expression)>) -> HashLiteral {
+        HashLiteral { token, pairs }
+    }
+}
+
+// start..end は end を含まず、start..=end は含む
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct RangeExpression {
+    pub token: Token,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+    pub inclusive: bool,
+}
+
+impl Node for RangeExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "({}{}{})",
+            self.start.as_ref().map_or(String::new(), |s| s.to_string()),
+            if self.inclusive { "..=" } else { ".." },
+            self.end.as_ref().map_or(String::new(), |e| e.to_string())
+        )
+    }
+}
+
+impl RangeExpression {
+    pub fn new(
+        token: Token,
+        start: Option<Expression>,
+        end: Option<Expression>,
+        inclusive: bool,
+    ) -> RangeExpression {
+        RangeExpression {
+            token,
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+            inclusive,
+        }
+    }
+}
+
+// x = v と x += v。代入先は名前か、名前から始まる添字式に限る
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct AssignExpression {
+    pub token: Token,
+    pub target: Box<Expression>,
+    pub operator: String,
+    pub value: Option<Box<Expression>>,
+}
+
+impl Node for AssignExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        format!(
+            "({} {} {})",
+            self.target.to_string(),
+            self.operator,
+            self.value.as_ref().map_or(String::new(), |v| v.to_string())
+        )
+    }
+}
+
+impl AssignExpression {
+    pub fn new(
+        token: Token,
+        target: Expression,
+        operator: impl ToString,
+        value: Option<Expression>,
+    ) -> AssignExpression {
+        AssignExpression {
+            token,
+            target: Box::new(target),
+            operator: operator.to_string(),
+            value: value.map(Box::new),
+        }
+    }
+
+    // 複合代入なら、代入の前に行う演算子
+    pub fn arithmetic_operator(&self) -> Option<&str> {
+        self.operator.strip_suffix('=').filter(|op| !op.is_empty())
+    }
+}
+
+// 代入できる式。添字式は左側をたどって名前に行き着くものだけ
+pub fn is_assignable(exp: &Expression) -> bool {
+    match exp {
+        Expression::Identifier(_) => true,
+        Expression::IndexExpression(e) => is_assignable(&e.left),
+        _ => false,
+    }
+}
+
+// match (value) { pattern if guard => body, ... }
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MatchExpression {
+    pub token: Token,
+    pub subject: Option<Box<Expression>>,
+    pub arms: Vec<MatchArm>,
+}
+
+impl Node for MatchExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        let subject = condition_to_string(self.subject.as_deref());
+        if self.arms.is_empty() {
+            return format!("match {} {{ }}", subject);
+        }
+        let arms: Vec<String> = self.arms.iter().map(|arm| arm.to_string()).collect();
+        format!("match {} {{ {} }}", subject, arms.join(", "))
+    }
+}
+
+impl MatchExpression {
+    pub fn new(token: Token, subject: Option<Expression>, arms: Vec<MatchArm>) -> MatchExpression {
+        MatchExpression {
+            token,
+            subject: subject.map(Box::new),
+            arms,
+        }
+    }
+}
+
+// トークンは '=>' の位置になる
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MatchArm {
+    pub token: Token,
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Option<Expression>,
+}
+
+impl Node for MatchArm {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        let mut s = self.pattern.to_string();
+        if let Some(guard) = &self.guard {
+            s.push_str(&format!(" if {}", guard.to_string()));
+        }
+        s.push_str(" => ");
+        if let Some(body) = &self.body {
+            s.push_str(&body.to_string());
+        }
+        s
+    }
+}
+
+impl MatchArm {
+    pub fn new(
+        token: Token,
+        pattern: Pattern,
+        guard: Option<Expression>,
+        body: Option<Expression>,
+    ) -> MatchArm {
+        MatchArm {
+            token,
+            pattern,
+            guard,
+            body,
+        }
+    }
+}
+
+// _ はどんな値にも一致し、何も束縛しない
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct WildcardPattern {
+    pub token: Token,
+}
+
+impl Node for WildcardPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        "_".to_string()
+    }
+}
+
+impl WildcardPattern {
+    pub fn new(token: Token) -> WildcardPattern {
+        WildcardPattern { token }
+    }
+}
+
+// [a, b, ..rest]。残りがなければ長さがちょうど等しい配列に一致する
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ArrayPattern {
+    pub token: Token,
+    pub elements: Vec<Pattern>,
+    pub rest: Option<RestPattern>,
+}
+
+impl Node for ArrayPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        let items: Vec<String> = self
+            .elements
+            .iter()
+            .map(|p| p.to_string())
+            .chain(self.rest.as_ref().map(|r| r.to_string()))
+            .collect();
+        format!("[{}]", items.join(", "))
+    }
+}
+
+impl ArrayPattern {
+    pub fn new(token: Token, elements: Vec<Pattern>, rest: Option<RestPattern>) -> ArrayPattern {
+        ArrayPattern {
+            token,
+            elements,
+            rest,
+        }
+    }
+}
+
+// 配列の残りの要素。名前があれば残りを配列として束縛する
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct RestPattern {
+    pub token: Token,
+    pub name: Option<Identifier>,
+}
+
+impl Node for RestPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        match &self.name {
+            Some(name) => format!("..{}", name.value),
+            None => "..".to_string(),
+        }
+    }
+}
+
+impl RestPattern {
+    pub fn new(token: Token, name: Option<Identifier>) -> RestPattern {
+        RestPattern { token, name }
+    }
+}
+
+// {"k": v}。挙げたキーをすべて持つハッシュに一致する。キーはリテラルに限る
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct HashPattern {
+    pub token: Token,
+    pub pairs: Vec<(Expression, Pattern)>,
+}
+
+impl Node for HashPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn to_string(&self) -> String {
+        let pairs: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.to_string(), value.to_string()))
+            .collect();
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
+
+impl HashPattern {
+    pub fn new(token: Token, pairs: Vec<(Expression, Pattern)>) -> HashPattern {
+        HashPattern { token, pairs }
+    }
 }
@@ -0,0 +1,975 @@
+use crate::ast::*;
+use crate::json::Json;
+use crate::lexer::{quote, Lexer};
+use crate::token::{Span, TokenKind};
+
+// 各ノードは {"type": ..., "span": ..., フィールド...} の形で表す
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+fn node(kind: &str, token: &Token, fields: Vec<(&str, Json)>) -> Json {
+    let mut all = vec![("type", kind.into()), ("span", span_to_json(token.span))];
+    all.extend(fields);
+    Json::object(all)
+}
+
+fn span_to_json(span: Span) -> Json {
+    Json::object(vec![("start", span.start.into()), ("end", span.end.into())])
+}
+
+impl ToJson for Program {
+    fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("type", "Program".into()),
+            (
+                "statements",
+                Json::Array(self.statements.iter().map(|s| s.to_json()).collect()),
+            ),
+        ])
+    }
+}
+
+impl ToJson for Statement {
+    fn to_json(&self) -> Json {
+        match self {
+            Statement::LetStatement(s) => s.to_json(),
+            Statement::ReturnStatement(s) => s.to_json(),
+            Statement::ExpressionStatement(s) => s.to_json(),
+            Statement::BlockStatement(s) => s.to_json(),
+            Statement::WhileStatement(s) => s.to_json(),
+            Statement::ForStatement(s) => s.to_json(),
+            Statement::BreakStatement(s) => node("BreakStatement", &s.token, vec![]),
+            Statement::ContinueStatement(s) => node("ContinueStatement", &s.token, vec![]),
+            Statement::ImportStatement(s) => s.to_json(),
+            Statement::ExportStatement(s) => node(
+                "ExportStatement",
+                &s.token,
+                vec![("statement", s.statement.to_json())],
+            ),
+        }
+    }
+}
+
+impl ToJson for Expression {
+    fn to_json(&self) -> Json {
+        match self {
+            Expression::Identifier(e) => e.to_json(),
+            Expression::IntegerLiteral(e) => e.to_json(),
+            Expression::PrefixExpression(e) => e.to_json(),
+            Expression::InfixExpression(e) => e.to_json(),
+            Expression::Boolean(e) => e.to_json(),
+            Expression::IfExpression(e) => e.to_json(),
+            Expression::FunctionLiteral(e) => e.to_json(),
+            Expression::CallExpression(e) => e.to_json(),
+            Expression::StringLiteral(e) => e.to_json(),
+            Expression::ArrayLiteral(e) => e.to_json(),
+            Expression::IndexExpression(e) => e.to_json(),
+            Expression::HashLiteral(e) => e.to_json(),
+            Expression::RangeExpression(e) => e.to_json(),
+            Expression::AssignExpression(e) => e.to_json(),
+            Expression::MatchExpression(e) => e.to_json(),
+            Expression::MemberExpression(e) => e.to_json(),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        self.as_ref().map_or(Json::Null, |v| v.to_json())
+    }
+}
+
+impl<T: ToJson> ToJson for Box<T> {
+    fn to_json(&self) -> Json {
+        self.as_ref().to_json()
+    }
+}
+
+impl ToJson for LetStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "LetStatement",
+            &self.token,
+            vec![
+                ("pattern", self.pattern.to_json()),
+                ("value", self.value.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for ImportStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "ImportStatement",
+            &self.token,
+            vec![
+                ("path", self.path.as_str().into()),
+                ("alias", self.alias.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for ReturnStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "ReturnStatement",
+            &self.token,
+            vec![("return_value", self.return_value.to_json())],
+        )
+    }
+}
+
+impl ToJson for ExpressionStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "ExpressionStatement",
+            &self.token,
+            vec![("expression", self.expression.to_json())],
+        )
+    }
+}
+
+impl ToJson for BlockStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "BlockStatement",
+            &self.token,
+            vec![(
+                "statements",
+                Json::Array(self.statements.iter().map(|s| s.to_json()).collect()),
+            )],
+        )
+    }
+}
+
+impl ToJson for WhileStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "WhileStatement",
+            &self.token,
+            vec![
+                ("condition", self.condition.to_json()),
+                ("body", self.body.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for ForStatement {
+    fn to_json(&self) -> Json {
+        node(
+            "ForStatement",
+            &self.token,
+            vec![
+                (
+                    "variables",
+                    Json::Array(self.variables.iter().map(|v| v.to_json()).collect()),
+                ),
+                ("iterable", self.iterable.to_json()),
+                ("body", self.body.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for Identifier {
+    fn to_json(&self) -> Json {
+        node(
+            "Identifier",
+            &self.token,
+            vec![("value", self.value.as_str().into())],
+        )
+    }
+}
+
+impl ToJson for IntegerLiteral {
+    fn to_json(&self) -> Json {
+        node(
+            "IntegerLiteral",
+            &self.token,
+            vec![("value", self.value.into())],
+        )
+    }
+}
+
+impl ToJson for PrefixExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "PrefixExpression",
+            &self.token,
+            vec![
+                ("operator", self.operator.as_str().into()),
+                ("right", self.right.to_json()),
+            ],
+        )
+    }
+}
+
+// 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左の端から組み立てる
+impl ToJson for InfixExpression {
+    fn to_json(&self) -> Json {
+        let mut chain = vec![self];
+        let mut leftmost = self.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            chain.push(inner);
+            leftmost = inner.left.as_deref();
+        }
+
+        let mut left = leftmost.map_or(Json::Null, Expression::to_json);
+        for e in chain.into_iter().rev() {
+            left = node(
+                "InfixExpression",
+                &e.token,
+                vec![
+                    ("left", left),
+                    ("operator", e.operator.as_str().into()),
+                    ("right", e.right.to_json()),
+                ],
+            );
+        }
+        left
+    }
+}
+
+impl ToJson for Boolean {
+    fn to_json(&self) -> Json {
+        node("Boolean", &self.token, vec![("value", self.value.into())])
+    }
+}
+
+impl ToJson for IfExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "IfExpression",
+            &self.token,
+            vec![
+                ("condition", self.condition.to_json()),
+                ("consequence", self.consequence.to_json()),
+                ("alternative", self.alternative.to_json()),
+                (
+                    "else_if",
+                    self.alternative
+                        .as_ref()
+                        .is_some_and(|block| block.token.kind == TokenKind::If)
+                        .into(),
+                ),
+            ],
+        )
+    }
+}
+
+impl ToJson for FunctionLiteral {
+    fn to_json(&self) -> Json {
+        node(
+            "FunctionLiteral",
+            &self.token,
+            vec![
+                (
+                    "parameters",
+                    Json::Array(self.parameters.iter().map(|p| p.to_json()).collect()),
+                ),
+                ("body", self.body.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for CallExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "CallExpression",
+            &self.token,
+            vec![
+                ("function", self.function.to_json()),
+                (
+                    "arguments",
+                    Json::Array(self.arguments.iter().map(|a| a.to_json()).collect()),
+                ),
+            ],
+        )
+    }
+}
+
+impl ToJson for StringLiteral {
+    fn to_json(&self) -> Json {
+        node(
+            "StringLiteral",
+            &self.token,
+            vec![("value", self.value.as_str().into())],
+        )
+    }
+}
+
+impl ToJson for ArrayLiteral {
+    fn to_json(&self) -> Json {
+        node(
+            "ArrayLiteral",
+            &self.token,
+            vec![(
+                "elements",
+                Json::Array(self.elements.iter().map(|e| e.to_json()).collect()),
+            )],
+        )
+    }
+}
+
+impl ToJson for IndexExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "IndexExpression",
+            &self.token,
+            vec![
+                ("left", self.left.to_json()),
+                ("index", self.index.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for MemberExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "MemberExpression",
+            &self.token,
+            vec![
+                ("object", self.object.to_json()),
+                ("property", self.property.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for HashLiteral {
+    fn to_json(&self) -> Json {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, value)| {
+                Json::object(vec![("key", key.to_json()), ("value", value.to_json())])
+            })
+            .collect();
+        node(
+            "HashLiteral",
+            &self.token,
+            vec![("pairs", Json::Array(pairs))],
+        )
+    }
+}
+
+impl ToJson for RangeExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "RangeExpression",
+            &self.token,
+            vec![
+                ("start", self.start.to_json()),
+                ("end", self.end.to_json()),
+                ("inclusive", self.inclusive.into()),
+            ],
+        )
+    }
+}
+
+impl ToJson for AssignExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "AssignExpression",
+            &self.token,
+            vec![
+                ("target", self.target.to_json()),
+                ("operator", self.operator.as_str().into()),
+                ("value", self.value.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for MatchExpression {
+    fn to_json(&self) -> Json {
+        node(
+            "MatchExpression",
+            &self.token,
+            vec![
+                ("subject", self.subject.to_json()),
+                (
+                    "arms",
+                    Json::Array(self.arms.iter().map(|a| a.to_json()).collect()),
+                ),
+            ],
+        )
+    }
+}
+
+impl ToJson for MatchArm {
+    fn to_json(&self) -> Json {
+        node(
+            "MatchArm",
+            &self.token,
+            vec![
+                ("pattern", self.pattern.to_json()),
+                ("guard", self.guard.to_json()),
+                ("body", self.body.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for Pattern {
+    fn to_json(&self) -> Json {
+        match self {
+            Pattern::WildcardPattern(p) => node("WildcardPattern", &p.token, vec![]),
+            Pattern::Identifier(p) => p.to_json(),
+            Pattern::IntegerLiteral(p) => p.to_json(),
+            Pattern::StringLiteral(p) => p.to_json(),
+            Pattern::Boolean(p) => p.to_json(),
+            Pattern::ArrayPattern(p) => p.to_json(),
+            Pattern::HashPattern(p) => p.to_json(),
+        }
+    }
+}
+
+impl ToJson for ArrayPattern {
+    fn to_json(&self) -> Json {
+        node(
+            "ArrayPattern",
+            &self.token,
+            vec![
+                (
+                    "elements",
+                    Json::Array(self.elements.iter().map(|e| e.to_json()).collect()),
+                ),
+                ("rest", self.rest.to_json()),
+            ],
+        )
+    }
+}
+
+impl ToJson for RestPattern {
+    fn to_json(&self) -> Json {
+        node(
+            "RestPattern",
+            &self.token,
+            vec![("name", self.name.to_json())],
+        )
+    }
+}
+
+impl ToJson for HashPattern {
+    fn to_json(&self) -> Json {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, value)| {
+                Json::object(vec![("key", key.to_json()), ("value", value.to_json())])
+            })
+            .collect();
+        node(
+            "HashPattern",
+            &self.token,
+            vec![("pairs", Json::Array(pairs))],
+        )
+    }
+}
+
+impl Program {
+    pub fn from_json(value: &Json) -> Result<Program, String> {
+        expect_type(value, "Program")?;
+        let statements = field(value, "Program", "statements")?
+            .as_array()
+            .ok_or("Program.statements must be an array")?
+            .iter()
+            .map(statement_from_json)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Program { statements })
+    }
+}
+
+fn type_of(value: &Json) -> Result<&str, String> {
+    value
+        .get("type")
+        .and_then(Json::as_str)
+        .ok_or_else(|| format!("node has no type: {}", value))
+}
+
+fn expect_type(value: &Json, expected: &str) -> Result<(), String> {
+    let actual = type_of(value)?;
+    if actual != expected {
+        return Err(format!("expected {}, got {}", expected, actual));
+    }
+    Ok(())
+}
+
+fn field<'a>(value: &'a Json, node: &str, name: &str) -> Result<&'a Json, String> {
+    value
+        .get(name)
+        .ok_or_else(|| format!("{} has no field '{}'", node, name))
+}
+
+fn str_field<'a>(value: &'a Json, node: &str, name: &str) -> Result<&'a str, String> {
+    field(value, node, name)?
+        .as_str()
+        .ok_or_else(|| format!("{}.{} must be a string", node, name))
+}
+
+fn optional<T>(
+    value: &Json,
+    f: impl FnOnce(&Json) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match value {
+        Json::Null => Ok(None),
+        v => f(v).map(Some),
+    }
+}
+
+fn span_from_json(value: &Json) -> Result<Span, String> {
+    let span = value.get("span").ok_or("node has no span")?;
+    let get = |name: &str| {
+        span.get(name)
+            .and_then(Json::as_i64)
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| format!("span.{} must be a non-negative integer", name))
+    };
+    Ok(Span::new(get("start")?, get("end")?))
+}
+
+fn token(value: &Json, kind: TokenKind, literal: impl ToString) -> Result<Token, String> {
+    Ok(Token::new(kind, literal).with_span(span_from_json(value)?))
+}
+
+// 演算子の文字列からトークンの種類を復元する
+fn operator_token(value: &Json, operator: &str) -> Result<Token, String> {
+    let kind = Lexer::new(operator).next_token().kind;
+    if kind == TokenKind::Illegal || kind.to_string() != operator {
+        return Err(format!("unknown operator '{}'", operator));
+    }
+    token(value, kind, operator)
+}
+
+fn statement_from_json(value: &Json) -> Result<Statement, String> {
+    match type_of(value)? {
+        "LetStatement" => Ok(let_from_json(value)?.into()),
+        "ReturnStatement" => {
+            let v = optional(
+                field(value, "ReturnStatement", "return_value")?,
+                expression_from_json,
+            )?;
+            Ok(ReturnStatement::new(token(value, TokenKind::Return, "return")?, v).into())
+        }
+        "ExpressionStatement" => {
+            let expression = optional(
+                field(value, "ExpressionStatement", "expression")?,
+                expression_from_json,
+            )?;
+            // 文のトークンは式の先頭のトークンにあたる
+            let first = expression
+                .as_ref()
+                .map(first_token)
+                .unwrap_or_default()
+                .with_span(span_from_json(value)?);
+            Ok(ExpressionStatement::new(first, expression).into())
+        }
+        "BlockStatement" => Ok(block_from_json(value)?.into()),
+        "WhileStatement" => {
+            let condition = optional(
+                field(value, "WhileStatement", "condition")?,
+                expression_from_json,
+            )?;
+            let body = block_from_json(field(value, "WhileStatement", "body")?)?;
+            Ok(
+                WhileStatement::new(token(value, TokenKind::While, "while")?, condition, body)
+                    .into(),
+            )
+        }
+        "ForStatement" => {
+            let variables = field(value, "ForStatement", "variables")?
+                .as_array()
+                .ok_or("ForStatement.variables must be an array")?
+                .iter()
+                .map(identifier_from_json)
+                .collect::<Result<Vec<_>, _>>()?;
+            let iterable = optional(
+                field(value, "ForStatement", "iterable")?,
+                expression_from_json,
+            )?;
+            let body = block_from_json(field(value, "ForStatement", "body")?)?;
+            Ok(ForStatement::new(
+                token(value, TokenKind::For, "for")?,
+                variables,
+                iterable,
+                body,
+            )
+            .into())
+        }
+        "BreakStatement" => {
+            Ok(BreakStatement::new(token(value, TokenKind::Break, "break")?).into())
+        }
+        "ContinueStatement" => {
+            Ok(ContinueStatement::new(token(value, TokenKind::Continue, "continue")?).into())
+        }
+        "ImportStatement" => {
+            let path = str_field(value, "ImportStatement", "path")?;
+            let alias = identifier_from_json(field(value, "ImportStatement", "alias")?)?;
+            Ok(
+                ImportStatement::new(token(value, TokenKind::Import, "import")?, path, alias)
+                    .into(),
+            )
+        }
+        "ExportStatement" => {
+            let stmt = let_from_json(field(value, "ExportStatement", "statement")?)?;
+            Ok(ExportStatement::new(token(value, TokenKind::Export, "export")?, stmt).into())
+        }
+        other => Err(format!("unknown statement type: {}", other)),
+    }
+}
+
+fn let_from_json(value: &Json) -> Result<LetStatement, String> {
+    expect_type(value, "LetStatement")?;
+    let pattern = binding_from_json(field(value, "LetStatement", "pattern")?)?;
+    let v = optional(field(value, "LetStatement", "value")?, expression_from_json)?;
+    Ok(LetStatement::new(
+        token(value, TokenKind::Let, "let")?,
+        pattern,
+        v,
+    ))
+}
+
+fn first_token(exp: &Expression) -> Token {
+    match exp {
+        Expression::InfixExpression(e) => e.left.as_deref().map_or(e.token.clone(), first_token),
+        Expression::Identifier(e) => e.token.clone(),
+        Expression::IntegerLiteral(e) => e.token.clone(),
+        Expression::PrefixExpression(e) => e.token.clone(),
+        Expression::Boolean(e) => e.token.clone(),
+        Expression::IfExpression(e) => e.token.clone(),
+        Expression::FunctionLiteral(e) => e.token.clone(),
+        Expression::CallExpression(e) => first_token(&e.function),
+        Expression::StringLiteral(e) => e.token.clone(),
+        Expression::ArrayLiteral(e) => e.token.clone(),
+        Expression::IndexExpression(e) => first_token(&e.left),
+        Expression::HashLiteral(e) => e.token.clone(),
+        Expression::RangeExpression(e) => e.start.as_deref().map_or(e.token.clone(), first_token),
+        Expression::AssignExpression(e) => first_token(&e.target),
+        Expression::MatchExpression(e) => e.token.clone(),
+        Expression::MemberExpression(e) => first_token(&e.object),
+    }
+}
+
+fn block_from_json(value: &Json) -> Result<BlockStatement, String> {
+    expect_type(value, "BlockStatement")?;
+    let statements = field(value, "BlockStatement", "statements")?
+        .as_array()
+        .ok_or("BlockStatement.statements must be an array")?
+        .iter()
+        .map(statement_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(BlockStatement::new(
+        token(value, TokenKind::LBrace, "{")?,
+        statements,
+    ))
+}
+
+fn identifier_from_json(value: &Json) -> Result<Identifier, String> {
+    expect_type(value, "Identifier")?;
+    let name = str_field(value, "Identifier", "value")?;
+    Ok(Identifier::new(token(value, TokenKind::Ident, name)?, name))
+}
+
+// 深い入れ子でもスタックが足りるよう、ここでは種類を見て振り分けるだけにする
+fn expression_from_json(value: &Json) -> Result<Expression, String> {
+    match type_of(value)? {
+        "Identifier" => Ok(identifier_from_json(value)?.into()),
+        "IntegerLiteral" => integer_from_json(value),
+        "Boolean" => boolean_from_json(value),
+        "PrefixExpression" => prefix_from_json(value),
+        "InfixExpression" => infix_from_json(value),
+        "IfExpression" => if_from_json(value),
+        "FunctionLiteral" => function_from_json(value),
+        "CallExpression" => call_from_json(value),
+        "StringLiteral" => string_from_json(value),
+        "ArrayLiteral" => array_from_json(value),
+        "IndexExpression" => index_from_json(value),
+        "MemberExpression" => member_from_json(value),
+        "HashLiteral" => hash_from_json(value),
+        "RangeExpression" => range_from_json(value),
+        "AssignExpression" => assign_from_json(value),
+        "MatchExpression" => match_from_json(value),
+        other => Err(format!("unknown expression type: {}", other)),
+    }
+}
+
+fn integer_from_json(value: &Json) -> Result<Expression, String> {
+    let n = field(value, "IntegerLiteral", "value")?
+        .as_i64()
+        .ok_or("IntegerLiteral.value must be an integer")?;
+    Ok(IntegerLiteral::new(token(value, TokenKind::Int, n)?, n).into())
+}
+
+fn boolean_from_json(value: &Json) -> Result<Expression, String> {
+    let b = field(value, "Boolean", "value")?
+        .as_bool()
+        .ok_or("Boolean.value must be a boolean")?;
+    let kind = if b { TokenKind::True } else { TokenKind::False };
+    Ok(Boolean::new(token(value, kind, b)?, b).into())
+}
+
+fn prefix_from_json(value: &Json) -> Result<Expression, String> {
+    let operator = str_field(value, "PrefixExpression", "operator")?;
+    let right = optional(
+        field(value, "PrefixExpression", "right")?,
+        expression_from_json,
+    )?;
+    Ok(PrefixExpression::new(operator_token(value, operator)?, operator, right).into())
+}
+
+// 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左の端から組み立てる
+fn infix_from_json(value: &Json) -> Result<Expression, String> {
+    let mut chain = vec![value];
+    let mut leftmost = field(value, "InfixExpression", "left")?;
+    while matches!(type_of(leftmost), Ok("InfixExpression")) {
+        chain.push(leftmost);
+        leftmost = field(leftmost, "InfixExpression", "left")?;
+    }
+
+    let mut left = optional(leftmost, expression_from_json)?;
+    for value in chain.into_iter().rev() {
+        let operator = str_field(value, "InfixExpression", "operator")?;
+        let right = optional(
+            field(value, "InfixExpression", "right")?,
+            expression_from_json,
+        )?;
+        let token = operator_token(value, operator)?;
+        left = Some(InfixExpression::new(token, left, operator, right).into());
+    }
+    Ok(left.expect("an infix chain is never empty"))
+}
+
+fn if_from_json(value: &Json) -> Result<Expression, String> {
+    let condition = optional(
+        field(value, "IfExpression", "condition")?,
+        expression_from_json,
+    )?;
+    let consequence = block_from_json(field(value, "IfExpression", "consequence")?)?;
+    let mut alternative = optional(
+        field(value, "IfExpression", "alternative")?,
+        block_from_json,
+    )?;
+    let else_if = field(value, "IfExpression", "else_if")?
+        .as_bool()
+        .ok_or("IfExpression.else_if must be a boolean")?;
+    // else if のブロックは、整形で else if に戻せるよう if のトークンを持つ
+    if let Some(block) = alternative.as_mut().filter(|_| else_if) {
+        block.token = Token::new(TokenKind::If, "if").with_span(block.token.span);
+    }
+    Ok(IfExpression::new(
+        token(value, TokenKind::If, "if")?,
+        condition,
+        consequence,
+        alternative,
+    )
+    .into())
+}
+
+fn function_from_json(value: &Json) -> Result<Expression, String> {
+    let parameters = field(value, "FunctionLiteral", "parameters")?
+        .as_array()
+        .ok_or("FunctionLiteral.parameters must be an array")?
+        .iter()
+        .map(binding_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    let body = block_from_json(field(value, "FunctionLiteral", "body")?)?;
+    Ok(FunctionLiteral::new(token(value, TokenKind::Function, "fn")?, parameters, body).into())
+}
+
+fn call_from_json(value: &Json) -> Result<Expression, String> {
+    let function = expression_from_json(field(value, "CallExpression", "function")?)?;
+    let arguments = field(value, "CallExpression", "arguments")?
+        .as_array()
+        .ok_or("CallExpression.arguments must be an array")?
+        .iter()
+        .map(expression_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(CallExpression::new(token(value, TokenKind::LParen, "(")?, function, arguments).into())
+}
+
+fn string_from_json(value: &Json) -> Result<Expression, String> {
+    let s = str_field(value, "StringLiteral", "value")?;
+    Ok(StringLiteral::new(token(value, TokenKind::String, quote(s))?, s).into())
+}
+
+fn array_from_json(value: &Json) -> Result<Expression, String> {
+    let elements = field(value, "ArrayLiteral", "elements")?
+        .as_array()
+        .ok_or("ArrayLiteral.elements must be an array")?
+        .iter()
+        .map(expression_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ArrayLiteral::new(token(value, TokenKind::LBracket, "[")?, elements).into())
+}
+
+fn index_from_json(value: &Json) -> Result<Expression, String> {
+    let left = expression_from_json(field(value, "IndexExpression", "left")?)?;
+    let index = expression_from_json(field(value, "IndexExpression", "index")?)?;
+    Ok(IndexExpression::new(token(value, TokenKind::LBracket, "[")?, left, index).into())
+}
+
+fn member_from_json(value: &Json) -> Result<Expression, String> {
+    let object = expression_from_json(field(value, "MemberExpression", "object")?)?;
+    let property = identifier_from_json(field(value, "MemberExpression", "property")?)?;
+    Ok(MemberExpression::new(token(value, TokenKind::Dot, ".")?, object, property).into())
+}
+
+fn hash_from_json(value: &Json) -> Result<Expression, String> {
+    let pairs = field(value, "HashLiteral", "pairs")?
+        .as_array()
+        .ok_or("HashLiteral.pairs must be an array")?
+        .iter()
+        .map(|pair| {
+            let key = expression_from_json(field(pair, "HashLiteral pair", "key")?)?;
+            let value = expression_from_json(field(pair, "HashLiteral pair", "value")?)?;
+            Ok((key, value))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(HashLiteral::new(token(value, TokenKind::LBrace, "{")?, pairs).into())
+}
+
+fn range_from_json(value: &Json) -> Result<Expression, String> {
+    let start = optional(
+        field(value, "RangeExpression", "start")?,
+        expression_from_json,
+    )?;
+    let end = optional(
+        field(value, "RangeExpression", "end")?,
+        expression_from_json,
+    )?;
+    let inclusive = field(value, "RangeExpression", "inclusive")?
+        .as_bool()
+        .ok_or("RangeExpression.inclusive must be a boolean")?;
+    let (kind, literal) = if inclusive {
+        (TokenKind::DotDotEq, "..=")
+    } else {
+        (TokenKind::DotDot, "..")
+    };
+    Ok(RangeExpression::new(token(value, kind, literal)?, start, end, inclusive).into())
+}
+
+fn assign_from_json(value: &Json) -> Result<Expression, String> {
+    let target = expression_from_json(field(value, "AssignExpression", "target")?)?;
+    if !is_assignable(&target) {
+        return Err("AssignExpression.target must be an identifier or index".to_string());
+    }
+    let operator = str_field(value, "AssignExpression", "operator")?;
+    if !matches!(operator, "=" | "+=" | "-=" | "*=" | "/=") {
+        return Err(format!("unknown assignment operator '{}'", operator));
+    }
+    let v = optional(
+        field(value, "AssignExpression", "value")?,
+        expression_from_json,
+    )?;
+    Ok(AssignExpression::new(operator_token(value, operator)?, target, operator, v).into())
+}
+
+fn match_from_json(value: &Json) -> Result<Expression, String> {
+    let subject = optional(
+        field(value, "MatchExpression", "subject")?,
+        expression_from_json,
+    )?;
+    let arms = field(value, "MatchExpression", "arms")?
+        .as_array()
+        .ok_or("MatchExpression.arms must be an array")?
+        .iter()
+        .map(match_arm_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(MatchExpression::new(token(value, TokenKind::Match, "match")?, subject, arms).into())
+}
+
+fn match_arm_from_json(value: &Json) -> Result<MatchArm, String> {
+    expect_type(value, "MatchArm")?;
+    let pattern = pattern_from_json(field(value, "MatchArm", "pattern")?)?;
+    let guard = optional(field(value, "MatchArm", "guard")?, expression_from_json)?;
+    let body = optional(field(value, "MatchArm", "body")?, expression_from_json)?;
+    Ok(MatchArm::new(
+        token(value, TokenKind::FatArrow, "=>")?,
+        pattern,
+        guard,
+        body,
+    ))
+}
+
+// let と引数の束縛先。パーサと同じく、リテラルは受け付けない
+fn binding_from_json(value: &Json) -> Result<Pattern, String> {
+    match pattern_from_json(value)? {
+        Pattern::IntegerLiteral(_) | Pattern::StringLiteral(_) | Pattern::Boolean(_) => {
+            Err(format!(
+                "binding pattern must not be a literal, got {}",
+                type_of(value)?
+            ))
+        }
+        pattern => Ok(pattern),
+    }
+}
+
+fn pattern_from_json(value: &Json) -> Result<Pattern, String> {
+    match type_of(value)? {
+        "WildcardPattern" => Ok(WildcardPattern::new(token(value, TokenKind::Ident, "_")?).into()),
+        "Identifier" => {
+            let name = identifier_from_json(value)?;
+            if name.value == "_" {
+                return Err("Identifier pattern must not be '_'".to_string());
+            }
+            Ok(name.into())
+        }
+        "IntegerLiteral" | "StringLiteral" | "Boolean" => literal_pattern_from_json(value),
+        "ArrayPattern" => {
+            let elements = field(value, "ArrayPattern", "elements")?
+                .as_array()
+                .ok_or("ArrayPattern.elements must be an array")?
+                .iter()
+                .map(pattern_from_json)
+                .collect::<Result<Vec<_>, _>>()?;
+            let rest = optional(field(value, "ArrayPattern", "rest")?, |rest| {
+                expect_type(rest, "RestPattern")?;
+                let name = optional(field(rest, "RestPattern", "name")?, identifier_from_json)?;
+                Ok(RestPattern::new(
+                    token(rest, TokenKind::DotDot, "..")?,
+                    name,
+                ))
+            })?;
+            Ok(ArrayPattern::new(token(value, TokenKind::LBracket, "[")?, elements, rest).into())
+        }
+        "HashPattern" => {
+            let pairs = field(value, "HashPattern", "pairs")?
+                .as_array()
+                .ok_or("HashPattern.pairs must be an array")?
+                .iter()
+                .map(|pair| {
+                    let key =
+                        match literal_pattern_from_json(field(pair, "HashPattern pair", "key")?)? {
+                            Pattern::IntegerLiteral(e) => e.into(),
+                            Pattern::StringLiteral(e) => e.into(),
+                            Pattern::Boolean(e) => e.into(),
+                            _ => unreachable!(),
+                        };
+                    let value = pattern_from_json(field(pair, "HashPattern pair", "value")?)?;
+                    Ok((key, value))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(HashPattern::new(token(value, TokenKind::LBrace, "{")?, pairs).into())
+        }
+        other => Err(format!("unknown pattern type: {}", other)),
+    }
+}
+
+// リテラルのパターンは式のリテラルと同じ形で表す
+fn literal_pattern_from_json(value: &Json) -> Result<Pattern, String> {
+    match expression_from_json(value)? {
+        Expression::IntegerLiteral(e) => Ok(e.into()),
+        Expression::StringLiteral(e) => Ok(e.into()),
+        Expression::Boolean(e) => Ok(e.into()),
+        _ => Err(format!("expected literal pattern, got {}", type_of(value)?)),
+    }
+}
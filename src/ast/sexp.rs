@@ -0,0 +1,272 @@
+use crate::ast::*;
+use crate::lexer::quote;
+
+// (program (let x (+ 1 2)) (expr (if (< x y) (block (expr x)))))
+pub trait ToSexp {
+    fn to_sexp(&self) -> String;
+}
+
+fn list(head: &str, items: impl IntoIterator<Item = String>) -> String {
+    let mut s = format!("({}", head);
+    for item in items {
+        s.push(' ');
+        s.push_str(&item);
+    }
+    s.push(')');
+    s
+}
+
+impl ToSexp for Program {
+    fn to_sexp(&self) -> String {
+        list("program", self.statements.iter().map(|s| s.to_sexp()))
+    }
+}
+
+impl ToSexp for Statement {
+    fn to_sexp(&self) -> String {
+        match self {
+            Statement::LetStatement(s) => s.to_sexp(),
+            Statement::ReturnStatement(s) => s.to_sexp(),
+            Statement::ExpressionStatement(s) => s.to_sexp(),
+            Statement::BlockStatement(s) => s.to_sexp(),
+            Statement::WhileStatement(s) => s.to_sexp(),
+            Statement::ForStatement(s) => s.to_sexp(),
+            Statement::BreakStatement(_) => "(break)".to_string(),
+            Statement::ContinueStatement(_) => "(continue)".to_string(),
+            Statement::ImportStatement(s) => list("import", [quote(&s.path), s.alias.to_sexp()]),
+            Statement::ExportStatement(s) => list("export", [s.statement.to_sexp()]),
+        }
+    }
+}
+
+impl ToSexp for Expression {
+    fn to_sexp(&self) -> String {
+        match self {
+            Expression::Identifier(e) => e.to_sexp(),
+            Expression::IntegerLiteral(e) => e.to_sexp(),
+            Expression::PrefixExpression(e) => e.to_sexp(),
+            Expression::InfixExpression(e) => e.to_sexp(),
+            Expression::Boolean(e) => e.to_sexp(),
+            Expression::IfExpression(e) => e.to_sexp(),
+            Expression::FunctionLiteral(e) => e.to_sexp(),
+            Expression::CallExpression(e) => e.to_sexp(),
+            Expression::StringLiteral(e) => e.to_sexp(),
+            Expression::ArrayLiteral(e) => e.to_sexp(),
+            Expression::IndexExpression(e) => e.to_sexp(),
+            Expression::HashLiteral(e) => e.to_sexp(),
+            Expression::RangeExpression(e) => e.to_sexp(),
+            Expression::AssignExpression(e) => e.to_sexp(),
+            Expression::MatchExpression(e) => e.to_sexp(),
+            Expression::MemberExpression(e) => e.to_sexp(),
+        }
+    }
+}
+
+// 欠けている部分式は nil で表す
+impl<T: ToSexp> ToSexp for Option<T> {
+    fn to_sexp(&self) -> String {
+        self.as_ref().map_or("nil".to_string(), |v| v.to_sexp())
+    }
+}
+
+impl<T: ToSexp> ToSexp for Box<T> {
+    fn to_sexp(&self) -> String {
+        self.as_ref().to_sexp()
+    }
+}
+
+impl ToSexp for LetStatement {
+    fn to_sexp(&self) -> String {
+        list("let", [self.pattern.to_sexp(), self.value.to_sexp()])
+    }
+}
+
+impl ToSexp for ReturnStatement {
+    fn to_sexp(&self) -> String {
+        list("return", [self.return_value.to_sexp()])
+    }
+}
+
+impl ToSexp for ExpressionStatement {
+    fn to_sexp(&self) -> String {
+        list("expr", [self.expression.to_sexp()])
+    }
+}
+
+impl ToSexp for BlockStatement {
+    fn to_sexp(&self) -> String {
+        list("block", self.statements.iter().map(|s| s.to_sexp()))
+    }
+}
+
+impl ToSexp for WhileStatement {
+    fn to_sexp(&self) -> String {
+        list("while", [self.condition.to_sexp(), self.body.to_sexp()])
+    }
+}
+
+impl ToSexp for ForStatement {
+    fn to_sexp(&self) -> String {
+        let variables = list("vars", self.variables.iter().map(|v| v.to_sexp()));
+        list(
+            "for",
+            [variables, self.iterable.to_sexp(), self.body.to_sexp()],
+        )
+    }
+}
+
+impl ToSexp for Identifier {
+    fn to_sexp(&self) -> String {
+        self.value.clone()
+    }
+}
+
+impl ToSexp for IntegerLiteral {
+    fn to_sexp(&self) -> String {
+        self.value.to_string()
+    }
+}
+
+impl ToSexp for PrefixExpression {
+    fn to_sexp(&self) -> String {
+        list(&self.operator, [self.right.to_sexp()])
+    }
+}
+
+impl ToSexp for InfixExpression {
+    fn to_sexp(&self) -> String {
+        list(&self.operator, [self.left.to_sexp(), self.right.to_sexp()])
+    }
+}
+
+impl ToSexp for Boolean {
+    fn to_sexp(&self) -> String {
+        self.value.to_string()
+    }
+}
+
+impl ToSexp for IfExpression {
+    fn to_sexp(&self) -> String {
+        let mut items = vec![self.condition.to_sexp(), self.consequence.to_sexp()];
+        if let Some(alt) = &self.alternative {
+            items.push(alt.to_sexp());
+        }
+        list("if", items)
+    }
+}
+
+impl ToSexp for FunctionLiteral {
+    fn to_sexp(&self) -> String {
+        let params = list("params", self.parameters.iter().map(|p| p.to_sexp()));
+        list("fn", [params, self.body.to_sexp()])
+    }
+}
+
+impl ToSexp for CallExpression {
+    fn to_sexp(&self) -> String {
+        let mut items = vec![self.function.to_sexp()];
+        items.extend(self.arguments.iter().map(|a| a.to_sexp()));
+        list("call", items)
+    }
+}
+
+impl ToSexp for StringLiteral {
+    fn to_sexp(&self) -> String {
+        quote(&self.value)
+    }
+}
+
+impl ToSexp for ArrayLiteral {
+    fn to_sexp(&self) -> String {
+        list("array", self.elements.iter().map(|e| e.to_sexp()))
+    }
+}
+
+impl ToSexp for IndexExpression {
+    fn to_sexp(&self) -> String {
+        list("index", [self.left.to_sexp(), self.index.to_sexp()])
+    }
+}
+
+impl ToSexp for MemberExpression {
+    fn to_sexp(&self) -> String {
+        list(".", [self.object.to_sexp(), self.property.to_sexp()])
+    }
+}
+
+impl ToSexp for HashLiteral {
+    fn to_sexp(&self) -> String {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, value)| list("pair", [key.to_sexp(), value.to_sexp()]));
+        list("hash", pairs)
+    }
+}
+
+impl ToSexp for RangeExpression {
+    fn to_sexp(&self) -> String {
+        let head = if self.inclusive { "..=" } else { ".." };
+        list(head, [self.start.to_sexp(), self.end.to_sexp()])
+    }
+}
+
+impl ToSexp for AssignExpression {
+    fn to_sexp(&self) -> String {
+        list(
+            &self.operator,
+            [self.target.to_sexp(), self.value.to_sexp()],
+        )
+    }
+}
+
+impl ToSexp for MatchExpression {
+    fn to_sexp(&self) -> String {
+        let mut items = vec![self.subject.to_sexp()];
+        items.extend(self.arms.iter().map(|a| a.to_sexp()));
+        list("match", items)
+    }
+}
+
+// 条件のない腕は guard を nil にする
+impl ToSexp for MatchArm {
+    fn to_sexp(&self) -> String {
+        list(
+            "arm",
+            [
+                self.pattern.to_sexp(),
+                self.guard.to_sexp(),
+                self.body.to_sexp(),
+            ],
+        )
+    }
+}
+
+impl ToSexp for Pattern {
+    fn to_sexp(&self) -> String {
+        match self {
+            Pattern::WildcardPattern(_) => "_".to_string(),
+            Pattern::Identifier(p) => p.to_sexp(),
+            Pattern::IntegerLiteral(p) => p.to_sexp(),
+            Pattern::StringLiteral(p) => p.to_sexp(),
+            Pattern::Boolean(p) => p.to_sexp(),
+            Pattern::ArrayPattern(p) => {
+                let rest = p
+                    .rest
+                    .as_ref()
+                    .map(|r| list("rest", r.name.iter().map(|n| n.to_sexp())));
+                list(
+                    "array-pattern",
+                    p.elements.iter().map(|e| e.to_sexp()).chain(rest),
+                )
+            }
+            Pattern::HashPattern(p) => {
+                let pairs = p
+                    .pairs
+                    .iter()
+                    .map(|(key, value)| list("pair", [key.to_sexp(), value.to_sexp()]));
+                list("hash-pattern", pairs)
+            }
+        }
+    }
+}
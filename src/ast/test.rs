@@ -1,4 +1,9 @@
+use super::json::ToJson;
+use super::sexp::ToSexp;
 use super::*;
+use crate::json::Json;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
 use crate::token::TokenKind;
 
 #[test]
@@ -16,3 +21,142 @@ fn test_string() {
 
     assert_eq!(program.to_string(), "let myVar = anotherVar;");
 }
+
+fn parse(input: &str) -> Program {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    program
+}
+
+#[test]
+fn test_to_json() {
+    let program = parse("let x = -a;");
+
+    assert_eq!(
+        program.to_json().to_string(),
+        r#"{"type":"Program","statements":[{"type":"LetStatement","span":{"start":0,"end":3},"pattern":{"type":"Identifier","span":{"start":4,"end":5},"value":"x"},"value":{"type":"PrefixExpression","span":{"start":8,"end":9},"operator":"-","right":{"type":"Identifier","span":{"start":9,"end":10},"value":"a"}}}]}"#
+    );
+}
+
+#[test]
+fn test_json_roundtrip() {
+    let input = "let x = 1 + 2 * 3; return !true; if (x < 10) { (x) } else { y == false }; \
+                 let add = fn(a, b) { a + b }; add(1, add(2, 3)); \
+                 {\"k\": [1, \"a\\n\"][0], true: {}}[\"k\"]; \
+                 while (x) { if (y) { break; } continue; } \
+                 for (k, v in 1..x + 1) { for (c in \"abc\") { k } } \
+                 x = y[0][\"k\"] += 2; \
+                 if (a) { 1 } else if (b) { 2 } else { 3 }; \
+                 match (x) { [h, ..t] if h > 0 => t, [..] => 0, {\"k\": -1, true: _} => 1, y => y }; \
+                 let [a, ..b] = fn([c], {1: d}, _) { c }; \
+                 import \"lib/m.monkey\" as m; export let z = m.f(1).g;";
+    let program = parse(input);
+    let value = program.to_json();
+    let restored = Program::from_json(&Json::parse(&value.to_string()).unwrap()).unwrap();
+
+    assert_eq!(restored.to_json(), value);
+    assert_eq!(restored.to_string(), program.to_string());
+
+    let statements = &restored.statements;
+    let let_stmt: LetStatement = (&statements[0]).try_into().unwrap();
+    assert_eq!(let_stmt, (&program.statements[0]).try_into().unwrap());
+}
+
+// else if は JSON を通しても else if のまま整形できる
+#[test]
+fn test_json_roundtrip_else_if() {
+    let program = parse("if (a) { 1 } else if (b) { 2 } else { if (c) { 3 } }");
+    let restored = Program::from_json(&program.to_json()).unwrap();
+    assert_eq!(restored, program);
+
+    let outer: ExpressionStatement = (&restored.statements[0]).try_into().unwrap();
+    let Some(Expression::IfExpression(outer)) = outer.expression else {
+        panic!("not an if expression");
+    };
+    let inner = outer.else_if().unwrap();
+    assert_eq!(
+        outer.alternative.as_ref().unwrap().token.kind,
+        TokenKind::If
+    );
+    assert_eq!(
+        inner.alternative.as_ref().unwrap().token.kind,
+        TokenKind::LBrace
+    );
+}
+
+#[test]
+fn test_from_json_errors() {
+    let tests = vec![
+        (r#"{"type":"Block"}"#, "expected Program, got Block"),
+        (r#"{"type":"Program"}"#, "Program has no field 'statements'"),
+        (
+            r#"{"type":"Program","statements":[{"type":"Foo"}]}"#,
+            "unknown statement type: Foo",
+        ),
+        (
+            r#"{"type":"Program","statements":[{"type":"ExpressionStatement","span":{"start":0,"end":1},"expression":{"type":"PrefixExpression","span":{"start":0,"end":1},"operator":"~","right":null}}]}"#,
+            "unknown operator '~'",
+        ),
+        (
+            r#"{"type":"Program","statements":[{"type":"ExpressionStatement","span":{"start":0,"end":1},"expression":{"type":"MatchExpression","span":{"start":0,"end":1},"subject":null,"arms":[{"type":"MatchArm","span":{"start":0,"end":1},"pattern":{"type":"ArrayLiteral","span":{"start":0,"end":1},"elements":[]},"guard":null,"body":null}]}}]}"#,
+            "unknown pattern type: ArrayLiteral",
+        ),
+        (
+            r#"{"type":"Program","statements":[{"type":"LetStatement","span":{"start":0,"end":3},"pattern":{"type":"IntegerLiteral","span":{"start":4,"end":5},"value":1},"value":null}]}"#,
+            "binding pattern must not be a literal, got IntegerLiteral",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let value = Json::parse(input).unwrap();
+        assert_eq!(Program::from_json(&value).unwrap_err(), expected);
+    }
+}
+
+#[test]
+fn test_to_sexp() {
+    let tests = vec![
+        ("let x = 1 + 2 * 3;", "(program (let x (+ 1 (* 2 3))))"),
+        ("return -a;", "(program (return (- a)))"),
+        (
+            "if (x < y) { x } else { !true }",
+            "(program (expr (if (< x y) (block (expr x)) (block (expr (! true))))))",
+        ),
+        ("if (x) {}", "(program (expr (if x (block))))"),
+        (
+            "let f = fn(a, b) { a }; f(1, g())",
+            "(program (let f (fn (params a b) (block (expr a)))) (expr (call f 1 (call g))))",
+        ),
+        (
+            r#"{"a": [1, "b"]}["a"][0]"#,
+            r#"(program (expr (index (index (hash (pair "a" (array 1 "b"))) "a") 0)))"#,
+        ),
+        (
+            "while (x) { break; continue; }",
+            "(program (while x (block (break) (continue))))",
+        ),
+        (
+            "for (k, v in 0..=n) { k }",
+            "(program (for (vars k v) (..= 0 n) (block (expr k))))",
+        ),
+        ("a = b[0] *= 2", "(program (expr (= a (*= (index b 0) 2))))"),
+        (
+            "let [a, ..b] = fn({1: c}) { c };",
+            "(program (let (array-pattern a (rest b)) (fn (params (hash-pattern (pair 1 c))) (block (expr c)))))",
+        ),
+        (
+            r#"match (x) { [h, ..t] if h => t, {"k": -1} => 0, [..] => 1, _ => 2 }"#,
+            r#"(program (expr (match x (arm (array-pattern h (rest t)) h t) (arm (hash-pattern (pair "k" -1)) nil 0) (arm (array-pattern (rest)) nil 1) (arm _ nil 2))))"#,
+        ),
+        (
+            r#"import "m.monkey" as m; export let x = m.a.b;"#,
+            r#"(program (import "m.monkey" m) (export (let x (. (. m a) b))))"#,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(parse(input).to_sexp(), expected);
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod test;
+
+use crate::object::{Builtin, Object};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// puts の書き込み先。REPL やテストでは標準出力以外に差し替える
+pub type Output = Rc<RefCell<dyn Write>>;
+
+pub fn stdout() -> Output {
+    Rc::new(RefCell::new(io::stdout()))
+}
+
+// VM はこの並びの番号で組み込み関数を呼ぶので、並べ替えてはいけない
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "len",
+        function: len,
+    },
+    Builtin {
+        name: "puts",
+        function: puts,
+    },
+    Builtin {
+        name: "first",
+        function: first,
+    },
+    Builtin {
+        name: "last",
+        function: last,
+    },
+    Builtin {
+        name: "rest",
+        function: rest,
+    },
+    Builtin {
+        name: "push",
+        function: push,
+    },
+    Builtin {
+        name: "type",
+        function: type_of,
+    },
+    Builtin {
+        name: "str",
+        function: str,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<(usize, Builtin)> {
+    BUILTINS
+        .iter()
+        .position(|b| b.name == name)
+        .map(|index| (index, BUILTINS[index]))
+}
+
+fn check_arity(args: &[Object], want: usize) -> Result<(), String> {
+    if args.len() != want {
+        return Err(format!(
+            "wrong number of arguments: want={}, got={}",
+            want,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn array_argument<'a>(name: &str, arg: &'a Object) -> Result<&'a [Object], String> {
+    match arg {
+        Object::Array(elements) => Ok(elements),
+        other => Err(format!(
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            other.type_name()
+        )),
+    }
+}
+
+fn len(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    let len = match &args[0] {
+        Object::String(value) => value.chars().count(),
+        Object::Array(elements) => elements.len(),
+        Object::Hash(hash) => hash.len(),
+        other => {
+            return Err(format!(
+                "argument to `len` not supported, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    Ok(Object::Integer(len as i64))
+}
+
+fn puts(args: &[Object], out: &mut dyn Write) -> Result<Object, String> {
+    for arg in args {
+        writeln!(out, "{}", arg).map_err(|e| format!("puts: {}", e))?;
+    }
+    Ok(Object::Null)
+}
+
+fn first(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    let elements = array_argument("first", &args[0])?;
+    Ok(elements.first().cloned().unwrap_or(Object::Null))
+}
+
+fn last(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    let elements = array_argument("last", &args[0])?;
+    Ok(elements.last().cloned().unwrap_or(Object::Null))
+}
+
+// 先頭を除いた新しい配列を返す。空の配列なら null
+fn rest(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    let elements = array_argument("rest", &args[0])?;
+    Ok(match elements.split_first() {
+        Some((_, rest)) => Object::Array(Rc::new(rest.to_vec())),
+        None => Object::Null,
+    })
+}
+
+// 元の配列は変えずに、末尾に足した新しい配列を返す
+fn push(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 2)?;
+    let mut elements = array_argument("push", &args[0])?.to_vec();
+    elements.push(args[1].clone());
+    Ok(Object::Array(Rc::new(elements)))
+}
+
+fn type_of(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    Ok(Object::String(args[0].type_name().into()))
+}
+
+fn str(args: &[Object], _: &mut dyn Write) -> Result<Object, String> {
+    check_arity(args, 1)?;
+    Ok(match &args[0] {
+        Object::String(_) => args[0].clone(),
+        other => Object::String(other.to_string().into()),
+    })
+}
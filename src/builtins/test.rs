@@ -0,0 +1,130 @@
+use super::*;
+use crate::engine::{Engine, Interpreter};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+// 両方のエンジンで実行し、結果が一致することも確かめる。エラーは場所を除いて比べる
+fn run(input: &str) -> Object {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+    let eval = Interpreter::new(Engine::Eval).run(&program);
+    let vm = Interpreter::new(Engine::Vm).run(&program);
+    assert_eq!(eval, vm, "input: {}", input);
+    match eval {
+        Some(Object::Error(e)) => error(&e.message),
+        eval => eval.unwrap_or(Object::Null),
+    }
+}
+
+fn string(value: &str) -> Object {
+    Object::String(value.into())
+}
+
+fn array(elements: Vec<Object>) -> Object {
+    Object::Array(Rc::new(elements))
+}
+
+fn error(message: &str) -> Object {
+    Object::error(message)
+}
+
+#[test]
+fn test_builtin_functions() {
+    let tests = vec![
+        (r#"len("")"#, Object::Integer(0)),
+        (r#"len("four")"#, Object::Integer(4)),
+        (r#"len("日本語")"#, Object::Integer(3)),
+        ("len([1, 2, 3])", Object::Integer(3)),
+        ("len([])", Object::Integer(0)),
+        ("first([1, 2, 3])", Object::Integer(1)),
+        ("first([])", Object::Null),
+        ("last([1, 2, 3])", Object::Integer(3)),
+        ("last([])", Object::Null),
+        (
+            "rest([1, 2, 3])",
+            array(vec![Object::Integer(2), Object::Integer(3)]),
+        ),
+        ("rest([1])", array(vec![])),
+        ("rest([])", Object::Null),
+        ("push([], 1)", array(vec![Object::Integer(1)])),
+        (
+            "let a = [1]; push(a, 2); a",
+            array(vec![Object::Integer(1)]),
+        ),
+        ("type(1)", string("INTEGER")),
+        (r#"type("")"#, string("STRING")),
+        ("type(len)", string("BUILTIN")),
+        ("str(12)", string("12")),
+        ("str([1, true])", string("[1, true]")),
+        (r#"str("a")"#, string("a")),
+        // ユーザーの束縛が組み込み関数より優先される
+        ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+        ("let f = fn(len) { len }; f(1)", Object::Integer(1)),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(run(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_builtin_errors() {
+    let tests = vec![
+        ("len(1)", "argument to `len` not supported, got INTEGER"),
+        (
+            r#"len("one", "two")"#,
+            "wrong number of arguments: want=1, got=2",
+        ),
+        ("first(1)", "argument to `first` must be ARRAY, got INTEGER"),
+        (
+            "last(true)",
+            "argument to `last` must be ARRAY, got BOOLEAN",
+        ),
+        (
+            r#"rest("ab")"#,
+            "argument to `rest` must be ARRAY, got STRING",
+        ),
+        (
+            "push(1, 1)",
+            "argument to `push` must be ARRAY, got INTEGER",
+        ),
+        ("push([])", "wrong number of arguments: want=2, got=1"),
+        ("type()", "wrong number of arguments: want=1, got=0"),
+        ("str(1, 2)", "wrong number of arguments: want=1, got=2"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(run(input), error(expected), "input: {}", input);
+    }
+}
+
+#[test]
+fn test_puts_writes_to_output() {
+    let input = r#"puts("hello", 1, [true]); puts(); puts("a\nb")"#;
+    let mut l = Lexer::new(input);
+    let program = Parser::new(&mut l).parse_program();
+
+    for engine in [Engine::Eval, Engine::Vm] {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let result = Interpreter::new(engine)
+            .with_output(output.clone())
+            .run(&program);
+        assert_eq!(result, Some(Object::Null), "engine: {}", engine);
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "hello\n1\n[true]\na\nb\n",
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn test_lookup() {
+    assert_eq!(lookup("len").map(|(i, b)| (i, b.name)), Some((0, "len")));
+    assert_eq!(lookup("str").map(|(i, _)| i), Some(BUILTINS.len() - 1));
+    assert!(lookup("print").is_none());
+}
@@ -0,0 +1,673 @@
+use crate::ast::json::ToJson;
+use crate::ast::sexp::ToSexp;
+use crate::ast::Program;
+use crate::compiler::Compiler;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::disasm::disassemble;
+use crate::engine::{has_result, Engine, Interpreter};
+use crate::evaluator::THREAD_STACK_SIZE;
+use crate::formatter;
+use crate::lexer::Lexer;
+use crate::limits::Limits;
+use crate::lint;
+use crate::lsp;
+use crate::mkc;
+use crate::module::Loader;
+use crate::object::Object;
+use crate::parser::{Parser, SyntaxError};
+use crate::repl;
+use crate::resolver::{Resolution, Resolver};
+use crate::typecheck;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+// monkey-rust fmt [--check] [--error-format human|json] [FILE...]
+// ファイルが指定されなければ標準入力を整形して標準出力に書き出す
+pub fn fmt(args: &[String]) -> ExitCode {
+    let mut check = false;
+    let mut error_format = ErrorFormat::default();
+    let mut paths = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        let input = match read_source(None) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        return match formatter::format(&input) {
+            Ok(formatted) if check => {
+                if formatted == input {
+                    ExitCode::SUCCESS
+                } else {
+                    eprintln!("<stdin> is not formatted");
+                    ExitCode::FAILURE
+                }
+            }
+            Ok(formatted) => {
+                print!("{}", formatted);
+                ExitCode::SUCCESS
+            }
+            Err(errors) => {
+                report_syntax_errors(error_format, "<stdin>", &input, &errors);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let mut ok = true;
+    for path in paths {
+        let input = match read_source(Some(path)) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+
+        match formatter::format(&input) {
+            Ok(formatted) if formatted == input => {}
+            Ok(_) if check => {
+                eprintln!("{} is not formatted", path);
+                ok = false;
+            }
+            Ok(formatted) => {
+                if let Err(e) = fs::write(path, formatted) {
+                    eprintln!("{}: {}", path, e);
+                    ok = false;
+                }
+            }
+            Err(errors) => {
+                report_syntax_errors(error_format, path, &input, &errors);
+                ok = false;
+            }
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// monkey-rust lint [--config FILE] [--error-format human|json] [FILE...]
+// 設定ファイルを指定しなければ、ファイルのあるディレクトリから上にたどって .monkeylint.json を探す。
+// 重大度が error のものがあれば失敗にする
+pub fn lint(args: &[String]) -> ExitCode {
+    let mut config_path = None;
+    let mut error_format = ErrorFormat::default();
+    let mut paths = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" => match iter.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--config requires a file");
+                    return ExitCode::from(2);
+                }
+            },
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => paths.push(Some(arg.as_str())),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(None);
+    }
+
+    let mut ok = true;
+    for path in paths {
+        let config = match config_path.clone().or_else(|| find_lint_config(path)) {
+            Some(config_path) => match fs::read_to_string(&config_path)
+                .map_err(|e| e.to_string())
+                .and_then(|input| lint::Config::parse(&input))
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("{}: {}", config_path.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            },
+            None => lint::Config::default(),
+        };
+        let input = match read_source(path) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+
+        let path = path.unwrap_or("<stdin>");
+        match lint::lint(&input, &config) {
+            Ok(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    report(error_format, path, &input, diagnostic);
+                    ok &= diagnostic.severity != Severity::Error;
+                }
+            }
+            Err(errors) => {
+                report_syntax_errors(error_format, path, &input, &errors);
+                ok = false;
+            }
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn find_lint_config(path: Option<&str>) -> Option<PathBuf> {
+    let start = match path {
+        Some(path) => fs::canonicalize(path).ok()?.parent()?.to_path_buf(),
+        None => env::current_dir().ok()?,
+    };
+    start
+        .ancestors()
+        .map(|dir| dir.join(".monkeylint.json"))
+        .find(|config| config.is_file())
+}
+
+// monkey-rust check [--error-format human|json] [FILE...]
+// 型を推論して、合わないところを警告する。実行を止めるものではないので、
+// 型の警告だけなら成功にする
+pub fn check(args: &[String]) -> ExitCode {
+    let mut error_format = ErrorFormat::default();
+    let mut paths = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => paths.push(Some(arg.as_str())),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(None);
+    }
+
+    let mut ok = true;
+    for path in paths {
+        let Ok((input, program)) = load_program(path, error_format) else {
+            ok = false;
+            continue;
+        };
+        let Ok(resolution) = resolve_program(path, error_format, &input, &program) else {
+            ok = false;
+            continue;
+        };
+        let typing = typecheck::check(&program, &resolution);
+        for warning in &typing.warnings {
+            let diagnostic = Diagnostic::warning(&warning.message).with_span(warning.span);
+            report(error_format, path.unwrap_or("<stdin>"), &input, &diagnostic);
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// monkey-rust lsp
+// 標準入出力で Language Server Protocol を話す
+pub fn lsp(args: &[String]) -> ExitCode {
+    if let Some(arg) = args.first() {
+        eprintln!("unexpected argument: {}", arg);
+        return ExitCode::from(2);
+    }
+    if lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// 診断の出力形式。human は標準エラー出力が端末なら色を付ける
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+fn parse_error_format(arg: Option<&String>) -> Result<ErrorFormat, ExitCode> {
+    match arg.map(String::as_str) {
+        Some("human") => Ok(ErrorFormat::Human),
+        Some("json") => Ok(ErrorFormat::Json),
+        f => {
+            eprintln!(
+                "unknown error format: {} (expected human or json)",
+                f.unwrap_or("")
+            );
+            Err(ExitCode::from(2))
+        }
+    }
+}
+
+// JSON は 1 件ずつ 1 行に書く
+fn report(format: ErrorFormat, path: &str, source: &str, diagnostic: &Diagnostic) {
+    match format {
+        ErrorFormat::Human => {
+            let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+            eprint!("{}", diagnostic.render(path, source, color));
+        }
+        ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(path, source)),
+    }
+}
+
+fn report_syntax_errors(format: ErrorFormat, path: &str, source: &str, errors: &[SyntaxError]) {
+    for error in errors {
+        report(format, path, source, &error.into());
+    }
+}
+
+// monkey-rust parse [--format json|sexp] [--error-format human|json] [FILE]
+pub fn parse(args: &[String]) -> ExitCode {
+    let mut format = "json";
+    let mut error_format = ErrorFormat::default();
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().map(String::as_str) {
+                Some(f @ ("json" | "sexp")) => format = f,
+                f => {
+                    eprintln!("unknown format: {}", f.unwrap_or(""));
+                    return ExitCode::from(2);
+                }
+            },
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => path = Some(arg.as_str()),
+        }
+    }
+
+    let (_, program) = match load_program(path, error_format) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+
+    match format {
+        "sexp" => println!("{}", program.to_sexp()),
+        _ => println!("{}", program.to_json().pretty()),
+    }
+    ExitCode::SUCCESS
+}
+
+// monkey-rust [--engine eval|vm]
+pub fn repl(args: &[String]) -> ExitCode {
+    let mut engine = Engine::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--engine" => match parse_engine(iter.next()) {
+                Ok(e) => engine = e,
+                Err(code) => return code,
+            },
+            _ => {
+                eprintln!("unknown argument: {}", arg);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    // コマンドは main が THREAD_STACK_SIZE のスレッドで動かす
+    repl::start_with_stack_size(io::stdin(), io::stdout(), engine, THREAD_STACK_SIZE);
+    ExitCode::SUCCESS
+}
+
+// monkey-rust run [--engine eval|vm] [--max-steps N] [--max-depth N] [--max-alloc BYTES]
+//                  [--timeout-ms MS] [--module-path DIR]... [--error-format human|json] [FILE]
+// 最後の式の値を表示する
+// .mkc ファイルはコンパイル済みのバイトコードとして VM で実行する
+pub fn run(args: &[String]) -> ExitCode {
+    let mut engine = None;
+    let mut limits = Limits::new();
+    let mut search_path = vec![];
+    let mut error_format = ErrorFormat::default();
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let result = match arg.as_str() {
+            "--engine" => parse_engine(iter.next()).map(|e| engine = Some(e)),
+            "--max-steps" => {
+                parse_number(arg, iter.next()).map(|n| limits = limits.with_max_steps(n))
+            }
+            "--max-depth" => parse_number(arg, iter.next())
+                .map(|n| limits = limits.with_max_call_depth(n as usize)),
+            "--max-alloc" => parse_number(arg, iter.next())
+                .map(|n| limits = limits.with_max_allocation(n as usize)),
+            "--timeout-ms" => parse_number(arg, iter.next())
+                .map(|n| limits = limits.with_timeout(Duration::from_millis(n))),
+            "--module-path" => parse_dir(iter.next()).map(|dir| search_path.push(dir)),
+            "--error-format" => parse_error_format(iter.next()).map(|f| error_format = f),
+            _ => {
+                path = Some(arg.as_str());
+                Ok(())
+            }
+        };
+        if let Err(code) = result {
+            return code;
+        }
+    }
+
+    let mut source = None;
+    let result = if let Some(path) = path.filter(|p| p.ends_with(".mkc")) {
+        if engine == Some(Engine::Eval) {
+            eprintln!("{}: the eval engine cannot run bytecode files", path);
+            return ExitCode::from(2);
+        }
+        let file = match fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| mkc::decode(&bytes))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let value = Interpreter::new(Engine::Vm)
+            .with_limits(limits)
+            .run_bytecode(file.bytecode);
+        (value.is_error() || file.has_result).then_some(value)
+    } else {
+        let program = match load_program(path, error_format) {
+            Ok((input, program)) => {
+                if let Err(code) = resolve_program(path, error_format, &input, &program) {
+                    return code;
+                }
+                source = Some(input);
+                program
+            }
+            Err(code) => return code,
+        };
+        Interpreter::new(engine.unwrap_or_default())
+            .with_limits(limits)
+            .with_stack_size(THREAD_STACK_SIZE)
+            .with_loader(module_loader(path, search_path))
+            .run(&program)
+    };
+
+    match result {
+        Some(Object::Error(error)) => {
+            let path = path.unwrap_or("<stdin>");
+            // エラーの場所や呼び出した場所があるモジュールは、読み直して引用する
+            let mut files: HashMap<Option<String>, (String, String)> = HashMap::new();
+            if let Some(source) = source {
+                files.insert(None, (path.to_string(), source));
+                let modules = error.stack.iter().map(|frame| frame.module.as_deref());
+                for module in modules.chain([error.module.as_deref()]).flatten() {
+                    let module_path = module_path(path, module);
+                    if let Ok(source) = fs::read_to_string(&module_path) {
+                        let file = (module_path.display().to_string(), source);
+                        files.insert(Some(module.to_string()), file);
+                    }
+                }
+            }
+            let file = |module: Option<&str>| {
+                files
+                    .get(&module.map(String::from))
+                    .map(|(path, source)| (path.as_str(), source.as_str()))
+            };
+            let diagnostic = Diagnostic::from_runtime_error_in(&error, file);
+            let (path, source) = file(error.module.as_deref()).unwrap_or((path, ""));
+            report(error_format, path, source, &diagnostic);
+            ExitCode::FAILURE
+        }
+        Some(result) => {
+            println!("{}", result);
+            ExitCode::SUCCESS
+        }
+        None => ExitCode::SUCCESS,
+    }
+}
+
+// monkey-rust build [--no-debug] [-o OUT] [--module-path DIR]... [--error-format human|json]
+//                    [FILE]
+// 出力先を省略すると FILE の拡張子を .mkc に替えたパスに書き出す
+pub fn build(args: &[String]) -> ExitCode {
+    let mut debug = true;
+    let mut output = None;
+    let mut search_path = vec![];
+    let mut error_format = ErrorFormat::default();
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--no-debug" => debug = false,
+            "-o" => match iter.next() {
+                Some(out) => output = Some(PathBuf::from(out)),
+                None => {
+                    eprintln!("-o requires a path");
+                    return ExitCode::from(2);
+                }
+            },
+            "--module-path" => match parse_dir(iter.next()) {
+                Ok(dir) => search_path.push(dir),
+                Err(code) => return code,
+            },
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => path = Some(arg.as_str()),
+        }
+    }
+
+    let Some(output) = output.or_else(|| path.map(|p| Path::new(p).with_extension("mkc"))) else {
+        eprintln!("an output path is required when reading from stdin");
+        return ExitCode::from(2);
+    };
+
+    let (source, program) = match load_program(path, error_format) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+    if let Err(code) = resolve_program(path, error_format, &source, &program) {
+        return code;
+    }
+
+    let mut compiler = Compiler::new().with_loader(module_loader(path, search_path));
+    if let Err(e) = compiler.compile(&program) {
+        let diagnostic =
+            Diagnostic::from_runtime_error(&e, path.unwrap_or("<stdin>"), Some(&source));
+        report(
+            error_format,
+            path.unwrap_or("<stdin>"),
+            &source,
+            &diagnostic,
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let file = mkc::BytecodeFile {
+        bytecode: compiler.bytecode(),
+        has_result: has_result(&program),
+    };
+    match mkc::encode(&file, debug).and_then(|bytes| {
+        fs::write(&output, bytes).map_err(|e| format!("{}: {}", output.display(), e))
+    }) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// monkey-rust disasm [--module-path DIR]... [--error-format human|json] [FILE]
+pub fn disasm(args: &[String]) -> ExitCode {
+    let mut search_path = vec![];
+    let mut error_format = ErrorFormat::default();
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--module-path" => match parse_dir(iter.next()) {
+                Ok(dir) => search_path.push(dir),
+                Err(code) => return code,
+            },
+            "--error-format" => match parse_error_format(iter.next()) {
+                Ok(f) => error_format = f,
+                Err(code) => return code,
+            },
+            _ => path = Some(arg.as_str()),
+        }
+    }
+    let (source, program) = match load_program(path, error_format) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+    if let Err(code) = resolve_program(path, error_format, &source, &program) {
+        return code;
+    }
+
+    let mut compiler = Compiler::new().with_loader(module_loader(path, search_path));
+    if let Err(e) = compiler.compile(&program) {
+        let diagnostic =
+            Diagnostic::from_runtime_error(&e, path.unwrap_or("<stdin>"), Some(&source));
+        report(
+            error_format,
+            path.unwrap_or("<stdin>"),
+            &source,
+            &diagnostic,
+        );
+        return ExitCode::FAILURE;
+    }
+    print!("{}", disassemble(&compiler.bytecode()));
+    ExitCode::SUCCESS
+}
+
+fn parse_engine(arg: Option<&String>) -> Result<Engine, ExitCode> {
+    arg.map_or("", String::as_str).parse().map_err(|e| {
+        eprintln!("{}", e);
+        ExitCode::from(2)
+    })
+}
+
+fn parse_number(flag: &str, arg: Option<&String>) -> Result<u64, ExitCode> {
+    arg.and_then(|a| a.parse().ok()).ok_or_else(|| {
+        eprintln!("{} requires a non-negative integer", flag);
+        ExitCode::from(2)
+    })
+}
+
+fn parse_dir(arg: Option<&String>) -> Result<PathBuf, ExitCode> {
+    arg.map(PathBuf::from).ok_or_else(|| {
+        eprintln!("--module-path requires a directory");
+        ExitCode::from(2)
+    })
+}
+
+// import は実行するファイルのあるディレクトリ、--module-path、MONKEY_PATH の順に探す
+fn module_loader(path: Option<&str>, mut search_path: Vec<PathBuf>) -> Loader {
+    if let Some(paths) = env::var_os("MONKEY_PATH") {
+        search_path.extend(env::split_paths(&paths));
+    }
+    match path {
+        Some(path) => Loader::for_file(path),
+        None => Loader::default(),
+    }
+    .with_search_path(search_path)
+}
+
+// モジュールの名前は実行したファイルのあるディレクトリからの相対パスか、絶対パス
+fn module_path(path: &str, module: &str) -> PathBuf {
+    Path::new(path)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(module)
+}
+
+// 構文エラーがあれば表示して終了コードを返す
+fn load_program(
+    path: Option<&str>,
+    error_format: ErrorFormat,
+) -> Result<(String, Program), ExitCode> {
+    let input = read_source(path).map_err(|e| {
+        eprintln!("{}", e);
+        ExitCode::FAILURE
+    })?;
+
+    let mut l = Lexer::new(&input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+        report_syntax_errors(error_format, path.unwrap_or("<stdin>"), &input, p.errors());
+        return Err(ExitCode::FAILURE);
+    }
+    // 警告は知らせるだけで、実行は止めない
+    for warning in p.warnings() {
+        let diagnostic = Diagnostic::warning(&warning.message).with_span(warning.span);
+        report(error_format, path.unwrap_or("<stdin>"), &input, &diagnostic);
+    }
+    Ok((input, program))
+}
+
+// 未定義の名前などは実行する前に知らせる。名前を隠しているのは警告にとどめる
+fn resolve_program(
+    path: Option<&str>,
+    error_format: ErrorFormat,
+    source: &str,
+    program: &Program,
+) -> Result<Resolution, ExitCode> {
+    let path = path.unwrap_or("<stdin>");
+    let resolution = Resolver::new().resolve(program);
+    for warning in &resolution.warnings {
+        let diagnostic = Diagnostic::warning(&warning.message).with_span(warning.span);
+        report(error_format, path, source, &diagnostic);
+    }
+    if !resolution.errors.is_empty() {
+        report_syntax_errors(error_format, path, source, &resolution.errors);
+        return Err(ExitCode::FAILURE);
+    }
+    Ok(resolution)
+}
+
+// パスが無ければ標準入力から読む
+fn read_source(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| format!("failed to read stdin: {}", e))?;
+            Ok(input)
+        }
+    }
+}
@@ -0,0 +1,267 @@
+#[cfg(test)]
+mod test;
+
+use crate::token::Span;
+
+pub type Instructions = Vec<u8>;
+
+// 命令の位置と、その命令を生んだソース上の範囲の対応。位置の昇順に並ぶ
+pub type Positions = Vec<(usize, Span)>;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    True,
+    False,
+    Null,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+    JumpNotTruthy,
+    Jump,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    Call,
+    ReturnValue,
+    Return,
+    Closure,
+    GetFree,
+    CurrentClosure,
+    Array,
+    Index,
+    GetBuiltin,
+    Hash,
+    Range,
+    IterInit,
+    IterNext,
+    Dup,
+    IndexPath,
+    SetGlobalIndex,
+    SetLocalIndex,
+    MatchArray,
+    MatchHash,
+    Slice,
+    NoMatch,
+    Mismatch,
+    Module,
+    Member,
+}
+
+// 命令の名前と、各オペランドのバイト幅
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+const OPCODES: [Opcode; 45] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Closure,
+    Opcode::GetFree,
+    Opcode::CurrentClosure,
+    Opcode::Array,
+    Opcode::Index,
+    Opcode::GetBuiltin,
+    Opcode::Hash,
+    Opcode::Range,
+    Opcode::IterInit,
+    Opcode::IterNext,
+    Opcode::Dup,
+    Opcode::IndexPath,
+    Opcode::SetGlobalIndex,
+    Opcode::SetLocalIndex,
+    Opcode::MatchArray,
+    Opcode::MatchHash,
+    Opcode::Slice,
+    Opcode::NoMatch,
+    Opcode::Mismatch,
+    Opcode::Module,
+    Opcode::Member,
+];
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            // 関数の定数の番号と、取り込む変数の数
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+            // 要素の数
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::Hash => ("OpHash", &[2]),
+            // 1 なら終端を含む
+            Opcode::Range => ("OpRange", &[1]),
+            Opcode::IterInit => ("OpIterInit", &[]),
+            // 取り出す値の数。値のあとに続きがあるかどうかを積む
+            Opcode::IterNext => ("OpIterNext", &[1]),
+            // 一番上から複製する値の数
+            Opcode::Dup => ("OpDup", &[1]),
+            // 添字の数。入れ物を取り、その下に積まれた添字をたどった要素を積む。添字は残す
+            Opcode::IndexPath => ("OpIndexPath", &[1]),
+            // 変数の番号と添字の数。添字と値を取り、変数の入れ物をその場で書き換えて値を積む
+            Opcode::SetGlobalIndex => ("OpSetGlobalIndex", &[2, 1]),
+            Opcode::SetLocalIndex => ("OpSetLocalIndex", &[1, 1]),
+            // 要素の数と、1 なら残りを受けることを表すフラグ。形が合うかどうかを積む
+            Opcode::MatchArray => ("OpMatchArray", &[2, 1]),
+            // キーの数。値とキーを取り、キーをすべて持つハッシュかどうかを積む
+            Opcode::MatchHash => ("OpMatchHash", &[2]),
+            // 配列のこの位置から後ろを取り出す
+            Opcode::Slice => ("OpSlice", &[2]),
+            // どの腕にも一致しなかった値を取り、実行時エラーにする
+            Opcode::NoMatch => ("OpNoMatch", &[]),
+            // パターンの文字列と形の合わなかった値を取り、実行時エラーにする
+            Opcode::Mismatch => ("OpMismatch", &[]),
+            // export した名前の数。モジュールの名前と、名前と値の組を取ってモジュールを積む
+            Opcode::Module => ("OpModule", &[2]),
+            // 値と名前を取り、モジュールが export したその名前の値を積む
+            Opcode::Member => ("OpMember", &[]),
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+
+    // スタックから取り除く値と積む値の数
+    pub fn stack_effect(self, operands: &[usize]) -> (usize, usize) {
+        let operand = operands.first().copied().unwrap_or(0);
+        match self {
+            Opcode::Constant | Opcode::True | Opcode::False | Opcode::Null => (0, 1),
+            Opcode::GetGlobal | Opcode::GetLocal => (0, 1),
+            Opcode::Pop | Opcode::SetGlobal | Opcode::SetLocal | Opcode::JumpNotTruthy => (1, 0),
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::GreaterThan
+            | Opcode::LessThan => (2, 1),
+            Opcode::Minus | Opcode::Bang => (1, 1),
+            Opcode::Jump | Opcode::Return => (0, 0),
+            Opcode::Call => (operand + 1, 1),
+            Opcode::ReturnValue => (1, 0),
+            // 取り込む変数の数は 2 つ目のオペランド
+            Opcode::Closure => (operands.get(1).copied().unwrap_or(0), 1),
+            Opcode::GetFree | Opcode::CurrentClosure | Opcode::GetBuiltin => (0, 1),
+            Opcode::Array | Opcode::Hash => (operand, 1),
+            Opcode::Index | Opcode::Range => (2, 1),
+            Opcode::IterInit => (1, 1),
+            Opcode::IterNext => (0, operand + 1),
+            Opcode::Dup => (operand, operand * 2),
+            Opcode::IndexPath => (operand + 1, operand + 1),
+            // 添字の数は 2 つ目のオペランド
+            Opcode::SetGlobalIndex | Opcode::SetLocalIndex => {
+                (operands.get(1).copied().unwrap_or(0) + 1, 1)
+            }
+            Opcode::MatchArray | Opcode::Slice => (1, 1),
+            Opcode::MatchHash => (operand + 1, 1),
+            // 先へは進まないが、match 式の値の代わりに一つ積んだことにする
+            Opcode::NoMatch => (1, 1),
+            // 先へは進まないが、束縛のあとへ続くことにする
+            Opcode::Mismatch => (2, 0),
+            Opcode::Module => (operand * 2 + 1, 1),
+            Opcode::Member => (2, 1),
+        }
+    }
+}
+
+// オペランドはビッグエンディアンで並べる
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let def = op.definition();
+    let mut instruction = vec![op as u8];
+
+    for (operand, width) in operands.iter().zip(def.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+
+    instruction
+}
+
+// 読んだオペランドと、読み進めたバイト数を返す
+pub fn read_operands(def: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(def.operand_widths.len());
+    let mut offset = 0;
+
+    for width in def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
@@ -0,0 +1,55 @@
+use super::*;
+
+#[test]
+fn test_make() {
+    let tests = vec![
+        (
+            Opcode::Constant,
+            vec![65534],
+            vec![Opcode::Constant as u8, 255, 254],
+        ),
+        (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+        (
+            Opcode::GetLocal,
+            vec![255],
+            vec![Opcode::GetLocal as u8, 255],
+        ),
+    ];
+
+    for (op, operands, expected) in tests {
+        assert_eq!(make(op, &operands), expected);
+    }
+}
+
+#[test]
+fn test_read_operands() {
+    let tests = vec![
+        (Opcode::Constant, vec![65535], 2),
+        (Opcode::GetLocal, vec![255], 1),
+        (Opcode::Pop, vec![], 0),
+        (Opcode::Module, vec![65535], 2),
+        (Opcode::SetLocalIndex, vec![255, 3], 2),
+    ];
+
+    for (op, operands, bytes_read) in tests {
+        let instruction = make(op, &operands);
+        let def = op.definition();
+        let (read, n) = read_operands(&def, &instruction[1..]);
+        assert_eq!(n, bytes_read);
+        assert_eq!(read, operands);
+    }
+}
+
+#[test]
+fn test_from_byte() {
+    for byte in 0..=u8::MAX {
+        if let Some(op) = Opcode::from_byte(byte) {
+            assert_eq!(op as u8, byte);
+        }
+    }
+    assert_eq!(
+        Opcode::from_byte(Opcode::Return as u8),
+        Some(Opcode::Return)
+    );
+    assert_eq!(Opcode::from_byte(200), None);
+}
@@ -0,0 +1,1021 @@
+mod symbol_table;
+#[cfg(test)]
+mod test;
+
+use crate::ast::{
+    AssignExpression, BlockStatement, Expression, ForStatement, FunctionLiteral, IfExpression,
+    ImportStatement, InfixExpression, LetStatement, MatchExpression, Node, Pattern, Program,
+    Statement, WhileStatement,
+};
+use crate::code::{make, Instructions, Opcode, Positions};
+use crate::module::{self, Loader};
+use crate::object::{CompiledFunction, Object, RuntimeError, StackFrame};
+use crate::token::Span;
+use std::path::Path;
+use std::rc::Rc;
+
+pub use symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    pub positions: Positions,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+// 関数ごとに命令列を分けて組み立てる
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    positions: Positions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    // 命令列のこの時点でスタックに積まれている値の数
+    stack_height: usize,
+    loops: Vec<Loop>,
+}
+
+// コンパイル中のループ。break の飛び先はループを出るときに書き換える
+struct Loop {
+    start: usize,
+    breaks: Vec<usize>,
+    stack_height: usize,
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    // いまコンパイルしているノードのソース上の範囲
+    span: Span,
+    // 次にコンパイルする関数リテラルを束縛する名前
+    function_name: Option<String>,
+    // コンパイル中の match 式の入れ子の数。値を入れておく変数の名前に使う
+    matches: usize,
+    loader: Loader,
+    // コンパイル中のコードがあるモジュール
+    module: Option<Rc<str>>,
+    // 読み込めなかった import のエラー。評価器と同じく import の場所を指す
+    import_error: Option<RuntimeError>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler::new_with_state(SymbolTable::new(), vec![])
+    }
+
+    // REPL では前の行までの定義を引き継ぐ
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Compiler {
+        Compiler {
+            constants,
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            span: Span::default(),
+            function_name: None,
+            matches: 0,
+            loader: Loader::default(),
+            module: None,
+            import_error: None,
+        }
+    }
+
+    pub fn with_loader(mut self, loader: Loader) -> Compiler {
+        self.loader = loader;
+        self
+    }
+
+    pub fn into_state(self) -> (SymbolTable, Vec<Object>) {
+        (self.symbol_table, self.constants)
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.current_scope().instructions.clone(),
+            constants: self.constants.clone(),
+            positions: self.current_scope().positions.clone(),
+        }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        self.compile_program(program).map_err(|message| {
+            self.import_error
+                .take()
+                .unwrap_or_else(|| RuntimeError::new(message))
+        })
+    }
+
+    fn compile_program(&mut self, program: &Program) -> Result<(), String> {
+        for stmt in &program.statements {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        let outer_span = std::mem::replace(&mut self.span, stmt.span());
+        self.compile_statement_inner(stmt)?;
+        self.span = outer_span;
+        Ok(())
+    }
+
+    fn compile_statement_inner(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::LetStatement(s) => self.compile_let(s)?,
+            Statement::ReturnStatement(s) => {
+                self.compile_optional(s.return_value.as_ref())?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            Statement::ExpressionStatement(s) => {
+                self.compile_optional(s.expression.as_ref())?;
+                self.emit(Opcode::Pop, &[]);
+            }
+            Statement::BlockStatement(s) => self.compile_block(s)?,
+            Statement::WhileStatement(s) => self.compile_while(s)?,
+            Statement::ForStatement(s) => self.compile_for(s)?,
+            Statement::BreakStatement(_) => {
+                let position = self.jump_out_of_loop("break", None)?;
+                let current = self.current_scope_mut().loops.last_mut();
+                current.expect("inside a loop").breaks.push(position);
+            }
+            Statement::ContinueStatement(_) => {
+                let start = self.current_loop("continue")?.start;
+                self.jump_out_of_loop("continue", Some(start))?;
+            }
+            Statement::ImportStatement(s) => self.compile_import(s)?,
+            Statement::ExportStatement(s) => self.compile_let(&s.statement)?,
+        }
+        Ok(())
+    }
+
+    fn compile_let(&mut self, s: &LetStatement) -> Result<(), String> {
+        // 関数には束縛する名前を教えて、本体から自分自身を参照できるようにする
+        if let (Some(Expression::FunctionLiteral(_)), Some(name)) =
+            (&s.value, s.pattern.as_identifier())
+        {
+            self.function_name = Some(name.value.clone());
+        }
+        self.compile_optional(s.value.as_ref())?;
+        if let Some(name) = s.pattern.as_identifier() {
+            let symbol = self.define(&name.value)?;
+            self.store_symbol(&symbol);
+        } else {
+            // 形をほどく値は隠れた変数に入れておき、そこから取り出す
+            let symbol = self.define("let")?;
+            self.store_symbol(&symbol);
+            self.compile_destructure(&s.pattern, &symbol)?;
+        }
+        Ok(())
+    }
+
+    // モジュールは最初の import でコンパイルし、実行してできた値を隠れたグローバル変数に
+    // 入れておく。二度目からはその変数を読むだけにする
+    fn compile_import(&mut self, s: &ImportStatement) -> Result<(), String> {
+        let span = s.token.span;
+        let path = self
+            .loader
+            .resolve(&s.path)
+            .map_err(|e| self.import_failed(span, e))?;
+        let cache_name = format!("module {}", path.display());
+        let cached = self.in_global_namespace(|c| c.symbol_table.resolve(&cache_name));
+        let cache = match cached {
+            Some(symbol) => symbol,
+            None => {
+                self.loader
+                    .enter(&path)
+                    .map_err(|e| self.import_failed(span, e))?;
+                let result = self.compile_module(&path, span);
+                self.loader.leave();
+                result?;
+                let symbol = self.in_global_namespace(|c| c.define(&cache_name))?;
+                self.store_symbol(&symbol);
+                symbol
+            }
+        };
+        self.load_symbol(&cache)?;
+        let alias = self.define(&s.alias.value)?;
+        self.store_symbol(&alias);
+        Ok(())
+    }
+
+    // モジュールの本体は引数のない関数にしてその場で呼び出し、export した名前と値から
+    // モジュールを作る。最上位の let はモジュールの名前空間のグローバル変数になる
+    fn compile_module(&mut self, path: &Path, span: Span) -> Result<(), String> {
+        let program = self
+            .loader
+            .load(path)
+            .map_err(|e| self.import_failed(span, e))?;
+        let name: Rc<str> = self.loader.name(path).into();
+
+        self.scopes.push(CompilationScope::default());
+        let namespace = self
+            .symbol_table
+            .set_namespace(Some(path.display().to_string()));
+        let outer_module = self.module.replace(name.clone());
+        let result = self.compile_module_body(&program);
+        self.module = outer_module;
+        self.symbol_table.set_namespace(namespace);
+        let scope = self.scopes.pop().expect("no compilation scope");
+        if let (Err(_), Some(error)) = (&result, &mut self.import_error) {
+            error.stack.push(StackFrame {
+                function: Some(format!("module {}", name).into()),
+                span: Some(span),
+                module: self.module.clone(),
+            });
+        }
+        let exports = result?;
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            positions: scope.positions,
+            name: Some(format!("module {}", name).into()),
+            module: Some(name.clone()),
+            ..CompiledFunction::default()
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)))?;
+        self.emit(Opcode::Closure, &[index, 0]);
+        self.emit(Opcode::Call, &[0]);
+        self.emit(Opcode::Pop, &[]);
+
+        if exports.len() > u16::MAX as usize {
+            return Err(format!("too many exports: {}", exports.len()));
+        }
+        self.load_literal(Object::String(name))?;
+        for (export, symbol) in &exports {
+            self.load_literal(Object::String(export.as_str().into()))?;
+            self.load_symbol(symbol)?;
+        }
+        self.emit(Opcode::Module, &[exports.len()]);
+        Ok(())
+    }
+
+    fn compile_module_body(&mut self, program: &Program) -> Result<Vec<(String, Symbol)>, String> {
+        self.compile_program(program)?;
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[]);
+        }
+        module::exports(program)
+            .into_iter()
+            .map(|export| {
+                let symbol = self.symbol_table.resolve(&export.value);
+                let symbol = symbol.ok_or_else(|| format!("undefined export: {}", export.value))?;
+                Ok((export.value.clone(), symbol))
+            })
+            .collect()
+    }
+
+    fn import_failed(&mut self, span: Span, message: String) -> String {
+        self.import_error = Some(RuntimeError {
+            module: self.module.clone(),
+            ..RuntimeError::new(&message).with_span(span)
+        });
+        message
+    }
+
+    // 隠れた変数は、どのモジュールからも同じものが見えるようにする
+    fn in_global_namespace<T>(&mut self, f: impl FnOnce(&mut Compiler) -> T) -> T {
+        let namespace = self.symbol_table.set_namespace(None);
+        let result = f(self);
+        self.symbol_table.set_namespace(namespace);
+        result
+    }
+
+    fn compile_while(&mut self, s: &WhileStatement) -> Result<(), String> {
+        let start = self.current_instructions().len();
+        self.compile_optional(s.condition.as_ref())?;
+        let exit = self.emit(Opcode::JumpNotTruthy, &[9999]);
+
+        let stack_height = self.current_scope().stack_height;
+        self.current_scope_mut().loops.push(Loop {
+            start,
+            breaks: vec![],
+            stack_height,
+        });
+        self.compile_block(&s.body)?;
+        self.emit(Opcode::Jump, &[jump_target(start)?]);
+        let current = self.current_scope_mut().loops.pop().expect("inside a loop");
+
+        let end = self.current_instructions().len();
+        self.change_operand(exit, end)?;
+        for position in current.breaks {
+            self.change_operand(position, end)?;
+        }
+        Ok(())
+    }
+
+    // 反復の状態はループの間スタックに置いておく。OpIterNext は変数に入れる値と、
+    // 続きがあるかどうかを積む。尽きたときの値は捨ててから反復の状態を取り除く
+    fn compile_for(&mut self, s: &ForStatement) -> Result<(), String> {
+        self.compile_optional(s.iterable.as_ref())?;
+        let span = s.iterable.as_ref().map_or(s.token.span, Expression::span);
+        let outer_span = std::mem::replace(&mut self.span, span);
+        self.emit(Opcode::IterInit, &[]);
+        self.span = outer_span;
+
+        let count = s.variables.len();
+        if !(1..=2).contains(&count) {
+            return Err("for loop needs one or two variables".to_string());
+        }
+        let stack_height = self.current_scope().stack_height;
+        let start = self.emit(Opcode::IterNext, &[count]);
+        let exit = self.emit(Opcode::JumpNotTruthy, &[9999]);
+
+        let symbols = s
+            .variables
+            .iter()
+            .map(|variable| self.define(&variable.value))
+            .collect::<Result<Vec<_>, _>>()?;
+        // 後に積んだ値から取り出す
+        for symbol in symbols.iter().rev() {
+            self.store_symbol(symbol);
+        }
+
+        self.current_scope_mut().loops.push(Loop {
+            start,
+            breaks: vec![],
+            stack_height,
+        });
+        self.compile_block(&s.body)?;
+        self.emit(Opcode::Jump, &[jump_target(start)?]);
+        let current = self.current_scope_mut().loops.pop().expect("inside a loop");
+
+        let exhausted = self.current_instructions().len();
+        self.change_operand(exit, exhausted)?;
+        self.current_scope_mut().stack_height = stack_height + count;
+        for _ in 0..count {
+            self.emit(Opcode::Pop, &[]);
+        }
+
+        let end = self.current_instructions().len();
+        for position in current.breaks {
+            self.change_operand(position, end)?;
+        }
+        self.emit(Opcode::Pop, &[]);
+        Ok(())
+    }
+
+    fn current_loop(&self, keyword: &str) -> Result<&Loop, String> {
+        self.current_scope()
+            .loops
+            .last()
+            .ok_or_else(|| format!("{} outside of loop", keyword))
+    }
+
+    // 式の途中から抜けることもあるので、ループに入ったときより上の値は捨ててから飛ぶ。
+    // 飛び先が決まっていなければあとで書き換える
+    fn jump_out_of_loop(&mut self, keyword: &str, target: Option<usize>) -> Result<usize, String> {
+        let stack_height = self.current_loop(keyword)?.stack_height;
+        for _ in stack_height..self.current_scope().stack_height {
+            self.emit(Opcode::Pop, &[]);
+        }
+        let target = match target {
+            Some(target) => jump_target(target)?,
+            None => 9999,
+        };
+        Ok(self.emit(Opcode::Jump, &[target]))
+    }
+
+    fn compile_block(&mut self, block: &BlockStatement) -> Result<(), String> {
+        for stmt in &block.statements {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    // 構文エラーで欠けた式は null とみなす
+    fn compile_optional(&mut self, exp: Option<&Expression>) -> Result<(), String> {
+        match exp {
+            Some(exp) => self.compile_expression(exp),
+            None => {
+                self.emit(Opcode::Null, &[]);
+                Ok(())
+            }
+        }
+    }
+
+    fn compile_expression(&mut self, exp: &Expression) -> Result<(), String> {
+        let outer_span = std::mem::replace(&mut self.span, exp.span());
+        self.compile_expression_inner(exp)?;
+        self.span = outer_span;
+        Ok(())
+    }
+
+    fn compile_expression_inner(&mut self, exp: &Expression) -> Result<(), String> {
+        match exp {
+            Expression::IntegerLiteral(e) => {
+                let index = self.add_constant(Object::Integer(e.value))?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::Boolean(e) => {
+                self.emit(if e.value { Opcode::True } else { Opcode::False }, &[]);
+            }
+            Expression::Identifier(e) => {
+                let symbol = self
+                    .symbol_table
+                    .resolve(&e.value)
+                    .ok_or_else(|| format!("identifier not found: {}", e.value))?;
+                self.load_symbol(&symbol)?;
+            }
+            Expression::PrefixExpression(e) => {
+                self.compile_optional(e.right.as_deref())?;
+                match e.operator.as_str() {
+                    "!" => self.emit(Opcode::Bang, &[]),
+                    "-" => self.emit(Opcode::Minus, &[]),
+                    op => return Err(format!("unknown operator: {}", op)),
+                };
+            }
+            Expression::InfixExpression(e) => self.compile_infix(e)?,
+            Expression::IfExpression(e) => self.compile_if(e)?,
+            Expression::FunctionLiteral(e) => self.compile_function(e)?,
+            Expression::CallExpression(e) => {
+                self.compile_expression(&e.function)?;
+                for arg in &e.arguments {
+                    self.compile_expression(arg)?;
+                }
+                if e.arguments.len() > u8::MAX as usize {
+                    return Err(format!("too many arguments: {}", e.arguments.len()));
+                }
+                self.emit(Opcode::Call, &[e.arguments.len()]);
+            }
+            Expression::StringLiteral(e) => {
+                let index = self.add_constant(Object::String(e.value.as_str().into()))?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::ArrayLiteral(e) => {
+                for element in &e.elements {
+                    self.compile_expression(element)?;
+                }
+                if e.elements.len() > u16::MAX as usize {
+                    return Err(format!("too many array elements: {}", e.elements.len()));
+                }
+                self.emit(Opcode::Array, &[e.elements.len()]);
+            }
+            Expression::IndexExpression(e) => {
+                self.compile_expression(&e.left)?;
+                self.compile_expression(&e.index)?;
+                self.emit(Opcode::Index, &[]);
+            }
+            // オペランドはキーと値を合わせた数
+            Expression::HashLiteral(e) => {
+                for (key, value) in &e.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                if e.pairs.len() * 2 > u16::MAX as usize {
+                    return Err(format!("too many hash pairs: {}", e.pairs.len()));
+                }
+                self.emit(Opcode::Hash, &[e.pairs.len() * 2]);
+            }
+            Expression::RangeExpression(e) => {
+                self.compile_optional(e.start.as_deref())?;
+                self.compile_optional(e.end.as_deref())?;
+                self.emit(Opcode::Range, &[e.inclusive as usize]);
+            }
+            Expression::AssignExpression(e) => self.compile_assign(e)?,
+            Expression::MatchExpression(e) => self.compile_match(e)?,
+            Expression::MemberExpression(e) => {
+                self.compile_expression(&e.object)?;
+                self.load_literal(Object::String(e.property.value.as_str().into()))?;
+                self.emit(Opcode::Member, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_if(&mut self, e: &IfExpression) -> Result<(), String> {
+        self.compile_optional(e.condition.as_deref())?;
+
+        // 飛び先はあとで書き換える
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[9999]);
+        let stack_height = self.current_scope().stack_height;
+
+        self.compile_block(&e.consequence)?;
+        self.finish_branch();
+
+        let jump = self.emit(Opcode::Jump, &[9999]);
+        let after_consequence = self.current_instructions().len();
+        self.change_operand(jump_not_truthy, after_consequence)?;
+        self.current_scope_mut().stack_height = stack_height;
+
+        match &e.alternative {
+            Some(alternative) => {
+                self.compile_block(alternative)?;
+                self.finish_branch();
+            }
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+
+        let after_alternative = self.current_instructions().len();
+        self.change_operand(jump, after_alternative)?;
+        // どちらの分岐を通っても値が一つ増える
+        self.current_scope_mut().stack_height = stack_height + 1;
+        Ok(())
+    }
+
+    fn compile_function(&mut self, e: &FunctionLiteral) -> Result<(), String> {
+        let name = self.function_name.take();
+        self.enter_scope();
+        if let Some(name) = &name {
+            self.symbol_table.define_function_name(name);
+        }
+
+        // 名前でない引数は隠れた変数で受け取り、本体の前でほどく
+        let mut patterns = vec![];
+        for (i, param) in e.parameters.iter().enumerate() {
+            match param.as_identifier() {
+                Some(name) => {
+                    self.define(&name.value)?;
+                }
+                None => patterns.push((param, self.define(&format!("param {}", i))?)),
+            }
+        }
+        for (pattern, symbol) in patterns {
+            self.compile_destructure(pattern, &symbol)?;
+        }
+
+        self.compile_block(&e.body)?;
+
+        // 最後の式の値を返り値にする
+        if self.last_instruction_is(Opcode::Pop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[]);
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions();
+        let scope = self.leave_scope();
+
+        // 取り込む変数を外側の関数で積んでからクロージャを作る
+        for symbol in &free_symbols {
+            self.load_symbol(symbol)?;
+        }
+        if free_symbols.len() > u8::MAX as usize {
+            return Err("too many free variables".to_string());
+        }
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals,
+            num_parameters: e.parameters.len(),
+            positions: scope.positions,
+            name: name.map(Into::into),
+            module: self.module.clone(),
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)))?;
+        self.emit(Opcode::Closure, &[index, free_symbols.len()]);
+        Ok(())
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順にコンパイルする
+    fn compile_infix(&mut self, e: &InfixExpression) -> Result<(), String> {
+        let mut chain = vec![e];
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            chain.push(inner);
+            leftmost = inner.left.as_deref();
+        }
+
+        let outer_span = self.span;
+        self.compile_optional(leftmost)?;
+        for e in chain.into_iter().rev() {
+            self.span = e.token.span;
+            self.compile_optional(e.right.as_deref())?;
+            match e.operator.as_str() {
+                "+" => self.emit(Opcode::Add, &[]),
+                "-" => self.emit(Opcode::Sub, &[]),
+                "*" => self.emit(Opcode::Mul, &[]),
+                "/" => self.emit(Opcode::Div, &[]),
+                ">" => self.emit(Opcode::GreaterThan, &[]),
+                "<" => self.emit(Opcode::LessThan, &[]),
+                "==" => self.emit(Opcode::Equal, &[]),
+                "!=" => self.emit(Opcode::NotEqual, &[]),
+                op => return Err(format!("unknown operator: {}", op)),
+            };
+        }
+        self.span = outer_span;
+        Ok(())
+    }
+
+    // 値はソースからは参照できない名前の変数に入れておき、腕ごとに添字をたどって形を確かめる。
+    // 形がすべて合えば名前を束縛し、条件を確かめてから本体を評価する
+    fn compile_match(&mut self, e: &MatchExpression) -> Result<(), String> {
+        self.compile_optional(e.subject.as_deref())?;
+        let subject = self.define(&format!("match {}", self.matches))?;
+        self.store_symbol(&subject);
+        let stack_height = self.current_scope().stack_height;
+
+        self.matches += 1;
+        let mut ends = vec![];
+        for arm in &e.arms {
+            let mut fails = vec![];
+            self.compile_pattern_test(&arm.pattern, &subject, &mut vec![], &mut fails)?;
+            self.compile_pattern_bindings(&arm.pattern, &subject, &mut vec![])?;
+            if let Some(guard) = &arm.guard {
+                self.compile_expression(guard)?;
+                fails.push(self.emit(Opcode::JumpNotTruthy, &[9999]));
+            }
+            self.compile_optional(arm.body.as_ref())?;
+            ends.push(self.emit(Opcode::Jump, &[9999]));
+
+            let next = self.current_instructions().len();
+            for position in fails {
+                self.change_operand(position, next)?;
+            }
+            self.current_scope_mut().stack_height = stack_height;
+        }
+        self.matches -= 1;
+
+        self.load_symbol(&subject)?;
+        self.emit(Opcode::NoMatch, &[]);
+        let end = self.current_instructions().len();
+        for position in ends {
+            self.change_operand(position, end)?;
+        }
+        Ok(())
+    }
+
+    // let と引数の束縛。形が合わなければパターンの場所で実行時エラーにする
+    fn compile_destructure(&mut self, pattern: &Pattern, subject: &Symbol) -> Result<(), String> {
+        let outer_span = std::mem::replace(&mut self.span, pattern.span());
+        let mut fails = vec![];
+        self.compile_pattern_test(pattern, subject, &mut vec![], &mut fails)?;
+        self.compile_pattern_bindings(pattern, subject, &mut vec![])?;
+        if !fails.is_empty() {
+            let end = self.emit(Opcode::Jump, &[9999]);
+            let mismatch = self.current_instructions().len();
+            for position in fails {
+                self.change_operand(position, mismatch)?;
+            }
+            self.load_literal(Object::String(pattern.to_string().into()))?;
+            self.load_symbol(subject)?;
+            self.emit(Opcode::Mismatch, &[]);
+            let after = self.current_instructions().len();
+            self.change_operand(end, after)?;
+        }
+        self.span = outer_span;
+        Ok(())
+    }
+
+    // 形が合わなければ次の腕へ飛ぶ。飛び先はあとで書き換える
+    fn compile_pattern_test(
+        &mut self,
+        pattern: &Pattern,
+        subject: &Symbol,
+        path: &mut Vec<Object>,
+        fails: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        match pattern {
+            Pattern::WildcardPattern(_) | Pattern::Identifier(_) => return Ok(()),
+            Pattern::IntegerLiteral(p) => {
+                self.load_path(subject, path)?;
+                self.load_literal(Object::Integer(p.value))?;
+                self.emit(Opcode::Equal, &[]);
+            }
+            Pattern::StringLiteral(p) => {
+                self.load_path(subject, path)?;
+                self.load_literal(Object::String(p.value.as_str().into()))?;
+                self.emit(Opcode::Equal, &[]);
+            }
+            Pattern::Boolean(p) => {
+                self.load_path(subject, path)?;
+                self.load_literal(Object::Boolean(p.value))?;
+                self.emit(Opcode::Equal, &[]);
+            }
+            Pattern::ArrayPattern(p) => {
+                if p.elements.len() > u16::MAX as usize {
+                    return Err(format!("too many pattern elements: {}", p.elements.len()));
+                }
+                self.load_path(subject, path)?;
+                self.emit(
+                    Opcode::MatchArray,
+                    &[p.elements.len(), p.rest.is_some() as usize],
+                );
+                fails.push(self.emit(Opcode::JumpNotTruthy, &[9999]));
+                for (i, element) in p.elements.iter().enumerate() {
+                    path.push(Object::Integer(i as i64));
+                    self.compile_pattern_test(element, subject, path, fails)?;
+                    path.pop();
+                }
+                return Ok(());
+            }
+            Pattern::HashPattern(p) => {
+                if p.pairs.len() > u16::MAX as usize {
+                    return Err(format!("too many pattern keys: {}", p.pairs.len()));
+                }
+                self.load_path(subject, path)?;
+                let keys: Vec<Object> = p
+                    .pairs
+                    .iter()
+                    .map(|(key, _)| Object::from_literal(key).unwrap_or(Object::Null))
+                    .collect();
+                for key in &keys {
+                    self.load_literal(key.clone())?;
+                }
+                self.emit(Opcode::MatchHash, &[keys.len()]);
+                fails.push(self.emit(Opcode::JumpNotTruthy, &[9999]));
+                for ((_, value), key) in p.pairs.iter().zip(keys) {
+                    path.push(key);
+                    self.compile_pattern_test(value, subject, path, fails)?;
+                    path.pop();
+                }
+                return Ok(());
+            }
+        }
+        fails.push(self.emit(Opcode::JumpNotTruthy, &[9999]));
+        Ok(())
+    }
+
+    // 形が合うと分かってから、左から順に名前へ値を入れる
+    fn compile_pattern_bindings(
+        &mut self,
+        pattern: &Pattern,
+        subject: &Symbol,
+        path: &mut Vec<Object>,
+    ) -> Result<(), String> {
+        match pattern {
+            Pattern::Identifier(p) => {
+                self.load_path(subject, path)?;
+                let symbol = self.define(&p.value)?;
+                self.store_symbol(&symbol);
+            }
+            Pattern::ArrayPattern(p) => {
+                for (i, element) in p.elements.iter().enumerate() {
+                    path.push(Object::Integer(i as i64));
+                    self.compile_pattern_bindings(element, subject, path)?;
+                    path.pop();
+                }
+                if let Some(name) = p.rest.as_ref().and_then(|r| r.name.as_ref()) {
+                    self.load_path(subject, path)?;
+                    self.emit(Opcode::Slice, &[p.elements.len()]);
+                    let symbol = self.define(&name.value)?;
+                    self.store_symbol(&symbol);
+                }
+            }
+            Pattern::HashPattern(p) => {
+                for (key, value) in &p.pairs {
+                    path.push(Object::from_literal(key).unwrap_or(Object::Null));
+                    self.compile_pattern_bindings(value, subject, path)?;
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // match の値から添字をたどった先の値を積む
+    fn load_path(&mut self, subject: &Symbol, path: &[Object]) -> Result<(), String> {
+        self.load_symbol(subject)?;
+        for key in path {
+            self.load_literal(key.clone())?;
+            self.emit(Opcode::Index, &[]);
+        }
+        Ok(())
+    }
+
+    fn load_literal(&mut self, value: Object) -> Result<(), String> {
+        match value {
+            Object::Boolean(true) => self.emit(Opcode::True, &[]),
+            Object::Boolean(false) => self.emit(Opcode::False, &[]),
+            value => {
+                let index = self.add_constant(value)?;
+                self.emit(Opcode::Constant, &[index])
+            }
+        };
+        Ok(())
+    }
+
+    // 添字と値を積んでから、OpSetGlobalIndex などで変数の入れ物をその場で書き換える。
+    // 入れ物をスタックに積まないので、ほかに参照がなければ複製されない。
+    // 代入した値が式全体の値になる
+    fn compile_assign(&mut self, e: &AssignExpression) -> Result<(), String> {
+        let mut indexes = vec![];
+        let mut target = &*e.target;
+        while let Expression::IndexExpression(index) = target {
+            indexes.push(&*index.index);
+            target = &index.left;
+        }
+        indexes.reverse();
+        let Expression::Identifier(name) = target else {
+            return Err("invalid assignment target".to_string());
+        };
+        let symbol = match self.symbol_table.resolve(&name.value) {
+            Some(symbol) if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) => {
+                symbol
+            }
+            Some(symbol) if symbol.scope != SymbolScope::Builtin => {
+                return Err(format!(
+                    "cannot assign to captured variable: {}",
+                    name.value
+                ));
+            }
+            _ => {
+                return Err(format!(
+                    "cannot assign to undefined variable: {}",
+                    name.value
+                ))
+            }
+        };
+
+        if indexes.len() > u8::MAX as usize {
+            return Err(format!("too many indexes: {}", indexes.len()));
+        }
+
+        for index in &indexes {
+            self.compile_expression(index)?;
+        }
+        if let Some(operator) = e.arithmetic_operator() {
+            self.load_symbol(&symbol)?;
+            if !indexes.is_empty() {
+                self.emit(Opcode::IndexPath, &[indexes.len()]);
+            }
+            self.compile_optional(e.value.as_deref())?;
+            match operator {
+                "+" => self.emit(Opcode::Add, &[]),
+                "-" => self.emit(Opcode::Sub, &[]),
+                "*" => self.emit(Opcode::Mul, &[]),
+                "/" => self.emit(Opcode::Div, &[]),
+                op => return Err(format!("unknown operator: {}=", op)),
+            };
+        } else {
+            self.compile_optional(e.value.as_deref())?;
+        }
+        if indexes.is_empty() {
+            self.emit(Opcode::Dup, &[1]);
+            self.store_symbol(&symbol);
+            return Ok(());
+        }
+        match symbol.scope {
+            SymbolScope::Global => {
+                self.emit(Opcode::SetGlobalIndex, &[symbol.index, indexes.len()])
+            }
+            _ => self.emit(Opcode::SetLocalIndex, &[symbol.index, indexes.len()]),
+        };
+        Ok(())
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol) {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+            _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+        };
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+            SymbolScope::Builtin => self.emit(Opcode::GetBuiltin, &[symbol.index]),
+        };
+        Ok(())
+    }
+
+    // 分岐の値を残すため、末尾の OpPop を取り除く。空の分岐は null を値にする
+    fn finish_branch(&mut self) {
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+        } else if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Null, &[]);
+        }
+    }
+
+    // オペランドの幅に収まらない番号は作れない
+    fn add_constant(&mut self, obj: Object) -> Result<usize, String> {
+        if self.constants.len() > u16::MAX as usize {
+            return Err("too many constants".to_string());
+        }
+        self.constants.push(obj);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn define(&mut self, name: &str) -> Result<Symbol, String> {
+        let symbol = self.symbol_table.define(name);
+        match symbol.scope {
+            SymbolScope::Global if symbol.index > u16::MAX as usize => {
+                Err("too many global variables".to_string())
+            }
+            SymbolScope::Local if symbol.index > u8::MAX as usize => {
+                Err("too many local variables".to_string())
+            }
+            _ => Ok(symbol),
+        }
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let ins = make(op, operands);
+        let position = self.add_instruction(&ins);
+        self.set_last_instruction(op, position);
+
+        let (pops, pushes) = op.stack_effect(operands);
+        let scope = self.current_scope_mut();
+        scope.stack_height = scope.stack_height.saturating_sub(pops) + pushes;
+        position
+    }
+
+    fn add_instruction(&mut self, ins: &[u8]) -> usize {
+        let span = self.span;
+        let scope = self.current_scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend_from_slice(ins);
+        // 同じ範囲が続くときは最初の命令だけ記録する
+        if scope.positions.last().map(|(_, s)| *s) != Some(span) {
+            scope.positions.push((position, span));
+        }
+        position
+    }
+
+    fn set_last_instruction(&mut self, opcode: Opcode, position: usize) {
+        let scope = self.current_scope_mut();
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction { opcode, position });
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        self.current_scope()
+            .last_instruction
+            .is_some_and(|last| last.opcode == op)
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction {
+            scope.instructions.truncate(last.position);
+            scope
+                .positions
+                .retain(|(position, _)| *position < last.position);
+            scope.last_instruction = scope.previous_instruction;
+            scope.stack_height += 1;
+        }
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction.as_mut() {
+            scope.instructions[last.position] = Opcode::ReturnValue as u8;
+            last.opcode = Opcode::ReturnValue;
+        }
+    }
+
+    // 書き換えるのは飛び先だけ
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), String> {
+        let op = Opcode::from_byte(self.current_instructions()[position]).expect("valid opcode");
+        let ins = make(op, &[jump_target(operand)?]);
+        let instructions = &mut self.current_scope_mut().instructions;
+        instructions[position..position + ins.len()].copy_from_slice(&ins);
+        Ok(())
+    }
+
+    fn current_scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("no compilation scope")
+    }
+
+    fn current_scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("no compilation scope")
+    }
+
+    fn current_instructions(&self) -> &Instructions {
+        &self.current_scope().instructions
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().expect("no compilation scope");
+        let outer = self
+            .symbol_table
+            .outer
+            .take()
+            .expect("no outer symbol table");
+        self.symbol_table = *outer;
+        scope
+    }
+}
+
+// 飛び先は 2 バイトのオペランドに収まらなければならない
+fn jump_target(position: usize) -> Result<usize, String> {
+    if position > u16::MAX as usize {
+        return Err(format!("jump target out of range: {}", position));
+    }
+    Ok(position)
+}
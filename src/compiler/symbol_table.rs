@@ -0,0 +1,129 @@
+use crate::builtins;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    // 外側の関数の局所変数を取り込んだもの
+    Free,
+    // 定義中の関数自身
+    Function,
+    Builtin,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+    // 取り込んだ変数の、外側の関数での Symbol
+    pub free_symbols: Vec<Symbol>,
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+    // モジュールをコンパイルしている間は、そのモジュールのグローバル変数だけが見える
+    namespace: Option<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    pub fn num_definitions(&self) -> usize {
+        self.num_definitions
+    }
+
+    // 前の名前空間を返す
+    pub fn set_namespace(&mut self, namespace: Option<String>) -> Option<String> {
+        std::mem::replace(&mut self.namespace, namespace)
+    }
+
+    // 名前空間の中のグローバル変数は、ソースには書けない名前で区別する
+    fn key(&self, name: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{} {}", namespace, name),
+            None => name.to_string(),
+        }
+    }
+
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let key = self.key(name);
+        // 同じ名前を定義し直したときは同じ場所を使う
+        if let Some(symbol) = self
+            .store
+            .get(&key)
+            .filter(|s| matches!(s.scope, SymbolScope::Global | SymbolScope::Local))
+        {
+            return symbol.clone();
+        }
+
+        let scope = if self.outer.is_some() {
+            SymbolScope::Local
+        } else {
+            SymbolScope::Global
+        };
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.store.insert(key, symbol.clone());
+        self.num_definitions += 1;
+        symbol
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    // 外側の関数の局所変数は、途中の関数すべてに取り込んでから使う
+    // 組み込み関数はどこにも定義されていない名前のときだけ使う
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(&self.key(name)) {
+            return Some(symbol.clone());
+        }
+        let Some(outer) = self.outer.as_mut() else {
+            let (index, _) = builtins::lookup(name)?;
+            return Some(Symbol {
+                name: name.to_string(),
+                scope: SymbolScope::Builtin,
+                index,
+            });
+        };
+        let symbol = outer.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
+    }
+}
@@ -0,0 +1,901 @@
+use super::*;
+use crate::disasm::disassemble;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn parse(input: &str) -> Program {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    program
+}
+
+fn compile(input: &str) -> Bytecode {
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse(input)).unwrap();
+    compiler.bytecode()
+}
+
+fn concat(instructions: Vec<Instructions>) -> Instructions {
+    instructions.concat()
+}
+
+fn function(instructions: Vec<Instructions>, num_locals: usize, num_parameters: usize) -> Object {
+    Object::CompiledFunction(Rc::new(CompiledFunction {
+        instructions: concat(instructions),
+        num_locals,
+        num_parameters,
+        positions: vec![],
+        name: None,
+        module: None,
+    }))
+}
+
+fn run_compiler_tests(tests: Vec<(&str, Vec<Object>, Vec<Instructions>)>) {
+    for (input, constants, instructions) in tests {
+        let bytecode = compile(input);
+        assert_eq!(
+            bytecode.instructions,
+            concat(instructions),
+            "input: {}",
+            input
+        );
+        assert_eq!(bytecode.constants, constants, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_integer_arithmetic() {
+    run_compiler_tests(vec![
+        (
+            "1 + 2",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "1; 2",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "-1",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Minus, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_boolean_expressions() {
+    run_compiler_tests(vec![
+        (
+            "1 < 2",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::LessThan, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "!true == false",
+            vec![],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::Bang, &[]),
+                make(Opcode::False, &[]),
+                make(Opcode::Equal, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_conditionals() {
+    run_compiler_tests(vec![
+        (
+            "if (true) { 10 }; 3333;",
+            vec![Object::Integer(10), Object::Integer(3333)],
+            vec![
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[11]),
+                // 0010
+                make(Opcode::Null, &[]),
+                // 0011
+                make(Opcode::Pop, &[]),
+                // 0012
+                make(Opcode::Constant, &[1]),
+                // 0015
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "if (true) { 10 } else { 20 }",
+            vec![Object::Integer(10), Object::Integer(20)],
+            vec![
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[13]),
+                // 0010
+                make(Opcode::Constant, &[1]),
+                // 0013
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "if (true) { }",
+            vec![],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[8]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Jump, &[9]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_global_let_statements() {
+    run_compiler_tests(vec![
+        (
+            "let one = 1; let two = 2;",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::SetGlobal, &[1]),
+            ],
+        ),
+        (
+            "let one = 1; one;",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_functions() {
+    run_compiler_tests(vec![
+        (
+            "fn() { return 5 + 10 }",
+            vec![
+                Object::Integer(5),
+                Object::Integer(10),
+                function(
+                    vec![
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    0,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        ),
+        (
+            "fn() { 1; 2 }",
+            vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                function(
+                    vec![
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Pop, &[]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    0,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        ),
+        (
+            "fn() { }",
+            vec![function(vec![make(Opcode::Return, &[])], 0, 0)],
+            vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+#[test]
+fn test_locals_and_calls() {
+    run_compiler_tests(vec![
+        (
+            "let num = 55; fn(a) { let b = a; num + b }(1)",
+            vec![
+                Object::Integer(55),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::SetLocal, &[1]),
+                        make(Opcode::GetGlobal, &[0]),
+                        make(Opcode::GetLocal, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    2,
+                    1,
+                ),
+                Object::Integer(1),
+            ],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Closure, &[1, 0]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            // 束縛する名前は関数自身を指す
+            "let f = fn() { f() };",
+            vec![function(
+                vec![
+                    make(Opcode::CurrentClosure, &[]),
+                    make(Opcode::Call, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                0,
+                0,
+            )],
+            vec![
+                make(Opcode::Closure, &[0, 0]),
+                make(Opcode::SetGlobal, &[0]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_closures() {
+    run_compiler_tests(vec![
+        (
+            "fn(a) { fn(b) { a + b } }",
+            vec![
+                function(
+                    vec![
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Closure, &[0, 1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        ),
+        (
+            // 二段外の変数は途中の関数を経由して取り込む
+            "fn(a) { fn(b) { fn(c) { a + b + c } } }",
+            vec![
+                function(
+                    vec![
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetFree, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Closure, &[0, 2]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Closure, &[1, 1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        ),
+        (
+            "fn() { let inner = fn(x) { inner(x) }; inner }",
+            vec![
+                function(
+                    vec![
+                        make(Opcode::CurrentClosure, &[]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::Closure, &[0, 0]),
+                        make(Opcode::SetLocal, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+#[test]
+fn test_strings_arrays_and_builtins() {
+    run_compiler_tests(vec![
+        (
+            r#""mon" + "key""#,
+            vec![Object::String("mon".into()), Object::String("key".into())],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "[1, 2][1]",
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Array, &[2]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Index, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "len([]); push([], 1);",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::GetBuiltin, &[0]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetBuiltin, &[5]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "{}",
+            vec![],
+            vec![make(Opcode::Hash, &[0]), make(Opcode::Pop, &[])],
+        ),
+        (
+            "{1: 2, 3: 4 * 5}[1]",
+            vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+                Object::Integer(4),
+                Object::Integer(5),
+                Object::Integer(1),
+            ],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Constant, &[3]),
+                make(Opcode::Constant, &[4]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::Hash, &[4]),
+                make(Opcode::Constant, &[5]),
+                make(Opcode::Index, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        // 同じ名前のグローバルがあれば組み込み関数は隠れる
+        (
+            "let len = 1; len",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "fn() { len }",
+            vec![function(
+                vec![
+                    make(Opcode::GetBuiltin, &[0]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                0,
+                0,
+            )],
+            vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+#[test]
+fn test_loops() {
+    run_compiler_tests(vec![
+        (
+            "while (true) { break; } 1",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Jump, &[10]),
+                make(Opcode::Jump, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        // 式の途中で抜けるときは積んである値を捨てる
+        (
+            "while (true) { 1 + if (true) { break; } }",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[25]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[19]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Jump, &[25]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Jump, &[20]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Jump, &[0]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_for_loops() {
+    run_compiler_tests(vec![
+        (
+            "0..=2",
+            vec![Object::Integer(0), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Range, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        // 尽きたときに積まれる null と、反復の状態を最後に捨てる
+        (
+            "for (x in [1]) { x }",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Array, &[1]),
+                make(Opcode::IterInit, &[]),
+                make(Opcode::IterNext, &[1]),
+                make(Opcode::JumpNotTruthy, &[22]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Jump, &[7]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        // 二つ目の変数から先に取り出す。break は反復の状態を捨てる所へ飛ぶ
+        (
+            "for (k, v in {}) { break; }",
+            vec![],
+            vec![
+                make(Opcode::Hash, &[0]),
+                make(Opcode::IterInit, &[]),
+                make(Opcode::IterNext, &[2]),
+                make(Opcode::JumpNotTruthy, &[21]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Jump, &[23]),
+                make(Opcode::Jump, &[4]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+    ]);
+}
+
+#[test]
+fn test_assign_expressions() {
+    run_compiler_tests(vec![
+        (
+            "let x = 1; x = 2",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Dup, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "fn(x) { x -= 1 }",
+            vec![
+                Object::Integer(1),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Sub, &[]),
+                        make(Opcode::Dup, &[1]),
+                        make(Opcode::SetLocal, &[0]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        ),
+        // 添字だけを積み、変数の入れ物はその場で書き換える
+        (
+            "let a = []; a[0][1] += 2",
+            vec![Object::Integer(0), Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Array, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::IndexPath, &[2]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Add, &[]),
+                make(Opcode::SetGlobalIndex, &[0, 2]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "fn(a) { a[0] = 1 }",
+            vec![
+                Object::Integer(0),
+                Object::Integer(1),
+                function(
+                    vec![
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::SetLocalIndex, &[0, 1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+// 値は隠れた変数に入れておき、腕ごとに読み直して確かめる
+#[test]
+fn test_match_expressions() {
+    run_compiler_tests(vec![
+        (
+            "match (1) { 2 => 3, x => x }",
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Equal, &[]),
+                make(Opcode::JumpNotTruthy, &[22]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Jump, &[38]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Jump, &[38]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::NoMatch, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        ),
+        (
+            "fn(a) { match (a) { [h, ..t] if h => t } }",
+            vec![
+                Object::Integer(0),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::SetLocal, &[1]),
+                        make(Opcode::GetLocal, &[1]),
+                        make(Opcode::MatchArray, &[1, 1]),
+                        make(Opcode::JumpNotTruthy, &[38]),
+                        make(Opcode::GetLocal, &[1]),
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Index, &[]),
+                        make(Opcode::SetLocal, &[2]),
+                        make(Opcode::GetLocal, &[1]),
+                        make(Opcode::Slice, &[1]),
+                        make(Opcode::SetLocal, &[3]),
+                        make(Opcode::GetLocal, &[2]),
+                        make(Opcode::JumpNotTruthy, &[38]),
+                        make(Opcode::GetLocal, &[3]),
+                        make(Opcode::Jump, &[41]),
+                        make(Opcode::GetLocal, &[1]),
+                        make(Opcode::NoMatch, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    4,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+#[test]
+fn test_destructuring() {
+    run_compiler_tests(vec![
+        (
+            "let [a] = [1];",
+            vec![
+                Object::Integer(1),
+                Object::Integer(0),
+                Object::String("[a]".into()),
+            ],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Array, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::MatchArray, &[1, 0]),
+                make(Opcode::JumpNotTruthy, &[32]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Index, &[]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Jump, &[39]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Mismatch, &[]),
+            ],
+        ),
+        (
+            "fn([a], b) { a }",
+            vec![
+                Object::Integer(0),
+                Object::String("[a]".into()),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::MatchArray, &[1, 0]),
+                        make(Opcode::JumpNotTruthy, &[20]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Index, &[]),
+                        make(Opcode::SetLocal, &[2]),
+                        make(Opcode::Jump, &[26]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Mismatch, &[]),
+                        make(Opcode::GetLocal, &[2]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    3,
+                    2,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        ),
+    ]);
+}
+
+#[test]
+fn test_member_expressions() {
+    run_compiler_tests(vec![(
+        "let m = 1; m.a.b",
+        vec![
+            Object::Integer(1),
+            Object::String("a".into()),
+            Object::String("b".into()),
+        ],
+        vec![
+            make(Opcode::Constant, &[0]),
+            make(Opcode::SetGlobal, &[0]),
+            make(Opcode::GetGlobal, &[0]),
+            make(Opcode::Constant, &[1]),
+            make(Opcode::Member, &[]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Member, &[]),
+            make(Opcode::Pop, &[]),
+        ],
+    )]);
+}
+
+#[test]
+fn test_compile_errors() {
+    let tests = vec![
+        ("x", "identifier not found: x"),
+        ("let y = y;", "identifier not found: y"),
+        ("fn() { fn() { b } }", "identifier not found: b"),
+        ("x = 1", "cannot assign to undefined variable: x"),
+        ("len = 1", "cannot assign to undefined variable: len"),
+        (
+            "fn(a) { fn() { a += 1 } }",
+            "cannot assign to captured variable: a",
+        ),
+        (
+            r#"import "no/such/module.monkey" as m;"#,
+            "module not found: no/such/module.monkey",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut compiler = Compiler::new();
+        assert_eq!(
+            compiler.compile(&parse(input)).map_err(|e| e.message),
+            Err(expected.to_string()),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_jump_target_out_of_range() {
+    // 分岐の中が 2 バイトで指せる範囲を越える
+    let input = format!("if (true) {{ {} }}", "1; ".repeat(20000));
+    let mut compiler = Compiler::new();
+    assert_eq!(
+        compiler.compile(&parse(&input)).map_err(|e| e.message),
+        Err("jump target out of range: 80006".to_string())
+    );
+}
+
+#[test]
+fn test_symbol_table() {
+    let mut global = SymbolTable::new();
+    assert_eq!(global.define("a").index, 0);
+    assert_eq!(global.define("b").index, 1);
+    assert_eq!(global.define("a").index, 0);
+
+    let mut local = SymbolTable::new_enclosed(global);
+    let c = local.define("c");
+    assert_eq!((c.scope, c.index), (SymbolScope::Local, 0));
+    assert_eq!(local.resolve("a").unwrap().scope, SymbolScope::Global);
+    assert_eq!(local.resolve("c"), Some(c.clone()));
+
+    // 外側の関数の局所変数は自由変数として取り込む
+    let mut nested = SymbolTable::new_enclosed(local);
+    let d = nested.define("d");
+    let free = nested.resolve("c").unwrap();
+    assert_eq!((free.scope, free.index), (SymbolScope::Free, 0));
+    assert_eq!(nested.resolve("c"), Some(free));
+    assert_eq!(nested.resolve("d"), Some(d));
+    assert_eq!(nested.resolve("b").unwrap().scope, SymbolScope::Global);
+    assert_eq!(nested.free_symbols, vec![c]);
+    assert_eq!(nested.resolve("e"), None);
+
+    nested.define_function_name("f");
+    let f = nested.resolve("f").unwrap();
+    assert_eq!((f.scope, f.index), (SymbolScope::Function, 0));
+}
+
+// testdata/*.monkey の逆アセンブル結果を *.golden と比べる
+// UPDATE_GOLDEN=1 を付けて実行すると期待値を書き直す
+#[test]
+fn test_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/compiler/testdata");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "monkey"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let input = fs::read_to_string(&path).unwrap();
+        let actual = disassemble(&compile(&input));
+        let golden = path.with_extension("golden");
+
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        let expected =
+            fs::read_to_string(&golden).unwrap_or_else(|e| panic!("{}: {}", golden.display(), e));
+        assert_eq!(actual, expected, "golden: {}", golden.display());
+    }
+}
+
+#[test]
+fn test_positions() {
+    let bytecode = compile("1 + true;\nif (true) { 2 }");
+
+    assert_eq!(
+        bytecode.positions,
+        vec![
+            (0, Span::new(0, 1)),
+            (3, Span::new(4, 8)),
+            (4, Span::new(2, 3)),
+            (5, Span::new(0, 1)),
+            (6, Span::new(14, 18)),
+            (7, Span::new(10, 12)),
+            (10, Span::new(22, 23)),
+            // 分岐を閉じる命令は if 式に戻る
+            (13, Span::new(10, 12)),
+        ]
+    );
+}
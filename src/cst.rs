@@ -0,0 +1,171 @@
+mod lower;
+mod parser;
+#[cfg(test)]
+mod test;
+
+use crate::token::{Span, Token};
+
+pub use crate::parser::SyntaxError;
+pub use parser::parse;
+
+// 空白やコメントも含めてすべてのトークンを保持する具象構文木
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NodeKind {
+    Program,
+    LetStatement,
+    ReturnStatement,
+    ExpressionStatement,
+    BlockStatement,
+    WhileStatement,
+    ForStatement,
+    BreakStatement,
+    ContinueStatement,
+    ImportStatement,
+    ExportStatement,
+    Identifier,
+    IntegerLiteral,
+    Boolean,
+    PrefixExpression,
+    InfixExpression,
+    GroupedExpression,
+    IfExpression,
+    FunctionLiteral,
+    CallExpression,
+    StringLiteral,
+    ArrayLiteral,
+    IndexExpression,
+    MemberExpression,
+    HashLiteral,
+    RangeExpression,
+    AssignExpression,
+    MatchExpression,
+    MatchArm,
+    // 名前や _ は Identifier、リテラルはそれぞれのノード、負の数は PrefixExpression で表す
+    ArrayPattern,
+    HashPattern,
+    RestPattern,
+    // 解釈できなかった範囲
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+    Trivia(Trivia),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Parse {
+    pub root: SyntaxNode,
+    pub errors: Vec<SyntaxError>,
+}
+
+impl SyntaxElement {
+    pub fn text(&self) -> String {
+        match self {
+            SyntaxElement::Node(n) => n.text(),
+            SyntaxElement::Token(t) => t.literal.clone(),
+            SyntaxElement::Trivia(t) => t.text.clone(),
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            SyntaxElement::Node(n) => n.span(),
+            SyntaxElement::Token(t) => Some(t.span),
+            SyntaxElement::Trivia(t) => Some(t.span),
+        }
+    }
+}
+
+impl SyntaxNode {
+    pub fn new(kind: NodeKind) -> SyntaxNode {
+        SyntaxNode {
+            kind,
+            children: vec![],
+        }
+    }
+
+    // 入力をそのまま復元する
+    pub fn text(&self) -> String {
+        self.children.iter().map(|c| c.text()).collect()
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        let first = self.children.iter().find_map(|c| c.span())?;
+        let last = self.children.iter().rev().find_map(|c| c.span())?;
+        Some(Span::new(first.start, last.end))
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(n) => Some(n),
+            _ => None,
+        })
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Token(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    // 子孫も含めて最初のトークン
+    pub fn first_token(&self) -> Option<&Token> {
+        self.children.iter().find_map(|c| match c {
+            SyntaxElement::Node(n) => n.first_token(),
+            SyntaxElement::Token(t) => Some(t),
+            SyntaxElement::Trivia(_) => None,
+        })
+    }
+
+    // 字下げ付きで木構造を表示する
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.write_dump(&mut out, 0);
+        out
+    }
+
+    fn write_dump(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let span = self.span().unwrap_or_default();
+        out.push_str(&format!(
+            "{}{:?}@{}..{}\n",
+            indent, self.kind, span.start, span.end
+        ));
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(n) => n.write_dump(out, depth + 1),
+                SyntaxElement::Token(t) => out.push_str(&format!(
+                    "{}  {} {:?}@{}..{}\n",
+                    indent, t.kind, t.literal, t.span.start, t.span.end
+                )),
+                SyntaxElement::Trivia(t) => out.push_str(&format!(
+                    "{}  {:?} {:?}@{}..{}\n",
+                    indent, t.kind, t.text, t.span.start, t.span.end
+                )),
+            }
+        }
+    }
+}
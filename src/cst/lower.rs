@@ -0,0 +1,424 @@
+use crate::ast::{
+    ArrayLiteral, ArrayPattern, AssignExpression, BlockStatement, Boolean, BreakStatement,
+    CallExpression, ContinueStatement, ExportStatement, Expression, ExpressionStatement,
+    ForStatement, FunctionLiteral, HashLiteral, HashPattern, Identifier, IfExpression,
+    ImportStatement, IndexExpression, InfixExpression, IntegerLiteral, LetStatement, MatchArm,
+    MatchExpression, MemberExpression, Pattern, PrefixExpression, Program, RangeExpression,
+    RestPattern, ReturnStatement, Statement, StringLiteral, WhileStatement, WildcardPattern,
+};
+use crate::cst::{NodeKind, SyntaxElement, SyntaxNode};
+use crate::lexer::unquote;
+use crate::token::{Span, Token, TokenKind};
+
+// CST から AST を組み立てる。壊れている部分は Parser と同じく捨てる
+impl SyntaxNode {
+    pub fn to_program(&self) -> Program {
+        Program {
+            statements: self.nodes().filter_map(lower_statement).collect(),
+        }
+    }
+}
+
+fn is_expression(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Identifier
+            | NodeKind::IntegerLiteral
+            | NodeKind::Boolean
+            | NodeKind::PrefixExpression
+            | NodeKind::InfixExpression
+            | NodeKind::GroupedExpression
+            | NodeKind::IfExpression
+            | NodeKind::FunctionLiteral
+            | NodeKind::CallExpression
+            | NodeKind::StringLiteral
+            | NodeKind::ArrayLiteral
+            | NodeKind::IndexExpression
+            | NodeKind::MemberExpression
+            | NodeKind::HashLiteral
+            | NodeKind::RangeExpression
+            | NodeKind::AssignExpression
+            | NodeKind::MatchExpression
+    )
+}
+
+fn is_pattern(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Identifier
+            | NodeKind::IntegerLiteral
+            | NodeKind::Boolean
+            | NodeKind::PrefixExpression
+            | NodeKind::StringLiteral
+            | NodeKind::ArrayPattern
+            | NodeKind::HashPattern
+    )
+}
+
+fn expressions(node: &SyntaxNode) -> impl Iterator<Item = &SyntaxNode> {
+    node.nodes().filter(|n| is_expression(n.kind))
+}
+
+fn lower_statement(node: &SyntaxNode) -> Option<Statement> {
+    let token = node.first_token()?.clone();
+
+    match node.kind {
+        NodeKind::LetStatement => {
+            // '=' より前にあるのが束縛先のパターン、後ろにあるのが値
+            let mut pattern = None;
+            let mut value = None;
+            let mut seen_assign = false;
+            for child in &node.children {
+                match child {
+                    SyntaxElement::Token(t) if t.kind == TokenKind::Assign => seen_assign = true,
+                    SyntaxElement::Node(n) if !seen_assign && is_pattern(n.kind) => {
+                        pattern = lower_pattern(n)
+                    }
+                    SyntaxElement::Node(n) if seen_assign && is_expression(n.kind) => {
+                        value = lower_expression(n)
+                    }
+                    _ => {}
+                }
+            }
+            Some(LetStatement::new(token, pattern?, value).into())
+        }
+        NodeKind::ReturnStatement => {
+            let value = expressions(node).next().and_then(lower_expression);
+            Some(ReturnStatement::new(token, value).into())
+        }
+        NodeKind::ExpressionStatement => {
+            let expression = expressions(node).next().and_then(lower_expression);
+            Some(ExpressionStatement::new(token, expression).into())
+        }
+        NodeKind::BlockStatement => Some(lower_block(node).into()),
+        NodeKind::WhileStatement => {
+            let condition = expressions(node).next().and_then(lower_expression);
+            let body = node.nodes().find(|n| n.kind == NodeKind::BlockStatement)?;
+            Some(WhileStatement::new(token, condition, lower_block(body)).into())
+        }
+        NodeKind::ForStatement => {
+            // 'in' より前にあるのが変数、後ろにあるのが反復する値
+            let mut variables = vec![];
+            let mut iterable = None;
+            let mut seen_in = false;
+            for child in &node.children {
+                match child {
+                    SyntaxElement::Token(t) if t.kind == TokenKind::In => seen_in = true,
+                    SyntaxElement::Node(n) if !seen_in && n.kind == NodeKind::Identifier => {
+                        variables.push(lower_identifier(n)?)
+                    }
+                    SyntaxElement::Node(n) if seen_in && is_expression(n.kind) => {
+                        iterable = lower_expression(n)
+                    }
+                    _ => {}
+                }
+            }
+            let body = node.nodes().find(|n| n.kind == NodeKind::BlockStatement)?;
+            Some(ForStatement::new(token, variables, iterable, lower_block(body)).into())
+        }
+        NodeKind::ImportStatement => {
+            let path = node.nodes().find(|n| n.kind == NodeKind::StringLiteral)?;
+            let path = unquote(&path.first_token()?.literal).ok()?;
+            let alias = node.nodes().find(|n| n.kind == NodeKind::Identifier)?;
+            Some(ImportStatement::new(token, path, lower_identifier(alias)?).into())
+        }
+        NodeKind::ExportStatement => match lower_statement(node.nodes().next()?)? {
+            Statement::LetStatement(stmt) => Some(ExportStatement::new(token, stmt).into()),
+            _ => None,
+        },
+        NodeKind::BreakStatement => Some(BreakStatement::new(token).into()),
+        NodeKind::ContinueStatement => Some(ContinueStatement::new(token).into()),
+        _ => None,
+    }
+}
+
+fn lower_block(node: &SyntaxNode) -> BlockStatement {
+    let token = node.first_token().cloned().unwrap_or_default();
+    BlockStatement::new(token, node.nodes().filter_map(lower_statement).collect())
+}
+
+fn lower_identifier(node: &SyntaxNode) -> Option<Identifier> {
+    let token = node.first_token()?.clone();
+    let value = token.literal.clone();
+    Some(Identifier::new(token, value))
+}
+
+// 種類ごとの組み立ては別の関数にして、入れ子の式で再帰するときのフレームを小さく保つ
+fn lower_expression(node: &SyntaxNode) -> Option<Expression> {
+    match node.kind {
+        NodeKind::Identifier => lower_identifier(node).map(Expression::from),
+        NodeKind::IntegerLiteral => lower_integer(node),
+        NodeKind::Boolean => lower_boolean(node),
+        NodeKind::PrefixExpression => lower_prefix(node),
+        NodeKind::InfixExpression => lower_infix(node),
+        // 括弧は AST には残らない
+        NodeKind::GroupedExpression => expressions(node).next().and_then(lower_expression),
+        NodeKind::IfExpression => lower_if(node),
+        NodeKind::FunctionLiteral => lower_function(node),
+        NodeKind::CallExpression => lower_call(node),
+        NodeKind::StringLiteral => lower_string(node),
+        NodeKind::ArrayLiteral => lower_array(node),
+        NodeKind::IndexExpression => lower_index(node),
+        NodeKind::MemberExpression => lower_member(node),
+        NodeKind::RangeExpression => lower_range(node),
+        NodeKind::AssignExpression => lower_assign(node),
+        NodeKind::HashLiteral => lower_hash(node),
+        NodeKind::MatchExpression => lower_match(node),
+        _ => None,
+    }
+}
+
+fn lower_integer(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.first_token()?.clone();
+    let value = token.literal.parse::<i64>().ok()?;
+    Some(IntegerLiteral::new(token, value).into())
+}
+
+fn lower_boolean(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.first_token()?.clone();
+    let value = token.literal == "true";
+    Some(Boolean::new(token, value).into())
+}
+
+fn lower_prefix(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let right = expressions(node).next().and_then(lower_expression);
+    let operator = token.literal.clone();
+    Some(PrefixExpression::new(token, operator, right).into())
+}
+
+// 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左の端から組み立てる
+fn lower_infix(node: &SyntaxNode) -> Option<Expression> {
+    let mut chain = vec![node];
+    let mut leftmost = expressions(node).next();
+    while let Some(n) = leftmost.filter(|n| n.kind == NodeKind::InfixExpression) {
+        chain.push(n);
+        leftmost = expressions(n).next();
+    }
+
+    let mut left = leftmost.and_then(lower_expression);
+    for node in chain.into_iter().rev() {
+        let token = node.tokens().next()?.clone();
+        let right = expressions(node).nth(1).and_then(lower_expression);
+        let operator = token.literal.clone();
+        left = Some(InfixExpression::new(token, left, operator, right).into());
+    }
+    left
+}
+
+// else if の続きは入れ子の IfExpression になっているので、条件は本体より前の式に限る
+fn lower_if(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let condition = node
+        .nodes()
+        .take_while(|n| n.kind != NodeKind::BlockStatement)
+        .find(|n| is_expression(n.kind))
+        .and_then(lower_expression);
+    let consequence = node.nodes().find(|n| n.kind == NodeKind::BlockStatement)?;
+    let alternative = node
+        .nodes()
+        .skip_while(|n| !std::ptr::eq(*n, consequence))
+        .skip(1)
+        .find_map(|n| match n.kind {
+            NodeKind::BlockStatement => Some(lower_block(n)),
+            NodeKind::IfExpression => match lower_expression(n)? {
+                Expression::IfExpression(next) => Some(next.into_else_block()),
+                _ => None,
+            },
+            _ => None,
+        });
+    Some(IfExpression::new(token, condition, lower_block(consequence), alternative).into())
+}
+
+fn lower_function(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let parameters = node
+        .nodes()
+        .filter(|n| is_pattern(n.kind))
+        .map(lower_pattern)
+        .collect::<Option<Vec<_>>>()?;
+    let body = node.nodes().find(|n| n.kind == NodeKind::BlockStatement)?;
+    Some(FunctionLiteral::new(token, parameters, lower_block(body)).into())
+}
+
+fn lower_call(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let mut operands = expressions(node);
+    let function = lower_expression(operands.next()?)?;
+    let arguments = operands.map(lower_expression).collect::<Option<Vec<_>>>()?;
+    Some(CallExpression::new(token, function, arguments).into())
+}
+
+fn lower_string(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.first_token()?.clone();
+    let value = unquote(&token.literal).ok()?;
+    Some(StringLiteral::new(token, value).into())
+}
+
+fn lower_array(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let elements = expressions(node)
+        .map(lower_expression)
+        .collect::<Option<Vec<_>>>()?;
+    Some(ArrayLiteral::new(token, elements).into())
+}
+
+fn lower_index(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let mut operands = expressions(node);
+    let left = lower_expression(operands.next()?)?;
+    let index = lower_expression(operands.next()?)?;
+    Some(IndexExpression::new(token, left, index).into())
+}
+
+fn lower_member(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let object = lower_expression(node.nodes().next()?)?;
+    let property = lower_identifier(node.nodes().nth(1)?)?;
+    Some(MemberExpression::new(token, object, property).into())
+}
+
+fn lower_range(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let mut operands = expressions(node);
+    let start = operands.next().and_then(lower_expression);
+    let end = operands.next().and_then(lower_expression);
+    let inclusive = token.kind == TokenKind::DotDotEq;
+    Some(RangeExpression::new(token, start, end, inclusive).into())
+}
+
+fn lower_assign(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let mut operands = expressions(node);
+    let target = lower_expression(operands.next()?)?;
+    let value = operands.next().and_then(lower_expression);
+    let operator = token.literal.clone();
+    Some(AssignExpression::new(token, target, operator, value).into())
+}
+
+// キーと値が交互に並んでいる。欠けていれば組にできない
+fn lower_hash(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let operands = expressions(node)
+        .map(lower_expression)
+        .collect::<Option<Vec<_>>>()?;
+    if operands.len() % 2 != 0 {
+        return None;
+    }
+    let mut operands = operands.into_iter();
+    let mut pairs = vec![];
+    while let (Some(key), Some(value)) = (operands.next(), operands.next()) {
+        pairs.push((key, value));
+    }
+    Some(HashLiteral::new(token, pairs).into())
+}
+
+fn lower_match(node: &SyntaxNode) -> Option<Expression> {
+    let token = node.tokens().next()?.clone();
+    let subject = expressions(node).next().and_then(lower_expression);
+    let arms = node
+        .nodes()
+        .filter(|n| n.kind == NodeKind::MatchArm)
+        .map(lower_match_arm)
+        .collect::<Option<Vec<_>>>()?;
+    Some(MatchExpression::new(token, subject, arms).into())
+}
+
+// 'if' と '=>' の位置で、条件と本体を見分ける
+fn lower_match_arm(node: &SyntaxNode) -> Option<MatchArm> {
+    let pattern = node.nodes().find(|n| is_pattern(n.kind))?;
+    let mut arrow = None;
+    let mut guard = None;
+    let mut body = None;
+    let mut seen_if = false;
+    for child in &node.children {
+        match child {
+            SyntaxElement::Token(t) if t.kind == TokenKind::If => seen_if = true,
+            SyntaxElement::Token(t) if t.kind == TokenKind::FatArrow => arrow = Some(t.clone()),
+            SyntaxElement::Node(n) if is_expression(n.kind) && arrow.is_some() => {
+                body = lower_expression(n)
+            }
+            SyntaxElement::Node(n) if is_expression(n.kind) && seen_if => {
+                guard = lower_expression(n)
+            }
+            _ => {}
+        }
+    }
+    Some(MatchArm::new(arrow?, lower_pattern(pattern)?, guard, body))
+}
+
+fn lower_pattern(node: &SyntaxNode) -> Option<Pattern> {
+    match node.kind {
+        NodeKind::Identifier => {
+            let identifier = lower_identifier(node)?;
+            if identifier.value == "_" {
+                return Some(WildcardPattern::new(identifier.token).into());
+            }
+            Some(identifier.into())
+        }
+        // 負の数は一つのリテラルにまとめる
+        NodeKind::PrefixExpression => {
+            let minus = node.tokens().next()?;
+            let digits = node.nodes().next()?.first_token()?;
+            let literal = format!("-{}", digits.literal);
+            let value = literal.parse::<i64>().ok()?;
+            let span = Span::new(minus.span.start, digits.span.end);
+            let token = Token::new(TokenKind::Int, literal).with_span(span);
+            Some(IntegerLiteral::new(token, value).into())
+        }
+        NodeKind::IntegerLiteral | NodeKind::StringLiteral | NodeKind::Boolean => {
+            match lower_expression(node)? {
+                Expression::IntegerLiteral(e) => Some(e.into()),
+                Expression::StringLiteral(e) => Some(e.into()),
+                Expression::Boolean(e) => Some(e.into()),
+                _ => None,
+            }
+        }
+        NodeKind::ArrayPattern => {
+            let token = node.tokens().next()?.clone();
+            let elements = node
+                .nodes()
+                .filter(|n| is_pattern(n.kind))
+                .map(lower_pattern)
+                .collect::<Option<Vec<_>>>()?;
+            let rest = match node.nodes().find(|n| n.kind == NodeKind::RestPattern) {
+                Some(rest) => {
+                    let token = rest.tokens().next()?.clone();
+                    let name = rest
+                        .nodes()
+                        .next()
+                        .and_then(lower_identifier)
+                        .filter(|name| name.value != "_");
+                    Some(RestPattern::new(token, name))
+                }
+                None => None,
+            };
+            Some(ArrayPattern::new(token, elements, rest).into())
+        }
+        // キーと値が交互に並んでいる。キーはリテラルに限る
+        NodeKind::HashPattern => {
+            let token = node.tokens().next()?.clone();
+            let operands = node
+                .nodes()
+                .filter(|n| is_pattern(n.kind))
+                .map(lower_pattern)
+                .collect::<Option<Vec<_>>>()?;
+            if operands.len() % 2 != 0 {
+                return None;
+            }
+            let mut operands = operands.into_iter();
+            let mut pairs = vec![];
+            while let (Some(key), Some(value)) = (operands.next(), operands.next()) {
+                let key = match key {
+                    Pattern::IntegerLiteral(e) => e.into(),
+                    Pattern::StringLiteral(e) => e.into(),
+                    Pattern::Boolean(e) => e.into(),
+                    _ => return None,
+                };
+                pairs.push((key, value));
+            }
+            Some(HashPattern::new(token, pairs).into())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,931 @@
+use crate::cst::{NodeKind, Parse, SyntaxElement, SyntaxError, SyntaxNode, Trivia, TriviaKind};
+use crate::lexer::{unquote, Lexer};
+use crate::parser::{
+    is_infix_operator, Parser, Precedence, MAX_ARGUMENTS, MAX_NESTING, MAX_OPERATORS,
+};
+use crate::token::{Span, Token, TokenKind};
+
+pub fn parse(input: &str) -> Parse {
+    let (tokens, trivia) = tokenize(input);
+    let mut p = CstParser {
+        tokens,
+        trivia,
+        pos: 0,
+        trivia_eaten: false,
+        stack: vec![],
+        errors: vec![],
+        depth: 0,
+        operators: 0,
+        nesting_error: None,
+        loops: 0,
+        blocks: 0,
+        arm_body: None,
+    };
+    p.parse_program();
+
+    Parse {
+        root: p.stack.pop().expect("program node"),
+        errors: p.errors,
+    }
+}
+
+// トークン列と、各トークンの直前にあるトリビアに分ける
+fn tokenize(input: &str) -> (Vec<Token>, Vec<Vec<Trivia>>) {
+    let chars: Vec<char> = input.chars().collect();
+    let mut l = Lexer::new(input);
+    let mut tokens = vec![];
+    let mut trivia = vec![];
+    let mut end = 0;
+
+    loop {
+        let mut tok = l.next_token();
+        trivia.push(split_trivia(&chars, end, tok.span.start));
+
+        if tok.kind == TokenKind::EOF {
+            // 入力の途中の '\0' は字句解析器が EOF とみなすので、残りはまとめて不正なトークンにする
+            if tok.span.start < chars.len() {
+                let rest: String = chars[tok.span.start..].iter().collect();
+                tokens.push(
+                    Token::new(TokenKind::Illegal, rest)
+                        .with_span(Span::new(tok.span.start, chars.len())),
+                );
+                trivia.push(vec![]);
+            }
+            tok.span = Span::new(chars.len(), chars.len());
+            tokens.push(tok);
+            return (tokens, trivia);
+        }
+
+        end = tok.span.end;
+        tokens.push(tok);
+    }
+}
+
+fn split_trivia(chars: &[char], start: usize, end: usize) -> Vec<Trivia> {
+    let mut trivia = vec![];
+    let mut pos = start;
+    while pos < end {
+        let begin = pos;
+        let kind = if chars[pos] == '/' {
+            while pos < end && chars[pos] != '\n' {
+                pos += 1;
+            }
+            TriviaKind::Comment
+        } else {
+            while pos < end && chars[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            TriviaKind::Whitespace
+        };
+
+        trivia.push(Trivia {
+            kind,
+            text: chars[begin..pos].iter().collect(),
+            span: Span::new(begin, pos),
+        });
+    }
+    trivia
+}
+
+struct CstParser {
+    tokens: Vec<Token>,
+    trivia: Vec<Vec<Trivia>>,
+    pos: usize,
+    trivia_eaten: bool,
+    stack: Vec<SyntaxNode>,
+    errors: Vec<SyntaxError>,
+    depth: usize,
+    operators: usize,
+    nesting_error: Option<usize>,
+    loops: usize,
+    blocks: usize,
+    // match の腕の本体の先頭のトークンの位置
+    arm_body: Option<usize>,
+}
+
+impl CstParser {
+    fn current(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn at(&self, k: TokenKind) -> bool {
+        self.current().kind == k
+    }
+
+    fn push(&mut self, element: SyntaxElement) {
+        self.stack
+            .last_mut()
+            .expect("no open node")
+            .children
+            .push(element);
+    }
+
+    // 現在のトークンの前にあるトリビアを、今開いているノードに入れる
+    fn eat_trivia(&mut self) {
+        if self.trivia_eaten || self.stack.is_empty() {
+            return;
+        }
+        self.trivia_eaten = true;
+        for t in std::mem::take(&mut self.trivia[self.pos]) {
+            self.push(SyntaxElement::Trivia(t));
+        }
+    }
+
+    fn bump(&mut self) {
+        self.eat_trivia();
+        let tok = self.current().clone();
+        self.push(SyntaxElement::Token(tok));
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+            self.trivia_eaten = false;
+        }
+    }
+
+    fn start_node(&mut self, kind: NodeKind) {
+        self.eat_trivia();
+        self.stack.push(SyntaxNode::new(kind));
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.eat_trivia();
+        self.stack.last().map_or(0, |n| n.children.len())
+    }
+
+    // checkpoint 以降に作った要素を子に持つノードを開く
+    fn start_node_at(&mut self, checkpoint: usize, kind: NodeKind) {
+        let parent = self.stack.last_mut().expect("no open node");
+        let children = parent.children.split_off(checkpoint);
+        self.stack.push(SyntaxNode { kind, children });
+    }
+
+    fn finish_node(&mut self) {
+        let node = self.stack.pop().expect("no open node");
+        if self.stack.is_empty() {
+            self.stack.push(node);
+        } else {
+            self.push(SyntaxElement::Node(node));
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        let span = self.current().span;
+        self.errors.push(SyntaxError { message, span });
+    }
+
+    fn expect(&mut self, k: TokenKind) -> bool {
+        if self.at(k) {
+            self.bump();
+            true
+        } else {
+            let msg = format!(
+                "expected next token to be {}, got {} instead",
+                k,
+                self.current().kind
+            );
+            self.error(msg);
+            false
+        }
+    }
+
+    fn parse_program(&mut self) {
+        self.stack.push(SyntaxNode::new(NodeKind::Program));
+
+        while !self.at(TokenKind::EOF) {
+            if self.at(TokenKind::RBrace) {
+                self.error("unexpected }".to_string());
+                self.start_node(NodeKind::Error);
+                self.bump();
+                self.finish_node();
+            } else {
+                self.parse_statement();
+            }
+        }
+
+        self.bump();
+    }
+
+    fn parse_statement(&mut self) {
+        match self.current().kind {
+            TokenKind::Let => self.parse_let_statement(),
+            TokenKind::Return => self.parse_return_statement(),
+            TokenKind::While => self.parse_while_statement(),
+            TokenKind::For => self.parse_for_statement(),
+            TokenKind::Break => self.parse_loop_control(NodeKind::BreakStatement),
+            TokenKind::Continue => self.parse_loop_control(NodeKind::ContinueStatement),
+            TokenKind::Import => self.parse_import_statement(),
+            TokenKind::Export => self.parse_export_statement(),
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    fn parse_let_statement(&mut self) {
+        self.start_node(NodeKind::LetStatement);
+        self.bump();
+
+        self.parse_binding();
+
+        if self.expect(TokenKind::Assign) {
+            self.parse_expression(Precedence::Lowest);
+        }
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_import_statement(&mut self) {
+        self.check_top_level();
+        self.start_node(NodeKind::ImportStatement);
+        self.bump();
+
+        if self.at(TokenKind::String) {
+            if let Err(msg) = unquote(&self.current().literal) {
+                self.error(msg);
+            }
+            self.parse_single(NodeKind::StringLiteral);
+        } else {
+            self.expect(TokenKind::String);
+        }
+        self.expect(TokenKind::As);
+        self.parse_variable();
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_export_statement(&mut self) {
+        self.check_top_level();
+        self.start_node(NodeKind::ExportStatement);
+        self.bump();
+
+        if self.at(TokenKind::Let) {
+            self.parse_let_statement();
+        } else {
+            self.expect(TokenKind::Let);
+        }
+        self.finish_node();
+    }
+
+    fn check_top_level(&mut self) {
+        if self.blocks > 0 {
+            let msg = format!(
+                "{} is only allowed at the top level",
+                self.current().literal
+            );
+            self.error(msg);
+        }
+    }
+
+    fn parse_return_statement(&mut self) {
+        self.start_node(NodeKind::ReturnStatement);
+        self.bump();
+
+        self.parse_expression(Precedence::Lowest);
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_while_statement(&mut self) {
+        self.start_node(NodeKind::WhileStatement);
+        self.bump();
+
+        self.expect(TokenKind::LParen);
+        self.parse_expression(Precedence::Lowest);
+        self.expect(TokenKind::RParen);
+
+        if self.at(TokenKind::LBrace) {
+            self.loops += 1;
+            self.nested(Self::parse_block_statement);
+            self.loops -= 1;
+        } else {
+            self.expect(TokenKind::LBrace);
+        }
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_for_statement(&mut self) {
+        self.start_node(NodeKind::ForStatement);
+        self.bump();
+
+        self.expect(TokenKind::LParen);
+        self.parse_variable();
+        if self.at(TokenKind::Comma) {
+            self.bump();
+            self.parse_variable();
+        }
+        self.expect(TokenKind::In);
+        self.parse_expression(Precedence::Lowest);
+        self.expect(TokenKind::RParen);
+
+        if self.at(TokenKind::LBrace) {
+            self.loops += 1;
+            self.nested(Self::parse_block_statement);
+            self.loops -= 1;
+        } else {
+            self.expect(TokenKind::LBrace);
+        }
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_variable(&mut self) {
+        if self.at(TokenKind::Ident) {
+            self.parse_single(NodeKind::Identifier);
+        } else {
+            self.expect(TokenKind::Ident);
+        }
+    }
+
+    fn parse_loop_control(&mut self, kind: NodeKind) {
+        if self.loops == 0 {
+            let msg = format!("{} outside of loop", self.current().literal);
+            self.error(msg);
+        }
+        self.start_node(kind);
+        self.bump();
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    fn parse_expression_statement(&mut self) {
+        self.start_node(NodeKind::ExpressionStatement);
+
+        self.parse_expression(Precedence::Lowest);
+
+        if self.at(TokenKind::SemiColon) {
+            self.bump();
+        }
+        self.finish_node();
+    }
+
+    // 入れ子の深さの上限は AST の構文解析器と同じ
+    fn parse_expression(&mut self, precedence: Precedence) {
+        self.nested(|p| p.parse_nested_expression(precedence));
+    }
+
+    fn nested(&mut self, parse: impl FnOnce(&mut Self)) {
+        let (depth, operators) = (self.depth, self.operators);
+        if self.nest() {
+            parse(self);
+        }
+        (self.depth, self.operators) = (depth, operators);
+        if depth == 0 {
+            if let Some(i) = self.nesting_error.take() {
+                self.errors.truncate(i + 1);
+            }
+        }
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) {
+        let checkpoint = self.checkpoint();
+
+        match self.current().kind {
+            TokenKind::Ident => self.parse_single(NodeKind::Identifier),
+            TokenKind::Int => {
+                if self.current().literal.parse::<i64>().is_err() {
+                    let msg = format!("could not parse {} as integer", self.current().literal);
+                    self.error(msg);
+                }
+                self.parse_single(NodeKind::IntegerLiteral);
+            }
+            TokenKind::True | TokenKind::False => self.parse_single(NodeKind::Boolean),
+            TokenKind::String => {
+                if let Err(msg) = unquote(&self.current().literal) {
+                    self.error(msg);
+                }
+                self.parse_single(NodeKind::StringLiteral);
+            }
+            TokenKind::LBracket => {
+                self.start_node(NodeKind::ArrayLiteral);
+                self.parse_expression_list(TokenKind::RBracket);
+                self.finish_node();
+            }
+            TokenKind::Bang | TokenKind::Minus => {
+                self.start_node(NodeKind::PrefixExpression);
+                self.bump();
+                self.parse_expression(Precedence::Prefix);
+                self.finish_node();
+            }
+            TokenKind::LParen => {
+                self.start_node(NodeKind::GroupedExpression);
+                self.bump();
+                self.parse_expression(Precedence::Lowest);
+                self.expect(TokenKind::RParen);
+                self.finish_node();
+            }
+            TokenKind::LBrace => self.parse_hash_literal(),
+            TokenKind::If => self.parse_if_expression(),
+            TokenKind::Match => self.parse_match_expression(),
+            TokenKind::Function => self.parse_function_literal(),
+            k => {
+                self.error(format!("no prefix parse function for {} found", k));
+                // 文の区切りになるトークンは読み飛ばさずに残しておく
+                if !matches!(k, TokenKind::SemiColon | TokenKind::RBrace | TokenKind::EOF) {
+                    self.start_node(NodeKind::Error);
+                    self.bump();
+                    self.finish_node();
+                }
+                return;
+            }
+        }
+
+        while precedence < Parser::get_precedence(self.current().kind) {
+            if is_infix_operator(self.current().kind) {
+                self.operators += 1;
+                if self.operators > MAX_OPERATORS {
+                    self.give_up("expression too long");
+                    return;
+                }
+            } else if !self.nest() {
+                return;
+            }
+            if self.at(TokenKind::LParen) {
+                self.start_node_at(checkpoint, NodeKind::CallExpression);
+                let paren = self.current().span;
+                let count = self.parse_expression_list(TokenKind::RParen);
+                self.check_count(paren, count, "arguments");
+                self.finish_node();
+                continue;
+            }
+
+            if self.at(TokenKind::LBracket) {
+                self.start_node_at(checkpoint, NodeKind::IndexExpression);
+                self.bump();
+                self.parse_expression(Precedence::Lowest);
+                self.expect(TokenKind::RBracket);
+                self.finish_node();
+                continue;
+            }
+
+            if self.at(TokenKind::Dot) {
+                self.start_node_at(checkpoint, NodeKind::MemberExpression);
+                self.bump();
+                self.parse_variable();
+                self.finish_node();
+                continue;
+            }
+
+            let kind = match self.current().kind {
+                TokenKind::DotDot | TokenKind::DotDotEq => NodeKind::RangeExpression,
+                k if is_assign_operator(k) => NodeKind::AssignExpression,
+                k if is_infix_operator(k) => NodeKind::InfixExpression,
+                _ => break,
+            };
+
+            // 代入は右結合なので、右辺では同じ優先順位の演算子も読む
+            let op_precedence = match kind {
+                NodeKind::AssignExpression => Precedence::Lowest,
+                _ => Parser::get_precedence(self.current().kind),
+            };
+            self.start_node_at(checkpoint, kind);
+            if kind == NodeKind::AssignExpression {
+                self.check_assign_target();
+            }
+            self.bump();
+            self.parse_expression(op_precedence);
+            self.finish_node();
+        }
+    }
+
+    // 開いたばかりの代入のノードには、代入先の式だけが入っている
+    fn check_assign_target(&mut self) {
+        let node = self.stack.last().expect("no open node");
+        let Some(target) = node.nodes().next() else {
+            return;
+        };
+        if !is_assignable(target) {
+            let msg = format!("invalid assignment target: {}", target.text().trim());
+            self.error(msg);
+        }
+    }
+
+    fn nest(&mut self) -> bool {
+        self.depth += 1;
+        if self.depth <= MAX_NESTING {
+            return true;
+        }
+        self.give_up("expression nested too deeply");
+        false
+    }
+
+    // 残りのトークンをまとめてエラーのノードにする
+    fn give_up(&mut self, message: &str) {
+        if self.nesting_error.is_none() {
+            self.nesting_error = Some(self.errors.len());
+            self.error(message.to_string());
+        }
+        if !self.at(TokenKind::EOF) {
+            self.start_node(NodeKind::Error);
+            while !self.at(TokenKind::EOF) {
+                self.bump();
+            }
+            self.finish_node();
+        }
+    }
+
+    fn parse_single(&mut self, kind: NodeKind) {
+        self.start_node(kind);
+        self.bump();
+        self.finish_node();
+    }
+
+    fn parse_if_expression(&mut self) {
+        self.start_node(NodeKind::IfExpression);
+        self.bump();
+
+        self.expect(TokenKind::LParen);
+        self.parse_expression(Precedence::Lowest);
+        self.expect(TokenKind::RParen);
+
+        if self.at(TokenKind::LBrace) {
+            self.parse_block_statement();
+        } else {
+            self.expect(TokenKind::LBrace);
+        }
+
+        if self.at(TokenKind::Else) {
+            self.bump();
+            // else if はつなげた分だけ入れ子になる
+            if self.at(TokenKind::If) {
+                self.nested(Self::parse_if_expression);
+            } else if self.at(TokenKind::LBrace) {
+                self.parse_block_statement();
+            } else {
+                self.expect(TokenKind::LBrace);
+            }
+        }
+
+        self.finish_node();
+    }
+
+    fn parse_match_expression(&mut self) {
+        self.start_node(NodeKind::MatchExpression);
+        self.bump();
+
+        self.expect(TokenKind::LParen);
+        self.parse_expression(Precedence::Lowest);
+        self.expect(TokenKind::RParen);
+
+        if self.expect(TokenKind::LBrace) {
+            while !self.at(TokenKind::RBrace) && !self.at(TokenKind::EOF) {
+                self.parse_match_arm();
+                if !self.at(TokenKind::Comma) {
+                    break;
+                }
+                self.bump();
+            }
+            self.expect(TokenKind::RBrace);
+        }
+
+        self.finish_node();
+    }
+
+    fn parse_match_arm(&mut self) {
+        self.start_node(NodeKind::MatchArm);
+
+        self.parse_pattern();
+        if self.at(TokenKind::If) {
+            self.bump();
+            self.parse_expression(Precedence::Lowest);
+        }
+        if self.expect(TokenKind::FatArrow) {
+            self.arm_body = Some(self.pos);
+            self.parse_expression(Precedence::Lowest);
+        }
+
+        self.finish_node();
+    }
+
+    // let と引数の束縛先。名前か配列・ハッシュの形に限る
+    fn parse_binding(&mut self) -> bool {
+        if self.at(TokenKind::Ident) || self.at(TokenKind::LBracket) || self.at(TokenKind::LBrace) {
+            self.parse_pattern();
+            true
+        } else {
+            self.expect(TokenKind::Ident);
+            false
+        }
+    }
+
+    fn check_count(&mut self, paren: Span, count: usize, what: &str) {
+        if count > MAX_ARGUMENTS {
+            self.errors.push(SyntaxError {
+                message: format!("too many {}: {}", what, count),
+                span: paren,
+            });
+        }
+    }
+
+    // 引数のパターンに出てくる名前はすべて束縛なので、トークンを見れば足りる
+    fn check_duplicate_parameters(&mut self, start: usize) {
+        let mut names: Vec<&str> = vec![];
+        for token in &self.tokens[start..self.pos] {
+            if token.kind != TokenKind::Ident || token.literal == "_" {
+                continue;
+            }
+            if names.contains(&token.literal.as_str()) {
+                self.errors.push(SyntaxError {
+                    message: format!("duplicate parameter {}", token.literal),
+                    span: token.span,
+                });
+            }
+            names.push(&token.literal);
+        }
+    }
+
+    // 式と同じく深さを数える
+    fn parse_pattern(&mut self) {
+        self.nested(Self::parse_nested_pattern);
+    }
+
+    fn parse_nested_pattern(&mut self) {
+        match self.current().kind {
+            TokenKind::Ident => self.parse_single(NodeKind::Identifier),
+            TokenKind::Int => {
+                if self.current().literal.parse::<i64>().is_err() {
+                    let msg = format!("could not parse {} as integer", self.current().literal);
+                    self.error(msg);
+                }
+                self.parse_single(NodeKind::IntegerLiteral);
+            }
+            // 負の数は一つのリテラルとして扱う
+            TokenKind::Minus
+                if self
+                    .tokens
+                    .get(self.pos + 1)
+                    .is_some_and(|t| t.kind == TokenKind::Int) =>
+            {
+                self.start_node(NodeKind::PrefixExpression);
+                self.bump();
+                let literal = format!("-{}", self.current().literal);
+                if literal.parse::<i64>().is_err() {
+                    self.error(format!("could not parse {} as integer", literal));
+                }
+                self.parse_single(NodeKind::IntegerLiteral);
+                self.finish_node();
+            }
+            TokenKind::True | TokenKind::False => self.parse_single(NodeKind::Boolean),
+            TokenKind::String => {
+                if let Err(msg) = unquote(&self.current().literal) {
+                    self.error(msg);
+                }
+                self.parse_single(NodeKind::StringLiteral);
+            }
+            TokenKind::LBracket => self.parse_array_pattern(),
+            TokenKind::LBrace => self.parse_hash_pattern(),
+            k => {
+                self.error(format!("expected pattern, got {} instead", k));
+                if !matches!(
+                    k,
+                    TokenKind::Comma
+                        | TokenKind::FatArrow
+                        | TokenKind::SemiColon
+                        | TokenKind::RBrace
+                        | TokenKind::EOF
+                ) {
+                    self.start_node(NodeKind::Error);
+                    self.bump();
+                    self.finish_node();
+                }
+            }
+        }
+    }
+
+    // 残りの要素は最後にだけ書ける
+    fn parse_array_pattern(&mut self) {
+        self.start_node(NodeKind::ArrayPattern);
+        self.bump();
+
+        while !self.at(TokenKind::RBracket) && !self.at(TokenKind::EOF) {
+            if self.at(TokenKind::DotDot) {
+                self.start_node(NodeKind::RestPattern);
+                self.bump();
+                if self.at(TokenKind::Ident) {
+                    self.parse_single(NodeKind::Identifier);
+                }
+                self.finish_node();
+
+                if self.at(TokenKind::Comma) {
+                    self.bump();
+                }
+                if !self.at(TokenKind::RBracket) {
+                    self.error("rest pattern must come last".to_string());
+                }
+                break;
+            }
+
+            self.parse_pattern();
+            if !self.at(TokenKind::Comma) {
+                break;
+            }
+            self.bump();
+        }
+
+        self.expect(TokenKind::RBracket);
+        self.finish_node();
+    }
+
+    fn parse_hash_pattern(&mut self) {
+        self.start_node(NodeKind::HashPattern);
+        self.bump();
+
+        while !self.at(TokenKind::RBrace) && !self.at(TokenKind::EOF) {
+            self.parse_pattern();
+            self.check_pattern_key();
+            if !self.expect(TokenKind::Colon) {
+                break;
+            }
+            self.parse_pattern();
+            if !self.at(TokenKind::Comma) {
+                break;
+            }
+            self.bump();
+        }
+
+        self.expect(TokenKind::RBrace);
+        self.finish_node();
+    }
+
+    // 読んだばかりのキーは、開いているハッシュパターンの最後の子になっている
+    fn check_pattern_key(&mut self) {
+        let node = self.stack.last().expect("no open node");
+        let Some(key) = node.nodes().last() else {
+            return;
+        };
+        if !is_literal_pattern(key) && key.kind != NodeKind::Error {
+            let msg = format!(
+                "hash pattern key must be a literal, got {}",
+                key.text().trim()
+            );
+            self.errors.push(SyntaxError {
+                message: msg,
+                span: key.span().unwrap_or_default(),
+            });
+        }
+    }
+
+    fn parse_function_literal(&mut self) {
+        self.start_node(NodeKind::FunctionLiteral);
+        self.bump();
+
+        let paren = self.current().span;
+        if self.expect(TokenKind::LParen) {
+            let start = self.pos;
+            let mut count = 0;
+            if !self.at(TokenKind::RParen) {
+                count += 1;
+                while self.parse_binding() && self.at(TokenKind::Comma) {
+                    self.bump();
+                    count += 1;
+                }
+            }
+            self.check_count(paren, count, "parameters");
+            self.check_duplicate_parameters(start);
+        }
+        self.expect(TokenKind::RParen);
+
+        if self.at(TokenKind::LBrace) {
+            let loops = std::mem::take(&mut self.loops);
+            self.parse_block_statement();
+            self.loops = loops;
+        } else {
+            self.expect(TokenKind::LBrace);
+        }
+
+        self.finish_node();
+    }
+
+    fn parse_hash_literal(&mut self) {
+        let in_arm = self.arm_body == Some(self.pos);
+        let brace = self.current().span;
+        self.start_node(NodeKind::HashLiteral);
+        self.bump();
+
+        let mut first = true;
+        while !self.at(TokenKind::RBrace) && !self.at(TokenKind::EOF) {
+            let errors = self.errors.len();
+            self.parse_expression(Precedence::Lowest);
+            if in_arm && first && !self.at(TokenKind::Colon) {
+                self.errors.truncate(errors);
+                self.block_in_match_arm(brace);
+                self.finish_node();
+                return;
+            }
+            first = false;
+            if !self.expect(TokenKind::Colon) {
+                break;
+            }
+            self.parse_expression(Precedence::Lowest);
+            if !self.at(TokenKind::Comma) {
+                break;
+            }
+            self.bump();
+        }
+
+        self.expect(TokenKind::RBrace);
+        self.finish_node();
+    }
+
+    // match の腕にブロックは書けない。対応する } までをそのまま取り込んで、括弧を使うよう知らせる
+    fn block_in_match_arm(&mut self, span: Span) {
+        let message = "match arm body cannot be a block; use parentheses around an expression instead of braces";
+        self.errors.push(SyntaxError {
+            message: message.to_string(),
+            span,
+        });
+        let mut depth = 1;
+        while !self.at(TokenKind::EOF) {
+            match self.current().kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    // 開き括弧から end までの、カンマ区切りの式の並び。式の数を返す
+    fn parse_expression_list(&mut self, end: TokenKind) -> usize {
+        self.bump();
+
+        let mut count = 0;
+        if !self.at(end) {
+            self.parse_expression(Precedence::Lowest);
+            count += 1;
+            while self.at(TokenKind::Comma) {
+                self.bump();
+                self.parse_expression(Precedence::Lowest);
+                count += 1;
+            }
+        }
+
+        self.expect(end);
+        count
+    }
+
+    fn parse_block_statement(&mut self) {
+        self.start_node(NodeKind::BlockStatement);
+        self.bump();
+        self.blocks += 1;
+
+        while !self.at(TokenKind::RBrace) && !self.at(TokenKind::EOF) {
+            self.parse_statement();
+        }
+
+        self.blocks -= 1;
+
+        self.expect(TokenKind::RBrace);
+        self.finish_node();
+    }
+}
+
+fn is_assign_operator(k: TokenKind) -> bool {
+    matches!(
+        k,
+        TokenKind::Assign
+            | TokenKind::PlusAssign
+            | TokenKind::MinusAssign
+            | TokenKind::AsteriskAssign
+            | TokenKind::SlashAssign
+    )
+}
+
+// 括弧は AST に残らないので、中身で判断する
+fn is_assignable(node: &SyntaxNode) -> bool {
+    match node.kind {
+        NodeKind::Identifier => true,
+        NodeKind::IndexExpression | NodeKind::GroupedExpression => {
+            node.nodes().next().is_some_and(is_assignable)
+        }
+        _ => false,
+    }
+}
+
+fn is_literal_pattern(node: &SyntaxNode) -> bool {
+    matches!(
+        node.kind,
+        NodeKind::IntegerLiteral
+            | NodeKind::StringLiteral
+            | NodeKind::Boolean
+            | NodeKind::PrefixExpression
+    )
+}
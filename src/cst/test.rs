@@ -0,0 +1,289 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, MAX_ARGUMENTS, MAX_NESTING};
+
+fn parse_ast(input: &str) -> (crate::ast::Program, Vec<SyntaxError>) {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    (program, p.errors().to_vec())
+}
+
+#[test]
+fn test_lossless() {
+    let inputs = [
+        "",
+        "   \n\t ",
+        "let x = 5;",
+        "  let   x=5 ;; // comment\n\n// another\r\n",
+        "if ((x)) { // c\n  x } else {y}",
+        "let = ;",
+        "}}} )) let",
+        "1 + @ # $ 2",
+        "let café = \"€\";",
+        "a\0b c",
+        "if (x { y",
+        "fn(a, { } f(1, ",
+    ];
+
+    for input in inputs {
+        let parse = parse(input);
+        assert_eq!(parse.root.text(), input);
+    }
+}
+
+#[test]
+fn test_to_program() {
+    let inputs = [
+        "let x = 5; let y = true; return x;",
+        "-a * b + !c == (d - e) / f",
+        "// leading\nif (x < y) { x } else { let z = y; z }; 3 + 4; -5 * 5",
+        "((a + b)) * c; (1)",
+        "if (a) {} 5 5",
+        "let add = fn(a, b) { a + b }; add(1, 2 * 3)(fn() {});",
+        r#"let h = {"a": [1, 2][0], true: "\t", 1 + 1: {}}; h["a"]"#,
+        "{}; {1: 2,}",
+        "x = (a[0][i]) += 1 == 2; y -= z *= 3 / 4",
+        "if (a) { 1 } else if ((b)) { 2 } else if (c) {} else { if (d) { 4 } }",
+        r#"match ((x)) { [h, ..t] if (h) => t, {"k": -1, 2: [..]} => 0, [_, .._] => 1, y => y, }"#,
+        "match (x) {}; let y = match (x) { a => match (a) { _ => a } } + 1",
+        r#"let [a, ..b] = x; let {"k": [_, c]} = fn([d], {1: e}, f) { d }; let _ = 1"#,
+        r#"import "lib/a.mk" as a import "b" as b; export let [x] = a.f(b.y.z)[0].w; x = a.v"#,
+    ];
+
+    for input in inputs {
+        let parse = parse(input);
+        assert!(parse.errors.is_empty(), "errors: {:?}", parse.errors);
+
+        let (expected, errors) = parse_ast(input);
+        assert!(errors.is_empty());
+        assert_eq!(parse.root.to_program(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_dump() {
+    let parse = parse("let x = (1 + 2); // three");
+
+    assert_eq!(
+        parse.root.dump(),
+        "Program@0..25
+  LetStatement@0..16
+    LET \"let\"@0..3
+    Whitespace \" \"@3..4
+    Identifier@4..5
+      IDENT \"x\"@4..5
+    Whitespace \" \"@5..6
+    = \"=\"@6..7
+    Whitespace \" \"@7..8
+    GroupedExpression@8..15
+      ( \"(\"@8..9
+      InfixExpression@9..14
+        IntegerLiteral@9..10
+          INT \"1\"@9..10
+        Whitespace \" \"@10..11
+        + \"+\"@11..12
+        Whitespace \" \"@12..13
+        IntegerLiteral@13..14
+          INT \"2\"@13..14
+      ) \")\"@14..15
+    ; \";\"@15..16
+  Whitespace \" \"@16..17
+  Comment \"// three\"@17..25
+  EOF \"\"@25..25
+"
+    );
+}
+
+#[test]
+fn test_error_nodes() {
+    let parse = parse("let x = 5 ) ; }");
+
+    let kinds: Vec<NodeKind> = parse.root.nodes().map(|n| n.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            NodeKind::LetStatement,
+            NodeKind::ExpressionStatement,
+            NodeKind::Error,
+        ]
+    );
+
+    let error_stmt = parse.root.nodes().nth(1).unwrap();
+    assert_eq!(error_stmt.text(), ") ;");
+    assert_eq!(error_stmt.nodes().next().unwrap().kind, NodeKind::Error);
+
+    let messages: Vec<&str> = parse.errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec!["no prefix parse function for ) found", "unexpected }"]
+    );
+    assert_eq!(parse.errors[0].span, Span::new(10, 11));
+
+    // 壊れた文は AST には含まれない
+    assert_eq!(parse.root.to_program().statements.len(), 2);
+}
+
+#[test]
+fn test_nesting_limit() {
+    let n = MAX_NESTING + 1;
+    let input = format!("let x = {}1{};\nx", "(".repeat(n), ")".repeat(n));
+    let parse = parse(&input);
+
+    let messages: Vec<&str> = parse.errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(messages, vec!["expression nested too deeply"]);
+    assert_eq!(
+        parse.errors[0].span,
+        Span::new(8 + MAX_NESTING, 9 + MAX_NESTING)
+    );
+    assert_eq!(parse.root.text(), input);
+}
+
+#[test]
+fn test_deep_statements() {
+    let inputs = [
+        format!("if (x) {{}}{}", " else if (x) {{}}".repeat(30000)),
+        format!("{}{}", "while (x) {".repeat(5000), "}".repeat(5000)),
+        format!("{}{}", "for (x in y) {".repeat(5000), "}".repeat(5000)),
+    ];
+    for input in inputs {
+        let parse = parse(&input);
+        let messages: Vec<&str> = parse.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["expression nested too deeply"]);
+        assert_eq!(parse.root.text(), input);
+    }
+}
+
+#[test]
+fn test_long_operator_chain() {
+    let input = format!("1{}", " + 1".repeat(999));
+    let parse = parse(&input);
+    assert!(parse.errors.is_empty(), "{:?}", parse.errors);
+    assert_eq!(parse.root.text(), input);
+}
+
+#[test]
+fn test_missing_tokens() {
+    let parse = parse("if (x { y");
+
+    let messages: Vec<&str> = parse.errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "expected next token to be ), got { instead",
+            "expected next token to be }, got EOF instead",
+        ]
+    );
+    assert_eq!(parse.root.text(), "if (x { y");
+}
+
+#[test]
+fn test_top_level_only() {
+    let parse = parse("if (x) { import \"a\" as a; export let b = 1; }");
+
+    let errors: Vec<(&str, Span)> = parse
+        .errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            ("import is only allowed at the top level", Span::new(9, 15)),
+            ("export is only allowed at the top level", Span::new(26, 32)),
+        ]
+    );
+}
+
+#[test]
+fn test_block_in_match_arm() {
+    let input = "match (x) { 1 => { let y = 1; { y } }, _ => { \"a\": y } }";
+    let parse = parse(input);
+
+    let errors: Vec<(&str, Span)> = parse
+        .errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(
+            "match arm body cannot be a block; use parentheses around an expression instead of braces",
+            Span::new(17, 18)
+        )]
+    );
+    assert_eq!(parse.root.text(), input);
+}
+
+// 引数の数の上限は AST のパーサと同じエラーになる
+#[test]
+fn test_argument_limit() {
+    let arguments = vec!["1"; MAX_ARGUMENTS + 1].join(", ");
+    let parameters = vec!["_"; MAX_ARGUMENTS + 1].join(", ");
+    for input in [
+        format!("f({})", arguments),
+        format!("fn({}) {{}}", parameters),
+    ] {
+        let parse = parse(&input);
+        let (_, errors) = parse_ast(&input);
+        assert_eq!(parse.errors.len(), 1, "input: {}", input);
+        assert_eq!(parse.errors, errors, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_duplicate_parameters() {
+    let parse = parse("fn(a, [b, ..a], {1: b}) {}");
+
+    let errors: Vec<(&str, Span)> = parse
+        .errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            ("duplicate parameter a", Span::new(12, 13)),
+            ("duplicate parameter b", Span::new(20, 21)),
+        ]
+    );
+}
+
+// xorshift64 でランダムな入力を作り、CST が常に入力を復元できることを確かめる
+#[test]
+fn test_random_input() {
+    let pieces = [
+        "let", "return", "if", "else", "fn", "true", "false", "x", "y1", "42", "=", "==", "!",
+        "!=", "+", "-", "*", "/", "<", ">", "(", ")", "{", "}", ",", ";", " ", "\n", "// c\n", "@",
+        "é", "while", "break", "continue", "for", "in", "..", "..=", "+=", "-=", "*=", "/=",
+        "match", "=>", "_", "[", "]", ":", "import", "export", "as", ".", "\"m\"",
+    ];
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+
+    for _ in 0..2000 {
+        let mut input = String::new();
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        for _ in 0..(state % 20) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            input.push_str(pieces[(state % pieces.len() as u64) as usize]);
+        }
+
+        let parse = parse(&input);
+        assert_eq!(parse.root.text(), input);
+
+        if parse.errors.is_empty() {
+            let (expected, errors) = parse_ast(&input);
+            assert!(
+                errors.is_empty(),
+                "input: {:?}, errors: {:?}",
+                input,
+                errors
+            );
+            assert_eq!(parse.root.to_program(), expected, "input: {:?}", input);
+        }
+    }
+}
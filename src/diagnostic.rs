@@ -0,0 +1,352 @@
+#[cfg(test)]
+mod test;
+
+use crate::json::Json;
+use crate::object::RuntimeError;
+use crate::parser::SyntaxError;
+use crate::token::Span;
+
+// これより深い呼び出しは数だけ示す
+const MAX_STACK_NOTES: usize = 16;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+// 範囲に添える説明。主な範囲には ^、それ以外には - で下線を引く
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// rustc のように、該当する行を引用して下線を引いた形で表示する
+//
+//   error: expected next token to be ), got { instead
+//    --> main.mk:1:7
+//     |
+//   1 | if (x { y }
+//     |       ^
+//     = note: ...
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    // 主な範囲。位置の表示にも使う
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl ToString) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl ToString) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: impl ToString) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.to_string(),
+            span: None,
+            labels: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl ToString) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    // 呼び出し中だった関数は、内側から順に注記にする。
+    // ソースがなければ呼び出した場所は示さない。モジュールの中の場所も示さない
+    pub fn from_runtime_error(
+        error: &RuntimeError,
+        path: &str,
+        source: Option<&str>,
+    ) -> Diagnostic {
+        Diagnostic::from_runtime_error_in(error, |module| match module {
+            None => source.map(|source| (path, source)),
+            Some(_) => None,
+        })
+    }
+
+    // file はモジュールの名前からパスとソースを引く。None は実行したファイル
+    pub fn from_runtime_error_in<'a>(
+        error: &RuntimeError,
+        file: impl Fn(Option<&str>) -> Option<(&'a str, &'a str)>,
+    ) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(&error.message);
+        if file(error.module.as_deref()).is_some() {
+            diagnostic.span = error.span;
+        }
+        for frame in error.stack.iter().take(MAX_STACK_NOTES) {
+            let note = match (frame.span, file(frame.module.as_deref())) {
+                (Some(span), Some((path, source))) => {
+                    let (line, column) = line_column(source, span.start);
+                    format!("in {}, called at {}:{}:{}", frame, path, line, column)
+                }
+                _ => format!("in {}", frame),
+            };
+            diagnostic.notes.push(note);
+        }
+        if error.stack.len() > MAX_STACK_NOTES {
+            let rest = error.stack.len() - MAX_STACK_NOTES;
+            diagnostic
+                .notes
+                .push(format!("... and {} more calls", rest));
+        }
+        diagnostic
+    }
+
+    pub fn render(&self, path: &str, source: &str, color: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let severity = self.severity;
+        let mut out = format!(
+            "{}{}\n",
+            paint(severity.color(), severity.as_str()),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+
+        let marks = self.marks(source);
+        let width = marks
+            .iter()
+            .map(|mark| mark.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = paint(BLUE, &format!("{:w$} |", "", w = width));
+
+        // 場所が分からなくてもファイルは示す
+        let arrow = paint(BLUE, &format!("{:w$}--> ", "", w = width));
+        let location = self.span.or_else(|| self.labels.first().map(|l| l.span));
+        match location {
+            Some(span) => {
+                let (line, column) = line_column(source, span.start);
+                out += &format!("{}{}:{}:{}\n", arrow, path, line, column);
+                out += &format!("{}\n", gutter);
+            }
+            None => out += &format!("{}{}\n", arrow, path),
+        }
+
+        // 同じ行の下線は続けて並べ、離れた行の間は ... で省く
+        let mut last_line = None;
+        for mark in &marks {
+            if last_line != Some(mark.line) {
+                if last_line.is_some_and(|last| mark.line > last + 1) {
+                    out += &format!("{}\n", paint(BLUE, "..."));
+                }
+                let number = paint(BLUE, &format!("{:w$} |", mark.line, w = width));
+                let text = line_text(source, mark.line).replace('\t', TAB);
+                out += &format!("{} {}\n", number, text);
+                last_line = Some(mark.line);
+            }
+            // 下線は引用した行と同じく、タブを広げて全角の文字は 2 桁に数えた位置に引く
+            let line = line_text(source, mark.line).chars();
+            let indent = display_width(line.clone().take(mark.column - 1));
+            let len = display_width(line.skip(mark.column - 1).take(mark.len)).max(1);
+            let (underline, code) = if mark.primary {
+                ("^".repeat(len), severity.color())
+            } else {
+                ("-".repeat(len), BLUE)
+            };
+            let mut text = underline;
+            if !mark.message.is_empty() {
+                text = format!("{} {}", text, mark.message);
+            }
+            let indent = " ".repeat(indent);
+            out += &format!("{} {}{}\n", gutter, indent, paint(code, &text));
+        }
+
+        for note in &self.notes {
+            out += &format!(
+                "{} {} {}\n",
+                paint(BLUE, &format!("{:w$} =", "", w = width)),
+                paint(BOLD, "note:"),
+                note
+            );
+        }
+        out
+    }
+
+    // 主な範囲を先に、行と列の順に並べる。主な範囲と同じ範囲のラベルはその説明にする
+    fn marks<'a>(&'a self, source: &str) -> Vec<Mark<'a>> {
+        let mut marks = vec![];
+        if let Some(span) = self.span {
+            let label = self.labels.iter().find(|l| l.span == span);
+            let message = label.map_or("", |l| l.message.as_str());
+            marks.push(Mark::new(source, span, true, message));
+        }
+        for label in self.labels.iter().filter(|l| Some(l.span) != self.span) {
+            marks.push(Mark::new(source, label.span, false, &label.message));
+        }
+        marks.sort_by_key(|mark| (mark.line, !mark.primary, mark.column));
+        marks
+    }
+
+    // 1 件を 1 行の JSON で表す。位置は文字単位で、行と列は 1 から数える
+    pub fn to_json(&self, path: &str, source: &str) -> Json {
+        let span_to_json = |span: Span| {
+            let (line, column) = line_column(source, span.start);
+            Json::object(vec![
+                ("start", span.start.into()),
+                ("end", span.end.into()),
+                ("line", line.into()),
+                ("column", column.into()),
+            ])
+        };
+        Json::object(vec![
+            ("severity", self.severity.as_str().into()),
+            ("message", self.message.as_str().into()),
+            ("file", path.into()),
+            ("span", self.span.map_or(Json::Null, span_to_json)),
+            (
+                "labels",
+                Json::Array(
+                    self.labels
+                        .iter()
+                        .map(|l| {
+                            Json::object(vec![
+                                ("span", span_to_json(l.span)),
+                                ("message", l.message.as_str().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "notes",
+                Json::Array(self.notes.iter().map(|n| n.as_str().into()).collect()),
+            ),
+        ])
+    }
+}
+
+impl From<&SyntaxError> for Diagnostic {
+    fn from(error: &SyntaxError) -> Diagnostic {
+        Diagnostic::error(&error.message).with_span(error.span)
+    }
+}
+
+struct Mark<'a> {
+    line: usize,
+    column: usize,
+    len: usize,
+    primary: bool,
+    message: &'a str,
+}
+
+impl Mark<'_> {
+    fn new<'a>(source: &str, span: Span, primary: bool, message: &'a str) -> Mark<'a> {
+        let (line, column) = line_column(source, span.start);
+        // 行をまたぐ範囲は行末までにする
+        let rest = (line_text(source, line).chars().count() + 1).saturating_sub(column);
+        Mark {
+            line,
+            column,
+            len: (span.end - span.start).min(rest).max(1),
+            primary,
+            message,
+        }
+    }
+}
+
+fn line_text(source: &str, line: usize) -> &str {
+    source.lines().nth(line - 1).unwrap_or("")
+}
+
+// 引用する行のタブはこの空白に置き換える
+const TAB: &str = "    ";
+
+// 端末で表示したときの桁数
+fn display_width(chars: impl Iterator<Item = char>) -> usize {
+    chars
+        .map(|c| match c {
+            '\t' => TAB.len(),
+            c if is_wide(c) => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+// 東アジアの全角の文字と絵文字のおもな範囲
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD
+    )
+}
+
+const BOLD: &str = "1";
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+
+// 文字の位置から 1 始まりの行と列を求める
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for c in source.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
@@ -0,0 +1,208 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::object::{ErrorKind, StackFrame};
+use crate::parser::Parser;
+
+fn syntax_errors(input: &str) -> Vec<SyntaxError> {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    p.parse_program();
+    p.errors().to_vec()
+}
+
+#[test]
+fn test_render_syntax_error() {
+    let source = "let x = 1;\nif (x { x }";
+    let errors = syntax_errors(source);
+    let expected = "\
+error: expected next token to be ), got { instead
+ --> main.mk:2:7
+  |
+2 | if (x { x }
+  |       ^
+";
+    assert_eq!(
+        Diagnostic::from(&errors[0]).render("main.mk", source, false),
+        expected
+    );
+}
+
+#[test]
+fn test_render_runtime_error() {
+    let source = "let add = fn(a, b) {\n  a + b\n};\nadd(1, true)";
+    let error = RuntimeError {
+        message: "type mismatch: INTEGER + BOOLEAN".to_string(),
+        kind: ErrorKind::Runtime,
+        span: Some(Span::new(25, 26)),
+        stack: vec![StackFrame {
+            function: Some("add".into()),
+            span: Some(Span::new(35, 36)),
+            module: None,
+        }],
+        module: None,
+    };
+    let expected = "\
+error: type mismatch: INTEGER + BOOLEAN
+ --> main.mk:2:5
+  |
+2 |   a + b
+  |     ^
+  = note: in add, called at main.mk:4:4
+";
+    let diagnostic = Diagnostic::from_runtime_error(&error, "main.mk", Some(source));
+    assert_eq!(diagnostic.render("main.mk", source, false), expected);
+}
+
+// ソースがなければ場所も呼び出し元の位置も示さない
+#[test]
+fn test_render_runtime_error_without_source() {
+    let error = RuntimeError {
+        message: "stack overflow".to_string(),
+        kind: ErrorKind::Runtime,
+        span: Some(Span::new(0, 1)),
+        stack: vec![StackFrame {
+            function: None,
+            span: Some(Span::new(0, 1)),
+            module: None,
+        }],
+        module: None,
+    };
+    let diagnostic = Diagnostic::from_runtime_error(&error, "main.mkc", None);
+    assert_eq!(
+        diagnostic.render("main.mkc", "", false),
+        "error: stack overflow\n --> main.mkc\n  = note: in <anonymous>\n"
+    );
+}
+
+#[test]
+fn test_stack_notes_are_capped() {
+    let frame = StackFrame {
+        function: Some("f".into()),
+        span: None,
+        module: None,
+    };
+    let error = RuntimeError {
+        message: "stack overflow".to_string(),
+        kind: ErrorKind::Runtime,
+        span: None,
+        stack: vec![frame; MAX_STACK_NOTES + 3],
+        module: None,
+    };
+    let diagnostic = Diagnostic::from_runtime_error(&error, "main.mk", Some(""));
+    assert_eq!(diagnostic.notes.len(), MAX_STACK_NOTES + 1);
+    assert_eq!(diagnostic.notes.last().unwrap(), "... and 3 more calls");
+}
+
+#[test]
+fn test_render_labels() {
+    let source = "let x = 1;\nlet y = 2;\nlet z = 3;\nlet x = 4;";
+    let diagnostic = Diagnostic::warning("x is defined twice")
+        .with_span(Span::new(37, 38))
+        .with_label(Span::new(37, 38), "redefined here")
+        .with_label(Span::new(4, 5), "first defined here")
+        .with_note("rename one of them");
+    let expected = "\
+warning: x is defined twice
+ --> main.mk:4:5
+  |
+1 | let x = 1;
+  |     - first defined here
+...
+4 | let x = 4;
+  |     ^ redefined here
+  = note: rename one of them
+";
+    assert_eq!(diagnostic.render("main.mk", source, false), expected);
+}
+
+#[test]
+fn test_render_color() {
+    let diagnostic = Diagnostic::error("oops").with_span(Span::new(0, 3));
+    let expected = "\
+\x1b[1;31merror\x1b[0m\x1b[1m: oops\x1b[0m
+\x1b[1;34m --> \x1b[0mmain.mk:1:1
+\x1b[1;34m  |\x1b[0m
+\x1b[1;34m1 |\x1b[0m abc
+\x1b[1;34m  |\x1b[0m \x1b[1;31m^^^\x1b[0m
+";
+    assert_eq!(diagnostic.render("main.mk", "abc", true), expected);
+}
+
+// 行をまたぐ範囲は行末で切る
+#[test]
+fn test_render_multiline_span() {
+    let diagnostic = Diagnostic::error("oops").with_span(Span::new(2, 6));
+    assert!(diagnostic
+        .render("main.mk", "a bc\nd", false)
+        .ends_with("1 | a bc\n  |   ^^\n"));
+}
+
+// タブは引用した行と同じだけ広げて、下線の位置をそろえる
+#[test]
+fn test_render_tab() {
+    let source = "let x = 1;\n\tif (x { x }";
+    let errors = syntax_errors(source);
+    let expected = "\
+error: expected next token to be ), got { instead
+ --> main.mk:2:8
+  |
+2 |     if (x { x }
+  |           ^
+";
+    assert_eq!(
+        Diagnostic::from(&errors[0]).render("main.mk", source, false),
+        expected
+    );
+}
+
+// 全角の文字は 2 桁として数え、下線もその幅で引く
+#[test]
+fn test_render_wide_characters() {
+    let source = "let s = \"日本語\" + 1;";
+    let diagnostic = Diagnostic::error("type mismatch: STRING + INTEGER")
+        .with_span(Span::new(16, 17))
+        .with_label(Span::new(8, 13), "string");
+    let expected = "\
+error: type mismatch: STRING + INTEGER
+ --> main.mk:1:17
+  |
+1 | let s = \"日本語\" + 1;
+  |                    ^
+  |         -------- string
+";
+    assert_eq!(diagnostic.render("main.mk", source, false), expected);
+}
+
+#[test]
+fn test_to_json() {
+    let source = "let x = 1;\nx + y";
+    let diagnostic = Diagnostic::error("identifier not found: y")
+        .with_span(Span::new(15, 16))
+        .with_label(Span::new(11, 12), "left operand")
+        .with_note("did you mean x?");
+    let expected = concat!(
+        r#"{"severity":"error","message":"identifier not found: y","file":"main.mk","#,
+        r#""span":{"start":15,"end":16,"line":2,"column":5},"#,
+        r#""labels":[{"span":{"start":11,"end":12,"line":2,"column":1},"message":"left operand"}],"#,
+        r#""notes":["did you mean x?"]}"#
+    );
+    assert_eq!(diagnostic.to_json("main.mk", source).to_string(), expected);
+
+    let json = Diagnostic::error("stack overflow").to_json("main.mk", "");
+    assert_eq!(json.get("span"), Some(&Json::Null));
+}
+
+#[test]
+fn test_line_column() {
+    let source = "ab\nc日本\n";
+    let tests = vec![
+        (0, (1, 1)),
+        (2, (1, 3)),
+        (3, (2, 1)),
+        (5, (2, 3)),
+        (7, (3, 1)),
+    ];
+    for (offset, expected) in tests {
+        assert_eq!(line_column(source, offset), expected, "offset: {}", offset);
+    }
+}
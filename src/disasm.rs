@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod test;
+
+use crate::builtins::BUILTINS;
+use crate::code::{Definition, Opcode};
+use crate::compiler::Bytecode;
+use crate::lexer::quote;
+use crate::object::{CompiledFunction, Object};
+use std::fmt::Write;
+
+// main:
+//   0000 OpConstant 0  ; 1
+// constants:
+//   0 INTEGER 1
+// fn#1 (params: 1, locals: 1):
+//   0000 OpGetLocal 0
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::new();
+
+    out.push_str("main:\n");
+    write_instructions(&mut out, &bytecode.instructions, &bytecode.constants);
+
+    if !bytecode.constants.is_empty() {
+        out.push_str("constants:\n");
+        for (i, constant) in bytecode.constants.iter().enumerate() {
+            writeln!(
+                out,
+                "  {} {} {}",
+                i,
+                constant.type_name(),
+                describe_constant(i, constant)
+            )
+            .unwrap();
+        }
+    }
+
+    // 入れ子の関数も定数に入っているので、定数の順に本体を並べる
+    for (i, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            writeln!(out, "{}:", function_label(i, function)).unwrap();
+            write_instructions(&mut out, &function.instructions, &bytecode.constants);
+        }
+    }
+
+    out
+}
+
+// 一行に一命令ずつ、位置と命令名とオペランドを並べる
+pub fn disassemble_instructions(ins: &[u8]) -> String {
+    let mut out = String::new();
+    write_instructions(&mut out, ins, &[]);
+    out
+}
+
+fn write_instructions(out: &mut String, ins: &[u8], constants: &[Object]) {
+    let mut offset = 0;
+    while offset < ins.len() {
+        write!(out, "  {:04} ", offset).unwrap();
+
+        let Some(op) = Opcode::from_byte(ins[offset]) else {
+            writeln!(out, "ERROR: unknown opcode {}", ins[offset]).unwrap();
+            offset += 1;
+            continue;
+        };
+        let def = op.definition();
+        let Some(operands) = decode_operands(&def, &ins[offset + 1..]) else {
+            writeln!(out, "{} ERROR: truncated operands", def.name).unwrap();
+            return;
+        };
+
+        out.push_str(def.name);
+        for operand in &operands {
+            write!(out, " {}", operand).unwrap();
+        }
+        if matches!(op, Opcode::Constant | Opcode::Closure) {
+            if let Some(constant) = constants.get(operands[0]) {
+                write!(out, "  ; {}", describe_constant(operands[0], constant)).unwrap();
+            }
+        }
+        if op == Opcode::GetBuiltin {
+            if let Some(builtin) = BUILTINS.get(operands[0]) {
+                write!(out, "  ; {}", builtin.name).unwrap();
+            }
+        }
+        out.push('\n');
+
+        offset += 1 + def.operand_widths.iter().sum::<usize>();
+    }
+}
+
+// 壊れた命令列でも読めるところまで読む
+fn decode_operands(def: &Definition, ins: &[u8]) -> Option<Vec<usize>> {
+    let mut operands = vec![];
+    let mut offset = 0;
+    for width in def.operand_widths {
+        let bytes = ins.get(offset..offset + width)?;
+        operands.push(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize));
+        offset += width;
+    }
+    Some(operands)
+}
+
+fn describe_constant(index: usize, constant: &Object) -> String {
+    match constant {
+        Object::CompiledFunction(function) => function_label(index, function),
+        Object::String(value) => quote(value),
+        other => other.to_string(),
+    }
+}
+
+fn function_label(index: usize, function: &CompiledFunction) -> String {
+    format!(
+        "fn#{} (params: {}, locals: {})",
+        index, function.num_parameters, function.num_locals
+    )
+}
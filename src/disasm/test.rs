@@ -0,0 +1,75 @@
+use super::*;
+use crate::code::make;
+use crate::compiler::Compiler;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+#[test]
+fn test_disassemble_instructions() {
+    let ins = [
+        make(Opcode::Add, &[]),
+        make(Opcode::GetLocal, &[1]),
+        make(Opcode::Constant, &[2]),
+        make(Opcode::Constant, &[65535]),
+        make(Opcode::Call, &[255]),
+    ]
+    .concat();
+
+    assert_eq!(
+        disassemble_instructions(&ins),
+        "  0000 OpAdd
+  0001 OpGetLocal 1
+  0003 OpConstant 2
+  0006 OpConstant 65535
+  0009 OpCall 255
+"
+    );
+}
+
+#[test]
+fn test_disassemble_malformed() {
+    let ins = [
+        vec![200],
+        make(Opcode::Pop, &[]),
+        vec![Opcode::Constant as u8, 1],
+    ]
+    .concat();
+
+    assert_eq!(
+        disassemble_instructions(&ins),
+        "  0000 ERROR: unknown opcode 200
+  0001 OpPop
+  0002 OpConstant ERROR: truncated operands
+"
+    );
+}
+
+#[test]
+fn test_disassemble() {
+    let mut l = Lexer::new("let f = fn(a) { a + 1 }; f(2)");
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+
+    assert_eq!(
+        disassemble(&compiler.bytecode()),
+        "main:
+  0000 OpClosure 1 0  ; fn#1 (params: 1, locals: 1)
+  0004 OpSetGlobal 0
+  0007 OpGetGlobal 0
+  0010 OpConstant 2  ; 2
+  0013 OpCall 1
+  0015 OpPop
+constants:
+  0 INTEGER 1
+  1 COMPILED_FUNCTION fn#1 (params: 1, locals: 1)
+  2 INTEGER 2
+fn#1 (params: 1, locals: 1):
+  0000 OpGetLocal 0
+  0002 OpConstant 0  ; 1
+  0005 OpAdd
+  0006 OpReturnValue
+"
+    );
+}
@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod test;
+
+use crate::ast::{Program, Statement};
+use crate::builtins::{self, Output};
+use crate::compiler::{Bytecode, Compiler, SymbolTable};
+use crate::evaluator::Evaluator;
+use crate::host::RuntimeError;
+use crate::limits::Limits;
+use crate::module::Loader;
+use crate::object::{HostFunction, Object};
+use crate::vm::Vm;
+use std::fmt;
+use std::str::FromStr;
+
+// 木をたどる評価器と、バイトコードの VM のどちらで実行するか
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Engine {
+    Eval,
+    #[default]
+    Vm,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Engine, String> {
+        match s {
+            "eval" => Ok(Engine::Eval),
+            "vm" => Ok(Engine::Vm),
+            _ => Err(format!("unknown engine: {} (expected eval or vm)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Engine::Eval => write!(f, "eval"),
+            Engine::Vm => write!(f, "vm"),
+        }
+    }
+}
+
+// 続けて実行するプログラムの間で束縛を引き継ぐ
+pub struct Interpreter {
+    engine: Engine,
+    evaluator: Evaluator,
+    symbol_table: SymbolTable,
+    constants: Vec<Object>,
+    globals: Vec<Object>,
+    output: Output,
+    limits: Limits,
+    loader: Loader,
+}
+
+impl Interpreter {
+    pub fn new(engine: Engine) -> Interpreter {
+        Interpreter {
+            engine,
+            evaluator: Evaluator::new(),
+            symbol_table: SymbolTable::new(),
+            constants: vec![],
+            globals: vec![],
+            output: builtins::stdout(),
+            limits: Limits::default(),
+            loader: Loader::default(),
+        }
+    }
+
+    // puts の書き込み先を差し替える
+    pub fn with_output(mut self, output: Output) -> Interpreter {
+        self.evaluator = self.evaluator.with_output(output.clone());
+        self.output = output;
+        self
+    }
+
+    // 上限は run を呼ぶたびに測り直す
+    pub fn with_limits(mut self, limits: Limits) -> Interpreter {
+        self.evaluator = self.evaluator.with_limits(limits);
+        self.limits = limits;
+        self
+    }
+
+    // 評価器を動かすスレッドのスタックの大きさ。VM は Rust のスタックを深く使わない
+    pub fn with_stack_size(mut self, bytes: usize) -> Interpreter {
+        self.evaluator = self.evaluator.with_stack_size(bytes);
+        self
+    }
+
+    // import のパスの探し方を差し替える
+    pub fn with_loader(mut self, loader: Loader) -> Interpreter {
+        self.evaluator = self.evaluator.with_loader(loader.clone());
+        self.loader = loader;
+        self
+    }
+
+    // 埋め込み側の関数を登録する。組み込み関数と同じように呼べるが、
+    // グローバルの束縛なので同じ名前の組み込み関数より優先され、let で上書きもできる
+    pub fn register(
+        &mut self,
+        name: &str,
+        function: impl Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    ) {
+        let function = Object::HostFunction(HostFunction::new(name, function));
+        self.evaluator.define(name, function.clone());
+
+        let index = self.symbol_table.define(name).index;
+        if index >= self.globals.len() {
+            self.globals.resize(index + 1, Object::Null);
+        }
+        self.globals[index] = function;
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    // 最後の文が式でなければ表示する値はない。エラーは Object::Error で返す
+    pub fn run(&mut self, program: &Program) -> Option<Object> {
+        let result = match self.engine {
+            Engine::Eval => self.evaluator.eval(program),
+            Engine::Vm => self.run_vm(program),
+        };
+
+        if result.is_error() || has_result(program) {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    // コンパイル済みのバイトコードを VM で実行する
+    pub fn run_bytecode(&mut self, bytecode: Bytecode) -> Object {
+        let mut vm = Vm::new_with_globals(bytecode, std::mem::take(&mut self.globals))
+            .with_output(self.output.clone())
+            .with_limits(self.limits);
+        // スタックがあふれて止まったときは、取り除いた値の位置が範囲外になる
+        let result = vm.run().map(|()| vm.last_popped_stack_elem());
+        self.globals = vm.into_globals();
+
+        result.unwrap_or_else(|e| Object::Error(Box::new(e)))
+    }
+
+    // 実行はせず、これまでの定義を踏まえてコンパイルだけする
+    pub fn compile(&self, program: &Program) -> Result<Bytecode, RuntimeError> {
+        let mut compiler =
+            Compiler::new_with_state(self.symbol_table.clone(), self.constants.clone())
+                .with_loader(self.loader.clone());
+        compiler.compile(program)?;
+        Ok(compiler.bytecode())
+    }
+
+    fn run_vm(&mut self, program: &Program) -> Object {
+        let mut compiler =
+            Compiler::new_with_state(self.symbol_table.clone(), self.constants.clone())
+                .with_loader(self.loader.clone());
+        if let Err(e) = compiler.compile(program) {
+            return Object::Error(Box::new(e));
+        }
+
+        let bytecode = compiler.bytecode();
+        (self.symbol_table, self.constants) = compiler.into_state();
+        self.run_bytecode(bytecode)
+    }
+}
+
+// 最後の文が式か return なら、その値がプログラムの結果になる
+pub fn has_result(program: &Program) -> bool {
+    matches!(
+        program.statements.last(),
+        Some(Statement::ExpressionStatement(_) | Statement::ReturnStatement(_))
+    )
+}
@@ -0,0 +1,482 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Span;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+fn parse(input: &str) -> Program {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    program
+}
+
+// どちらのエンジンでも同じ結果になる
+#[test]
+fn test_engines_agree() {
+    let inputs = [
+        "1 + 2 * 3",
+        "if (1 > 2) { 10 }",
+        "let x = 5;",
+        "let x = 5; x",
+        "-true",
+        "1 == true",
+        "5 + true",
+        "true < false",
+        "return 7; 8",
+        "let f = fn(a, b) { let c = a * b; return c - 1; c }; f(3, 4)",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)",
+        "let f = fn() {}; f()",
+        "undefined",
+        "fn(x) { x }(1, 2)",
+        "1 / 0",
+        "let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)",
+        "let f = fn(a) { let g = fn(n) { if (n > a) { n } else { g(n + 1) } }; g(0) }; f(3)",
+        "let f = fn(a) { fn() { a + b } }; f(1)()",
+        r#"let s = "a"; s + "b\n""#,
+        "[1, [2, 3], fn(x) { x }][1][0]",
+        "[1, 2][2]",
+        r#"[1]["a"]"#,
+        "len",
+        "let map = fn(arr, f) { if (len(arr) == 0) { [] } else { push(map(rest(arr), f), f(first(arr))) } }; map([1, 2, 3], fn(x) { x * 2 })",
+        "first(1)",
+        "str(1) + type(true)",
+        r#"let h = {"a": 1, 2: [3], true: {}}; h["a"] + h[2][0] + len(h[true])"#,
+        "{1: 2}[[]]",
+        "{[]: 1}",
+        r#"type({})"#,
+        "let i = 0; let s = 0; while (i < 10) { let i = i + 1; if (i == 3) { continue; } if (i > 6) { break; } let s = s + i; } s",
+        "let f = fn() { while (true) { return 1; } }; f()",
+        "let i = 0; let xs = []; while (i < 3) { let i = i + 1; let xs = push(xs, 1 + if (i == 2) { continue; } else { i }); } xs",
+        "while (false) { 1 }",
+        "let s = 0; for (i in 0..=10) { if (i == 2) { continue; } if (i == 8) { break; } let s = s + i; } s",
+        r#"let s = []; for (k, v in {"x": [1], 2: "y"}) { let s = push(s, [k, v]); } s"#,
+        r#"let s = []; for (i, c in "ab") { let s = push(s, str(i) + c); } s"#,
+        "let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } } 0 }; [f([1, 2, 3]), f([])]",
+        "for (x in true) {}",
+        "let r = 2..=0; [r, type(r), r == 2..=0, r == 2..0]",
+        "true..1",
+        "let x = 1; x += 2; x *= x = 5; x",
+        r#"let a = [1, {"k": [2]}]; let b = a; a[1]["k"][0] -= 5; a[1]["n"] = a[0] = 9; [a, b]"#,
+        "let f = fn(n) { let s = 0; while (n > 0) { s += n; n -= 1; } s }; f(4)",
+        "let a = [1]; a[1] = 2",
+        "let a = [[1]]; a[5][0] = 1",
+        "let a = [1]; a[5][0][0] = 1",
+        r#"let h = {"k": [1]}; h[[]][0] = 1"#,
+        r#"let h = {"k": [1, 2]}; let i = 0; h["k"][i += 1] += i * 10; h"#,
+        "let h = {}; h[[]] = 1",
+        "let x = 1; x /= 0",
+        "let s = \"a\"; s[0] = \"b\"",
+        "y = 1",
+        "len = 1",
+        "let f = fn(x) { fn() { x = 2 } }; f(1)()",
+        r#"let f = fn(v) { match (v) { 1 => "one", "1" => "str", true => "bool", [] => "empty", [x, ..r] if len(r) > 0 => r, [x] => x, {"k": k} => k, _ => "other" } }; [f(1), f("1"), f(true), f([]), f([1, 2]), f([[3]]), f({"k": 4}), f(2), f(fn() {})]"#,
+        "match (3) { 1 => 1 }",
+        r#"match ({1: [2]}) { {1: [a, ..]} => a * 10 }"#,
+        "let n = 1; match ([5]) { [n] if n > 9 => 0, _ => n }",
+        "match ([1]) { [a] => a + true }",
+        r#"let [a, [b, ..c]] = [1, [2, 3, 4]]; let {"k": k} = {"k": a + b}; [k, c]"#,
+        "let [a] = [];",
+        "let f = fn([x, y], z) { [x + y, z] }; [f([1, 2], 3), f([1], 2)]",
+        // 名前は束縛したあとからしか見えない。相互再帰や後の let への参照はどちらも受け付けない
+        "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(4)",
+        "let f = fn() { let a = fn() { b }; let b = 7; a() }; f()",
+        "let a = fn() { b }; let b = 1; a()",
+        "if (true) { c } else { 1 }; let c = 1;",
+    ];
+
+    for input in inputs {
+        let program = parse(input);
+        let eval = Interpreter::new(Engine::Eval).run(&program);
+        let vm = Interpreter::new(Engine::Vm).run(&program);
+        match (&eval, &vm) {
+            // VM では未定義の名前はコンパイルのエラーになり、場所を持たない
+            (Some(Object::Error(e)), Some(Object::Error(v))) if v.span.is_none() => {
+                assert_eq!(e.message, v.message, "input: {}", input)
+            }
+            _ => assert_eq!(eval, vm, "input: {}", input),
+        }
+    }
+}
+
+// 左につなげた演算子は、入れ子の上限を越えるほど長くても計算できる
+#[test]
+fn test_long_operator_chains() {
+    let tests = [
+        (format!("1{}", " + 1".repeat(999)), Object::Integer(1000)),
+        (
+            format!("\"\"{}", " + \"ab\"".repeat(500)),
+            Object::String("ab".repeat(500).into()),
+        ),
+    ];
+    for (input, expected) in tests {
+        let program = parse(&input);
+        for engine in [Engine::Eval, Engine::Vm] {
+            let result = Interpreter::new(engine).run(&program);
+            assert_eq!(result.as_ref(), Some(&expected), "engine: {}", engine);
+        }
+    }
+
+    // 途中で起きたエラーは、その演算子の場所を持つ
+    let program = parse("1 + 2 + true + 3");
+    for engine in [Engine::Eval, Engine::Vm] {
+        let Some(Object::Error(error)) = Interpreter::new(engine).run(&program) else {
+            panic!("engine: {}", engine);
+        };
+        assert_eq!(error.message, "type mismatch: INTEGER + BOOLEAN");
+        assert_eq!(error.span, Some(Span::new(6, 7)), "engine: {}", engine);
+    }
+}
+
+// エラーは起こしたノードの場所と、呼び出し中の関数を内側から順に持つ
+#[test]
+fn test_error_locations() {
+    let input = "let add = fn(a, b) { a + b };\nlet g = fn(x) { add(x, true) };\ng(1)";
+    let at = |pattern: &str, offset: usize| {
+        let start = input.find(pattern).unwrap() + offset;
+        Some(Span::new(start, start + 1))
+    };
+    let tests = vec![
+        (
+            input,
+            "type mismatch: INTEGER + BOOLEAN",
+            at("+ b", 0),
+            vec![(Some("add"), at("add(x", 3)), (Some("g"), at("g(1)", 1))],
+        ),
+        (
+            "fn() { 1 / 0 }()",
+            "division by zero",
+            Some(Span::new(9, 10)),
+            vec![(None, Some(Span::new(14, 15)))],
+        ),
+        (
+            "let f = fn() { len(1) }; f()",
+            "argument to `len` not supported, got INTEGER",
+            Some(Span::new(18, 19)),
+            vec![(Some("f"), Some(Span::new(26, 27)))],
+        ),
+        (
+            "let f = fn(a, [b]) { b };\nf(1, 2)",
+            "pattern [b] does not match INTEGER",
+            Some(Span::new(14, 15)),
+            vec![(Some("f"), Some(Span::new(27, 28)))],
+        ),
+        (
+            "let {1: x} = {};",
+            "pattern {1: x} does not match HASH",
+            Some(Span::new(4, 5)),
+            vec![],
+        ),
+        (
+            "[1][true]",
+            "index operator not supported: ARRAY[BOOLEAN]",
+            Some(Span::new(3, 4)),
+            vec![],
+        ),
+    ];
+
+    for (input, message, span, stack) in tests {
+        let program = parse(input);
+        for engine in [Engine::Eval, Engine::Vm] {
+            let Some(Object::Error(error)) = Interpreter::new(engine).run(&program) else {
+                panic!("expected an error: {}", input);
+            };
+            let actual: Vec<(Option<&str>, Option<Span>)> = error
+                .stack
+                .iter()
+                .map(|frame| (frame.function.as_deref(), frame.span))
+                .collect();
+            assert_eq!(
+                error.message, message,
+                "engine: {}, input: {}",
+                engine, input
+            );
+            assert_eq!(error.span, span, "engine: {}, input: {}", engine, input);
+            assert_eq!(actual, stack, "engine: {}, input: {}", engine, input);
+        }
+    }
+}
+
+fn module_loader() -> Loader {
+    Loader::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/module/testdata"))
+}
+
+// モジュールのファイルは src/module/testdata にある
+#[test]
+fn test_modules() {
+    let tests = vec![
+        (
+            r#"import "math.monkey" as m; [m.square(3), m.one, m.two]"#,
+            "[9, 1, 2]",
+        ),
+        (
+            r#"import "math.monkey" as m; [m, type(m)]"#,
+            "[module math.monkey, MODULE]",
+        ),
+        (
+            r#"import "math.monkey" as m; m.offset"#,
+            "ERROR: module math.monkey has no export offset",
+        ),
+        (r#"import "lib/geometry.monkey" as g; g.area(2, 3)"#, "6"),
+        (
+            r#"import "early.monkey" as e; [e.before, e.after]"#,
+            "[1, null]",
+        ),
+        (
+            r#"import "math.monkey" as a; import "math.monkey" as b; a == b"#,
+            "true",
+        ),
+        // モジュールの中からは読み込んだ側の束縛は見えない
+        (r#"let offset = 5; import "math.monkey" as m; offset"#, "5"),
+        (
+            r#"let h = {"a": 1}; h.a"#,
+            "ERROR: member access not supported: HASH",
+        ),
+        (
+            r#"import "missing.monkey" as m;"#,
+            "ERROR: module not found: missing.monkey",
+        ),
+        (
+            r#"import "cycle_a.monkey" as a;"#,
+            "ERROR: import cycle: cycle_a.monkey -> cycle_b.monkey -> cycle_a.monkey",
+        ),
+        (
+            r#"import "broken.monkey" as b;"#,
+            "ERROR: broken.monkey:1:12: expected next token to be IDENT, got = instead",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input);
+        for engine in [Engine::Eval, Engine::Vm] {
+            let result = Interpreter::new(engine)
+                .with_loader(module_loader())
+                .run(&program);
+            assert_eq!(
+                result.map(|r| r.to_string()).as_deref(),
+                Some(expected),
+                "engine: {}, input: {}",
+                engine,
+                input
+            );
+        }
+    }
+}
+
+// 何度読み込んでも、モジュールの本体は一度しか実行しない
+#[test]
+fn test_modules_run_once() {
+    let input = r#"import "counter.monkey" as a; import "counter.monkey" as b; a.count + b.count"#;
+    let program = parse(input);
+
+    for engine in [Engine::Eval, Engine::Vm] {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new(engine)
+            .with_output(output.clone())
+            .with_loader(module_loader());
+        assert_eq!(interpreter.run(&program), Some(Object::Integer(2)));
+        assert_eq!(
+            interpreter.run(&parse(r#"import "counter.monkey" as c; c.count"#)),
+            Some(Object::Integer(1))
+        );
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "loading counter\n",
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+// モジュールの中で起きたエラーは、そのモジュールの中の場所を指す
+#[test]
+fn test_module_error_locations() {
+    let source = include_str!("../module/testdata/failing.monkey");
+    let at = |pattern: &str, offset: usize| {
+        let start = source.find(pattern).unwrap() + offset;
+        Some(Span::new(start, start + 1))
+    };
+    let input = r#"let x = 1;
+import "failing.monkey" as f;"#;
+    let program = parse(input);
+
+    for engine in [Engine::Eval, Engine::Vm] {
+        let Some(Object::Error(error)) = Interpreter::new(engine)
+            .with_loader(module_loader())
+            .run(&program)
+        else {
+            panic!("expected an error, engine: {}", engine);
+        };
+        let stack: Vec<(Option<&str>, Option<Span>, Option<&str>)> = error
+            .stack
+            .iter()
+            .map(|frame| {
+                (
+                    frame.function.as_deref(),
+                    frame.span,
+                    frame.module.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(error.message, "type mismatch: INTEGER + BOOLEAN");
+        assert_eq!(error.span, at("+ true", 0), "engine: {}", engine);
+        assert_eq!(error.module.as_deref(), Some("failing.monkey"));
+        assert_eq!(
+            stack,
+            vec![
+                (Some("check"), at("check(1)", 5), Some("failing.monkey")),
+                (Some("module failing.monkey"), Some(Span::new(11, 17)), None),
+            ],
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+// 読み込めなかったモジュールのエラーは、どちらのエンジンでも import の場所を指す
+#[test]
+fn test_import_error_locations() {
+    let import = Some(Span::new(11, 17));
+    let tests = vec![
+        (
+            r#"let x = 1;
+import "missing.monkey" as m;"#,
+            import,
+            None,
+            vec![],
+        ),
+        (
+            r#"let x = 1;
+import "broken.monkey" as b;"#,
+            import,
+            None,
+            vec![],
+        ),
+        (
+            r#"let x = 1;
+import "cycle_a.monkey" as a;"#,
+            Some(Span::new(0, 6)),
+            Some("cycle_b.monkey"),
+            vec![
+                (
+                    Some("module cycle_b.monkey"),
+                    Some(Span::new(0, 6)),
+                    Some("cycle_a.monkey"),
+                ),
+                (Some("module cycle_a.monkey"), import, None),
+            ],
+        ),
+    ];
+
+    for (input, span, module, stack) in tests {
+        let program = parse(input);
+        for engine in [Engine::Eval, Engine::Vm] {
+            let Some(Object::Error(error)) = Interpreter::new(engine)
+                .with_loader(module_loader())
+                .run(&program)
+            else {
+                panic!("expected an error, engine: {}, input: {}", engine, input);
+            };
+            let actual: Vec<(Option<&str>, Option<Span>, Option<&str>)> = error
+                .stack
+                .iter()
+                .map(|frame| {
+                    (
+                        frame.function.as_deref(),
+                        frame.span,
+                        frame.module.as_deref(),
+                    )
+                })
+                .collect();
+            assert_eq!(error.span, span, "engine: {}, input: {}", engine, input);
+            assert_eq!(
+                error.module.as_deref(),
+                module,
+                "engine: {}, input: {}",
+                engine,
+                input
+            );
+            assert_eq!(actual, stack, "engine: {}, input: {}", engine, input);
+        }
+    }
+}
+
+// 関数は同じ値どうしだけが等しく、同じ式から作った別の関数とは等しくない
+#[test]
+fn test_function_equality() {
+    let input = "let mk = fn() { fn() { 1 } }; let f = mk();
+        [mk() == mk(), mk() != mk(), f == f, mk == mk, len == len]";
+    for engine in [Engine::Eval, Engine::Vm] {
+        let result = Interpreter::new(engine).run(&parse(input));
+        assert_eq!(
+            result.map(|r| r.to_string()).as_deref(),
+            Some("[false, true, true, true, true]"),
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+// 添字への代入は変数の入れ物をその場で書き換え、ほかに参照がなければ複製しない
+#[test]
+fn test_index_assignment_in_place() {
+    let input = r#"let a = [0, {"k": [1]}]; let p = [addr(a), addr(a[1]), addr(a[1]["k"])];
+        let f = fn() { let b = [0]; let p = addr(b); b[0] = 1; p == addr(b) };
+        a[0] = 1; a[1]["k"][0] += 1; a[1]["n"] = 2;
+        [a, p == [addr(a), addr(a[1]), addr(a[1]["k"])], f()]"#;
+    for engine in [Engine::Eval, Engine::Vm] {
+        let mut interpreter = Interpreter::new(engine);
+        interpreter.register("addr", |args| {
+            let address = match &args[0] {
+                Object::Array(elements) => Rc::as_ptr(elements) as usize,
+                Object::Hash(hash) => Rc::as_ptr(hash) as usize,
+                _ => 0,
+            };
+            Ok(Object::Integer(address as i64))
+        });
+        let result = interpreter.run(&parse(input)).map(|r| r.to_string());
+        assert_eq!(
+            result.as_deref(),
+            Some(r#"[[1, {k: [2], n: 2}], true, true]"#),
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn test_bindings_persist() {
+    for engine in [Engine::Eval, Engine::Vm] {
+        let mut interpreter = Interpreter::new(engine);
+        assert_eq!(interpreter.run(&parse("let a = 1;")), None);
+        assert_eq!(interpreter.run(&parse("let add = fn(x) { x + a };")), None);
+        // エラーになった行のあとも続けて使える
+        assert!(interpreter.run(&parse("b")).unwrap().is_error());
+        // 前の行の名前は見えるが、同じ行のあとの let は見えない
+        let forward = interpreter.run(&parse("let g = fn() { add(b) }; let b = 1;"));
+        let Some(Object::Error(error)) = forward else {
+            panic!("engine: {}", engine);
+        };
+        assert_eq!(
+            error.message, "identifier not found: b",
+            "engine: {}",
+            engine
+        );
+        assert_eq!(
+            interpreter.run(&parse("add(41)")),
+            Some(Object::Integer(42)),
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn test_parse_engine() {
+    assert_eq!("eval".parse::<Engine>(), Ok(Engine::Eval));
+    assert_eq!("vm".parse::<Engine>(), Ok(Engine::Vm));
+    assert!("jit".parse::<Engine>().is_err());
+}
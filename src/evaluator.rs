@@ -0,0 +1,911 @@
+#[cfg(test)]
+mod test;
+
+use crate::ast::{
+    ArrayLiteral, AssignExpression, BlockStatement, CallExpression, Expression, ForStatement,
+    HashLiteral, IfExpression, ImportStatement, InfixExpression, LetStatement, MatchExpression,
+    Node, Pattern, Program, RangeExpression, Statement, WhileStatement,
+};
+use crate::builtins::{self, Output};
+use crate::limits::{Limits, Meter};
+use crate::module::{self, Loader};
+use crate::object::{
+    Environment, Function, Hash, HashKey, Iteration, Module, Object, Range, RuntimeError,
+    StackFrame,
+};
+use crate::resolver::Resolver;
+use crate::token::Span;
+use crate::vm::MAX_FRAMES;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// 評価器は関数を呼ぶたびに Rust のスタックを使う。このくらいのスタックを持つスレッドで
+// 動かして with_stack_size で知らせれば、上限を決めなくても VM と同じ深さまで関数を呼べる
+pub const THREAD_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// 知らされなければ、Rust が作るスレッドの既定の大きさで動いているとみなす
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+// 上限を決めなくても、VM のフレームの数を越える呼び出しは stack overflow にする
+const MAX_CALLS: usize = MAX_FRAMES;
+
+// AST をそのままたどって評価する
+pub struct Evaluator {
+    env: Rc<RefCell<Environment>>,
+    output: Output,
+    limits: Limits,
+    meter: Meter,
+    // 動いているスレッドのスタックの大きさと、eval を始めたときのスタックの位置
+    stack_size: usize,
+    stack_base: usize,
+    // 呼び出し中の関数の数
+    calls: usize,
+    // 次に評価する関数リテラルを束縛する名前
+    function_name: Option<Rc<str>>,
+    loader: Loader,
+    // 読み込んだモジュール。実行し直さずに使い回す
+    modules: HashMap<PathBuf, Object>,
+    // 評価中のコードがあるモジュール
+    module: Option<Rc<str>>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator {
+            env: Rc::new(RefCell::new(Environment::new())),
+            output: builtins::stdout(),
+            limits: Limits::default(),
+            meter: Meter::start(Limits::default()),
+            stack_size: DEFAULT_STACK_SIZE,
+            stack_base: 0,
+            calls: 0,
+            function_name: None,
+            loader: Loader::default(),
+            modules: HashMap::new(),
+            module: None,
+        }
+    }
+
+    pub fn with_output(mut self, output: Output) -> Evaluator {
+        self.output = output;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Evaluator {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_loader(mut self, loader: Loader) -> Evaluator {
+        self.loader = loader;
+        self
+    }
+
+    // eval を呼ぶスレッドのスタックの大きさ
+    pub fn with_stack_size(mut self, bytes: usize) -> Evaluator {
+        self.stack_size = bytes;
+        self
+    }
+
+    // 外から値を束縛する。埋め込み側の関数の登録に使う
+    pub fn define(&mut self, name: &str, value: Object) {
+        self.env.borrow_mut().set(name, value);
+    }
+
+    pub fn eval(&mut self, program: &Program) -> Object {
+        self.meter = Meter::start(self.limits);
+        self.stack_base = stack_position();
+        self.calls = 0;
+        self.function_name = None;
+
+        let env = self.env.clone();
+        if let Err(error) = check_names(program, &env.borrow()) {
+            return Object::Error(Box::new(error));
+        }
+        let mut result = Object::Null;
+
+        for stmt in &program.statements {
+            result = self.eval_statement(stmt, &env);
+
+            match result {
+                Object::ReturnValue(value) => return *value,
+                Object::Error(_) => return result,
+                Object::Break | Object::Continue => return outside_of_loop(&result),
+                _ => {}
+            }
+        }
+
+        result
+    }
+
+    // VM は最上位のコードにもフレームを使うので、それに合わせて数える
+    fn check_call_depth(&self) -> Result<(), RuntimeError> {
+        self.meter.check_call_depth(self.calls + 1)?;
+        if self.calls + 1 >= MAX_CALLS {
+            return Err(RuntimeError::new("stack overflow"));
+        }
+        Ok(())
+    }
+
+    // eval を呼ぶまでに積んであった分と、確かめてから次に確かめるまでに積む分のために、
+    // スタックの半分は残しておく
+    fn stack_exhausted(&self) -> bool {
+        self.stack_base.abs_diff(stack_position()) > self.stack_size / 2
+    }
+
+    fn eval_block_statement(
+        &mut self,
+        block: &BlockStatement,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let mut result = Object::Null;
+
+        // return はブロックを抜けて関数の呼び出しまで、break と continue はループまで伝える
+        for stmt in &block.statements {
+            result = self.eval_statement(stmt, env);
+            if is_abrupt(&result) {
+                return result;
+            }
+        }
+
+        result
+    }
+
+    fn eval_statement(&mut self, stmt: &Statement, env: &Rc<RefCell<Environment>>) -> Object {
+        match stmt {
+            Statement::LetStatement(s) => self.eval_let_statement(s, env),
+            Statement::ReturnStatement(s) => {
+                let value = self.eval_optional(s.return_value.as_ref(), env);
+                if is_abrupt(&value) {
+                    return value;
+                }
+                Object::ReturnValue(Box::new(value))
+            }
+            Statement::ExpressionStatement(s) => self.eval_optional(s.expression.as_ref(), env),
+            Statement::BlockStatement(s) => self.eval_block_statement(s, env),
+            Statement::WhileStatement(s) => self.eval_while_statement(s, env),
+            Statement::ForStatement(s) => self.eval_for_statement(s, env),
+            Statement::BreakStatement(_) => Object::Break,
+            Statement::ContinueStatement(_) => Object::Continue,
+            Statement::ImportStatement(s) => match self.import(s) {
+                Ok(module) => {
+                    env.borrow_mut().set(&s.alias.value, module);
+                    Object::Null
+                }
+                Err(error) => Object::Error(Box::new(error)),
+            },
+            Statement::ExportStatement(s) => self.eval_let_statement(&s.statement, env),
+        }
+    }
+
+    fn eval_let_statement(&mut self, s: &LetStatement, env: &Rc<RefCell<Environment>>) -> Object {
+        // 関数には束縛する名前を付けて、スタックトレースに出す
+        if let (Some(Expression::FunctionLiteral(_)), Some(name)) =
+            (&s.value, s.pattern.as_identifier())
+        {
+            self.function_name = Some(name.value.as_str().into());
+        }
+        let value = self.eval_optional(s.value.as_ref(), env);
+        if is_abrupt(&value) {
+            return value;
+        }
+        match destructure(&s.pattern, value, &mut env.borrow_mut()) {
+            Ok(()) => Object::Null,
+            Err(error) => Object::Error(Box::new(error)),
+        }
+    }
+
+    // モジュールは最初に読み込んだときだけ実行し、あとは同じものを返す
+    fn import(&mut self, s: &ImportStatement) -> Result<Object, RuntimeError> {
+        let error = |message: String| RuntimeError::new(message).with_span(s.token.span);
+        let path = self.loader.resolve(&s.path).map_err(error)?;
+        if let Some(module) = self.modules.get(&path) {
+            return Ok(module.clone());
+        }
+
+        self.loader.enter(&path).map_err(error)?;
+        let result = self.eval_module(&path, s.token.span);
+        self.loader.leave();
+
+        let module = result?;
+        self.modules.insert(path, module.clone());
+        Ok(module)
+    }
+
+    // モジュールは組み込み関数だけが見える環境で評価する。
+    // 中で起きたエラーには、モジュールを呼び出しのように積む
+    fn eval_module(&mut self, path: &Path, span: Span) -> Result<Object, RuntimeError> {
+        let error = |message: String| RuntimeError::new(message).with_span(span);
+        let program = self.loader.load(path).map_err(error)?;
+        self.check_call_depth().map_err(|e| e.with_span(span))?;
+
+        let name: Rc<str> = self.loader.name(path).into();
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let importer = self.module.replace(name.clone());
+        self.calls += 1;
+
+        let mut result = match check_names(&program, &env.borrow()) {
+            Ok(()) => Object::Null,
+            Err(error) => Object::Error(Box::new(error)),
+        };
+        for stmt in &program.statements {
+            if result.is_error() {
+                break;
+            }
+            result = self.eval_statement(stmt, &env);
+            if is_abrupt(&result) {
+                break;
+            }
+        }
+
+        self.calls -= 1;
+        self.module = importer;
+
+        if let Object::Break | Object::Continue = result {
+            result = outside_of_loop(&result);
+        }
+        if let Object::Error(mut error) = result {
+            if error.stack.is_empty() {
+                error.module = Some(name.clone());
+            }
+            error.stack.push(StackFrame {
+                function: Some(format!("module {}", name).into()),
+                span: Some(span),
+                module: self.module.clone(),
+            });
+            return Err(*error);
+        }
+
+        // 最上位の return で途中までしか実行しなかったときは、残りの名前は null になる
+        let exports = module::exports(&program)
+            .into_iter()
+            .map(|export| {
+                let value = env.borrow().get(&export.value).unwrap_or(Object::Null);
+                (export.value.clone(), value)
+            })
+            .collect();
+        Ok(Object::Module(Rc::new(Module { name, exports })))
+    }
+
+    // ループそのものは値を持たない
+    fn eval_while_statement(
+        &mut self,
+        s: &WhileStatement,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        loop {
+            let condition = self.eval_optional(s.condition.as_ref(), env);
+            if is_abrupt(&condition) {
+                return condition;
+            }
+            if !condition.is_truthy() {
+                return Object::Null;
+            }
+
+            match self.eval_block_statement(&s.body, env) {
+                Object::Break => return Object::Null,
+                result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+                _ => {}
+            }
+        }
+    }
+
+    // ループの変数は囲んでいる環境に束縛する。範囲は要素を作らずに数える
+    fn eval_for_statement(&mut self, s: &ForStatement, env: &Rc<RefCell<Environment>>) -> Object {
+        let iterable = self.eval_optional(s.iterable.as_ref(), env);
+        if is_abrupt(&iterable) {
+            return iterable;
+        }
+        let mut iteration = match Iteration::new(iterable) {
+            Ok(iteration) => iteration,
+            // 反復できない値は、その値を作った式の場所で報告する
+            Err(e) => {
+                let span = s.iterable.as_ref().map_or(s.token.span, Expression::span);
+                return Object::Error(Box::new(RuntimeError::new(e).with_span(span)));
+            }
+        };
+
+        loop {
+            match s.variables.as_slice() {
+                [name] => match iteration.next_single() {
+                    Some(value) => env.borrow_mut().set(&name.value, value),
+                    None => return Object::Null,
+                },
+                [key_name, value_name] => match iteration.next_pair() {
+                    Some((key, value)) => {
+                        let mut env = env.borrow_mut();
+                        env.set(&key_name.value, key);
+                        env.set(&value_name.value, value);
+                    }
+                    None => return Object::Null,
+                },
+                _ => return Object::error("for loop needs one or two variables"),
+            }
+
+            // 本体が空でも歩数を数え、上限で止まるようにする
+            if let Err(e) = self.meter.step() {
+                return Object::Error(Box::new(e));
+            }
+            match self.eval_block_statement(&s.body, env) {
+                Object::Break => return Object::Null,
+                result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+                _ => {}
+            }
+        }
+    }
+
+    // 構文エラーで欠けた式は null とみなす
+    fn eval_optional(
+        &mut self,
+        exp: Option<&Expression>,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        match exp {
+            Some(exp) => self.eval_expression(exp, env),
+            None => Object::Null,
+        }
+    }
+
+    fn eval_expression(&mut self, exp: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        let mut result = if let Err(e) = self.meter.step() {
+            Object::Error(Box::new(e))
+        } else if self.stack_exhausted() {
+            Object::error("stack overflow")
+        } else {
+            self.eval_expression_node(exp, env)
+        };
+
+        // 場所のないエラーは、いま評価したこの式が起こしたもの
+        if let Object::Error(error) = &mut result {
+            error.span.get_or_insert(exp.span());
+        }
+        result
+    }
+
+    // 再帰のたびにこのフレームが積まれるので、大きな処理はそれぞれの関数に分ける
+    fn eval_expression_node(&mut self, exp: &Expression, env: &Rc<RefCell<Environment>>) -> Object {
+        match exp {
+            Expression::IntegerLiteral(e) => Object::Integer(e.value),
+            Expression::Boolean(e) => Object::Boolean(e.value),
+            Expression::Identifier(e) => eval_identifier(&e.value, env),
+            Expression::PrefixExpression(e) => {
+                let right = self.eval_optional(e.right.as_deref(), env);
+                if is_abrupt(&right) {
+                    return right;
+                }
+                eval_prefix_expression(&e.operator, right)
+            }
+            Expression::InfixExpression(e) => self.eval_infix_expression(e, env),
+            Expression::IfExpression(e) => self.eval_if_expression(e, env),
+            Expression::FunctionLiteral(e) => Object::Function(Rc::new(Function {
+                name: self.function_name.take(),
+                parameters: e.parameters.clone(),
+                body: (*e.body).clone(),
+                env: env.clone(),
+                module: self.module.clone(),
+            })),
+            Expression::CallExpression(e) => self.eval_call_expression(e, env),
+            Expression::StringLiteral(e) => Object::String(e.value.as_str().into()),
+            Expression::ArrayLiteral(e) => self.eval_array_literal(e, env),
+            Expression::IndexExpression(e) => {
+                let left = self.eval_expression(&e.left, env);
+                if is_abrupt(&left) {
+                    return left;
+                }
+                let index = self.eval_expression(&e.index, env);
+                if is_abrupt(&index) {
+                    return index;
+                }
+                eval_index_expression(left, index)
+            }
+            Expression::HashLiteral(e) => self.eval_hash_literal(e, env),
+            Expression::RangeExpression(e) => self.eval_range_expression(e, env),
+            Expression::AssignExpression(e) => self.eval_assign_expression(e, env),
+            Expression::MatchExpression(e) => self.eval_match_expression(e, env),
+            Expression::MemberExpression(e) => {
+                let object = self.eval_expression(&e.object, env);
+                if is_abrupt(&object) {
+                    return object;
+                }
+                object
+                    .member(&e.property.value)
+                    .unwrap_or_else(Object::error)
+            }
+        }
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順に計算する
+    fn eval_infix_expression(
+        &mut self,
+        e: &InfixExpression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let mut chain = vec![e];
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            if let Err(e) = self.meter.step() {
+                return Object::Error(Box::new(e));
+            }
+            chain.push(inner);
+            leftmost = inner.left.as_deref();
+        }
+
+        let mut left = self.eval_optional(leftmost, env);
+        for e in chain.into_iter().rev() {
+            if is_abrupt(&left) {
+                return left;
+            }
+            let right = self.eval_optional(e.right.as_deref(), env);
+            if is_abrupt(&right) {
+                return right;
+            }
+            left = self.apply_infix_operator(&e.operator, left, right);
+            if let Object::Error(error) = &mut left {
+                error.span.get_or_insert(e.token.span);
+            }
+        }
+        left
+    }
+
+    fn apply_infix_operator(&self, operator: &str, left: Object, right: Object) -> Object {
+        if let (Object::String(l), Object::String(r)) = (&left, &right) {
+            if let Err(e) = self.meter.check_allocation(l.len() + r.len()) {
+                return Object::Error(Box::new(e));
+            }
+        }
+        eval_infix_expression(operator, left, right)
+    }
+
+    // 添字と値を先に評価してから、束縛された入れ物をその場で書き換える。
+    // 式の値は代入した値になる
+    fn eval_assign_expression(
+        &mut self,
+        e: &AssignExpression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let mut indexes = vec![];
+        let mut target = &*e.target;
+        while let Expression::IndexExpression(index) = target {
+            indexes.push(&*index.index);
+            target = &index.left;
+        }
+        indexes.reverse();
+        let Expression::Identifier(name) = target else {
+            return Object::error("invalid assignment target");
+        };
+        if let Err(e) = env.borrow().check_assignable(&name.value) {
+            return Object::error(e);
+        }
+
+        let mut keys = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let key = self.eval_expression(index, env);
+            if is_abrupt(&key) {
+                return key;
+            }
+            keys.push(key);
+        }
+
+        let value = match e.arithmetic_operator() {
+            Some(operator) => {
+                let mut current = eval_identifier(&name.value, env);
+                for key in &keys {
+                    if current.is_error() {
+                        break;
+                    }
+                    current = eval_index_expression(current, key.clone());
+                }
+                if current.is_error() {
+                    return current;
+                }
+                let value = self.eval_optional(e.value.as_deref(), env);
+                if is_abrupt(&value) {
+                    return value;
+                }
+                self.apply_infix_operator(operator, current, value)
+            }
+            None => self.eval_optional(e.value.as_deref(), env),
+        };
+        if is_abrupt(&value) {
+            return value;
+        }
+
+        let updated = env.borrow_mut().update(&name.value, |slot| {
+            let container = slot.set_index(&keys, value.clone())?;
+            self.meter.check_object(container)
+        });
+        if let Some(Err(e)) = updated {
+            return Object::Error(Box::new(e));
+        }
+        value
+    }
+
+    fn eval_range_expression(
+        &mut self,
+        e: &RangeExpression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let start = self.eval_optional(e.start.as_deref(), env);
+        if is_abrupt(&start) {
+            return start;
+        }
+        let end = self.eval_optional(e.end.as_deref(), env);
+        if is_abrupt(&end) {
+            return end;
+        }
+        match Range::from_bounds(&start, &end, e.inclusive) {
+            Ok(range) => Object::Range(range),
+            Err(e) => Object::error(e),
+        }
+    }
+
+    fn eval_array_literal(&mut self, e: &ArrayLiteral, env: &Rc<RefCell<Environment>>) -> Object {
+        let size = e.elements.len() * size_of::<Object>();
+        if let Err(e) = self.meter.check_allocation(size) {
+            return Object::Error(Box::new(e));
+        }
+        let mut elements = Vec::with_capacity(e.elements.len());
+        for element in &e.elements {
+            let value = self.eval_expression(element, env);
+            if is_abrupt(&value) {
+                return value;
+            }
+            elements.push(value);
+        }
+        Object::Array(Rc::new(elements))
+    }
+
+    fn eval_hash_literal(&mut self, e: &HashLiteral, env: &Rc<RefCell<Environment>>) -> Object {
+        let size = e.pairs.len() * 2 * size_of::<Object>();
+        if let Err(e) = self.meter.check_allocation(size) {
+            return Object::Error(Box::new(e));
+        }
+        let mut hash = Hash::new();
+        for (key, value) in &e.pairs {
+            let key = self.eval_expression(key, env);
+            if is_abrupt(&key) {
+                return key;
+            }
+            let key = match HashKey::from_object(&key) {
+                Ok(key) => key,
+                Err(e) => return Object::error(e),
+            };
+            let value = self.eval_expression(value, env);
+            if is_abrupt(&value) {
+                return value;
+            }
+            hash.insert(key, value);
+        }
+        Object::Hash(Rc::new(hash))
+    }
+
+    fn eval_if_expression(&mut self, e: &IfExpression, env: &Rc<RefCell<Environment>>) -> Object {
+        let condition = self.eval_optional(e.condition.as_deref(), env);
+        if is_abrupt(&condition) {
+            return condition;
+        }
+
+        if condition.is_truthy() {
+            self.eval_block_statement(&e.consequence, env)
+        } else if let Some(alternative) = &e.alternative {
+            self.eval_block_statement(alternative, env)
+        } else {
+            Object::Null
+        }
+    }
+
+    // 上の腕から順に試す。形が合えば名前を束縛してから条件を確かめる
+    fn eval_match_expression(
+        &mut self,
+        e: &MatchExpression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let subject = self.eval_optional(e.subject.as_deref(), env);
+        if is_abrupt(&subject) {
+            return subject;
+        }
+
+        for arm in &e.arms {
+            let mut bindings = vec![];
+            if !match_pattern(&arm.pattern, &subject, &mut bindings) {
+                continue;
+            }
+            for (name, value) in bindings {
+                env.borrow_mut().set(name, value);
+            }
+            if let Some(guard) = &arm.guard {
+                let guard = self.eval_expression(guard, env);
+                if is_abrupt(&guard) {
+                    return guard;
+                }
+                if !guard.is_truthy() {
+                    continue;
+                }
+            }
+            return self.eval_optional(arm.body.as_ref(), env);
+        }
+
+        Object::error(format!("no match arm for {}", subject.type_name()))
+    }
+
+    fn eval_call_expression(
+        &mut self,
+        e: &CallExpression,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Object {
+        let function = self.eval_expression(&e.function, env);
+        if is_abrupt(&function) {
+            return function;
+        }
+
+        let mut args = Vec::with_capacity(e.arguments.len());
+        for arg in &e.arguments {
+            let value = self.eval_expression(arg, env);
+            if is_abrupt(&value) {
+                return value;
+            }
+            args.push(value);
+        }
+
+        self.apply_function(function, args, e.token.span)
+    }
+
+    fn apply_function(&mut self, function: Object, args: Vec<Object>, span: Span) -> Object {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => {
+                let mut output = self.output.borrow_mut();
+                let result = (builtin.function)(&args, &mut *output);
+                return self.check_result(result);
+            }
+            Object::HostFunction(host) => {
+                let result = (host.function)(&args).map_err(|e| e.message);
+                return self.check_result(result);
+            }
+            other => return Object::error(format!("not a function: {}", other.type_name())),
+        };
+
+        if function.parameters.len() != args.len() {
+            return Object::error(format!(
+                "wrong number of arguments: want={}, got={}",
+                function.parameters.len(),
+                args.len()
+            ));
+        }
+
+        if let Err(e) = self.check_call_depth() {
+            return Object::Error(Box::new(e));
+        }
+
+        // 関数を定義した環境を外側にする。let で束縛した名前もそこから見える
+        let mut call_env = Environment::new_enclosed(function.env.clone());
+        let bound = function
+            .parameters
+            .iter()
+            .zip(args)
+            .try_for_each(|(param, arg)| destructure(param, arg, &mut call_env));
+
+        // 引数が形に合わなかったエラーも、関数の中で起きたものとして扱う
+        let caller = std::mem::replace(&mut self.module, function.module.clone());
+        let result = match bound {
+            Ok(()) => {
+                self.calls += 1;
+                let call_env = Rc::new(RefCell::new(call_env));
+                let result = self.eval_block_statement(&function.body, &call_env);
+                self.calls -= 1;
+                result
+            }
+            Err(error) => Object::Error(Box::new(error)),
+        };
+        self.module = caller;
+
+        match result {
+            Object::ReturnValue(value) => *value,
+            Object::Break | Object::Continue => outside_of_loop(&result),
+            // 関数の中で起きたエラーには、この呼び出しを積む
+            Object::Error(mut error) => {
+                if error.stack.is_empty() {
+                    error.module = function.module.clone();
+                }
+                error.stack.push(StackFrame {
+                    function: function.name.clone(),
+                    span: Some(span),
+                    module: self.module.clone(),
+                });
+                Object::Error(error)
+            }
+            result => result,
+        }
+    }
+
+    // 組み込み関数が作った値も大きさの上限を確かめる
+    fn check_result(&self, result: Result<Object, String>) -> Object {
+        let result = result.map_err(RuntimeError::from);
+        match result.and_then(|value| self.meter.check_object(&value).map(|()| value)) {
+            Ok(value) => value,
+            Err(e) => Object::Error(Box::new(e)),
+        }
+    }
+}
+
+// エラーと return・break・continue は、評価中の式を打ち切って外へ伝える
+fn is_abrupt(obj: &Object) -> bool {
+    matches!(
+        obj,
+        Object::Error(_) | Object::ReturnValue(_) | Object::Break | Object::Continue
+    )
+}
+
+// 構文解析器を通さずに組み立てた AST では、ループの外の break が関数の外まで届く
+fn outside_of_loop(signal: &Object) -> Object {
+    Object::error(format!("{} outside of loop", signal))
+}
+
+// いま使っているスタックのおおよその位置
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// VM と同じく、前方参照や相互再帰を実行する前にコンパイラと同じ決め方で弾く
+fn check_names(program: &Program, env: &Environment) -> Result<(), RuntimeError> {
+    let resolution = Resolver::new().with_globals(env.names()).resolve(program);
+    match resolution.errors.into_iter().next() {
+        Some(error) => Err(RuntimeError::new(error.message).with_span(error.span)),
+        None => Ok(()),
+    }
+}
+
+// 組み込み関数は利用者の束縛より後に探す
+fn eval_identifier(name: &str, env: &Rc<RefCell<Environment>>) -> Object {
+    match env.borrow().get(name) {
+        Some(value) => value,
+        None => match builtins::lookup(name) {
+            Some((_, builtin)) => Object::Builtin(builtin),
+            None => Object::error(format!("identifier not found: {}", name)),
+        },
+    }
+}
+
+fn eval_prefix_expression(operator: &str, right: Object) -> Object {
+    match (operator, right) {
+        ("!", right) => Object::Boolean(!right.is_truthy()),
+        ("-", Object::Integer(value)) => Object::Integer(value.wrapping_neg()),
+        (_, right) => Object::error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+fn eval_infix_expression(operator: &str, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(operator, *l, *r),
+        (Object::String(l), Object::String(r)) if operator == "+" => {
+            Object::String(format!("{}{}", l, r).into())
+        }
+        _ if operator == "==" => Object::Boolean(left == right),
+        _ if operator == "!=" => Object::Boolean(left != right),
+        _ if left.type_name() != right.type_name() => Object::error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        _ => Object::error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+// 整数の演算はあふれたら折り返す
+fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Object {
+    match operator {
+        "+" => Object::Integer(left.wrapping_add(right)),
+        "-" => Object::Integer(left.wrapping_sub(right)),
+        "*" => Object::Integer(left.wrapping_mul(right)),
+        "/" if right == 0 => Object::error("division by zero"),
+        "/" => Object::Integer(left.wrapping_div(right)),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+// 範囲外の添字は null になる
+fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| elements.get(i).cloned())
+            .unwrap_or(Object::Null),
+        (Object::Hash(hash), _) => match HashKey::from_object(&index) {
+            Ok(key) => hash.get(&key).cloned().unwrap_or(Object::Null),
+            Err(e) => Object::error(e),
+        },
+        _ => Object::error(format!(
+            "index operator not supported: {}[{}]",
+            left.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+// let と引数の束縛。形に合わなければ、パターンの場所を指すエラーにする
+fn destructure(
+    pattern: &Pattern,
+    value: Object,
+    env: &mut Environment,
+) -> Result<(), RuntimeError> {
+    let mut bindings = vec![];
+    if !match_pattern(pattern, &value, &mut bindings) {
+        let message = format!(
+            "pattern {} does not match {}",
+            pattern.to_string(),
+            value.describe()
+        );
+        return Err(RuntimeError::new(message).with_span(pattern.span()));
+    }
+    for (name, value) in bindings {
+        env.set(&name, value);
+    }
+    Ok(())
+}
+
+// 一致すれば束縛する名前と値を bindings に足していく。リテラルは == と同じく比べる
+fn match_pattern(pattern: &Pattern, value: &Object, bindings: &mut Vec<(String, Object)>) -> bool {
+    match pattern {
+        Pattern::WildcardPattern(_) => true,
+        Pattern::Identifier(p) => {
+            bindings.push((p.value.clone(), value.clone()));
+            true
+        }
+        Pattern::IntegerLiteral(p) => *value == Object::Integer(p.value),
+        Pattern::StringLiteral(p) => *value == Object::String(p.value.as_str().into()),
+        Pattern::Boolean(p) => *value == Object::Boolean(p.value),
+        Pattern::ArrayPattern(p) => {
+            if !value.matches_array(p.elements.len(), p.rest.is_some()) {
+                return false;
+            }
+            for (i, element) in p.elements.iter().enumerate() {
+                let item = eval_index_expression(value.clone(), Object::Integer(i as i64));
+                if !match_pattern(element, &item, bindings) {
+                    return false;
+                }
+            }
+            if let Some(name) = p.rest.as_ref().and_then(|r| r.name.as_ref()) {
+                bindings.push((name.value.clone(), value.slice_from(p.elements.len())));
+            }
+            true
+        }
+        Pattern::HashPattern(p) => {
+            let keys: Vec<Object> = p
+                .pairs
+                .iter()
+                .map(|(key, _)| Object::from_literal(key).unwrap_or(Object::Null))
+                .collect();
+            if !value.has_keys(&keys) {
+                return false;
+            }
+            p.pairs.iter().zip(keys).all(|((_, pattern), key)| {
+                let item = eval_index_expression(value.clone(), key);
+                match_pattern(pattern, &item, bindings)
+            })
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use super::*;
+use crate::ast::Node;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn test_eval(input: &str) -> Object {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    Evaluator::new().eval(&program)
+}
+
+#[test]
+fn test_eval_integer_expression() {
+    let tests = vec![
+        ("5", 5),
+        ("-10", -10),
+        ("5 + 5 + 5 + 5 - 10", 10),
+        ("2 * 2 * 2 * 2 * 2", 32),
+        ("-50 + 100 + -50", 0),
+        ("20 + 2 * -10", 0),
+        ("50 / 2 * 2 + 10", 60),
+        ("3 * (3 * 3) + 10", 37),
+        ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ("9223372036854775807 + 1", i64::MIN),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Integer(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_eval_boolean_expression() {
+    let tests = vec![
+        ("true", true),
+        ("1 < 2", true),
+        ("1 > 2", false),
+        ("1 == 1", true),
+        ("1 != 1", false),
+        ("true == true", true),
+        ("true != false", true),
+        ("(1 < 2) == true", true),
+        ("(1 > 2) == true", false),
+        ("1 == true", false),
+        ("!true", false),
+        ("!5", false),
+        ("!!5", true),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Boolean(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_if_else_expressions() {
+    let tests = vec![
+        ("if (true) { 10 }", Object::Integer(10)),
+        ("if (false) { 10 }", Object::Null),
+        ("if (1) { 10 }", Object::Integer(10)),
+        ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+        ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_return_statements() {
+    let tests = vec![
+        ("return 10;", 10),
+        ("return 10; 9;", 10),
+        ("9; return 2 * 5; 9;", 10),
+        ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Integer(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_error_handling() {
+    let tests = vec![
+        ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+        ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+        ("-true", "unknown operator: -BOOLEAN"),
+        ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+        (
+            "if (10 > 1) { return true + false; }",
+            "unknown operator: BOOLEAN + BOOLEAN",
+        ),
+        ("foobar", "identifier not found: foobar"),
+        ("1 / 0", "division by zero"),
+        ("5(1)", "not a function: INTEGER"),
+        ("fn(x) { x }()", "wrong number of arguments: want=1, got=0"),
+        (r#""a" - "b""#, "unknown operator: STRING - STRING"),
+        (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+        ("[1][true]", "index operator not supported: ARRAY[BOOLEAN]"),
+        ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+        (
+            r#"{"name": "Monkey"}[fn(x) { x }];"#,
+            "unusable as hash key: FUNCTION",
+        ),
+        ("{[1]: 2}", "unusable as hash key: ARRAY"),
+    ];
+
+    for (input, expected) in tests {
+        match test_eval(input) {
+            Object::Error(e) => assert_eq!(e.message, expected, "input: {}", input),
+            other => panic!("expected error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+#[test]
+fn test_let_statements() {
+    let tests = vec![
+        ("let a = 5; a;", 5),
+        ("let a = 5 * 5; a;", 25),
+        ("let a = 5; let b = a; b;", 5),
+        ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Integer(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_function_object() {
+    match test_eval("fn(x) { x + 2; };") {
+        Object::Function(function) => {
+            assert_eq!(function.parameters.len(), 1);
+            assert_eq!(function.parameters[0].to_string(), "x");
+            assert_eq!(function.body.to_string(), "{ (x + 2) }");
+        }
+        other => panic!("object is not Function. got={:?}", other),
+    }
+}
+
+#[test]
+fn test_function_application() {
+    let tests = vec![
+        ("let identity = fn(x) { x; }; identity(5);", 5),
+        ("let identity = fn(x) { return x; }; identity(5);", 5),
+        ("let double = fn(x) { x * 2; }; double(5);", 10),
+        ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+        ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+        ("fn(x) { x; }(5)", 5),
+        (
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15);",
+            610,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Integer(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_function_scope() {
+    // 引数は呼び出しの外には漏れない
+    assert_eq!(
+        test_eval("let f = fn(x) { let y = x; y }; f(1); y").to_string(),
+        "ERROR: identifier not found: y"
+    );
+    assert_eq!(
+        test_eval("let x = 1; let f = fn(x) { x }; f(2); x"),
+        Object::Integer(1)
+    );
+}
+
+#[test]
+fn test_closures() {
+    let tests = vec![
+        ("let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)", 5),
+        ("let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)", 6),
+        (
+            "let wrapper = fn() { let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1) }; countDown(5) }; wrapper()",
+            0,
+        ),
+        // 呼び出し側の局所変数は見えない
+        ("let x = 1; let f = fn() { x }; let g = fn(x) { f() }; g(2)", 1),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::Integer(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_string_expressions() {
+    let tests = vec![
+        (r#""Hello World!""#, "Hello World!"),
+        (r#""Hello" + " " + "World!""#, "Hello World!"),
+        (r#"let s = "a\tb"; s + s"#, "a\tba\tb"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(
+            test_eval(input),
+            Object::String(expected.into()),
+            "input: {}",
+            input
+        );
+    }
+
+    assert_eq!(test_eval(r#""a" == "a""#), Object::Boolean(true));
+    assert_eq!(test_eval(r#""a" != "b""#), Object::Boolean(true));
+}
+
+#[test]
+fn test_array_index_expressions() {
+    let tests = vec![
+        ("[1, 2, 3][0]", Object::Integer(1)),
+        ("[1, 2, 3][2]", Object::Integer(3)),
+        ("let i = 0; [1][i]", Object::Integer(1)),
+        ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+        (
+            "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+            Object::Integer(6),
+        ),
+        ("[1, 2, 3][3]", Object::Null),
+        ("[1, 2, 3][-1]", Object::Null),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+
+    assert_eq!(test_eval("[1, 2 * 2, 3 + 3]").to_string(), "[1, 4, 6]");
+}
+
+#[test]
+fn test_hash_literals() {
+    let input = r#"let two = "two";
+    {
+        "one": 10 - 9,
+        two: 1 + 1,
+        "thr" + "ee": 6 / 2,
+        4: 4,
+        true: 5,
+        false: 6
+    }"#;
+
+    assert_eq!(
+        test_eval(input).to_string(),
+        "{one: 1, two: 2, three: 3, 4: 4, true: 5, false: 6}"
+    );
+    // 同じキーは後の値で置き換わる
+    assert_eq!(test_eval("{1: 1, 2: 2, 1: 3}").to_string(), "{1: 3, 2: 2}");
+}
+
+#[test]
+fn test_hash_index_expressions() {
+    let tests = vec![
+        (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+        (r#"{"foo": 5}["bar"]"#, Object::Null),
+        (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+        (r#"{}["foo"]"#, Object::Null),
+        ("{5: 5}[5]", Object::Integer(5)),
+        ("{true: 5}[true]", Object::Integer(5)),
+        ("{false: 5}[false]", Object::Integer(5)),
+        (r#"len({1: 2, "a": 3})"#, Object::Integer(2)),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_while_loops() {
+    let tests = vec![
+        (
+            "let i = 0; let s = 0; while (i < 5) { let i = i + 1; let s = s + i; } s",
+            Object::Integer(15),
+        ),
+        ("while (false) { 1 }", Object::Null),
+        (
+            "let i = 0; while (true) { let i = i + 1; if (i == 3) { break; } } i",
+            Object::Integer(3),
+        ),
+        (
+            "let i = 0; let s = 0; while (i < 5) { let i = i + 1; if (i == 2) { continue; } let s = s + i; } s",
+            Object::Integer(13),
+        ),
+        (
+            "let f = fn() { while (true) { return 7; } }; f()",
+            Object::Integer(7),
+        ),
+        // 式の途中からでもループを抜ける
+        (
+            "let i = 0; while (i < 3) { let i = i + 1; 1 + if (i == 2) { break; } else { 0 }; } i",
+            Object::Integer(2),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_for_loops() {
+    let tests = vec![
+        // 範囲は要素を作らずに数える
+        (
+            "let s = 0; for (i in 0..100000) { let s = s + i; } s",
+            Object::Integer(4999950000),
+        ),
+        (
+            "let s = 0; for (i in 1..=4) { let s = s * 10 + i; } s",
+            Object::Integer(1234),
+        ),
+        ("let n = 0; for (i in 5..5) { let n = 1; } n", Object::Integer(0)),
+        (
+            "let s = 0; for (i, x in [5, 6, 7]) { let s = s + i * x; } s",
+            Object::Integer(20),
+        ),
+        (
+            r#"let s = ""; for (c in "héllo") { let s = c + s; } s"#,
+            Object::String("olléh".into()),
+        ),
+        (
+            r#"let s = ""; for (k in {"a": 1, "b": 2}) { let s = s + k; } s"#,
+            Object::String("ab".into()),
+        ),
+        (
+            r#"let s = 0; for (k, v in {"a": 1, "b": 2}) { let s = s + v; } s"#,
+            Object::Integer(3),
+        ),
+        (
+            "let n = 0; for (i in 0..10) { if (i == 2) { continue; } if (i == 5) { break; } let n = n + i; } n",
+            Object::Integer(8),
+        ),
+        (
+            "let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } } 0 }; f([1, 2, 3])",
+            Object::Integer(2),
+        ),
+        ("for (x in [1]) { x }", Object::Null),
+        ("0..3", Object::Range(Range::new(0, 3, false))),
+        ("type(1..=2)", Object::String("RANGE".into())),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_assign_expressions() {
+    let tests = vec![
+        ("let x = 1; x = 2; x", Object::Integer(2)),
+        ("let x = 1; x = x + 1", Object::Integer(2)),
+        ("let a = 0; let b = 0; a = b = 3; a + b", Object::Integer(6)),
+        (
+            "let x = 10; x += 5; x -= 3; x *= 2; x /= 4; x",
+            Object::Integer(6),
+        ),
+        (r#"let s = "a"; s += "b"; s"#, Object::String("ab".into())),
+        ("let a = [1, 2]; a[1] = 5; a[1]", Object::Integer(5)),
+        (
+            "let a = [[1], [2]]; a[1][0] += 40; a[1][0]",
+            Object::Integer(42),
+        ),
+        (
+            r#"let h = {"k": 1}; h["k"] += 1; h["n"] = 3; h["k"] + h["n"]"#,
+            Object::Integer(5),
+        ),
+        // 配列は値として振る舞うので、ほかの名前から見た中身は変わらない
+        ("let a = [1]; let b = a; a[0] = 2; b[0]", Object::Integer(1)),
+        (
+            "let f = fn() { let n = 0; while (n < 3) { n += 1; } n }; f()",
+            Object::Integer(3),
+        ),
+        (
+            "let g = 1; let f = fn() { g = 5 }; f(); g",
+            Object::Integer(5),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_assign_errors() {
+    let tests = vec![
+        ("x = 1", "cannot assign to undefined variable: x"),
+        ("len = 1", "cannot assign to undefined variable: len"),
+        (
+            "let f = fn(x) { fn() { x = 2 } }; f(1)()",
+            "cannot assign to captured variable: x",
+        ),
+        ("let a = [1]; a[1] = 2", "index out of range: 1 (len 1)"),
+        (
+            "let x = 1; x[0] = 2",
+            "index assignment not supported: INTEGER[INTEGER]",
+        ),
+        (
+            "let h = {}; h[fn() {}] = 1",
+            "unusable as hash key: FUNCTION",
+        ),
+        ("let x = true; x += 1", "type mismatch: BOOLEAN + INTEGER"),
+    ];
+
+    for (input, expected) in tests {
+        match test_eval(input) {
+            Object::Error(e) => assert_eq!(e.message, expected, "input: {}", input),
+            other => panic!("input: {}: expected error, got {}", input, other),
+        }
+    }
+}
+
+#[test]
+fn test_match_expressions() {
+    let tests = vec![
+        (
+            "match (2) { 1 => 10, 2 => 20, _ => 30 }",
+            Object::Integer(20),
+        ),
+        ("match (5) { 1 => 10, n => n * 2 }", Object::Integer(10)),
+        (r#"match ("b") { "a" => 1, "b" => 2 }"#, Object::Integer(2)),
+        (
+            "match (-3) { -3 => true, _ => false }",
+            Object::Boolean(true),
+        ),
+        (
+            "match (1 < 2) { false => 0, true => 1 }",
+            Object::Integer(1),
+        ),
+        // リテラルは同じ型の値にだけ一致する
+        (r#"match ("1") { 1 => 0, _ => 1 }"#, Object::Integer(1)),
+        (
+            "match ([1, 2]) { [a] => a, [a, b] => a + b }",
+            Object::Integer(3),
+        ),
+        (
+            "match ([1, 2, 3]) { [x, ..rest] => len(rest) * 10 + x }",
+            Object::Integer(21),
+        ),
+        ("match ([]) { [_, ..] => 1, [..] => 2 }", Object::Integer(2)),
+        (
+            "match ([1, [2, 3]]) { [1, [a, b]] => a * b, _ => 0 }",
+            Object::Integer(6),
+        ),
+        (
+            r#"match ({"k": 1, "v": [4]}) { {"k": 2} => 0, {"v": [n], "k": k} => n + k }"#,
+            Object::Integer(5),
+        ),
+        (
+            r#"match ({}) { {"k": _} => 1, {} => 2 }"#,
+            Object::Integer(2),
+        ),
+        (
+            "match (5) { n if n > 10 => 1, n if n > 3 => 2, _ => 3 }",
+            Object::Integer(2),
+        ),
+        // 条件を確かめる前に名前は束縛される
+        (
+            "let n = 0; match (7) { n if false => 1, _ => n }",
+            Object::Integer(7),
+        ),
+        (
+            "let f = fn(x) { match (x) { [h, ..t] => h + f(t), [] => 0 } }; f([1, 2, 3])",
+            Object::Integer(6),
+        ),
+        (
+            "match (match (1) { 1 => [2] }) { [a] => a }",
+            Object::Integer(2),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_match_errors() {
+    let tests = vec![
+        ("match (3) { 1 => 1, 2 => 2 }", "no match arm for INTEGER"),
+        ("match ([1]) { [] => 0 }", "no match arm for ARRAY"),
+        ("match (1) { _ if x => 1 }", "identifier not found: x"),
+        ("match (y) { _ => 1 }", "identifier not found: y"),
+        (
+            "match (1) { _ => 1 + true }",
+            "type mismatch: INTEGER + BOOLEAN",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        match test_eval(input) {
+            Object::Error(e) => assert_eq!(e.message, expected, "input: {}", input),
+            other => panic!("input: {}: expected error, got {}", input, other),
+        }
+    }
+}
+
+#[test]
+fn test_destructuring() {
+    let tests = vec![
+        ("let [a, b] = [1, 2]; a * 10 + b", Object::Integer(12)),
+        (
+            "let [head, ..tail] = [1, 2, 3]; head + len(tail)",
+            Object::Integer(3),
+        ),
+        (
+            r#"let {"x": x, "y": y} = {"y": 2, "x": 1, "z": 3}; x - y"#,
+            Object::Integer(-1),
+        ),
+        ("let [_, [b]] = [1, [2]]; b", Object::Integer(2)),
+        ("let _ = 5;", Object::Null),
+        (
+            "let f = fn([a, b], c) { a + b * c }; f([1, 2], 3)",
+            Object::Integer(7),
+        ),
+        (
+            r#"let f = fn({"n": n}) { n }; f({"n": 4})"#,
+            Object::Integer(4),
+        ),
+        (
+            "let sum = fn(xs) { match (xs) { [] => 0, [h, ..t] => h + sum(t) } }; let [a, ..r] = [5, 6, 7]; a + sum(r)",
+            Object::Integer(18),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_destructuring_errors() {
+    let tests = vec![
+        (
+            "let [a, b] = [1];",
+            "pattern [a, b] does not match ARRAY of length 1",
+        ),
+        ("let [a, ..] = 1;", "pattern [a, ..] does not match INTEGER"),
+        (
+            r#"let {"x": x} = {"y": 1};"#,
+            r#"pattern {"x": x} does not match HASH"#,
+        ),
+        (
+            "let f = fn([a]) { a }; f([])",
+            "pattern [a] does not match ARRAY of length 0",
+        ),
+        ("let [a] = y;", "identifier not found: y"),
+    ];
+
+    for (input, expected) in tests {
+        match test_eval(input) {
+            Object::Error(e) => assert_eq!(e.message, expected, "input: {}", input),
+            other => panic!("input: {}: expected error, got {}", input, other),
+        }
+    }
+}
+
+#[test]
+fn test_for_loop_errors() {
+    let tests = vec![
+        ("for (x in 1) {}", "not iterable: INTEGER"),
+        (
+            r#"for (x in 0.."a") {}"#,
+            "range bounds must be INTEGER, got INTEGER..STRING",
+        ),
+        (
+            "for (x in [1]) { x + true }",
+            "type mismatch: INTEGER + BOOLEAN",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        match test_eval(input) {
+            Object::Error(e) => assert_eq!(e.message, expected, "input: {}", input),
+            other => panic!("input: {}: expected error, got {}", input, other),
+        }
+    }
+}
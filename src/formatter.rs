@@ -0,0 +1,537 @@
+#[cfg(test)]
+mod test;
+
+use crate::ast::{
+    BlockStatement, Expression, IfExpression, InfixExpression, LetStatement, MatchExpression,
+    Pattern, Program, Statement,
+};
+use crate::lexer::{quote, Lexer};
+use crate::parser::{Parser, Precedence, SyntaxError};
+use crate::token::{Comment, Token, TokenKind};
+use std::collections::HashMap;
+
+const INDENT: &str = "    ";
+
+pub fn format(input: &str) -> Result<String, Vec<SyntaxError>> {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+        return Err(p.errors().to_vec());
+    }
+
+    let mut f = Formatter::new(input, l.comments().clone());
+    f.write_program(&program);
+
+    Ok(f.out)
+}
+
+struct Formatter {
+    input: Vec<char>,
+    tokens: Vec<Token>,
+    closing_braces: HashMap<usize, usize>,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    at_block_start: bool,
+}
+
+impl Formatter {
+    fn new(input: &str, comments: Vec<Comment>) -> Formatter {
+        let mut l = Lexer::new(input);
+        let mut tokens = vec![];
+        loop {
+            let tok = l.next_token();
+            if tok.kind == TokenKind::EOF {
+                break;
+            }
+            tokens.push(tok);
+        }
+
+        // ブロックの閉じ括弧の位置を開き括弧の位置から引けるようにしておく
+        let mut closing_braces = HashMap::new();
+        let mut stack = vec![];
+        for tok in &tokens {
+            match tok.kind {
+                TokenKind::LBrace => stack.push(tok.span.start),
+                TokenKind::RBrace => {
+                    if let Some(start) = stack.pop() {
+                        closing_braces.insert(start, tok.span.start);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Formatter {
+            input: input.chars().collect(),
+            tokens,
+            closing_braces,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            at_block_start: true,
+        }
+    }
+
+    fn write_program(&mut self, program: &Program) {
+        self.write_statements(&program.statements, false);
+        self.write_comments_before(usize::MAX);
+    }
+
+    fn write_statements(&mut self, statements: &[Statement], in_block: bool) {
+        for (i, stmt) in statements.iter().enumerate() {
+            let start = statement_start(stmt);
+            self.write_comments_before(start);
+            self.start_line(start);
+
+            let is_tail = in_block && i == statements.len() - 1;
+            self.write_statement(stmt, is_tail, statements.get(i + 1));
+            self.out.push('\n');
+            self.at_block_start = false;
+        }
+    }
+
+    fn write_statement(&mut self, stmt: &Statement, is_tail: bool, next: Option<&Statement>) {
+        match stmt {
+            Statement::LetStatement(s) => self.write_let(s),
+            Statement::ReturnStatement(s) => {
+                self.out.push_str("return");
+                if let Some(value) = &s.return_value {
+                    self.out.push(' ');
+                    self.write_expression(value);
+                }
+                self.out.push(';');
+            }
+            Statement::ExpressionStatement(s) => {
+                if let Some(expression) = &s.expression {
+                    self.write_expression(expression);
+                    if !is_tail && needs_semicolon(expression, next) {
+                        self.out.push(';');
+                    }
+                }
+            }
+            Statement::BlockStatement(s) => self.write_block(s),
+            Statement::WhileStatement(s) => {
+                self.out.push_str("while (");
+                if let Some(condition) = &s.condition {
+                    self.write_expression(condition);
+                }
+                self.out.push_str(") ");
+                self.write_block(&s.body);
+            }
+            Statement::ForStatement(s) => {
+                let variables: Vec<&str> = s.variables.iter().map(|v| v.value.as_str()).collect();
+                self.out
+                    .push_str(&format!("for ({} in ", variables.join(", ")));
+                if let Some(iterable) = &s.iterable {
+                    self.write_expression(iterable);
+                }
+                self.out.push_str(") ");
+                self.write_block(&s.body);
+            }
+            Statement::BreakStatement(_) => self.out.push_str("break;"),
+            Statement::ContinueStatement(_) => self.out.push_str("continue;"),
+            Statement::ImportStatement(s) => {
+                self.out
+                    .push_str(&format!("import {} as {};", quote(&s.path), s.alias.value));
+            }
+            Statement::ExportStatement(s) => {
+                self.out.push_str("export ");
+                self.write_let(&s.statement);
+            }
+        }
+    }
+
+    fn write_let(&mut self, s: &LetStatement) {
+        self.out.push_str("let ");
+        self.write_pattern(&s.pattern);
+        self.out.push_str(" = ");
+        if let Some(value) = &s.value {
+            self.write_expression(value);
+        }
+        self.out.push(';');
+    }
+
+    fn write_block(&mut self, block: &BlockStatement) {
+        let end = self
+            .closing_braces
+            .get(&block.token.span.start)
+            .copied()
+            .unwrap_or(usize::MAX);
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|c| c.span.start < end);
+
+        if block.statements.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.at_block_start = true;
+
+        self.write_statements(&block.statements, true);
+        self.write_comments_before(end);
+
+        self.indent -= 1;
+        self.out.push_str(&INDENT.repeat(self.indent));
+        self.out.push('}');
+    }
+
+    fn write_if(&mut self, e: &IfExpression) {
+        self.out.push_str("if (");
+        if let Some(condition) = &e.condition {
+            self.write_expression(condition);
+        }
+        self.out.push_str(") ");
+        self.write_block(&e.consequence);
+        // else { if ... } と書かれたものはそのまま残す
+        match (&e.alternative, e.else_if()) {
+            (Some(alternative), Some(next)) if alternative.token.kind == TokenKind::If => {
+                self.out.push_str(" else ");
+                self.write_if(next);
+            }
+            (Some(alternative), _) => {
+                self.out.push_str(" else ");
+                self.write_block(alternative);
+            }
+            (None, _) => {}
+        }
+    }
+
+    fn write_expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::Identifier(e) => self.out.push_str(&e.value),
+            Expression::IntegerLiteral(e) => self.out.push_str(&e.value.to_string()),
+            Expression::Boolean(e) => self.out.push_str(&e.value.to_string()),
+            Expression::PrefixExpression(e) => {
+                self.out.push_str(&e.operator);
+                if let Some(right) = &e.right {
+                    self.write_operand(right, |p| p < Precedence::Prefix);
+                }
+            }
+            Expression::InfixExpression(e) => self.write_infix(e),
+            Expression::IfExpression(e) => self.write_if(e),
+            Expression::FunctionLiteral(e) => {
+                self.out.push_str("fn(");
+                for (i, parameter) in e.parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.write_pattern(parameter);
+                }
+                self.out.push_str(") ");
+                self.write_block(&e.body);
+            }
+            Expression::CallExpression(e) => {
+                self.write_operand(&e.function, |p| p < Precedence::Call);
+                self.out.push('(');
+                self.write_expression_list(&e.arguments);
+                self.out.push(')');
+            }
+            Expression::StringLiteral(e) => self.out.push_str(&quote(&e.value)),
+            Expression::ArrayLiteral(e) => {
+                self.out.push('[');
+                self.write_expression_list(&e.elements);
+                self.out.push(']');
+            }
+            Expression::IndexExpression(e) => {
+                self.write_operand(&e.left, |p| p < Precedence::Index);
+                self.out.push('[');
+                self.write_expression(&e.index);
+                self.out.push(']');
+            }
+            Expression::MemberExpression(e) => {
+                self.write_operand(&e.object, |p| p < Precedence::Index);
+                self.out.push('.');
+                self.out.push_str(&e.property.value);
+            }
+            Expression::HashLiteral(e) => {
+                self.out.push('{');
+                for (i, (key, value)) in e.pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.write_expression(key);
+                    self.out.push_str(": ");
+                    self.write_expression(value);
+                }
+                self.out.push('}');
+            }
+            // 範囲の演算子は前後を空けない
+            Expression::RangeExpression(e) => {
+                if let Some(start) = &e.start {
+                    self.write_operand(start, |p| p < Precedence::Range);
+                }
+                self.out.push_str(if e.inclusive { "..=" } else { ".." });
+                if let Some(end) = &e.end {
+                    self.write_operand(end, |p| p <= Precedence::Range);
+                }
+            }
+            // 代入は右結合で優先順位が最も低いので、どちらの側にも括弧はいらない
+            Expression::AssignExpression(e) => {
+                self.write_expression(&e.target);
+                self.out.push_str(&format!(" {} ", e.operator));
+                if let Some(value) = &e.value {
+                    self.write_expression(value);
+                }
+            }
+            Expression::MatchExpression(e) => self.write_match(e),
+        }
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順に書く
+    fn write_infix(&mut self, e: &InfixExpression) {
+        let mut chain = vec![(e, false)];
+        let mut precedence = Parser::get_precedence(e.token.kind);
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            let inner_precedence = Parser::get_precedence(inner.token.kind);
+            chain.push((inner, inner_precedence < precedence));
+            precedence = inner_precedence;
+            leftmost = inner.left.as_deref();
+        }
+
+        // 外側より優先順位の低い左辺を囲む括弧は、まとめて先に開く
+        for _ in chain.iter().filter(|(_, parens)| *parens) {
+            self.out.push('(');
+        }
+        if let Some(left) = leftmost {
+            self.write_operand(left, |p| p < precedence);
+        }
+        for (e, parens) in chain.into_iter().rev() {
+            let precedence = Parser::get_precedence(e.token.kind);
+            self.out.push_str(&format!(" {} ", e.operator));
+            if let Some(right) = &e.right {
+                // 演算子は左結合なので、同じ優先順位の右辺は括弧が必要
+                self.write_operand(right, |p| p <= precedence);
+            }
+            if parens {
+                self.out.push(')');
+            }
+        }
+    }
+
+    // 腕は一行に一つずつ並べ、最後の腕にもカンマを付ける
+    fn write_match(&mut self, e: &MatchExpression) {
+        self.out.push_str("match (");
+        if let Some(subject) = &e.subject {
+            self.write_expression(subject);
+        }
+        self.out.push_str(") ");
+        if e.arms.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.at_block_start = true;
+        for arm in &e.arms {
+            let start = arm.pattern.span().start;
+            self.write_comments_before(start);
+            self.start_line(start);
+            self.write_pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                self.out.push_str(" if ");
+                self.write_expression(guard);
+            }
+            self.out.push_str(" => ");
+            if let Some(body) = &arm.body {
+                self.write_expression(body);
+            }
+            self.out.push_str(",\n");
+            self.at_block_start = false;
+        }
+        self.indent -= 1;
+        self.out.push_str(&INDENT.repeat(self.indent));
+        self.out.push('}');
+    }
+
+    fn write_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::WildcardPattern(_) => self.out.push('_'),
+            Pattern::Identifier(p) => self.out.push_str(&p.value),
+            Pattern::IntegerLiteral(p) => self.out.push_str(&p.value.to_string()),
+            Pattern::StringLiteral(p) => self.out.push_str(&quote(&p.value)),
+            Pattern::Boolean(p) => self.out.push_str(&p.value.to_string()),
+            Pattern::ArrayPattern(p) => {
+                self.out.push('[');
+                for (i, element) in p.elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.write_pattern(element);
+                }
+                if let Some(rest) = &p.rest {
+                    if !p.elements.is_empty() {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str("..");
+                    if let Some(name) = &rest.name {
+                        self.out.push_str(&name.value);
+                    }
+                }
+                self.out.push(']');
+            }
+            Pattern::HashPattern(p) => {
+                self.out.push('{');
+                for (i, (key, value)) in p.pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.write_expression(key);
+                    self.out.push_str(": ");
+                    self.write_pattern(value);
+                }
+                self.out.push('}');
+            }
+        }
+    }
+
+    fn write_expression_list(&mut self, list: &[Expression]) {
+        for (i, exp) in list.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.write_expression(exp);
+        }
+    }
+
+    fn write_operand(&mut self, exp: &Expression, needs_parens: impl Fn(Precedence) -> bool) {
+        if precedence_of(exp).is_some_and(needs_parens) {
+            self.out.push('(');
+            self.write_expression(exp);
+            self.out.push(')');
+        } else {
+            self.write_expression(exp);
+        }
+    }
+
+    fn write_comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).cloned() {
+            if comment.span.start >= pos {
+                break;
+            }
+            self.next_comment += 1;
+
+            if self.is_trailing(&comment) && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+            } else {
+                self.start_line(comment.span.start);
+                self.at_block_start = false;
+            }
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+    }
+
+    fn start_line(&mut self, pos: usize) {
+        if !self.at_block_start && self.has_blank_line_before(pos) {
+            self.out.push('\n');
+        }
+        self.out.push_str(&INDENT.repeat(self.indent));
+    }
+
+    // 直前のトークンと同じ行にあるコメントは行末コメントとして扱う
+    fn is_trailing(&self, comment: &Comment) -> bool {
+        let prev = self
+            .tokens
+            .partition_point(|t| t.span.end <= comment.span.start);
+        match prev.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(tok) => !self.input[tok.span.end..comment.span.start].contains(&'\n'),
+            None => false,
+        }
+    }
+
+    // 空行は連続していても1行にまとめる
+    fn has_blank_line_before(&self, pos: usize) -> bool {
+        let pos = pos.min(self.input.len());
+        let prev_token = self.tokens.partition_point(|t| t.span.end <= pos);
+        let prev_comment = self.comments.partition_point(|c| c.span.end <= pos);
+
+        let prev_end = [
+            prev_token.checked_sub(1).map(|i| self.tokens[i].span.end),
+            prev_comment
+                .checked_sub(1)
+                .map(|i| self.comments[i].span.end),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+
+        match prev_end {
+            Some(end) => self.input[end..pos].iter().filter(|&&c| c == '\n').count() >= 2,
+            None => false,
+        }
+    }
+}
+
+fn statement_start(stmt: &Statement) -> usize {
+    stmt.span().start
+}
+
+// None は括弧を必要としない式
+fn precedence_of(exp: &Expression) -> Option<Precedence> {
+    match exp {
+        Expression::PrefixExpression(_) => Some(Precedence::Prefix),
+        Expression::InfixExpression(e) => Some(Parser::get_precedence(e.token.kind)),
+        Expression::RangeExpression(_) => Some(Precedence::Range),
+        Expression::AssignExpression(_) => Some(Precedence::Assign),
+        _ => None,
+    }
+}
+
+// if 式と match 式の後ろは、次の文が中置演算子として続けて読まれてしまう場合だけセミコロンを付ける
+fn needs_semicolon(exp: &Expression, next: Option<&Statement>) -> bool {
+    if !matches!(
+        exp,
+        Expression::IfExpression(_) | Expression::MatchExpression(_)
+    ) {
+        return true;
+    }
+
+    match next {
+        Some(Statement::ExpressionStatement(s)) => {
+            s.expression.as_ref().is_some_and(starts_with_continuation)
+        }
+        _ => false,
+    }
+}
+
+fn starts_with_continuation(exp: &Expression) -> bool {
+    match exp {
+        Expression::PrefixExpression(e) => e.operator == "-",
+        Expression::InfixExpression(e) => {
+            let precedence = Parser::get_precedence(e.token.kind);
+            e.left.as_ref().is_some_and(|left| {
+                precedence_of(left).is_some_and(|p| p < precedence)
+                    || starts_with_continuation(left)
+            })
+        }
+        Expression::RangeExpression(e) => e.start.as_ref().is_some_and(|start| {
+            precedence_of(start).is_some_and(|p| p < Precedence::Range)
+                || starts_with_continuation(start)
+        }),
+        Expression::AssignExpression(e) => starts_with_continuation(&e.target),
+        // 括弧で囲んだ呼び出し先は関数呼び出しとして続けて読まれてしまう
+        Expression::CallExpression(e) => {
+            precedence_of(&e.function).is_some() || starts_with_continuation(&e.function)
+        }
+        Expression::IndexExpression(e) => {
+            precedence_of(&e.left).is_some() || starts_with_continuation(&e.left)
+        }
+        Expression::MemberExpression(e) => {
+            precedence_of(&e.object).is_some() || starts_with_continuation(&e.object)
+        }
+        // 配列リテラルは添字として続けて読まれてしまう
+        Expression::ArrayLiteral(_) => true,
+        _ => false,
+    }
+}
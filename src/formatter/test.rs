@@ -0,0 +1,195 @@
+use super::*;
+use crate::ast::Node;
+
+fn parse(input: &str) -> Program {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    program
+}
+
+#[test]
+fn test_format() {
+    let tests = vec![
+        ("let x=5", "let x = 5;\n"),
+        ("let   y = true;return y", "let y = true;\nreturn y;\n"),
+        ("((1 + 2)) * 3;", "(1 + 2) * 3;\n"),
+        ("1 + (2 * 3)", "1 + 2 * 3;\n"),
+        ("(1 + 2) + 3", "1 + 2 + 3;\n"),
+        ("1 + (2 + 3)", "1 + (2 + 3);\n"),
+        ("1 - (2 - 3)", "1 - (2 - 3);\n"),
+        ("-(a * b)", "-(a * b);\n"),
+        ("(-a) * b", "-a * b;\n"),
+        ("!(-a)", "!-a;\n"),
+        ("(5 > 4) == (3 < 4)", "5 > 4 == 3 < 4;\n"),
+        (
+            "if (x < y) { x } else { y }",
+            "if (x < y) {\n    x\n} else {\n    y\n}\n",
+        ),
+        ("if (x) {}", "if (x) {}\n"),
+        (
+            "let a = if (x) { if (y) { 1; 2 } }",
+            "let a = if (x) {\n    if (y) {\n        1;\n        2\n    }\n};\n",
+        ),
+        (
+            "let add=fn(a,b){a+b};add(1,(2))",
+            "let add = fn(a, b) {\n    a + b\n};\nadd(1, 2);\n",
+        ),
+        ("fn(){}()", "fn() {}();\n"),
+        ("(-f)(x)", "(-f)(x);\n"),
+        (
+            "if (x) { 1 }; (a + b)(c)",
+            "if (x) {\n    1\n};\n(a + b)(c);\n",
+        ),
+        (
+            r#"puts( "a\tb" ,"\"q\"")"#,
+            "puts(\"a\\tb\", \"\\\"q\\\"\");\n",
+        ),
+        ("[1,2*3][(0)]", "[1, 2 * 3][0];\n"),
+        ("(-a)[0]", "(-a)[0];\n"),
+        ("-(a[0])", "-a[0];\n"),
+        ("if (x) { 1 }; [2]", "if (x) {\n    1\n};\n[2];\n"),
+        (
+            r#"{"a":1,true:[2],}["a"]"#,
+            "{\"a\": 1, true: [2]}[\"a\"];\n",
+        ),
+        ("let h={}", "let h = {};\n"),
+        ("if (x) { 1 }; a[2]", "if (x) {\n    1\n}\na[2];\n"),
+        (
+            "while(x<3){if(x){break};continue}",
+            "while (x < 3) {\n    if (x) {\n        break;\n    }\n    continue;\n}\n",
+        ),
+        ("while (x) {}", "while (x) {}\n"),
+        (
+            "for(k,v in h){puts(k)}",
+            "for (k, v in h) {\n    puts(k)\n}\n",
+        ),
+        ("for (i in (0 .. n+1)) {}", "for (i in 0..n + 1) {}\n"),
+        ("(a..b)..(c..=d)", "a..b..(c..=d);\n"),
+        ("(-1)..=(x == y)", "-1..=x == y;\n"),
+        ("x+=1", "x += 1;\n"),
+        ("(a[0])=(b=c)", "a[0] = b = c;\n"),
+        ("(x = 1) * 2", "(x = 1) * 2;\n"),
+        (
+            "if(a){1}else if(b){2}else{3}",
+            "if (a) {\n    1\n} else if (b) {\n    2\n} else {\n    3\n}\n",
+        ),
+        (
+            "if (a) {} else { if (b) {} }",
+            "if (a) {} else {\n    if (b) {}\n}\n",
+        ),
+        ("match(x){}", "match (x) {}\n"),
+        (
+            "match(x){[a,..r]if a>0=>a+1,{\"k\":-1}=>(0),_=>match(y){_=>{}}}",
+            "match (x) {\n    [a, ..r] if a > 0 => a + 1,\n    {\"k\": -1} => 0,\n    _ => match (y) {\n        _ => {},\n    },\n}\n",
+        ),
+        (
+            "let [a,..b]=x;let {\"k\":[_]}=y;fn([a],{1:b}){a}",
+            "let [a, ..b] = x;\nlet {\"k\": [_]} = y;\nfn([a], {1: b}) {\n    a\n};\n",
+        ),
+        (
+            "let v = match (x) { [..] => 1 }; v",
+            "let v = match (x) {\n    [..] => 1,\n};\nv;\n",
+        ),
+        (
+            "import \"lib/m.monkey\"as m export let[x]=(m.f)(1) . y;(-a).b",
+            "import \"lib/m.monkey\" as m;\nexport let [x] = m.f(1).y;\n(-a).b;\n",
+        ),
+        ("if (x) { 1 }; m.a", "if (x) {\n    1\n}\nm.a;\n"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(format(input).unwrap(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_format_if_followed_by_prefix() {
+    // 次の文が中置演算子として読まれないようにセミコロンを残す
+    let input = "if (x) { 1 }; -5; if (y) { 2 } !z;";
+    let expected = "if (x) {\n    1\n};\n-5;\nif (y) {\n    2\n}\n!z;\n";
+    assert_eq!(format(input).unwrap(), expected);
+}
+
+#[test]
+fn test_format_match_comments() {
+    let input = "match (x) {\n    // one\n    1 => a, // trailing\n\n    _ => b,\n}";
+    let expected = "match (x) {\n    // one\n    1 => a, // trailing\n\n    _ => b,\n}\n";
+    assert_eq!(format(input).unwrap(), expected);
+}
+
+#[test]
+fn test_format_comments() {
+    let input = "// header
+
+let x = 5; // five
+// about y
+
+
+let y = 10;
+
+if (x < y) { // compare
+    // inside
+    x
+    // before brace
+}
+// trailer
+";
+    let expected = "// header
+
+let x = 5; // five
+// about y
+
+let y = 10;
+
+if (x < y) { // compare
+    // inside
+    x
+    // before brace
+}
+// trailer
+";
+    assert_eq!(format(input).unwrap(), expected);
+}
+
+#[test]
+fn test_format_empty_block_with_comment() {
+    let input = "if (x) { // nothing\n}";
+    assert_eq!(format(input).unwrap(), "if (x) { // nothing\n}\n");
+}
+
+#[test]
+fn test_format_preserves_ast() {
+    let inputs = vec![
+        "let x = 1 + 2 * 3 - (4 - 5) / -6;",
+        "return !(a == b) != (c < d);",
+        "a * (b + c) * (d * e); f",
+        "if ((1 + 2) > x) { let y = x; y } else { if (y) { return -(-y); } }",
+        "let z = if (a) { 1 } else { 2 } + 3;",
+        "if (a) { 1 }; -b",
+        "while (x) { let x = x - 1; if (x) { continue; } break; }",
+        "for (i, x in (0..n)..(1..=2)) { if (x) { 1 }; -1..2 }",
+        "a = (b[0] += 1 + 2) * 3; (c[x = 1]) -= 1..2",
+        "let x = if (a) { 1 } else if (b) { 2 } else if (c) {} + 1; -x",
+        "match (x) { [h, ..t] if h => t, {1: true} => -1 } -2; match (y) {} + 1",
+        "let [a, ..] = fn([b], {\"k\": _}) { b }; let _ = a",
+    ];
+
+    for input in inputs {
+        let formatted = format(input).unwrap();
+        assert_eq!(
+            parse(&formatted).to_string(),
+            parse(input).to_string(),
+            "formatted: {}",
+            formatted
+        );
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+}
+
+#[test]
+fn test_format_parse_error() {
+    let errors = format("let = 5;").unwrap_err();
+    assert_eq!(errors.len(), 2);
+}
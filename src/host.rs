@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod test;
+
+use crate::object::{Hash, HashKey, Object};
+use std::collections::HashMap;
+use std::hash::Hash as StdHash;
+use std::rc::Rc;
+
+// 埋め込み側の関数もこのエラーを返す。Monkey からは ERROR として見える
+pub use crate::object::RuntimeError;
+
+fn mismatch(expected: &str, object: &Object) -> RuntimeError {
+    RuntimeError::new(format!("expected {}, got {}", expected, object.type_name()))
+}
+
+// Rust の値から Monkey の値へ
+pub trait IntoObject {
+    fn into_object(self) -> Object;
+}
+
+// Monkey の値から Rust の値へ。型が合わなければエラー
+pub trait FromObject: Sized {
+    fn from_object(object: &Object) -> Result<Self, RuntimeError>;
+}
+
+// ハッシュのキーになれる型
+pub trait IntoHashKey {
+    fn into_hash_key(self) -> HashKey;
+}
+
+impl IntoObject for Object {
+    fn into_object(self) -> Object {
+        self
+    }
+}
+
+impl FromObject for Object {
+    fn from_object(object: &Object) -> Result<Object, RuntimeError> {
+        Ok(object.clone())
+    }
+}
+
+impl IntoObject for () {
+    fn into_object(self) -> Object {
+        Object::Null
+    }
+}
+
+impl IntoObject for i64 {
+    fn into_object(self) -> Object {
+        Object::Integer(self)
+    }
+}
+
+impl FromObject for i64 {
+    fn from_object(object: &Object) -> Result<i64, RuntimeError> {
+        match object {
+            Object::Integer(value) => Ok(*value),
+            other => Err(mismatch("INTEGER", other)),
+        }
+    }
+}
+
+impl IntoObject for bool {
+    fn into_object(self) -> Object {
+        Object::Boolean(self)
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(object: &Object) -> Result<bool, RuntimeError> {
+        match object {
+            Object::Boolean(value) => Ok(*value),
+            other => Err(mismatch("BOOLEAN", other)),
+        }
+    }
+}
+
+impl IntoObject for String {
+    fn into_object(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl IntoObject for &str {
+    fn into_object(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl FromObject for String {
+    fn from_object(object: &Object) -> Result<String, RuntimeError> {
+        match object {
+            Object::String(value) => Ok(value.to_string()),
+            other => Err(mismatch("STRING", other)),
+        }
+    }
+}
+
+// None は null になる
+impl<T: IntoObject> IntoObject for Option<T> {
+    fn into_object(self) -> Object {
+        self.map_or(Object::Null, T::into_object)
+    }
+}
+
+impl<T: FromObject> FromObject for Option<T> {
+    fn from_object(object: &Object) -> Result<Option<T>, RuntimeError> {
+        match object {
+            Object::Null => Ok(None),
+            other => T::from_object(other).map(Some),
+        }
+    }
+}
+
+impl<T: IntoObject> IntoObject for Vec<T> {
+    fn into_object(self) -> Object {
+        Object::Array(Rc::new(self.into_iter().map(T::into_object).collect()))
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(object: &Object) -> Result<Vec<T>, RuntimeError> {
+        match object {
+            Object::Array(elements) => elements.iter().map(T::from_object).collect(),
+            other => Err(mismatch("ARRAY", other)),
+        }
+    }
+}
+
+impl<K: IntoHashKey, V: IntoObject> IntoObject for HashMap<K, V> {
+    fn into_object(self) -> Object {
+        let hash: Hash = self
+            .into_iter()
+            .map(|(key, value)| (key.into_hash_key(), value.into_object()))
+            .collect();
+        Object::Hash(Rc::new(hash))
+    }
+}
+
+// キーは Monkey の値に戻してから変換する
+impl<K: FromObject + Eq + StdHash, V: FromObject> FromObject for HashMap<K, V> {
+    fn from_object(object: &Object) -> Result<HashMap<K, V>, RuntimeError> {
+        match object {
+            Object::Hash(hash) => hash
+                .iter()
+                .map(|(key, value)| Ok((K::from_object(&key.to_object())?, V::from_object(value)?)))
+                .collect(),
+            other => Err(mismatch("HASH", other)),
+        }
+    }
+}
+
+impl IntoHashKey for i64 {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::Integer(self)
+    }
+}
+
+impl IntoHashKey for bool {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::Boolean(self)
+    }
+}
+
+impl IntoHashKey for String {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::String(self.into())
+    }
+}
+
+impl IntoHashKey for &str {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::String(self.into())
+    }
+}
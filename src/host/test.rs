@@ -0,0 +1,152 @@
+use super::*;
+use crate::engine::{Engine, Interpreter};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::cell::Cell;
+
+fn run(interpreter: &mut Interpreter, input: &str) -> Option<Object> {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    interpreter.run(&program)
+}
+
+fn fetch_config(args: &[Object]) -> Result<Object, RuntimeError> {
+    let [key] = args else {
+        return Err(RuntimeError::new("fetch_config takes one argument"));
+    };
+    match String::from_object(key)?.as_str() {
+        "workers" => Ok(4.into_object()),
+        "name" => Ok("monkey".into_object()),
+        other => Err(format!("no such key: {}", other).into()),
+    }
+}
+
+#[test]
+fn test_register() {
+    let tests = vec![
+        (r#"fetch_config("workers") * 2"#, Object::Integer(8)),
+        (r#"fetch_config("name")"#, Object::String("monkey".into())),
+        (
+            r#"fetch_config("port")"#,
+            Object::error("no such key: port"),
+        ),
+        (
+            "fetch_config(1)",
+            Object::error("expected STRING, got INTEGER"),
+        ),
+        (
+            "fetch_config()",
+            Object::error("fetch_config takes one argument"),
+        ),
+        // 組み込み関数と同じように値として渡せる
+        (
+            r#"let get = fn(f) { f("workers") }; get(fetch_config)"#,
+            Object::Integer(4),
+        ),
+        (
+            r#"let f = fn() { fn() { fetch_config("workers") } }; f()()"#,
+            Object::Integer(4),
+        ),
+        ("type(fetch_config)", Object::String("BUILTIN".into())),
+        (
+            "fetch_config",
+            Object::String("builtin fetch_config".into()),
+        ),
+    ];
+
+    for engine in [Engine::Eval, Engine::Vm] {
+        let mut interpreter = Interpreter::new(engine);
+        interpreter.register("fetch_config", fetch_config);
+
+        for (input, expected) in &tests {
+            let mut result = run(&mut interpreter, input).unwrap();
+            match &result {
+                Object::HostFunction(_) => result = Object::String(result.to_string().into()),
+                Object::Error(e) => result = Object::error(&e.message),
+                _ => {}
+            }
+            assert_eq!(&result, expected, "engine: {}, input: {}", engine, input);
+        }
+    }
+}
+
+#[test]
+fn test_register_closure_state() {
+    for engine in [Engine::Eval, Engine::Vm] {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+
+        let mut interpreter = Interpreter::new(engine);
+        interpreter.register("tick", move |_| {
+            counter.set(counter.get() + 1);
+            Ok(counter.get().into_object())
+        });
+        // 同じ名前の組み込み関数より優先される
+        interpreter.register("len", |_| Ok(Object::Integer(-1)));
+
+        run(&mut interpreter, "let a = 1;");
+        assert_eq!(
+            run(&mut interpreter, "tick(); tick() + a"),
+            Some(Object::Integer(3)),
+            "engine: {}",
+            engine
+        );
+        assert_eq!(calls.get(), 2);
+        assert_eq!(
+            run(&mut interpreter, "len([1, 2])"),
+            Some(Object::Integer(-1)),
+            "engine: {}",
+            engine
+        );
+        assert_eq!(
+            run(&mut interpreter, "let tick = 10; tick"),
+            Some(Object::Integer(10)),
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn test_into_object() {
+    assert_eq!(42.into_object(), Object::Integer(42));
+    assert_eq!(true.into_object(), Object::Boolean(true));
+    assert_eq!("a".to_string().into_object(), Object::String("a".into()));
+    assert_eq!(().into_object(), Object::Null);
+    assert_eq!(None::<i64>.into_object(), Object::Null);
+    assert_eq!(vec![vec![1], vec![]].into_object().to_string(), "[[1], []]");
+
+    let map = HashMap::from([("a", vec![true])]);
+    assert_eq!(map.into_object().to_string(), "{a: [true]}");
+}
+
+#[test]
+fn test_from_object() {
+    let mut interpreter = Interpreter::new(Engine::Vm);
+    let object = run(&mut interpreter, r#"{"a": [1, 2], "b": []}"#).unwrap();
+
+    let map = HashMap::<String, Vec<i64>>::from_object(&object).unwrap();
+    assert_eq!(
+        map,
+        HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])])
+    );
+
+    // 往復しても同じ値になる
+    assert_eq!(map.clone().into_object(), object);
+
+    assert_eq!(Option::<bool>::from_object(&Object::Null), Ok(None));
+    assert_eq!(
+        HashMap::<i64, Vec<i64>>::from_object(&object),
+        Err(RuntimeError::new("expected INTEGER, got STRING"))
+    );
+    assert_eq!(
+        Vec::<i64>::from_object(&vec![Object::Boolean(true)].into_object()),
+        Err(RuntimeError::new("expected INTEGER, got BOOLEAN"))
+    );
+    assert_eq!(
+        i64::from_object(&object),
+        Err(RuntimeError::new("expected INTEGER, got HASH"))
+    );
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+#[test]
+fn test_parse() {
+    let input = r#" {"a": [1, -2, 3.5, true, false, null], "b": "x\"\né😀", "c": {}} "#;
+    let value = Json::parse(input).unwrap();
+
+    assert_eq!(
+        value,
+        Json::Object(vec![
+            (
+                "a".to_string(),
+                Json::Array(vec![
+                    Json::Int(1),
+                    Json::Int(-2),
+                    Json::Float(3.5),
+                    Json::Bool(true),
+                    Json::Bool(false),
+                    Json::Null,
+                ])
+            ),
+            ("b".to_string(), Json::String("x\"\né😀".to_string())),
+            ("c".to_string(), Json::Object(vec![])),
+        ])
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    let tests = ["", "[1,", "{\"a\" 1}", "tru", "\"abc", "[1] 2", "{1: 2}"];
+
+    for input in tests {
+        assert!(Json::parse(input).is_err(), "input: {}", input);
+    }
+}
+
+#[test]
+fn test_nesting_limit() {
+    let nested = |n: usize| format!("{}1{}", "[".repeat(n), "]".repeat(n));
+    assert!(Json::parse(&nested(MAX_NESTING)).is_ok());
+    assert_eq!(
+        Json::parse(&nested(MAX_NESTING + 1)),
+        Err(format!("nesting too deep at {}", MAX_NESTING))
+    );
+    let objects = "{\"a\": ".repeat(100_000);
+    assert_eq!(
+        Json::parse(&objects),
+        Err(format!("nesting too deep at {}", 6 * MAX_NESTING))
+    );
+}
+
+#[test]
+fn test_display() {
+    let value = Json::object(vec![
+        ("type", "Identifier".into()),
+        ("value", Json::Int(i64::MAX)),
+        ("items", vec![Json::Null, "a\tb".into()].into()),
+    ]);
+
+    assert_eq!(
+        value.to_string(),
+        r#"{"type":"Identifier","value":9223372036854775807,"items":[null,"a\tb"]}"#
+    );
+    assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+}
+
+#[test]
+fn test_pretty() {
+    let value = Json::object(vec![
+        ("a", vec![Json::Int(1)].into()),
+        ("b", Json::Array(vec![])),
+    ]);
+
+    assert_eq!(value.pretty(), "{\n  \"a\": [\n    1\n  ],\n  \"b\": []\n}");
+}
@@ -1,22 +1,24 @@
-use crate::token::{Token, TokenKind};
+use crate::token::{Comment, Span, Token, TokenKind};
 
 #[cfg(test)]
 mod test;
 
 pub struct Lexer {
-    input: String,
+    input: Vec<char>,
     position: usize,
     read_position: usize,
     ch: char,
+    comments: Vec<Comment>,
 }
 
 impl Lexer {
     pub fn new(input: impl ToString) -> Lexer {
         let mut l = Lexer {
-            input: input.to_string(),
+            input: input.to_string().chars().collect(),
             position: 0,
             read_position: 0,
             ch: '\0',
+            comments: Vec::new(),
         };
         l.read_char();
 
@@ -27,16 +29,31 @@ impl Lexer {
         let mut tok = Token::default();
 
         self.skip_whitespace();
+        let start = self.position;
 
         match self.ch {
             '=' => {
                 if self.peek_char() == '=' {
                     self.read_char();
                     tok = Token::new(TokenKind::Eq, "==");
+                } else if self.peek_char() == '>' {
+                    self.read_char();
+                    tok = Token::new(TokenKind::FatArrow, "=>");
                 } else {
                     tok = Token::new(TokenKind::Assign, self.ch);
                 }
             }
+            '+' | '-' | '*' | '/' if self.peek_char() == '=' => {
+                let kind = match self.ch {
+                    '+' => TokenKind::PlusAssign,
+                    '-' => TokenKind::MinusAssign,
+                    '*' => TokenKind::AsteriskAssign,
+                    _ => TokenKind::SlashAssign,
+                };
+                let literal = format!("{}=", self.ch);
+                self.read_char();
+                tok = Token::new(kind, literal);
+            }
             '+' => tok = Token::new(TokenKind::Plus, self.ch),
             '-' => tok = Token::new(TokenKind::Minus, self.ch),
             '!' => {
@@ -53,26 +70,50 @@ impl Lexer {
             '>' => tok = Token::new(TokenKind::Gt, self.ch),
             ';' => tok = Token::new(TokenKind::SemiColon, self.ch),
             ',' => tok = Token::new(TokenKind::Comma, self.ch),
+            ':' => tok = Token::new(TokenKind::Colon, self.ch),
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    tok = Token::new(TokenKind::DotDotEq, "..=");
+                } else {
+                    tok = Token::new(TokenKind::DotDot, "..");
+                }
+            }
+            '.' => tok = Token::new(TokenKind::Dot, self.ch),
             '(' => tok = Token::new(TokenKind::LParen, self.ch),
             ')' => tok = Token::new(TokenKind::RParen, self.ch),
             '{' => tok = Token::new(TokenKind::LBrace, self.ch),
             '}' => tok = Token::new(TokenKind::RBrace, self.ch),
+            '[' => tok = Token::new(TokenKind::LBracket, self.ch),
+            ']' => tok = Token::new(TokenKind::RBracket, self.ch),
+            '"' => {
+                tok.kind = TokenKind::String;
+                tok.literal = self.read_string();
+                return tok.with_span(Span::new(start, self.position));
+            }
             '\0' => tok = Token::new(TokenKind::EOF, ""),
             _ => {
                 if self.is_letter() {
                     tok.literal = self.read_identifier();
                     tok.kind = TokenKind::look_up_ident(&tok.literal);
-                    return tok;
+                    return tok.with_span(Span::new(start, self.position));
                 } else if self.is_digit() {
                     tok.kind = TokenKind::Int;
                     tok.literal = self.read_number();
-                    return tok;
+                    return tok.with_span(Span::new(start, self.position));
+                } else {
+                    tok = Token::new(TokenKind::Illegal, self.ch);
                 }
             }
         };
 
         self.read_char();
-        tok
+        tok.with_span(Span::new(start, self.position))
+    }
+
+    pub fn comments(&self) -> &Vec<Comment> {
+        &self.comments
     }
 
     fn read_identifier(&mut self) -> String {
@@ -80,7 +121,7 @@ impl Lexer {
         while self.is_letter() {
             self.read_char();
         }
-        self.input[position..self.position].to_string()
+        self.input[position..self.position].iter().collect()
     }
 
     fn read_number(&mut self) -> String {
@@ -88,7 +129,23 @@ impl Lexer {
         while self.is_digit() {
             self.read_char();
         }
-        self.input[position..self.position].to_string()
+        self.input[position..self.position].iter().collect()
+    }
+
+    // 引用符も含めてソースのまま読む。エスケープの解釈は unquote で行う
+    fn read_string(&mut self) -> String {
+        let position = self.position;
+        self.read_char();
+        while self.ch != '"' && self.ch != '\0' {
+            if self.ch == '\\' && self.peek_char() != '\0' {
+                self.read_char();
+            }
+            self.read_char();
+        }
+        if self.ch == '"' {
+            self.read_char();
+        }
+        self.input[position..self.position].iter().collect()
     }
 
     fn is_letter(&self) -> bool {
@@ -100,18 +157,92 @@ impl Lexer {
     }
 
     fn read_char(&mut self) {
-        self.ch = self.input.chars().nth(self.read_position).unwrap_or('\0');
+        self.ch = self.input.get(self.read_position).copied().unwrap_or('\0');
         self.position = self.read_position;
         self.read_position += 1;
     }
 
     fn peek_char(&self) -> char {
-        self.input.chars().nth(self.read_position).unwrap_or('\0')
+        self.input.get(self.read_position).copied().unwrap_or('\0')
     }
 
     fn skip_whitespace(&mut self) {
-        while self.ch.is_ascii_whitespace() {
+        loop {
+            while self.ch.is_ascii_whitespace() {
+                self.read_char();
+            }
+
+            if self.ch == '/' && self.peek_char() == '/' {
+                self.read_comment();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // 行末までをコメントとして読み飛ばし、フォーマッタ用に記録しておく
+    fn read_comment(&mut self) {
+        let position = self.position;
+        while self.ch != '\n' && self.ch != '\0' {
             self.read_char();
         }
+        self.comments.push(Comment {
+            text: self.input[position..self.position]
+                .iter()
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+            span: Span::new(position, self.position),
+        });
+    }
+}
+
+// 文字列トークンのリテラルから値を取り出す
+pub fn unquote(literal: &str) -> Result<String, String> {
+    let body = literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|body| !ends_with_escape(body))
+        .ok_or_else(|| "unterminated string literal".to_string())?;
+
+    let mut value = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('"') => value.push('"'),
+            Some('\\') => value.push('\\'),
+            Some(c) => return Err(format!("unknown escape sequence \\{}", c)),
+            None => return Err("unterminated string literal".to_string()),
+        }
+    }
+    Ok(value)
+}
+
+// 末尾のバックスラッシュが奇数個なら、閉じ引用符はエスケープされている
+fn ends_with_escape(s: &str) -> bool {
+    s.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+// unquote の逆。to_string やフォーマッタが使う
+pub fn quote(value: &str) -> String {
+    let mut s = String::from('"');
+    for c in value.chars() {
+        match c {
+            '\n' => s.push_str("\\n"),
+            '\t' => s.push_str("\\t"),
+            '\r' => s.push_str("\\r"),
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            c => s.push(c),
+        }
     }
+    s.push('"');
+    s
 }
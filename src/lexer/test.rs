@@ -1,9 +1,9 @@
-use crate::lexer::Lexer;
+use crate::lexer::{quote, unquote, Lexer};
 use crate::token::TokenKind;
 
 #[test]
 fn test_next_token() {
-    let input = "let five = 5;
+    let input = r#"let five = 5;
 let ten = 10;
 
 let add = fn(x, y) {
@@ -22,7 +22,12 @@ if (5 < 10) {
 
 10 == 10;
 10 != 9;
-";
+"foobar"
+"foo bar"
+[1, 2];
+"a\"b // c"
+{"foo": "bar"}
+"#;
 
     let tests = vec![
         (TokenKind::Let, "let"),
@@ -98,6 +103,20 @@ if (5 < 10) {
         (TokenKind::NotEq, "!="),
         (TokenKind::Int, "9"),
         (TokenKind::SemiColon, ";"),
+        (TokenKind::String, r#""foobar""#),
+        (TokenKind::String, r#""foo bar""#),
+        (TokenKind::LBracket, "["),
+        (TokenKind::Int, "1"),
+        (TokenKind::Comma, ","),
+        (TokenKind::Int, "2"),
+        (TokenKind::RBracket, "]"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::String, r#""a\"b // c""#),
+        (TokenKind::LBrace, "{"),
+        (TokenKind::String, r#""foo""#),
+        (TokenKind::Colon, ":"),
+        (TokenKind::String, r#""bar""#),
+        (TokenKind::RBrace, "}"),
         (TokenKind::EOF, ""),
     ];
 
@@ -110,3 +129,284 @@ if (5 < 10) {
         assert_eq!(tok.literal, expected_literal);
     }
 }
+
+#[test]
+fn test_loop_keywords() {
+    let mut l = Lexer::new("while break continue whiles");
+    let kinds: Vec<TokenKind> = (0..5).map(|_| l.next_token().kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::While,
+            TokenKind::Break,
+            TokenKind::Continue,
+            TokenKind::Ident,
+            TokenKind::EOF,
+        ]
+    );
+}
+
+#[test]
+fn test_ranges() {
+    let mut l = Lexer::new("for (i in 0..n) 1..=2 a.b");
+    let tokens: Vec<(TokenKind, String)> = (0..14)
+        .map(|_| {
+            let tok = l.next_token();
+            (tok.kind, tok.literal)
+        })
+        .collect();
+    let expected = vec![
+        (TokenKind::For, "for"),
+        (TokenKind::LParen, "("),
+        (TokenKind::Ident, "i"),
+        (TokenKind::In, "in"),
+        (TokenKind::Int, "0"),
+        (TokenKind::DotDot, ".."),
+        (TokenKind::Ident, "n"),
+        (TokenKind::RParen, ")"),
+        (TokenKind::Int, "1"),
+        (TokenKind::DotDotEq, "..="),
+        (TokenKind::Int, "2"),
+        (TokenKind::Ident, "a"),
+        (TokenKind::Dot, "."),
+        (TokenKind::Ident, "b"),
+    ];
+    let expected: Vec<(TokenKind, String)> = expected
+        .into_iter()
+        .map(|(kind, literal)| (kind, literal.to_string()))
+        .collect();
+    assert_eq!(tokens, expected);
+}
+
+#[test]
+fn test_comments() {
+    let input = "// leading
+let x = 5; // trailing
+x / 2";
+
+    let mut l = Lexer::new(input);
+    let mut kinds = vec![];
+    loop {
+        let tok = l.next_token();
+        if tok.kind == TokenKind::EOF {
+            break;
+        }
+        kinds.push(tok.kind);
+    }
+
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Let,
+            TokenKind::Ident,
+            TokenKind::Assign,
+            TokenKind::Int,
+            TokenKind::SemiColon,
+            TokenKind::Ident,
+            TokenKind::Slash,
+            TokenKind::Int,
+        ]
+    );
+
+    let comments: Vec<&str> = l.comments().iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(comments, vec!["// leading", "// trailing"]);
+    assert_eq!(l.comments()[1].span.start, 22);
+}
+
+// 位置は文字単位で数える。ASCII 以外の文字があっても後ろのトークンがずれない
+#[test]
+fn test_non_ascii_comments() {
+    let input = "// héllo ✓\nlet x = 1; // 終わり";
+    let mut l = Lexer::new(input);
+    let tokens: Vec<(TokenKind, String, usize)> = (0..5)
+        .map(|_| l.next_token())
+        .map(|t| (t.kind, t.literal, t.span.start))
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Let, "let".to_string(), 11),
+            (TokenKind::Ident, "x".to_string(), 15),
+            (TokenKind::Assign, "=".to_string(), 17),
+            (TokenKind::Int, "1".to_string(), 19),
+            (TokenKind::SemiColon, ";".to_string(), 20),
+        ]
+    );
+    assert_eq!(l.next_token().kind, TokenKind::EOF);
+
+    let comments: Vec<(&str, usize, usize)> = l
+        .comments()
+        .iter()
+        .map(|c| (c.text.as_str(), c.span.start, c.span.end))
+        .collect();
+    assert_eq!(comments, vec![("// héllo ✓", 0, 10), ("// 終わり", 22, 28)]);
+}
+
+#[test]
+fn test_spans() {
+    let input = "let ab = 10;";
+
+    let mut l = Lexer::new(input);
+    let spans: Vec<(usize, usize)> = (0..5)
+        .map(|_| l.next_token().span)
+        .map(|s| (s.start, s.end))
+        .collect();
+
+    assert_eq!(spans, vec![(0, 3), (4, 6), (7, 8), (9, 11), (11, 12)]);
+}
+
+#[test]
+fn test_illegal() {
+    let mut l = Lexer::new("a @ b");
+    l.next_token();
+    let tok = l.next_token();
+
+    assert_eq!(tok.kind, TokenKind::Illegal);
+    assert_eq!(tok.literal, "@");
+    assert_eq!((tok.span.start, tok.span.end), (2, 3));
+}
+
+#[test]
+fn test_unterminated_string() {
+    let mut l = Lexer::new("\"abc\\\"");
+    let tok = l.next_token();
+
+    assert_eq!(tok.kind, TokenKind::String);
+    assert_eq!(tok.literal, "\"abc\\\"");
+    assert_eq!(l.next_token().kind, TokenKind::EOF);
+}
+
+#[test]
+fn test_unquote() {
+    let tests = vec![
+        (r#""""#, Ok("")),
+        (r#""hello world""#, Ok("hello world")),
+        (r#""a\n\t\"\\b""#, Ok("a\n\t\"\\b")),
+        (r#""abc"#, Err("unterminated string literal")),
+        (r#""abc\""#, Err("unterminated string literal")),
+        (r#"""#, Err("unterminated string literal")),
+        (r#""\q""#, Err("unknown escape sequence \\q")),
+    ];
+
+    for (input, expected) in tests {
+        let expected = expected.map(str::to_string).map_err(str::to_string);
+        assert_eq!(unquote(input), expected, "input: {}", input);
+        if let Ok(value) = expected {
+            assert_eq!(unquote(&quote(&value)), Ok(value));
+        }
+    }
+}
+
+#[test]
+fn test_assignment_operators() {
+    let mut l = Lexer::new("x = 1; x += 2 -= 3 *= 4 /= 5 == 6; a+=-1 // c\n");
+    let tokens: Vec<(TokenKind, String)> = (0..20)
+        .map(|_| {
+            let tok = l.next_token();
+            (tok.kind, tok.literal)
+        })
+        .collect();
+    let expected = vec![
+        (TokenKind::Ident, "x"),
+        (TokenKind::Assign, "="),
+        (TokenKind::Int, "1"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Ident, "x"),
+        (TokenKind::PlusAssign, "+="),
+        (TokenKind::Int, "2"),
+        (TokenKind::MinusAssign, "-="),
+        (TokenKind::Int, "3"),
+        (TokenKind::AsteriskAssign, "*="),
+        (TokenKind::Int, "4"),
+        (TokenKind::SlashAssign, "/="),
+        (TokenKind::Int, "5"),
+        (TokenKind::Eq, "=="),
+        (TokenKind::Int, "6"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Ident, "a"),
+        (TokenKind::PlusAssign, "+="),
+        (TokenKind::Minus, "-"),
+        (TokenKind::Int, "1"),
+    ];
+    let expected: Vec<(TokenKind, String)> = expected
+        .into_iter()
+        .map(|(kind, literal)| (kind, literal.to_string()))
+        .collect();
+    assert_eq!(tokens, expected);
+    assert_eq!(l.next_token().kind, TokenKind::EOF);
+}
+
+#[test]
+fn test_match_tokens() {
+    let mut l = Lexer::new("match (x) { [a, ..r] => a, _ => 0 } == =>");
+    let tokens: Vec<(TokenKind, String)> = (0..20)
+        .map(|_| {
+            let tok = l.next_token();
+            (tok.kind, tok.literal)
+        })
+        .collect();
+    let expected = vec![
+        (TokenKind::Match, "match"),
+        (TokenKind::LParen, "("),
+        (TokenKind::Ident, "x"),
+        (TokenKind::RParen, ")"),
+        (TokenKind::LBrace, "{"),
+        (TokenKind::LBracket, "["),
+        (TokenKind::Ident, "a"),
+        (TokenKind::Comma, ","),
+        (TokenKind::DotDot, ".."),
+        (TokenKind::Ident, "r"),
+        (TokenKind::RBracket, "]"),
+        (TokenKind::FatArrow, "=>"),
+        (TokenKind::Ident, "a"),
+        (TokenKind::Comma, ","),
+        (TokenKind::Ident, "_"),
+        (TokenKind::FatArrow, "=>"),
+        (TokenKind::Int, "0"),
+        (TokenKind::RBrace, "}"),
+        (TokenKind::Eq, "=="),
+        (TokenKind::FatArrow, "=>"),
+    ];
+    let expected: Vec<(TokenKind, String)> = expected
+        .into_iter()
+        .map(|(kind, literal)| (kind, literal.to_string()))
+        .collect();
+    assert_eq!(tokens, expected);
+    assert_eq!(l.next_token().kind, TokenKind::EOF);
+}
+
+#[test]
+fn test_module_tokens() {
+    let mut l = Lexer::new("import \"lib\" as lib; export let x = lib.y..lib.z;");
+    let tokens: Vec<(TokenKind, String)> = (0..16)
+        .map(|_| {
+            let tok = l.next_token();
+            (tok.kind, tok.literal)
+        })
+        .collect();
+    let expected = vec![
+        (TokenKind::Import, "import"),
+        (TokenKind::String, "\"lib\""),
+        (TokenKind::As, "as"),
+        (TokenKind::Ident, "lib"),
+        (TokenKind::SemiColon, ";"),
+        (TokenKind::Export, "export"),
+        (TokenKind::Let, "let"),
+        (TokenKind::Ident, "x"),
+        (TokenKind::Assign, "="),
+        (TokenKind::Ident, "lib"),
+        (TokenKind::Dot, "."),
+        (TokenKind::Ident, "y"),
+        (TokenKind::DotDot, ".."),
+        (TokenKind::Ident, "lib"),
+        (TokenKind::Dot, "."),
+        (TokenKind::Ident, "z"),
+    ];
+    let expected: Vec<(TokenKind, String)> = expected
+        .into_iter()
+        .map(|(kind, literal)| (kind, literal.to_string()))
+        .collect();
+    assert_eq!(tokens, expected);
+    assert_eq!(l.next_token().kind, TokenKind::SemiColon);
+}
@@ -0,0 +1,26 @@
+pub mod ast;
+pub mod builtins;
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod cst;
+pub mod diagnostic;
+pub mod disasm;
+pub mod engine;
+pub mod evaluator;
+pub mod formatter;
+pub mod host;
+pub mod json;
+pub mod lexer;
+pub mod limits;
+pub mod lint;
+pub mod lsp;
+pub mod mkc;
+pub mod module;
+pub mod object;
+pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod token;
+pub mod typecheck;
+pub mod vm;
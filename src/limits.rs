@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod test;
+
+use crate::object::{ErrorKind, Limit, Object, RuntimeError};
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+// 信頼できないコードを動かすときの上限。None なら制限しない
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Limits {
+    // 評価器では式の評価、VM では命令の実行を 1 ステップと数える
+    pub max_steps: Option<u64>,
+    pub max_call_depth: Option<usize>,
+    // 一度に作る文字列や配列、ハッシュのおおよその大きさ (バイト)
+    pub max_allocation: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    pub fn with_max_steps(mut self, steps: u64) -> Limits {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    pub fn with_max_call_depth(mut self, depth: usize) -> Limits {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    pub fn with_max_allocation(mut self, bytes: usize) -> Limits {
+        self.max_allocation = Some(bytes);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Limits {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+// 時計を読むのは重いので、この回数ごとにだけ確かめる
+const DEADLINE_CHECK_INTERVAL: u64 = 256;
+
+// 1 回の実行で使った量を数える
+#[derive(Debug)]
+pub struct Meter {
+    limits: Limits,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Meter {
+    // 時間はここから測り始める
+    pub fn start(limits: Limits) -> Meter {
+        Meter {
+            limits,
+            steps: 0,
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps.filter(|&max| self.steps > max) {
+            let message = format!("step limit exceeded: {} steps", max);
+            return Err(exceeded(Limit::Steps, message));
+        }
+        if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) {
+            self.check_deadline()?;
+        }
+        Ok(())
+    }
+
+    pub fn check_deadline(&self) -> Result<(), RuntimeError> {
+        match (self.deadline, self.limits.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                let message = format!("deadline exceeded: {}ms", timeout.as_millis());
+                Err(exceeded(Limit::Deadline, message))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // depth は呼び出し中の関数の数
+    pub fn check_call_depth(&self, depth: usize) -> Result<(), RuntimeError> {
+        match self.limits.max_call_depth {
+            Some(max) if depth > max => {
+                let message = format!("call depth limit exceeded: {} calls", max);
+                Err(exceeded(Limit::CallDepth, message))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_allocation(&self, bytes: usize) -> Result<(), RuntimeError> {
+        match self.limits.max_allocation {
+            Some(max) if bytes > max => {
+                let message = format!("allocation limit exceeded: {} bytes", max);
+                Err(exceeded(Limit::Allocation, message))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_object(&self, object: &Object) -> Result<(), RuntimeError> {
+        self.check_allocation(allocation_size(object))
+    }
+}
+
+fn exceeded(limit: Limit, message: String) -> RuntimeError {
+    RuntimeError::new(message).with_kind(ErrorKind::LimitExceeded(limit))
+}
+
+// 値そのものが持つ領域のおおよその大きさ。要素の先は数えない
+pub fn allocation_size(object: &Object) -> usize {
+    match object {
+        Object::String(value) => value.len(),
+        Object::Array(elements) => elements.len() * size_of::<Object>(),
+        Object::Hash(hash) => hash.len() * 2 * size_of::<Object>(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,184 @@
+use super::*;
+use crate::ast::Program;
+use crate::engine::{Engine, Interpreter};
+use crate::evaluator::THREAD_STACK_SIZE;
+use crate::lexer::Lexer;
+use crate::object::{ErrorKind, Limit};
+use crate::parser::Parser;
+use std::rc::Rc;
+use std::thread;
+
+fn parse(input: &str) -> Program {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    program
+}
+
+fn run(limits: Limits, engine: Engine, input: &str) -> Option<Object> {
+    Interpreter::new(engine)
+        .with_limits(limits)
+        .run(&parse(input))
+}
+
+const FIB: &str = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)";
+
+// どちらのエンジンでも上限ごとに別のエラーになる
+#[test]
+fn test_limits() {
+    let tests = vec![
+        (Limits::new().with_max_steps(1000), FIB, Limit::Steps),
+        (
+            Limits::new().with_max_call_depth(10),
+            "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(20)",
+            Limit::CallDepth,
+        ),
+        (
+            Limits::new().with_max_allocation(100),
+            r#"let f = fn(s) { if (len(s) > 200) { s } else { f(s + s) } }; f("ab")"#,
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_max_allocation(100),
+            "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]",
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_max_allocation(100),
+            "{1: 1, 2: 2, 3: 3, 4: 4, 5: 5, 6: 6, 7: 7, 8: 8}",
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_max_allocation(100),
+            "let f = fn(a) { if (len(a) > 20) { a } else { f(push(a, 0)) } }; f([])",
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_max_allocation(1000),
+            "let h = {}; let i = 0; while (true) { h[i] = i; i += 1 }",
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_max_allocation(1000),
+            "let f = fn() { let a = [{}]; let i = 0; while (true) { a[0][i] = i; i += 1 } }; f()",
+            Limit::Allocation,
+        ),
+        (
+            Limits::new().with_timeout(Duration::ZERO),
+            FIB,
+            Limit::Deadline,
+        ),
+    ];
+
+    for (limits, input, expected) in tests {
+        for engine in [Engine::Eval, Engine::Vm] {
+            let kind = match run(limits, engine, input) {
+                Some(Object::Error(error)) => Some(error.kind),
+                _ => None,
+            };
+            assert_eq!(
+                kind,
+                Some(ErrorKind::LimitExceeded(expected)),
+                "engine: {}, input: {}",
+                engine,
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_within_limits() {
+    let limits = Limits::new()
+        .with_max_steps(1_000_000)
+        .with_max_call_depth(20)
+        .with_max_allocation(1024)
+        .with_timeout(Duration::from_secs(60));
+    for engine in [Engine::Eval, Engine::Vm] {
+        assert_eq!(run(limits, engine, FIB), Some(Object::Integer(610)));
+    }
+}
+
+// 上限を決めなくても、ふつうの再帰はどちらのエンジンでも動き、
+// 深すぎる再帰は Rust のスタックを使い切る前にエラーになる
+#[test]
+fn test_deep_recursion() {
+    let tests = [
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(500)",
+            "500",
+        ),
+        (
+            "let sum = fn(xs) { if (len(xs) == 0) { 0 } else { first(xs) + sum(rest(xs)) } };
+            let xs = []; for (i in 0..150) { xs = push(xs, i) }; sum(xs)",
+            "11175",
+        ),
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(100000)",
+            "ERROR: stack overflow",
+        ),
+    ];
+    for (input, expected) in tests {
+        for engine in [Engine::Eval, Engine::Vm] {
+            let result = thread::Builder::new()
+                .stack_size(THREAD_STACK_SIZE)
+                .spawn(move || {
+                    let program = parse(input);
+                    let mut interpreter =
+                        Interpreter::new(engine).with_stack_size(THREAD_STACK_SIZE);
+                    interpreter.run(&program).map(|result| result.to_string())
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+            assert_eq!(
+                result.as_deref(),
+                Some(expected),
+                "engine: {}, input: {}",
+                engine,
+                input
+            );
+        }
+    }
+}
+
+// スタックの大きさを知らせずに既定の大きさのスレッドで動かしても、
+// プロセスを落とさずにエラーになる
+#[test]
+fn test_deep_recursion_on_default_stack() {
+    let tests = [
+        "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(100000)",
+        "let f = fn(n) { if (n == 0) { 0 } else { [[[[[[[[f(n - 1)]]]]]]]] } }; f(100000)",
+    ];
+    for input in tests {
+        for engine in [Engine::Eval, Engine::Vm] {
+            let result = thread::spawn(move || {
+                let program = parse(input);
+                match Interpreter::new(engine).run(&program) {
+                    Some(Object::Error(error)) => Some(error.message),
+                    _ => None,
+                }
+            })
+            .join()
+            .expect("the interpreter overflowed the thread's stack");
+            assert_eq!(
+                result.as_deref(),
+                Some("stack overflow"),
+                "engine: {}, input: {}",
+                engine,
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_allocation_size() {
+    assert_eq!(allocation_size(&Object::String("abc".into())), 3);
+    assert_eq!(allocation_size(&Object::Integer(1)), 0);
+    assert_eq!(
+        allocation_size(&Object::Array(Rc::new(vec![Object::Null; 4]))),
+        4 * size_of::<Object>()
+    );
+}
@@ -0,0 +1,403 @@
+mod config;
+#[cfg(test)]
+mod test;
+
+use crate::ast::{
+    BlockStatement, Expression, IfExpression, InfixExpression, Node, Program, Statement,
+};
+use crate::builtins;
+use crate::diagnostic::{line_column, Diagnostic, Label, Severity};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, SyntaxError};
+use crate::resolver::{BindingKind, Resolution, Resolver};
+use crate::token::{Comment, Span};
+use std::collections::{HashMap, HashSet};
+
+pub use config::{Config, Rule};
+
+// 構文エラーや未定義の名前があれば、調べずにそれを返す。結果は場所の順に並ぶ
+pub fn lint(input: &str, config: &Config) -> Result<Vec<Diagnostic>, Vec<SyntaxError>> {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    if !p.errors().is_empty() {
+        return Err(p.errors().to_vec());
+    }
+    let resolution = Resolver::new().resolve(&program);
+    if !resolution.errors.is_empty() {
+        return Err(resolution.errors);
+    }
+
+    let mut linter = Linter {
+        config,
+        found: vec![],
+        depth: 0,
+        too_deep: false,
+    };
+    linter.check_bindings(&program, &resolution);
+    linter.check_statements(&program.statements);
+
+    let suppressions = Suppressions::new(input, l.comments());
+    let mut diagnostics = suppressions.problems.clone();
+    for (rule, diagnostic) in linter.found {
+        let line = diagnostic
+            .span
+            .map_or(0, |span| line_column(input, span.start).0);
+        if !suppressions.allows(rule, line) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics.sort_by_key(|d| d.span.map(|span| span.start));
+    Ok(diagnostics)
+}
+
+struct Linter<'a> {
+    config: &'a Config,
+    found: Vec<(Rule, Diagnostic)>,
+    // 関数の中での入れ子の深さ。関数ごとに数え直す
+    depth: usize,
+    // 深すぎると知らせたブロックの中にいる
+    too_deep: bool,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, span: Span, message: String) -> Option<&mut Diagnostic> {
+        let severity = self.config.severity(rule)?;
+        let diagnostic = match severity {
+            Severity::Error => Diagnostic::error(message),
+            Severity::Warning => Diagnostic::warning(message),
+        }
+        .with_span(span)
+        .with_note(format!("lint rule {}", rule.name()));
+        self.found.push((rule, diagnostic));
+        self.found.last_mut().map(|(_, d)| d)
+    }
+
+    // 名前についての規則は、名前の解決の結果から調べる
+    fn check_bindings(&mut self, program: &Program, resolution: &Resolution) {
+        // 書き出した名前はほかのモジュールから使われる
+        let exported: HashSet<Span> = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::ExportStatement(s) => Some(s.statement.pattern.bindings()),
+                _ => None,
+            })
+            .flatten()
+            .map(|name| name.token.span)
+            .collect();
+
+        for definition in resolution.definitions() {
+            let name = &definition.name;
+            if builtins::lookup(name).is_some() {
+                let msg = format!("{} shadows the builtin function {}", name, name);
+                self.report(Rule::ShadowedBuiltin, definition.span, msg);
+            }
+
+            // _ で始まる名前は使わないことを明示している
+            let used = resolution.references_to(definition.span).count() > 1;
+            if definition.kind == BindingKind::Let
+                && !used
+                && !name.starts_with('_')
+                && !exported.contains(&definition.span)
+            {
+                let msg = format!("unused let binding {}", name);
+                if let Some(d) = self.report(Rule::UnusedLet, definition.span, msg) {
+                    d.notes
+                        .push(format!("prefix it with an underscore: _{}", name));
+                }
+            }
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Statement]) {
+        // return や break の後ろの文は、最初のものだけを知らせる
+        let exit = statements.iter().position(|stmt| {
+            matches!(
+                stmt,
+                Statement::ReturnStatement(_)
+                    | Statement::BreakStatement(_)
+                    | Statement::ContinueStatement(_)
+            )
+        });
+        if let Some(i) = exit.filter(|&i| i + 1 < statements.len()) {
+            let (exit, next) = (&statements[i], &statements[i + 1]);
+            let msg = "unreachable code".to_string();
+            if let Some(d) = self.report(Rule::UnreachableCode, next.span(), msg) {
+                d.labels.push(Label {
+                    span: exit.span(),
+                    message: format!(
+                        "any code following this {} is unreachable",
+                        exit.token_literal()
+                    ),
+                });
+            }
+        }
+
+        for stmt in statements {
+            self.check_statement(stmt);
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::LetStatement(s) => self.check_optional(s.value.as_ref()),
+            Statement::ExportStatement(s) => self.check_optional(s.statement.value.as_ref()),
+            Statement::ReturnStatement(s) => self.check_optional(s.return_value.as_ref()),
+            Statement::ExpressionStatement(s) => self.check_optional(s.expression.as_ref()),
+            Statement::BlockStatement(s) => self.check_statements(&s.statements),
+            Statement::WhileStatement(s) => {
+                self.check_optional(s.condition.as_ref());
+                self.check_nested(s.token.span, &s.body);
+            }
+            Statement::ForStatement(s) => {
+                self.check_optional(s.iterable.as_ref());
+                self.check_nested(s.token.span, &s.body);
+            }
+            Statement::ImportStatement(_)
+            | Statement::BreakStatement(_)
+            | Statement::ContinueStatement(_) => {}
+        }
+    }
+
+    fn check_optional(&mut self, exp: Option<&Expression>) {
+        if let Some(exp) = exp {
+            self.check_expression(exp);
+        }
+    }
+
+    fn check_expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::Identifier(_)
+            | Expression::IntegerLiteral(_)
+            | Expression::Boolean(_)
+            | Expression::StringLiteral(_) => {}
+            Expression::PrefixExpression(e) => self.check_optional(e.right.as_deref()),
+            Expression::InfixExpression(e) => self.check_infix(e),
+            Expression::IfExpression(e) => self.check_if(e),
+            // 関数の本体の深さは 0 から数える
+            Expression::FunctionLiteral(e) => {
+                let depth = std::mem::take(&mut self.depth);
+                let too_deep = std::mem::take(&mut self.too_deep);
+                self.check_statements(&e.body.statements);
+                self.depth = depth;
+                self.too_deep = too_deep;
+            }
+            Expression::CallExpression(e) => {
+                self.check_expression(&e.function);
+                for argument in &e.arguments {
+                    self.check_expression(argument);
+                }
+            }
+            Expression::ArrayLiteral(e) => {
+                for element in &e.elements {
+                    self.check_expression(element);
+                }
+            }
+            Expression::IndexExpression(e) => {
+                self.check_expression(&e.left);
+                self.check_expression(&e.index);
+            }
+            Expression::MemberExpression(e) => self.check_expression(&e.object),
+            Expression::HashLiteral(e) => {
+                for (key, value) in &e.pairs {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+            }
+            Expression::RangeExpression(e) => {
+                self.check_optional(e.start.as_deref());
+                self.check_optional(e.end.as_deref());
+            }
+            Expression::AssignExpression(e) => {
+                self.check_expression(&e.target);
+                self.check_optional(e.value.as_deref());
+            }
+            Expression::MatchExpression(e) => {
+                self.check_optional(e.subject.as_deref());
+                self.enter(e.token.span);
+                for arm in &e.arms {
+                    self.check_optional(arm.guard.as_ref());
+                    self.check_optional(arm.body.as_ref());
+                }
+                self.leave();
+            }
+        }
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順に調べる
+    fn check_infix(&mut self, e: &InfixExpression) {
+        let mut chain = vec![e];
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            chain.push(inner);
+            leftmost = inner.left.as_deref();
+        }
+
+        self.check_optional(leftmost);
+        for e in chain.into_iter().rev() {
+            if let (Some(left), Some(right)) = (&e.left, &e.right) {
+                let comparison =
+                    matches!(e.operator.as_str(), "==" | "!=" | "<" | ">" | "<=" | ">=");
+                if comparison && is_pure(left) && left.to_string() == right.to_string() {
+                    let always = matches!(e.operator.as_str(), "==" | "<=" | ">=");
+                    let msg = format!("both sides of {} are the same", e.operator);
+                    if let Some(d) = self.report(Rule::SelfComparison, e.token.span, msg) {
+                        d.notes.push(format!("this is always {}", always));
+                    }
+                }
+            }
+            self.check_optional(e.right.as_deref());
+        }
+    }
+
+    // else if は入れ子にせず、最初の if と同じ深さで調べる
+    fn check_if(&mut self, e: &IfExpression) {
+        if let Some(condition) = e.condition.as_deref() {
+            if is_constant(condition) {
+                let msg = format!("condition is always {}", condition.to_string());
+                self.report(Rule::ConstantCondition, condition.span(), msg);
+            }
+            self.check_expression(condition);
+        }
+        self.check_nested(e.token.span, &e.consequence);
+        match (e.else_if(), &e.alternative) {
+            (Some(next), _) => self.check_if(next),
+            (None, Some(alternative)) => self.check_nested(e.token.span, alternative),
+            (None, None) => {}
+        }
+    }
+
+    fn check_nested(&mut self, span: Span, block: &BlockStatement) {
+        self.enter(span);
+        self.check_statements(&block.statements);
+        self.leave();
+    }
+
+    // 深すぎるところは、いちばん外側の 1 か所だけを知らせる
+    fn enter(&mut self, span: Span) {
+        self.depth += 1;
+        if self.depth > self.config.max_depth && !self.too_deep {
+            let msg = format!(
+                "blocks are nested too deeply ({} levels, at most {})",
+                self.depth, self.config.max_depth
+            );
+            if self.report(Rule::DeepNesting, span, msg).is_some() {
+                self.too_deep = true;
+            }
+        }
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+        if self.depth <= self.config.max_depth {
+            self.too_deep = false;
+        }
+    }
+}
+
+// 評価しても何も起きず、何度評価しても同じ値になる式
+fn is_pure(exp: &Expression) -> bool {
+    match exp {
+        Expression::Identifier(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::Boolean(_)
+        | Expression::StringLiteral(_) => true,
+        Expression::PrefixExpression(e) => e.right.as_deref().is_some_and(is_pure),
+        Expression::InfixExpression(e) => {
+            e.left.as_deref().is_some_and(is_pure) && e.right.as_deref().is_some_and(is_pure)
+        }
+        Expression::IndexExpression(e) => is_pure(&e.left) && is_pure(&e.index),
+        Expression::MemberExpression(e) => is_pure(&e.object),
+        _ => false,
+    }
+}
+
+// 値がソースだけで決まる条件
+fn is_constant(exp: &Expression) -> bool {
+    match exp {
+        Expression::IntegerLiteral(_)
+        | Expression::Boolean(_)
+        | Expression::StringLiteral(_)
+        | Expression::FunctionLiteral(_) => true,
+        Expression::PrefixExpression(e) => e.right.as_deref().is_some_and(is_constant),
+        Expression::InfixExpression(e) => {
+            e.left.as_deref().is_some_and(is_constant)
+                && e.right.as_deref().is_some_and(is_constant)
+        }
+        _ => false,
+    }
+}
+
+// 抑制コメント。コードの後ろに書けばその行を、単独で書けば次の行を対象にする
+//
+//   let unused = 1; // lint: allow(unused-let)
+//   // lint: allow(self-comparison, constant-condition)
+//   // lint: allow-file(deep-nesting)
+struct Suppressions {
+    lines: HashMap<usize, Vec<Rule>>,
+    file: Vec<Rule>,
+    // 読めなかった抑制コメント
+    problems: Vec<Diagnostic>,
+}
+
+impl Suppressions {
+    fn new(source: &str, comments: &[Comment]) -> Suppressions {
+        let mut suppressions = Suppressions {
+            lines: HashMap::new(),
+            file: vec![],
+            problems: vec![],
+        };
+        for comment in comments {
+            let text = comment.text.trim_start_matches('/').trim();
+            let Some(directive) = text.strip_prefix("lint:") else {
+                continue;
+            };
+            let directive = directive.trim();
+            let (file, names) = if let Some(names) = directive.strip_prefix("allow-file(") {
+                (true, names)
+            } else if let Some(names) = directive.strip_prefix("allow(") {
+                (false, names)
+            } else {
+                let d = Diagnostic::warning(format!("unknown lint directive: {}", directive));
+                suppressions.problems.push(d.with_span(comment.span));
+                continue;
+            };
+            let Some(names) = names.strip_suffix(')') else {
+                let d = Diagnostic::warning("expected ) at the end of the lint directive");
+                suppressions.problems.push(d.with_span(comment.span));
+                continue;
+            };
+
+            let mut rules = vec![];
+            for name in names.split(',').map(str::trim) {
+                match Rule::from_name(name) {
+                    Some(rule) => rules.push(rule),
+                    None => {
+                        let d = Diagnostic::warning(format!("unknown lint rule: {}", name));
+                        suppressions.problems.push(d.with_span(comment.span));
+                    }
+                }
+            }
+
+            if file {
+                suppressions.file.extend(rules);
+                continue;
+            }
+            let (line, column) = line_column(source, comment.span.start);
+            let before = source.lines().nth(line - 1).unwrap_or("");
+            let alone = before.chars().take(column - 1).all(char::is_whitespace);
+            let line = if alone { line + 1 } else { line };
+            suppressions.lines.entry(line).or_default().extend(rules);
+        }
+        suppressions
+    }
+
+    fn allows(&self, rule: Rule, line: usize) -> bool {
+        self.file.contains(&rule)
+            || self
+                .lines
+                .get(&line)
+                .is_some_and(|rules| rules.contains(&rule))
+    }
+}
@@ -0,0 +1,125 @@
+use crate::diagnostic::Severity;
+use crate::json::Json;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Rule {
+    UnusedLet,
+    UnreachableCode,
+    ConstantCondition,
+    SelfComparison,
+    ShadowedBuiltin,
+    DeepNesting,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedLet,
+        Rule::UnreachableCode,
+        Rule::ConstantCondition,
+        Rule::SelfComparison,
+        Rule::ShadowedBuiltin,
+        Rule::DeepNesting,
+    ];
+
+    // 設定ファイルや抑制コメントで使う名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedLet => "unused-let",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::SelfComparison => "self-comparison",
+            Rule::ShadowedBuiltin => "shadowed-builtin",
+            Rule::DeepNesting => "deep-nesting",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+// 規則ごとの重大度。設定ファイルは JSON で、書かなかった規則は警告になる
+//
+//   {
+//     "rules": {
+//       "unused-let": "error",
+//       "self-comparison": "off",
+//       "deep-nesting": { "severity": "warning", "max-depth": 3 }
+//     }
+//   }
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Config {
+    // None は無効にした規則
+    severities: HashMap<Rule, Option<Severity>>,
+    // これより深く入れ子にしたブロックを知らせる
+    pub max_depth: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            severities: Rule::ALL
+                .into_iter()
+                .map(|rule| (rule, Some(Severity::Warning)))
+                .collect(),
+            max_depth: 4,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(input: &str) -> Result<Config, String> {
+        let json = Json::parse(input)?;
+        let mut config = Config::default();
+        let rules = match json.get("rules") {
+            None => return Ok(config),
+            Some(Json::Object(rules)) => rules,
+            Some(_) => return Err("rules must be an object".to_string()),
+        };
+
+        for (name, value) in rules {
+            let rule =
+                Rule::from_name(name).ok_or_else(|| format!("unknown lint rule: {}", name))?;
+            let severity = match value {
+                Json::Object(_) => value.get("severity").unwrap_or(&Json::Null),
+                _ => value,
+            };
+            if *severity != Json::Null {
+                config.set(rule, parse_severity(severity)?);
+            }
+
+            if let Some(depth) = value.get("max-depth") {
+                if rule != Rule::DeepNesting {
+                    return Err(format!("max-depth is not an option of {}", name));
+                }
+                config.max_depth = depth
+                    .as_i64()
+                    .filter(|&n| n > 0)
+                    .ok_or("max-depth must be a positive integer")?
+                    as usize;
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn severity(&self, rule: Rule) -> Option<Severity> {
+        self.severities.get(&rule).copied().flatten()
+    }
+
+    pub fn set(&mut self, rule: Rule, severity: Option<Severity>) {
+        self.severities.insert(rule, severity);
+    }
+}
+
+fn parse_severity(value: &Json) -> Result<Option<Severity>, String> {
+    match value.as_str() {
+        Some("off") => Ok(None),
+        Some("warning") => Ok(Some(Severity::Warning)),
+        Some("error") => Ok(Some(Severity::Error)),
+        _ => Err(format!(
+            "unknown severity: {} (expected off, warning or error)",
+            value
+        )),
+    }
+}
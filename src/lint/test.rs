@@ -0,0 +1,286 @@
+use super::*;
+
+// (重大度, メッセージ, 範囲の先頭)
+fn check(input: &str, config: &Config) -> Vec<(Severity, String, usize)> {
+    lint(input, config)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.severity, d.message, d.span.unwrap().start))
+        .collect()
+}
+
+fn warnings(input: &str) -> Vec<(String, usize)> {
+    check(input, &Config::default())
+        .into_iter()
+        .map(|(severity, message, start)| {
+            assert_eq!(severity, Severity::Warning);
+            (message, start)
+        })
+        .collect()
+}
+
+fn w(message: &str, start: usize) -> (String, usize) {
+    (message.to_string(), start)
+}
+
+#[test]
+fn test_unused_let() {
+    let tests = vec![
+        ("let x = 1; puts(x);", vec![]),
+        ("let x = 1;", vec![w("unused let binding x", 4)]),
+        (
+            "let f = fn() { let y = 2; 3 }; f()",
+            vec![w("unused let binding y", 19)],
+        ),
+        // 定義し直す前の値を使っていれば使っている
+        (
+            "let x = 1; let x = x + 1;",
+            vec![w("unused let binding x", 15)],
+        ),
+        ("let [a, b] = [1, 2]; a", vec![w("unused let binding b", 8)]),
+        // 引数や for の変数、_ で始まる名前、書き出した名前は対象にしない
+        ("fn(a) { for (i in [1]) { 1 } }", vec![]),
+        ("let _x = 1; export let y = 2;", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_unreachable_code() {
+    let tests = vec![
+        (
+            "fn() { return 1; puts(2); puts(3); }",
+            vec![w("unreachable code", 17)],
+        ),
+        (
+            "while (true) { break; puts(1); }",
+            vec![w("unreachable code", 22)],
+        ),
+        ("fn(x) { if (x) { return 1; } return 2; }", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+
+    let diagnostics = lint("fn() { return 1; 2 }", &Config::default()).unwrap();
+    assert_eq!(
+        diagnostics[0].labels,
+        vec![Label {
+            span: Span::new(7, 13),
+            message: "any code following this return is unreachable".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_constant_condition() {
+    let tests = vec![
+        ("if (true) { 1 }", vec![w("condition is always true", 4)]),
+        (
+            "if (1 < 2) { 1 } else if (\"s\") { 2 }",
+            vec![
+                w("condition is always (1 < 2)", 6),
+                w("condition is always \"s\"", 26),
+            ],
+        ),
+        ("let x = 1; if (x) { 1 }", vec![]),
+        // while (true) はよく使う書き方なので対象にしない
+        ("while (true) { break; }", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_self_comparison() {
+    let tests = vec![
+        (
+            "let x = 1; x == x",
+            vec![w("both sides of == are the same", 13)],
+        ),
+        (
+            "let a = [1]; a[0] != a[0]",
+            vec![w("both sides of != are the same", 18)],
+        ),
+        // 呼び出しは毎回違う値を返すかもしれない
+        ("let f = fn() { 1 }; f() == f()", vec![]),
+        ("let x = 1; x + x", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_shadowed_builtin() {
+    let tests = vec![
+        (
+            "let len = fn(x) { 1 }; len(2)",
+            vec![w("len shadows the builtin function len", 4)],
+        ),
+        (
+            "fn(puts) { puts }",
+            vec![w("puts shadows the builtin function puts", 3)],
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_deep_nesting() {
+    let mut config = Config::default();
+    config.max_depth = 2;
+
+    let tests = vec![
+        ("let x = 1; if (x) { while (x) { break; } }", vec![]),
+        (
+            "let x = 1; if (x) { while (x) { for (i in []) { if (x) { 1 } } } }",
+            vec![(
+                Severity::Warning,
+                "blocks are nested too deeply (3 levels, at most 2)".to_string(),
+                32,
+            )],
+        ),
+        // else if は深くしない
+        (
+            "let x = 1; if (x) { 1 } else if (x) { 2 } else if (x) { 3 }",
+            vec![],
+        ),
+        // 関数ごとに数え直す
+        (
+            "let x = 1; if (x) { if (x) { fn() { if (x) { 1 } } } }",
+            vec![],
+        ),
+        (
+            "let x = 1; if (x) { match (x) { _ => match (x) { _ => 1 } } }",
+            vec![(
+                Severity::Warning,
+                "blocks are nested too deeply (3 levels, at most 2)".to_string(),
+                37,
+            )],
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(check(input, &config), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_config() {
+    let config = Config::parse(
+        r#"{"rules": {
+            "unused-let": "error",
+            "self-comparison": "off",
+            "deep-nesting": {"max-depth": 1}
+        }}"#,
+    )
+    .unwrap();
+    assert_eq!(config.severity(Rule::UnusedLet), Some(Severity::Error));
+    assert_eq!(config.severity(Rule::SelfComparison), None);
+    assert_eq!(config.severity(Rule::DeepNesting), Some(Severity::Warning));
+    assert_eq!(config.max_depth, 1);
+
+    assert_eq!(
+        check(
+            "let x = 1; let y = 2; if (y == y) { if (y) { 1 } }",
+            &config
+        ),
+        vec![
+            (Severity::Error, "unused let binding x".to_string(), 4),
+            (
+                Severity::Warning,
+                "blocks are nested too deeply (2 levels, at most 1)".to_string(),
+                36,
+            ),
+        ]
+    );
+    assert_eq!(Config::parse("{}"), Ok(Config::default()));
+
+    let tests = vec![
+        (r#"{"rules": []}"#, "rules must be an object"),
+        (
+            r#"{"rules": {"unused": "off"}}"#,
+            "unknown lint rule: unused",
+        ),
+        (
+            r#"{"rules": {"unused-let": "fatal"}}"#,
+            "unknown severity: \"fatal\" (expected off, warning or error)",
+        ),
+        (
+            r#"{"rules": {"deep-nesting": {"max-depth": 0}}}"#,
+            "max-depth must be a positive integer",
+        ),
+        (
+            r#"{"rules": {"unused-let": {"max-depth": 2}}}"#,
+            "max-depth is not an option of unused-let",
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(Config::parse(input), Err(expected.to_string()), "{}", input);
+    }
+}
+
+#[test]
+fn test_suppressions() {
+    let tests = vec![
+        ("let x = 1; // lint: allow(unused-let)", vec![]),
+        (
+            "// lint: allow(unused-let)\nlet x = 1;\nlet y = 2;",
+            vec![w("unused let binding y", 42)],
+        ),
+        // 抑制したもの以外の規則は知らせる
+        (
+            "let len = 1; // lint: allow(unused-let)",
+            vec![w("len shadows the builtin function len", 4)],
+        ),
+        (
+            "// lint: allow-file(unused-let, shadowed-builtin)\nlet len = 1;\nlet y = 2;",
+            vec![],
+        ),
+        (
+            "let x = 1; // lint: allow(unused-let, unused)",
+            vec![w("unknown lint rule: unused", 11)],
+        ),
+        (
+            "// lint: deny(unused-let)\n1",
+            vec![w("unknown lint directive: deny(unused-let)", 0)],
+        ),
+        (
+            "// lint: allow(unused-let\n1",
+            vec![w("expected ) at the end of the lint directive", 0)],
+        ),
+        // 普通のコメントは関係ない
+        ("// lint the code\n1", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_errors() {
+    let config = Config::default();
+    let errors = lint("let x = ;", &config).unwrap_err();
+    assert_eq!(errors[0].message, "no prefix parse function for ; found");
+
+    let errors = lint("let x = y;", &config).unwrap_err();
+    assert_eq!(
+        errors,
+        vec![SyntaxError {
+            message: "identifier not found: y".to_string(),
+            span: Span::new(8, 9),
+        }]
+    );
+}
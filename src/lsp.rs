@@ -0,0 +1,451 @@
+mod document;
+#[cfg(test)]
+mod test;
+
+use crate::ast::{Expression, Statement};
+use crate::builtins::BUILTINS;
+use crate::compiler::SymbolScope;
+use crate::formatter;
+use crate::json::Json;
+use crate::lsp::document::Document;
+use crate::parser::SyntaxError;
+use crate::resolver::{BindingKind, Reference};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+
+const KEYWORDS: &[&str] = &[
+    "let", "fn", "true", "false", "if", "else", "return", "while", "break", "continue", "for",
+    "in", "match", "import", "export", "as",
+];
+
+// JSON-RPC のエラーコード
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// LSP の定数
+const SEVERITY_ERROR: i64 = 1;
+const SEVERITY_WARNING: i64 = 2;
+const SYMBOL_MODULE: i64 = 2;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_MODULE: i64 = 9;
+const COMPLETION_KEYWORD: i64 = 14;
+
+// 標準入出力で Language Server Protocol を話す。文書は毎回すべてを受け取る。
+// exit を受け取るか入力が終わるまで続け、その前に shutdown を受け取っていれば true を返す
+pub fn serve(input: impl Read, mut output: impl Write) -> bool {
+    let mut reader = BufReader::new(input);
+    let mut server = Server::default();
+
+    loop {
+        let message = match read_message(&mut reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => return server.shut_down,
+            Err(e) => {
+                eprintln!("lsp: {}", e);
+                return false;
+            }
+        };
+        let message = match Json::parse(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                let response = error_response(Json::Null, PARSE_ERROR, e);
+                write_message(&mut output, &response);
+                continue;
+            }
+        };
+
+        if message.get("method").and_then(Json::as_str) == Some("exit") {
+            return server.shut_down;
+        }
+        if let Some(out) = server.handle(&message) {
+            write_message(&mut output, &out);
+        }
+    }
+}
+
+// Content-Length のヘッダに続く本体を読む。入力が終わっていれば None
+fn read_message(reader: &mut impl BufRead) -> Result<Option<String>, String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                let value = value.trim();
+                length = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid Content-Length: {}", value))?,
+                );
+            }
+        }
+    }
+
+    let length = length.ok_or("missing Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| "message is not valid UTF-8".to_string())
+}
+
+fn write_message(output: &mut impl Write, message: &Json) {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| output.flush())
+        .expect("failed to write message");
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        ("result", result),
+    ])
+}
+
+fn error_response(id: Json, code: i64, message: impl ToString) -> Json {
+    let error = Json::object(vec![
+        ("code", code.into()),
+        ("message", message.to_string().into()),
+    ]);
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        ("error", error),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn get<'a>(json: &'a Json, path: &[&str]) -> Option<&'a Json> {
+    path.iter().try_fold(json, |json, key| json.get(key))
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    // 送り返すメッセージを返す。通知には応答しない
+    fn handle(&mut self, message: &Json) -> Option<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let Some(id) = message.get("id").cloned() else {
+            return self.notify(method, params);
+        };
+
+        Some(match self.request(method, params) {
+            Ok(result) => response(id, result),
+            Err((code, message)) => error_response(id, code, message),
+        })
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        if self.shut_down {
+            return Err((INVALID_REQUEST, "server is shut down".to_string()));
+        }
+        match method {
+            "initialize" => return Ok(capabilities()),
+            "shutdown" => {
+                self.shut_down = true;
+                return Ok(Json::Null);
+            }
+            _ => {}
+        }
+
+        // ほかは開いている文書についての要求
+        let uri = get(params, &["textDocument", "uri"]).and_then(Json::as_str);
+        let document = uri.and_then(|uri| self.documents.get(uri));
+        let (Some(uri), Some(document)) = (uri, document) else {
+            if method.starts_with("textDocument/") {
+                return Err((INVALID_PARAMS, "unknown document".to_string()));
+            }
+            return Err((METHOD_NOT_FOUND, format!("method not found: {}", method)));
+        };
+        let offset = || {
+            let position = params.get("position").unwrap_or(&Json::Null);
+            document
+                .offset(position)
+                .ok_or((INVALID_PARAMS, "invalid position".to_string()))
+        };
+        match method {
+            "textDocument/hover" => Ok(hover(document, offset()?)),
+            "textDocument/definition" => Ok(definition(document, uri, offset()?)),
+            "textDocument/references" => {
+                let declaration = get(params, &["context", "includeDeclaration"])
+                    .and_then(Json::as_bool)
+                    .unwrap_or(true);
+                Ok(references(document, uri, offset()?, declaration))
+            }
+            "textDocument/documentSymbol" => Ok(document_symbols(document)),
+            "textDocument/completion" => Ok(completion(document, offset()?)),
+            "textDocument/formatting" => Ok(formatting(document)),
+            _ => Err((METHOD_NOT_FOUND, format!("method not found: {}", method))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let uri = get(params, &["textDocument", "uri"]).and_then(Json::as_str)?;
+        let text = match method {
+            "textDocument/didOpen" => get(params, &["textDocument", "text"]),
+            // 文書全体を受け取るので、最後の変更だけを見ればよい
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .and_then(Json::as_array)
+                .and_then(|changes| changes.last())
+                .and_then(|change| change.get("text")),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                let params = Json::object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", Json::Array(vec![])),
+                ]);
+                return Some(notification("textDocument/publishDiagnostics", params));
+            }
+            _ => None,
+        }?;
+
+        let document = Document::new(text.as_str()?.to_string());
+        let params = Json::object(vec![
+            ("uri", uri.into()),
+            ("diagnostics", diagnostics(&document)),
+        ]);
+        self.documents.insert(uri.to_string(), document);
+        Some(notification("textDocument/publishDiagnostics", params))
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                ("textDocumentSync", 1i64.into()),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+                ("completionProvider", Json::object(vec![])),
+                ("documentFormattingProvider", true.into()),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![
+                ("name", "monkey-rust".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+// 構文エラーがあれば、名前の解決は読めたところまでの結果なので知らせない
+fn diagnostics(document: &Document) -> Json {
+    let resolution = &document.resolution;
+    let (errors, warnings): (Vec<&SyntaxError>, Vec<&SyntaxError>) = if document.errors.is_empty() {
+        let warnings = document.warnings.iter().chain(&resolution.warnings);
+        (resolution.errors.iter().collect(), warnings.collect())
+    } else {
+        (
+            document.errors.iter().collect(),
+            document.warnings.iter().collect(),
+        )
+    };
+
+    let diagnostic = |error: &SyntaxError, severity: i64| {
+        Json::object(vec![
+            ("range", document.range(error.span)),
+            ("severity", severity.into()),
+            ("source", "monkey-rust".into()),
+            ("message", error.message.as_str().into()),
+        ])
+    };
+    let errors = errors.into_iter().map(|e| diagnostic(e, SEVERITY_ERROR));
+    let warnings = warnings
+        .into_iter()
+        .map(|e| diagnostic(e, SEVERITY_WARNING));
+    Json::Array(errors.chain(warnings).collect())
+}
+
+fn hover(document: &Document, offset: usize) -> Json {
+    let Some(reference) = document.reference_at(offset) else {
+        return Json::Null;
+    };
+    let mut value = format!(
+        "```monkey\n{}\n```\n{}",
+        reference.name,
+        describe(reference)
+    );
+    if let Some(definition) = reference.definition {
+        let line = document
+            .position(definition.start)
+            .get("line")
+            .and_then(Json::as_i64);
+        value += &format!("\n\ndefined on line {}", line.unwrap_or_default() + 1);
+    }
+    Json::object(vec![
+        (
+            "contents",
+            Json::object(vec![("kind", "markdown".into()), ("value", value.into())]),
+        ),
+        ("range", document.range(reference.span)),
+    ])
+}
+
+fn describe(reference: &Reference) -> String {
+    let kind = match reference.kind {
+        BindingKind::Let => "let binding",
+        BindingKind::Parameter => "parameter",
+        BindingKind::For => "for loop variable",
+        BindingKind::Match => "match binding",
+        BindingKind::Import => "imported module",
+        BindingKind::Builtin => return "builtin function".to_string(),
+    };
+    match reference.scope {
+        SymbolScope::Global => format!("global {}", kind),
+        SymbolScope::Local => format!("local {}", kind),
+        SymbolScope::Free => format!("{} captured from an outer function", kind),
+        SymbolScope::Function => "the function being defined".to_string(),
+        SymbolScope::Builtin => "builtin function".to_string(),
+    }
+}
+
+fn location(document: &Document, uri: &str, reference: &Reference) -> Json {
+    Json::object(vec![
+        ("uri", uri.into()),
+        ("range", document.range(reference.span)),
+    ])
+}
+
+fn definition(document: &Document, uri: &str, offset: usize) -> Json {
+    let definition = document
+        .reference_at(offset)
+        .and_then(|reference| reference.definition);
+    let resolution = &document.resolution;
+    match definition.and_then(|span| resolution.definitions().find(|d| d.span == span)) {
+        Some(definition) => location(document, uri, definition),
+        None => Json::Null,
+    }
+}
+
+fn references(document: &Document, uri: &str, offset: usize, declaration: bool) -> Json {
+    let Some(definition) = document.reference_at(offset).and_then(|r| r.definition) else {
+        return Json::Null;
+    };
+    Json::Array(
+        document
+            .resolution
+            .references_to(definition)
+            .filter(|r| declaration || !r.is_definition())
+            .map(|r| location(document, uri, r))
+            .collect(),
+    )
+}
+
+// let で束縛した名前と読み込んだモジュール。関数の中の let は子にする
+fn document_symbols(document: &Document) -> Json {
+    fn symbols(document: &Document, statements: &[Statement]) -> Vec<Json> {
+        let mut out = vec![];
+        for stmt in statements {
+            let let_statement = match stmt {
+                Statement::LetStatement(s) => s,
+                Statement::ExportStatement(s) => &s.statement,
+                Statement::ImportStatement(s) => {
+                    let range = document.range(s.alias.token.span);
+                    out.push(Json::object(vec![
+                        ("name", s.alias.value.as_str().into()),
+                        ("detail", s.path.as_str().into()),
+                        ("kind", SYMBOL_MODULE.into()),
+                        ("range", range.clone()),
+                        ("selectionRange", range),
+                    ]));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let function = match &let_statement.value {
+                Some(Expression::FunctionLiteral(f)) => Some(f),
+                _ => None,
+            };
+            for name in let_statement.pattern.bindings() {
+                let range = document.range(name.token.span);
+                let (kind, children) = match function {
+                    Some(f) if let_statement.pattern.as_identifier().is_some() => {
+                        (SYMBOL_FUNCTION, symbols(document, &f.body.statements))
+                    }
+                    _ => (SYMBOL_VARIABLE, vec![]),
+                };
+                out.push(Json::object(vec![
+                    ("name", name.value.as_str().into()),
+                    ("kind", kind.into()),
+                    ("range", range.clone()),
+                    ("selectionRange", range),
+                    ("children", Json::Array(children)),
+                ]));
+            }
+        }
+        out
+    }
+
+    Json::Array(symbols(document, &document.program.statements))
+}
+
+// キーワードと組み込み関数に加えて、カーソルの位置から見える名前を候補にする
+fn completion(document: &Document, offset: usize) -> Json {
+    let item =
+        |label: &str, kind: i64| Json::object(vec![("label", label.into()), ("kind", kind.into())]);
+    let mut seen = HashSet::new();
+    let mut items = vec![];
+    for definition in document.resolution.definitions() {
+        if document.in_scope(definition.span, offset) && seen.insert(definition.name.as_str()) {
+            let kind = match definition.kind {
+                BindingKind::Import => COMPLETION_MODULE,
+                _ => COMPLETION_VARIABLE,
+            };
+            items.push(item(&definition.name, kind));
+        }
+    }
+    for builtin in BUILTINS {
+        if seen.insert(builtin.name) {
+            items.push(item(builtin.name, COMPLETION_FUNCTION));
+        }
+    }
+    items.extend(KEYWORDS.iter().map(|k| item(k, COMPLETION_KEYWORD)));
+
+    Json::object(vec![
+        ("isIncomplete", false.into()),
+        ("items", Json::Array(items)),
+    ])
+}
+
+// 構文エラーがあれば整形しない
+fn formatting(document: &Document) -> Json {
+    match formatter::format(&document.text) {
+        Ok(formatted) if formatted != document.text => Json::Array(vec![Json::object(vec![
+            ("range", document.whole_range()),
+            ("newText", formatted.into()),
+        ])]),
+        _ => Json::Array(vec![]),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::ast::Program;
+use crate::cst::{self, NodeKind, SyntaxNode};
+use crate::json::Json;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, SyntaxError};
+use crate::resolver::{Reference, Resolution, Resolver};
+use crate::token::Span;
+
+// 開いている文書と、それを解析した結果。変更のたびに作り直す
+pub struct Document {
+    pub text: String,
+    chars: Vec<char>,
+    // 各行の先頭の文字位置
+    lines: Vec<usize>,
+    pub program: Program,
+    pub errors: Vec<SyntaxError>,
+    pub warnings: Vec<SyntaxError>,
+    // 構文エラーがあっても、読めたところまでで解決する
+    pub resolution: Resolution,
+    // 関数リテラルの全体の範囲。構文木には先頭の位置しかないので、具象構文木から求める
+    functions: Vec<Span>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Document {
+        let mut l = Lexer::new(&text);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        let errors = p.errors().to_vec();
+        let warnings = p.warnings().to_vec();
+        let resolution = Resolver::new().resolve(&program);
+        let mut functions = vec![];
+        collect_functions(&cst::parse(&text).root, &mut functions);
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut lines = vec![0];
+        lines.extend(
+            chars
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| c == '\n')
+                .map(|(i, _)| i + 1),
+        );
+        Document {
+            text,
+            chars,
+            lines,
+            program,
+            errors,
+            warnings,
+            resolution,
+            functions,
+        }
+    }
+
+    // LSP の位置は 0 始まりの行と、行頭からの UTF-16 の単位で数える
+    pub fn position(&self, offset: usize) -> Json {
+        let offset = offset.min(self.chars.len());
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        let character: usize = self.chars[self.lines[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    }
+
+    // 行末を越える位置は行末にする
+    pub fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position.get("line")?.as_i64()? as usize;
+        let character = position.get("character")?.as_i64()? as usize;
+        let mut offset = *self.lines.get(line)?;
+        let mut units = 0;
+        while let Some(&c) = self.chars.get(offset) {
+            if c == '\n' || units >= character {
+                break;
+            }
+            units += c.len_utf16();
+            offset += 1;
+        }
+        Some(offset)
+    }
+
+    pub fn range(&self, span: Span) -> Json {
+        Json::object(vec![
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+
+    pub fn whole_range(&self) -> Json {
+        self.range(Span::new(0, self.chars.len()))
+    }
+
+    // definition で束縛した名前が offset の位置から見えるか。
+    // 束縛したあとで、束縛したいちばん内側の関数の中にあれば見える
+    pub fn in_scope(&self, definition: Span, offset: usize) -> bool {
+        let contains =
+            |outer: &Span, inner: Span| outer.start <= inner.start && inner.end <= outer.end;
+        let function = self
+            .functions
+            .iter()
+            .filter(|f| contains(f, definition))
+            .min_by_key(|f| f.end - f.start);
+        definition.end <= offset && function.is_none_or(|f| contains(f, Span::new(offset, offset)))
+    }
+
+    // カーソルが名前の直後にあっても、その名前を指しているとみなす
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.resolution.at(offset).or_else(|| {
+            let before = offset.checked_sub(1)?;
+            self.resolution.at(before)
+        })
+    }
+}
+
+fn collect_functions(node: &SyntaxNode, functions: &mut Vec<Span>) {
+    if node.kind == NodeKind::FunctionLiteral {
+        functions.extend(node.span());
+    }
+    for child in node.nodes() {
+        collect_functions(child, functions);
+    }
+}
@@ -0,0 +1,487 @@
+use super::*;
+
+// 要求や通知を書き溜めてサーバーに渡し、返ってきたメッセージを読む
+#[derive(Default)]
+struct Client {
+    input: Vec<u8>,
+    next_id: i64,
+}
+
+impl Client {
+    fn send(&mut self, message: Json) {
+        write_message(&mut self.input, &message);
+    }
+
+    fn request(&mut self, method: &str, params: Json) -> i64 {
+        self.next_id += 1;
+        self.send(Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", self.next_id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]));
+        self.next_id
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(notification(method, params));
+    }
+
+    fn open(&mut self, uri: &str, text: &str) {
+        let document = Json::object(vec![
+            ("uri", uri.into()),
+            ("languageId", "monkey".into()),
+            ("version", 1i64.into()),
+            ("text", text.into()),
+        ]);
+        self.notify(
+            "textDocument/didOpen",
+            Json::object(vec![("textDocument", document)]),
+        );
+    }
+
+    // 文書の中の位置についての要求
+    fn at(&mut self, method: &str, uri: &str, line: i64, character: i64) -> i64 {
+        self.request(method, position_params(uri, line, character))
+    }
+
+    // サーバーを最後まで動かし、終了の状態と受け取ったメッセージを返す
+    fn run(self) -> (bool, Vec<Json>) {
+        let mut output = vec![];
+        let clean = serve(self.input.as_slice(), &mut output);
+        let mut reader = output.as_slice();
+        let mut messages = vec![];
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(Json::parse(&message).unwrap());
+        }
+        (clean, messages)
+    }
+}
+
+fn document(uri: &str) -> Json {
+    Json::object(vec![(
+        "textDocument",
+        Json::object(vec![("uri", uri.into())]),
+    )])
+}
+
+fn position_params(uri: &str, line: i64, character: i64) -> Json {
+    let position = Json::object(vec![("line", line.into()), ("character", character.into())]);
+    Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", uri.into())])),
+        ("position", position),
+    ])
+}
+
+fn result(messages: &[Json], id: i64) -> &Json {
+    let response = messages
+        .iter()
+        .find(|m| m.get("id") == Some(&Json::Int(id)))
+        .unwrap_or_else(|| panic!("no response to {}", id));
+    response
+        .get("result")
+        .unwrap_or_else(|| panic!("error response: {}", response))
+}
+
+// (開始行, 開始列, 終了行, 終了列)
+type Range = (i64, i64, i64, i64);
+
+fn range(json: &Json) -> Range {
+    let n = |path: &[&str]| get(json, path).and_then(Json::as_i64).unwrap();
+    (
+        n(&["start", "line"]),
+        n(&["start", "character"]),
+        n(&["end", "line"]),
+        n(&["end", "character"]),
+    )
+}
+
+// 知らせた順に、文書ごとの (重大度, メッセージ, 範囲)
+fn diagnostics(messages: &[Json]) -> Vec<Vec<(i64, String, Range)>> {
+    messages
+        .iter()
+        .filter(|m| {
+            m.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics")
+        })
+        .map(|m| {
+            let diagnostics = get(m, &["params", "diagnostics"]).and_then(Json::as_array);
+            diagnostics
+                .unwrap()
+                .iter()
+                .map(|d| {
+                    (
+                        d.get("severity").and_then(Json::as_i64).unwrap(),
+                        d.get("message").and_then(Json::as_str).unwrap().to_string(),
+                        range(d.get("range").unwrap()),
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+const URI: &str = "file:///main.mk";
+
+const SOURCE: &str = "let x = 1;
+let add = fn(a, b) { a + b + x };
+add(x, 2)";
+
+#[test]
+fn test_lifecycle() {
+    let mut client = Client::default();
+    let initialize = client.request("initialize", Json::object(vec![]));
+    client.notify("initialized", Json::object(vec![]));
+    let unknown = client.request("workspace/unknown", Json::Null);
+    let shutdown = client.request("shutdown", Json::Null);
+    let after = client.request("textDocument/hover", position_params(URI, 0, 0));
+    client.notify("exit", Json::Null);
+    // exit の後ろは読まない
+    client.request("shutdown", Json::Null);
+
+    let (clean, messages) = client.run();
+    assert!(clean);
+    assert_eq!(messages.len(), 4);
+    let capabilities = result(&messages, initialize).get("capabilities").unwrap();
+    for provider in [
+        "hoverProvider",
+        "definitionProvider",
+        "referencesProvider",
+        "documentSymbolProvider",
+        "documentFormattingProvider",
+    ] {
+        assert_eq!(
+            capabilities.get(provider),
+            Some(&Json::Bool(true)),
+            "{}",
+            provider
+        );
+    }
+    assert_eq!(capabilities.get("textDocumentSync"), Some(&Json::Int(1)));
+    assert_eq!(result(&messages, shutdown), &Json::Null);
+
+    let error = |id: i64| {
+        let response = messages
+            .iter()
+            .find(|m| m.get("id") == Some(&Json::Int(id)));
+        let error = response.and_then(|r| r.get("error")).unwrap();
+        (
+            error.get("code").and_then(Json::as_i64).unwrap(),
+            error
+                .get("message")
+                .and_then(Json::as_str)
+                .unwrap()
+                .to_string(),
+        )
+    };
+    assert_eq!(
+        error(unknown),
+        (
+            METHOD_NOT_FOUND,
+            "method not found: workspace/unknown".to_string()
+        )
+    );
+    assert_eq!(
+        error(after),
+        (INVALID_REQUEST, "server is shut down".to_string())
+    );
+
+    // shutdown の前に終わるのは異常な終了
+    let mut client = Client::default();
+    client.request("initialize", Json::object(vec![]));
+    client.notify("exit", Json::Null);
+    assert!(!client.run().0);
+
+    // 壊れたメッセージにはエラーを返して続ける
+    let mut client = Client::default();
+    client.input.extend(b"Content-Length: 5\r\n\r\n{oops");
+    let shutdown = client.request("shutdown", Json::Null);
+    let (_, messages) = client.run();
+    assert_eq!(
+        get(&messages[0], &["error", "code"]),
+        Some(&Json::Int(PARSE_ERROR))
+    );
+    assert_eq!(result(&messages, shutdown), &Json::Null);
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut client = Client::default();
+    client.open(URI, SOURCE);
+    client.open("file:///broken.mk", "let x = ;\nlet = 2;");
+    // 構文が正しければ、名前の解決の結果を知らせる
+    client.open(
+        "file:///names.mk",
+        "let x = 1;\nfn(x) { y }\nmatch (x) { _ => 1, 2 => 3 }",
+    );
+    let change = Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", URI.into())])),
+        (
+            "contentChanges",
+            Json::Array(vec![Json::object(vec![("text", "if (x {".into())])]),
+        ),
+    ]);
+    client.notify("textDocument/didChange", change);
+    client.notify("textDocument/didClose", document(URI));
+
+    let (_, messages) = client.run();
+    assert_eq!(
+        diagnostics(&messages),
+        vec![
+            vec![],
+            vec![
+                (
+                    1,
+                    "no prefix parse function for ; found".to_string(),
+                    (0, 8, 0, 9)
+                ),
+                (
+                    1,
+                    "expected next token to be IDENT, got = instead".to_string(),
+                    (1, 4, 1, 5)
+                ),
+                (
+                    1,
+                    "no prefix parse function for = found".to_string(),
+                    (1, 4, 1, 5)
+                ),
+            ],
+            vec![
+                (1, "identifier not found: y".to_string(), (1, 8, 1, 9)),
+                (2, "unreachable match arm".to_string(), (2, 20, 2, 21)),
+                (
+                    2,
+                    "x shadows a binding from an outer scope".to_string(),
+                    (1, 3, 1, 4)
+                ),
+            ],
+            vec![
+                (
+                    1,
+                    "expected next token to be ), got { instead".to_string(),
+                    (0, 6, 0, 7)
+                ),
+                (
+                    1,
+                    "no prefix parse function for EOF found".to_string(),
+                    (0, 7, 0, 7)
+                ),
+                (
+                    1,
+                    "expected next token to be :, got EOF instead".to_string(),
+                    (0, 7, 0, 7)
+                ),
+            ],
+            vec![],
+        ]
+    );
+}
+
+#[test]
+fn test_navigation() {
+    let mut client = Client::default();
+    client.open(URI, SOURCE);
+    let hover_free = client.at("textDocument/hover", URI, 1, 29);
+    let hover_param = client.at("textDocument/hover", URI, 1, 22);
+    let hover_builtin = client.at("textDocument/hover", URI, 0, 3);
+    let definition = client.at("textDocument/definition", URI, 2, 5);
+    let nothing = client.at("textDocument/definition", URI, 0, 8);
+    let mut params = position_params(URI, 0, 4);
+    if let Json::Object(fields) = &mut params {
+        let context = Json::object(vec![("includeDeclaration", false.into())]);
+        fields.push(("context".to_string(), context));
+    }
+    let references = client.request("textDocument/references", params);
+    let all_references = client.at("textDocument/references", URI, 1, 13);
+
+    let (_, messages) = client.run();
+    let hover = |id| {
+        get(result(&messages, id), &["contents", "value"])
+            .and_then(Json::as_str)
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        hover(hover_free),
+        "```monkey\nx\n```\nglobal let binding\n\ndefined on line 1"
+    );
+    assert_eq!(
+        hover(hover_param),
+        "```monkey\na\n```\nlocal parameter\n\ndefined on line 2"
+    );
+    assert_eq!(result(&messages, hover_builtin), &Json::Null);
+    assert_eq!(
+        range(result(&messages, hover_free).get("range").unwrap()),
+        (1, 29, 1, 30)
+    );
+
+    // カーソルが名前の直後にあっても名前を指す
+    let location = result(&messages, definition);
+    assert_eq!(location.get("uri").and_then(Json::as_str), Some(URI));
+    assert_eq!(range(location.get("range").unwrap()), (0, 4, 0, 5));
+    assert_eq!(result(&messages, nothing), &Json::Null);
+
+    let ranges = |id| -> Vec<_> {
+        result(&messages, id)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| range(l.get("range").unwrap()))
+            .collect()
+    };
+    assert_eq!(ranges(references), vec![(1, 29, 1, 30), (2, 4, 2, 5)]);
+    assert_eq!(ranges(all_references), vec![(1, 13, 1, 14), (1, 21, 1, 22)]);
+}
+
+#[test]
+fn test_hover_descriptions() {
+    let source = "import \"lib\" as m;\nlet f = fn(n) { let g = fn() { n + f() }; len(g) }";
+    let mut client = Client::default();
+    client.open(URI, source);
+    let ids: Vec<i64> = [(1, 31), (1, 35), (1, 42), (1, 5)]
+        .into_iter()
+        .map(|(line, character)| client.at("textDocument/hover", URI, line, character))
+        .collect();
+    let module = client.at("textDocument/hover", URI, 0, 16);
+
+    let (_, messages) = client.run();
+    let hover = |id| {
+        let value = get(result(&messages, id), &["contents", "value"]).and_then(Json::as_str);
+        value.unwrap().lines().nth(3).unwrap_or("").to_string()
+    };
+    assert_eq!(hover(ids[0]), "parameter captured from an outer function");
+    assert_eq!(hover(ids[1]), "let binding captured from an outer function");
+    assert_eq!(hover(ids[2]), "builtin function");
+    assert_eq!(hover(ids[3]), "global let binding");
+    assert_eq!(hover(module), "global imported module");
+}
+
+#[test]
+fn test_document_symbols() {
+    let source = "import \"lib\" as m;
+let x = 1;
+export let f = fn(a) { let inner = 2; let g = fn() { 3 }; g };
+let [p, q] = [1, 2];";
+    let mut client = Client::default();
+    client.open(URI, source);
+    let symbols = client.request("textDocument/documentSymbol", document(URI));
+
+    let (_, messages) = client.run();
+    fn names(symbols: &Json) -> Vec<String> {
+        symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                let name = s.get("name").and_then(Json::as_str).unwrap();
+                let kind = s.get("kind").and_then(Json::as_i64).unwrap();
+                match s.get("children").and_then(Json::as_array) {
+                    Some(children) if !children.is_empty() => {
+                        let children = names(&Json::Array(children.clone()));
+                        format!("{}:{}({})", name, kind, children.join(" "))
+                    }
+                    _ => format!("{}:{}", name, kind),
+                }
+            })
+            .collect()
+    }
+    let symbols = result(&messages, symbols);
+    assert_eq!(
+        names(symbols),
+        vec!["m:2", "x:13", "f:12(inner:13 g:12)", "p:13", "q:13"]
+    );
+    let f = &symbols.as_array().unwrap()[2];
+    assert_eq!(range(f.get("selectionRange").unwrap()), (2, 11, 2, 12));
+}
+
+#[test]
+fn test_completion() {
+    let source = "let x = 1;
+let f = fn(a) { let b = 2;  };
+let g = fn(c) {  };
+";
+    let mut client = Client::default();
+    client.open(URI, source);
+    let in_f = client.at("textDocument/completion", URI, 1, 27);
+    let in_g = client.at("textDocument/completion", URI, 2, 16);
+    let top = client.at("textDocument/completion", URI, 3, 0);
+
+    let (_, messages) = client.run();
+    let labels = |id| -> Vec<String> {
+        let items = result(&messages, id).get("items").and_then(Json::as_array);
+        items
+            .unwrap()
+            .iter()
+            .filter(|item| item.get("kind") != Some(&Json::Int(COMPLETION_KEYWORD)))
+            .filter(|item| item.get("kind") != Some(&Json::Int(COMPLETION_FUNCTION)))
+            .map(|item| {
+                item.get("label")
+                    .and_then(Json::as_str)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+    // 束縛した後の名前だけが見える。ほかの関数の局所変数は見えない。
+    // let で束縛する関数は本体から自分を参照できる
+    assert_eq!(labels(in_f), vec!["x", "a", "b", "f"]);
+    assert_eq!(labels(in_g), vec!["x", "f", "c", "g"]);
+    assert_eq!(labels(top), vec!["x", "f", "g"]);
+
+    let items = result(&messages, top).get("items").and_then(Json::as_array);
+    let all: Vec<_> = items
+        .unwrap()
+        .iter()
+        .map(|item| item.get("label").and_then(Json::as_str).unwrap())
+        .collect();
+    assert!(all.contains(&"len"));
+    assert!(all.contains(&"match"));
+}
+
+#[test]
+fn test_formatting() {
+    let mut client = Client::default();
+    client.open(URI, "let x=1;\nlet y = fn(a){a+x};");
+    client.open("file:///formatted.mk", "let x = 1;\n");
+    client.open("file:///broken.mk", "let x = ;");
+    let edits = client.request("textDocument/formatting", document(URI));
+    let formatted = client.request("textDocument/formatting", document("file:///formatted.mk"));
+    let broken = client.request("textDocument/formatting", document("file:///broken.mk"));
+    let unknown = client.request("textDocument/formatting", document("file:///unknown.mk"));
+
+    let (_, messages) = client.run();
+    let edits = result(&messages, edits).as_array().unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(range(edits[0].get("range").unwrap()), (0, 0, 1, 19));
+    assert_eq!(
+        edits[0].get("newText").and_then(Json::as_str),
+        Some("let x = 1;\nlet y = fn(a) {\n    a + x\n};\n")
+    );
+    assert_eq!(result(&messages, formatted), &Json::Array(vec![]));
+    assert_eq!(result(&messages, broken), &Json::Array(vec![]));
+    let unknown = messages
+        .iter()
+        .find(|m| m.get("id") == Some(&Json::Int(unknown)));
+    assert_eq!(
+        unknown.and_then(|m| get(m, &["error", "code"])),
+        Some(&Json::Int(INVALID_PARAMS))
+    );
+}
+
+#[test]
+fn test_positions() {
+    // 位置は UTF-16 の単位で数える
+    let document = Document::new("let s = \"😀\"; let t = s;\nt".to_string());
+    let position = |line: i64, character: i64| {
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    };
+    assert_eq!(document.offset(&position(0, 18)), Some(17));
+    assert_eq!(document.position(17), position(0, 18));
+    assert_eq!(document.offset(&position(1, 0)), Some(24));
+    // 行末を越えた位置は行末にする
+    assert_eq!(document.offset(&position(0, 100)), Some(23));
+    assert_eq!(document.offset(&position(5, 0)), None);
+    assert_eq!(
+        document.reference_at(17).map(|r| r.name.as_str()),
+        Some("t")
+    );
+}
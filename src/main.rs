@@ -1,11 +1,35 @@
-use std::io;
+use monkey_rust::cli;
+use monkey_rust::evaluator::THREAD_STACK_SIZE;
+use std::env;
+use std::process::ExitCode;
+use std::thread;
 
-mod ast;
-mod lexer;
-mod parser;
-mod repl;
-mod token;
+// 評価器の深い再帰に足りるスタックを持つスレッドで動かす
+fn main() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(THREAD_STACK_SIZE)
+        .spawn(run)
+        .expect("failed to spawn the main thread")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn run() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-fn main() {
-    repl::start(io::stdin(), io::stdout());
+    match args.first().map(String::as_str) {
+        None | Some("--engine") => cli::repl(&args),
+        Some("fmt") => cli::fmt(&args[1..]),
+        Some("parse") => cli::parse(&args[1..]),
+        Some("run") => cli::run(&args[1..]),
+        Some("disasm") => cli::disasm(&args[1..]),
+        Some("build") => cli::build(&args[1..]),
+        Some("lint") => cli::lint(&args[1..]),
+        Some("check") => cli::check(&args[1..]),
+        Some("lsp") => cli::lsp(&args[1..]),
+        Some(command) => {
+            eprintln!("unknown command: {}", command);
+            ExitCode::from(2)
+        }
+    }
 }
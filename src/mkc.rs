@@ -0,0 +1,386 @@
+#[cfg(test)]
+mod test;
+
+use crate::builtins::BUILTINS;
+use crate::code::{read_u16, Instructions, Opcode, Positions};
+use crate::compiler::Bytecode;
+use crate::object::{CompiledFunction, Object};
+use crate::token::Span;
+use std::rc::Rc;
+
+// .mkc ファイルの構成。数値はすべてビッグエンディアン
+//
+//   magic      "MKC\0"
+//   version    u16
+//   flags      u8   (FLAG_DEBUG | FLAG_RESULT)
+//   constants  u32 個数, 定数...
+//   main       code
+//
+//   定数       u8 タグ。TAG_INTEGER なら i64、TAG_FUNCTION なら
+//              u16 局所変数の数, u8 引数の数, code。FLAG_DEBUG があれば続けて
+//              u32 長さ, 関数の名前 (名前がなければ長さ 0)、
+//              u32 長さ, 定義したモジュールの名前 (メインのプログラムなら長さ 0)。
+//              TAG_STRING なら u32 長さ, UTF-8 のバイト列
+//   code       u32 長さ, 命令列。FLAG_DEBUG があれば続けて
+//              u32 個数, (u32 命令の位置, u32 開始, u32 終了)...
+pub const MAGIC: &[u8; 4] = b"MKC\0";
+pub const VERSION: u16 = 5;
+
+const FLAG_DEBUG: u8 = 1 << 0;
+const FLAG_RESULT: u8 = 1 << 1;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_FUNCTION: u8 = 1;
+const TAG_STRING: u8 = 2;
+
+pub struct BytecodeFile {
+    pub bytecode: Bytecode,
+    // 最後の文が式で、その値をプログラムの結果として表示するか
+    pub has_result: bool,
+}
+
+pub fn encode(file: &BytecodeFile, debug: bool) -> Result<Vec<u8>, String> {
+    let mut w = Writer { out: vec![], debug };
+
+    w.out.extend_from_slice(MAGIC);
+    w.u16(VERSION);
+    let mut flags = 0;
+    if debug {
+        flags |= FLAG_DEBUG;
+    }
+    if file.has_result {
+        flags |= FLAG_RESULT;
+    }
+    w.u8(flags);
+
+    let bytecode = &file.bytecode;
+    w.u32(bytecode.constants.len())?;
+    for constant in &bytecode.constants {
+        match constant {
+            Object::Integer(value) => {
+                w.u8(TAG_INTEGER);
+                w.out.extend_from_slice(&value.to_be_bytes());
+            }
+            Object::CompiledFunction(function) => {
+                w.u8(TAG_FUNCTION);
+                w.u16(function.num_locals as u16);
+                w.u8(function.num_parameters as u8);
+                w.code(&function.instructions, &function.positions)?;
+                if debug {
+                    w.string(function.name.as_deref().unwrap_or(""))?;
+                    w.string(function.module.as_deref().unwrap_or(""))?;
+                }
+            }
+            Object::String(value) => {
+                w.u8(TAG_STRING);
+                w.string(value)?;
+            }
+            other => {
+                return Err(format!(
+                    "cannot serialize constant of type {}",
+                    other.type_name()
+                ))
+            }
+        }
+    }
+    w.code(&bytecode.instructions, &bytecode.positions)?;
+
+    Ok(w.out)
+}
+
+// 壊れたファイルを VM に渡さないよう、命令列も検査してから返す
+pub fn decode(bytes: &[u8]) -> Result<BytecodeFile, String> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a Monkey bytecode file (bad magic)".to_string());
+    }
+    let mut r = Reader {
+        bytes,
+        pos: MAGIC.len(),
+        debug: false,
+    };
+
+    let version = r.u16()?;
+    if version != VERSION {
+        return Err(format!(
+            "unsupported bytecode version {} (expected {})",
+            version, VERSION
+        ));
+    }
+
+    let flags = r.u8()?;
+    if flags & !(FLAG_DEBUG | FLAG_RESULT) != 0 {
+        return Err(format!("unknown flags: {:#04x}", flags));
+    }
+    r.debug = flags & FLAG_DEBUG != 0;
+
+    let count = r.u32()?;
+    let mut constants = vec![];
+    for i in 0..count {
+        let constant = match r.u8()? {
+            TAG_INTEGER => Object::Integer(i64::from_be_bytes(r.array()?)),
+            TAG_FUNCTION => {
+                let num_locals = r.u16()? as usize;
+                let num_parameters = r.u8()? as usize;
+                let (instructions, positions) = r.code()?;
+                let (name, module) = if r.debug {
+                    let name = r.name().map_err(|e| format!("constant {}: {}", i, e))?;
+                    let module = r.name().map_err(|e| format!("constant {}: {}", i, e))?;
+                    (name, module)
+                } else {
+                    (None, None)
+                };
+                if num_parameters > num_locals {
+                    return Err(format!(
+                        "constant {}: function has {} parameters but only {} locals",
+                        i, num_parameters, num_locals
+                    ));
+                }
+                Object::CompiledFunction(Rc::new(CompiledFunction {
+                    instructions,
+                    num_locals,
+                    num_parameters,
+                    positions,
+                    name,
+                    module,
+                }))
+            }
+            TAG_STRING => {
+                let value = r.string().map_err(|e| format!("constant {}: {}", i, e))?;
+                Object::String(value.into())
+            }
+            tag => return Err(format!("constant {}: unknown tag {}", i, tag)),
+        };
+        constants.push(constant);
+    }
+
+    let (instructions, positions) = r.code()?;
+    if r.pos != bytes.len() {
+        return Err(format!("trailing data after bytecode at byte {}", r.pos));
+    }
+
+    for (i, constant) in constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            verify(
+                &function.instructions,
+                function.num_locals,
+                true,
+                constants.len(),
+            )
+            .map_err(|e| format!("constant {}: {}", i, e))?;
+        }
+    }
+    verify(&instructions, 0, false, constants.len()).map_err(|e| format!("main: {}", e))?;
+
+    Ok(BytecodeFile {
+        bytecode: Bytecode {
+            instructions,
+            constants,
+            positions,
+        },
+        has_result: flags & FLAG_RESULT != 0,
+    })
+}
+
+struct Writer {
+    out: Vec<u8>,
+    debug: bool,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.out.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: usize) -> Result<(), String> {
+        let value = u32::try_from(value).map_err(|_| format!("{} does not fit in u32", value))?;
+        self.out.extend_from_slice(&value.to_be_bytes());
+        Ok(())
+    }
+
+    fn string(&mut self, value: &str) -> Result<(), String> {
+        self.u32(value.len())?;
+        self.out.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn code(&mut self, instructions: &[u8], positions: &[(usize, Span)]) -> Result<(), String> {
+        self.u32(instructions.len())?;
+        self.out.extend_from_slice(instructions);
+
+        if self.debug {
+            self.u32(positions.len())?;
+            for (offset, span) in positions {
+                self.u32(*offset)?;
+                self.u32(span.start)?;
+                self.u32(span.end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    debug: bool,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("unexpected end of file at byte {}", self.pos))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn string(&mut self) -> Result<&str, String> {
+        let len = self.u32()?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| "string is not valid UTF-8".to_string())
+    }
+
+    // 長さ 0 の文字列は名前がないことを表す
+    fn name(&mut self) -> Result<Option<Rc<str>>, String> {
+        let name = self.string()?;
+        Ok(Some(name).filter(|name| !name.is_empty()).map(Into::into))
+    }
+
+    fn code(&mut self) -> Result<(Instructions, Positions), String> {
+        let len = self.u32()?;
+        let instructions = self.take(len)?.to_vec();
+
+        let mut positions = vec![];
+        if self.debug {
+            let count = self.u32()?;
+            for _ in 0..count {
+                let offset = self.u32()?;
+                let span = Span::new(self.u32()?, self.u32()?);
+                if offset > instructions.len() || span.start > span.end {
+                    return Err(format!("invalid debug entry at byte {}", self.pos - 12));
+                }
+                positions.push((offset, span));
+            }
+        }
+        Ok((instructions, positions))
+    }
+}
+
+// 命令の区切り、オペランドの範囲、分岐先、スタックの深さを調べる
+fn verify(
+    ins: &[u8],
+    num_locals: usize,
+    in_function: bool,
+    num_constants: usize,
+) -> Result<(), String> {
+    let mut starts = vec![false; ins.len() + 1];
+    let mut offset = 0;
+    while offset < ins.len() {
+        let op = Opcode::from_byte(ins[offset])
+            .ok_or_else(|| format!("unknown opcode {} at {:04}", ins[offset], offset))?;
+        let width: usize = op.definition().operand_widths.iter().sum();
+        if offset + 1 + width > ins.len() {
+            return Err(format!("truncated instruction at {:04}", offset));
+        }
+        starts[offset] = true;
+        offset += 1 + width;
+    }
+    starts[ins.len()] = true;
+
+    let mut heights: Vec<Option<usize>> = vec![None; ins.len()];
+    let mut worklist = vec![(0, 0)];
+    while let Some((offset, height)) = worklist.pop() {
+        if offset == ins.len() {
+            if in_function {
+                return Err("function body does not end with a return".to_string());
+            }
+            continue;
+        }
+        match heights[offset] {
+            Some(h) if h == height => continue,
+            Some(_) => return Err(format!("inconsistent stack height at {:04}", offset)),
+            None => heights[offset] = Some(height),
+        }
+
+        let op = Opcode::from_byte(ins[offset]).expect("checked above");
+        let (operand, operand2) = match op.definition().operand_widths {
+            [2] => (read_u16(&ins[offset + 1..]) as usize, 0),
+            [1] => (ins[offset + 1] as usize, 0),
+            [2, 1] => (
+                read_u16(&ins[offset + 1..]) as usize,
+                ins[offset + 3] as usize,
+            ),
+            [1, 1] => (ins[offset + 1] as usize, ins[offset + 2] as usize),
+            _ => (0, 0),
+        };
+        let next = offset + 1 + op.definition().operand_widths.iter().sum::<usize>();
+
+        let (pops, pushes) = match op {
+            Opcode::Constant | Opcode::Closure if operand >= num_constants => {
+                return Err(format!(
+                    "constant {} out of range at {:04}",
+                    operand, offset
+                ))
+            }
+            Opcode::GetLocal | Opcode::SetLocal | Opcode::SetLocalIndex
+                if operand >= num_locals =>
+            {
+                return Err(format!("local {} out of range at {:04}", operand, offset))
+            }
+            Opcode::Jump | Opcode::JumpNotTruthy if operand > ins.len() || !starts[operand] => {
+                return Err(format!("invalid jump target {} at {:04}", operand, offset))
+            }
+            Opcode::GetBuiltin if operand >= BUILTINS.len() => {
+                return Err(format!("builtin {} out of range at {:04}", operand, offset))
+            }
+            Opcode::IterNext if !(1..=2).contains(&operand) => {
+                return Err(format!(
+                    "bad loop variable count {} at {:04}",
+                    operand, offset
+                ))
+            }
+            Opcode::Hash if operand % 2 != 0 => {
+                return Err(format!("odd hash operand {} at {:04}", operand, offset))
+            }
+            _ => op.stack_effect(&[operand, operand2]),
+        };
+        if height < pops {
+            return Err(format!("stack underflow at {:04}", offset));
+        }
+        let height = height - pops + pushes;
+
+        match op {
+            Opcode::Jump => worklist.push((operand, height)),
+            Opcode::JumpNotTruthy => {
+                worklist.push((next, height));
+                worklist.push((operand, height));
+            }
+            Opcode::ReturnValue | Opcode::Return => {}
+            _ => worklist.push((next, height)),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use super::*;
+use crate::code::make;
+use crate::compiler::Compiler;
+use crate::engine::{Engine, Interpreter};
+use crate::lexer::Lexer;
+use crate::module::Loader;
+use crate::parser::Parser;
+use std::cell::RefCell;
+
+fn compile(input: &str) -> BytecodeFile {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+    BytecodeFile {
+        bytecode: compiler.bytecode(),
+        has_result: true,
+    }
+}
+
+const FIBONACCI: &str =
+    "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15)";
+
+// 定数がひとつもない、命令列だけのファイルを作る
+fn raw_file(constants: &[u8], main: &[u8]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_be_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(constants);
+    bytes.extend_from_slice(&(main.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(main);
+    bytes
+}
+
+#[test]
+fn test_roundtrip() {
+    let file = compile(FIBONACCI);
+
+    for debug in [true, false] {
+        let bytes = encode(&file, debug).unwrap();
+        let decoded = decode(&bytes).unwrap();
+
+        assert!(decoded.has_result);
+        assert_eq!(decoded.bytecode.instructions, file.bytecode.instructions);
+        assert_eq!(decoded.bytecode.constants, file.bytecode.constants);
+        if debug {
+            assert_eq!(decoded.bytecode.positions, file.bytecode.positions);
+        } else {
+            assert!(decoded.bytecode.positions.is_empty());
+        }
+
+        let result = Interpreter::new(Engine::Vm).run_bytecode(decoded.bytecode);
+        assert_eq!(result, Object::Integer(610));
+    }
+}
+
+#[test]
+fn test_string_constants() {
+    let file = compile(r#"let s = "日本\n"; puts(s); s + "語""#);
+    let decoded = decode(&encode(&file, false).unwrap()).unwrap();
+    assert_eq!(decoded.bytecode.constants, file.bytecode.constants);
+
+    let result = Interpreter::new(Engine::Vm)
+        .with_output(Rc::new(RefCell::new(Vec::new())))
+        .run_bytecode(decoded.bytecode);
+    assert_eq!(result, Object::String("日本\n語".into()));
+}
+
+#[test]
+fn test_debug_positions() {
+    let file = compile(FIBONACCI);
+    let decoded = decode(&encode(&file, true).unwrap()).unwrap();
+
+    let function = decoded
+        .bytecode
+        .constants
+        .iter()
+        .find_map(|c| match c {
+            Object::CompiledFunction(f) => Some(f.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert!(!function.positions.is_empty());
+    // 関数本体の最初の命令は条件式の 'n' を読む
+    assert_eq!(function.positions[0], (0, Span::new(22, 23)));
+    assert_eq!(function.name.as_deref(), Some("fib"));
+}
+
+// 関数を定義したモジュールの名前もデバッグ情報に残す
+#[test]
+fn test_debug_module_names() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/module/testdata");
+    let mut l = Lexer::new(r#"import "math.monkey" as m; m.square(3)"#);
+    let program = Parser::new(&mut l).parse_program();
+    let mut compiler = Compiler::new().with_loader(Loader::new(dir));
+    compiler.compile(&program).unwrap();
+    let file = BytecodeFile {
+        bytecode: compiler.bytecode(),
+        has_result: true,
+    };
+
+    for (debug, module) in [(true, Some("math.monkey")), (false, None)] {
+        let decoded = decode(&encode(&file, debug).unwrap()).unwrap();
+        let modules: Vec<Option<&str>> = decoded
+            .bytecode
+            .constants
+            .iter()
+            .filter_map(|c| match c {
+                Object::CompiledFunction(f) => Some(f.module.as_deref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(modules, vec![module; 2]);
+
+        let result = Interpreter::new(Engine::Vm).run_bytecode(decoded.bytecode);
+        assert_eq!(result, Object::Integer(9));
+    }
+}
+
+// デバッグ情報がなければ関数の名前も場所も残らない
+#[test]
+fn test_runtime_error_locations() {
+    let file = compile("let f = fn(x) { x + true }; f(1)");
+    for (debug, span, name) in [
+        (true, Some(Span::new(18, 19)), Some("f")),
+        (false, None, None),
+    ] {
+        let decoded = decode(&encode(&file, debug).unwrap()).unwrap();
+        let Object::Error(error) = Interpreter::new(Engine::Vm).run_bytecode(decoded.bytecode)
+        else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.message, "type mismatch: INTEGER + BOOLEAN");
+        assert_eq!(error.span, span);
+        assert_eq!(error.stack.len(), 1);
+        assert_eq!(error.stack[0].function.as_deref(), name);
+    }
+}
+
+#[test]
+fn test_header_errors() {
+    let bytes = encode(&compile("1"), false).unwrap();
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4..6].copy_from_slice(&99u16.to_be_bytes());
+    let mut unknown_flags = bytes.clone();
+    unknown_flags[6] = 0x80;
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+
+    let tests = vec![
+        (b"".to_vec(), "not a Monkey bytecode file (bad magic)"),
+        (
+            b"\x7fELF\x02\x01".to_vec(),
+            "not a Monkey bytecode file (bad magic)",
+        ),
+        (
+            wrong_version,
+            "unsupported bytecode version 99 (expected 5)",
+        ),
+        (unknown_flags, "unknown flags: 0x80"),
+        (trailing, "trailing data after bytecode at byte 28"),
+        (bytes[..5].to_vec(), "unexpected end of file at byte 4"),
+        (raw_file(&[0, 0, 0, 1, 7], &[]), "constant 0: unknown tag 7"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(decode(&input).err().as_deref(), Some(expected));
+    }
+}
+
+#[test]
+fn test_verify_errors() {
+    let no_constants = [0, 0, 0, 0];
+    let tests = vec![
+        (vec![200], "main: unknown opcode 200 at 0000"),
+        (
+            make(Opcode::Constant, &[0])[..2].to_vec(),
+            "main: truncated instruction at 0000",
+        ),
+        (
+            make(Opcode::Constant, &[0]),
+            "main: constant 0 out of range at 0000",
+        ),
+        (make(Opcode::Pop, &[]), "main: stack underflow at 0000"),
+        (
+            make(Opcode::GetLocal, &[0]),
+            "main: local 0 out of range at 0000",
+        ),
+        (
+            make(Opcode::Jump, &[2]),
+            "main: invalid jump target 2 at 0000",
+        ),
+        (
+            make(Opcode::Jump, &[100]),
+            "main: invalid jump target 100 at 0000",
+        ),
+        (
+            [
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[8]),
+                make(Opcode::True, &[]),
+                make(Opcode::Jump, &[8]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ]
+            .concat(),
+            "main: inconsistent stack height at 0008",
+        ),
+        (
+            [make(Opcode::True, &[]), make(Opcode::Hash, &[1])].concat(),
+            "main: odd hash operand 1 at 0001",
+        ),
+        (
+            make(Opcode::GetBuiltin, &[200]),
+            "main: builtin 200 out of range at 0000",
+        ),
+    ];
+
+    for (main, expected) in tests {
+        assert_eq!(
+            decode(&raw_file(&no_constants, &main)).err().as_deref(),
+            Some(expected)
+        );
+    }
+
+    // 関数の本体は return で終わらなければならない
+    let mut function = vec![0, 0, 0, 1, TAG_FUNCTION, 0, 0, 0];
+    let body = make(Opcode::Null, &[]);
+    function.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    function.extend_from_slice(&body);
+    assert_eq!(
+        decode(&raw_file(&function, &[])).err().as_deref(),
+        Some("constant 0: function body does not end with a return")
+    );
+
+    let string = [0, 0, 0, 1, TAG_STRING, 0, 0, 0, 1, 0xff];
+    assert_eq!(
+        decode(&raw_file(&string, &[])).err().as_deref(),
+        Some("constant 0: string is not valid UTF-8")
+    );
+}
+
+// どこで切れていても、どのバイトが壊れていても panic しない
+#[test]
+fn test_malformed_never_panics() {
+    let bytes = encode(&compile(FIBONACCI), true).unwrap();
+
+    for len in 0..bytes.len() {
+        assert!(decode(&bytes[..len]).is_err(), "truncated at {}", len);
+    }
+
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    for _ in 0..5000 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let mut corrupted = bytes.clone();
+        let index = (state % bytes.len() as u64) as usize;
+        corrupted[index] ^= (state >> 32) as u8 | 1;
+        let _ = decode(&corrupted);
+    }
+}
@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod test;
+
+use crate::ast::{Identifier, Program, Statement};
+use crate::diagnostic::line_column;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// import のパスからファイルを探して読み込む。読み込み中のモジュールを覚えておき、
+// 自分自身を読み込もうとする循環を見つける
+#[derive(Debug, Clone)]
+pub struct Loader {
+    // 実行したファイルのあるディレクトリ。モジュールの名前はここからの相対パスで表す
+    root: PathBuf,
+    search_path: Vec<PathBuf>,
+    // 読み込み中のモジュール。後ろほど内側
+    loading: Vec<PathBuf>,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Loader::new(".")
+    }
+}
+
+impl Loader {
+    pub fn new(root: impl AsRef<Path>) -> Loader {
+        let root = root.as_ref();
+        let root = if root.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            root
+        };
+        Loader {
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            search_path: vec![],
+            loading: vec![],
+        }
+    }
+
+    // 実行するファイルを起点にする。そのファイル自身を import するのも循環になる
+    pub fn for_file(path: impl AsRef<Path>) -> Loader {
+        let path = path.as_ref();
+        let mut loader = Loader::new(path.parent().unwrap_or(Path::new(".")));
+        if let Ok(path) = path.canonicalize() {
+            loader.loading.push(path);
+        }
+        loader
+    }
+
+    pub fn with_search_path(mut self, search_path: Vec<PathBuf>) -> Loader {
+        self.search_path = search_path;
+        self
+    }
+
+    // 読み込み中のモジュールのあるディレクトリ、検索パスの順に探す
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let base = self
+            .loading
+            .last()
+            .and_then(|p| p.parent())
+            .unwrap_or(&self.root);
+        std::iter::once(base)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .and_then(|candidate| candidate.canonicalize().ok())
+            .ok_or_else(|| format!("module not found: {}", path))
+    }
+
+    // エラーやスタックトレースに出す名前
+    pub fn name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    // 読み込み中のモジュールをもう一度読み込もうとしたら、循環を最初から並べて返す
+    pub fn enter(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|p| p == path) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .map(|p| self.name(p))
+                .chain([self.name(path)])
+                .collect();
+            return Err(format!("import cycle: {}", cycle.join(" -> ")));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.loading.pop();
+    }
+
+    // 構文エラーは最初のものを、場所を添えて返す
+    pub fn load(&self, path: &Path) -> Result<Program, String> {
+        let name = self.name(path);
+        let input = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+        let mut l = Lexer::new(&input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        match p.errors().first() {
+            Some(error) => {
+                let (line, column) = line_column(&input, error.span.start);
+                Err(format!("{}:{}:{}: {}", name, line, column, error.message))
+            }
+            None => Ok(program),
+        }
+    }
+}
+
+// export した let が束縛する名前
+pub fn exports(program: &Program) -> Vec<&Identifier> {
+    program
+        .statements
+        .iter()
+        .flat_map(|stmt| match stmt {
+            Statement::ExportStatement(s) => s.statement.pattern.bindings(),
+            _ => vec![],
+        })
+        .collect()
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+fn testdata() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/module/testdata")
+}
+
+#[test]
+fn test_resolve() {
+    let mut loader = Loader::new(testdata());
+
+    let math = loader.resolve("math.monkey").unwrap();
+    assert_eq!(math, testdata().join("math.monkey").canonicalize().unwrap());
+    assert_eq!(loader.name(&math), "math.monkey");
+
+    let geometry = loader.resolve("lib/geometry.monkey").unwrap();
+    assert_eq!(loader.name(&geometry), "lib/geometry.monkey");
+
+    // 読み込み中のモジュールからの相対パスで探す
+    assert!(loader.resolve("helper.monkey").is_err());
+    loader.enter(&geometry).unwrap();
+    let helper = loader.resolve("helper.monkey").unwrap();
+    assert_eq!(loader.name(&helper), "lib/helper.monkey");
+    loader.leave();
+
+    assert_eq!(
+        loader.resolve("missing.monkey"),
+        Err("module not found: missing.monkey".to_string())
+    );
+    // ディレクトリはモジュールではない
+    assert!(loader.resolve("lib").is_err());
+}
+
+#[test]
+fn test_search_path() {
+    let loader = Loader::new(testdata().join("lib")).with_search_path(vec![testdata()]);
+
+    let helper = loader.resolve("helper.monkey").unwrap();
+    assert_eq!(loader.name(&helper), "helper.monkey");
+
+    // 起点の外にあるモジュールは絶対パスで表す
+    let math = loader.resolve("math.monkey").unwrap();
+    assert!(math.is_absolute());
+    assert_eq!(loader.name(&math), math.display().to_string());
+}
+
+#[test]
+fn test_cycle() {
+    let mut loader = Loader::for_file(testdata().join("cycle_a.monkey"));
+    let a = loader.resolve("cycle_a.monkey").unwrap();
+    let b = loader.resolve("cycle_b.monkey").unwrap();
+
+    assert_eq!(loader.enter(&b), Ok(()));
+    assert_eq!(
+        loader.enter(&a),
+        Err("import cycle: cycle_a.monkey -> cycle_b.monkey -> cycle_a.monkey".to_string())
+    );
+    // 実行したファイル自身を読み込むのも循環になる
+    loader.leave();
+    assert_eq!(
+        loader.enter(&a),
+        Err("import cycle: cycle_a.monkey -> cycle_a.monkey".to_string())
+    );
+}
+
+#[test]
+fn test_load() {
+    let loader = Loader::new(testdata());
+
+    let program = loader.load(&testdata().join("math.monkey")).unwrap();
+    let names: Vec<&str> = exports(&program)
+        .iter()
+        .map(|name| name.value.as_str())
+        .collect();
+    assert_eq!(names, vec!["square", "one", "two"]);
+
+    assert_eq!(
+        loader.load(&testdata().join("broken.monkey")),
+        Err("broken.monkey:1:12: expected next token to be IDENT, got = instead".to_string())
+    );
+}
@@ -0,0 +1,368 @@
+mod environment;
+mod error;
+mod hash;
+mod iteration;
+mod module;
+
+use crate::ast::{BlockStatement, Expression, Node, Pattern};
+use crate::code::{Instructions, Positions};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+pub use environment::Environment;
+pub use error::{ErrorKind, Limit, RuntimeError, StackFrame};
+pub use hash::{Hash, HashKey};
+pub use iteration::{Iteration, Range};
+pub use module::Module;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    Null,
+    ReturnValue(Box<Object>),
+    // ループを抜ける・次の繰り返しに進むことを、ループまで伝える
+    Break,
+    Continue,
+    Error(Box<RuntimeError>),
+    Function(Rc<Function>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    String(Rc<str>),
+    Array(Rc<Vec<Object>>),
+    Builtin(Builtin),
+    Hash(Rc<Hash>),
+    HostFunction(HostFunction),
+    Range(Range),
+    // VM が for 文の間スタックに置いておく反復の状態
+    Iteration(Box<Iteration>),
+    Module(Rc<Module>),
+}
+
+// 評価器が使う関数。定義された環境を抱えている
+pub struct Function {
+    // let で束縛した関数ならその名前。スタックトレースに使う
+    pub name: Option<Rc<str>>,
+    pub parameters: Vec<Pattern>,
+    pub body: BlockStatement,
+    pub env: Rc<RefCell<Environment>>,
+    // 定義したモジュール。本体で起きたエラーの場所に使う
+    pub module: Option<Rc<str>>,
+}
+
+// 環境は自分自身を含みうるので、表示も比較もたどらない
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// VM が使う関数
+#[derive(Debug, Default)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    pub positions: Positions,
+    pub name: Option<Rc<str>>,
+    // positions の範囲がどのモジュールのソースを指すか
+    pub module: Option<Rc<str>>,
+}
+
+// VM が使う関数と、取り込んだ自由変数の値
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+// 評価器の関数と同じく、同じ式の評価で作ったクロージャどうしだけが等しい
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// 組み込み関数。puts などの出力は呼び出し側が渡す書き込み先へ送る
+pub type BuiltinFunction = fn(&[Object], &mut dyn Write) -> Result<Object, String>;
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+// 埋め込み側が登録した関数
+pub type HostFn = dyn Fn(&[Object]) -> Result<Object, RuntimeError>;
+
+#[derive(Clone)]
+pub struct HostFunction {
+    pub name: Rc<str>,
+    pub function: Rc<HostFn>,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: &str,
+        function: impl Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    ) -> HostFunction {
+        HostFunction {
+            name: name.into(),
+            function: Rc::new(function),
+        }
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+// positions と name と module はデバッグ情報なので比較しない
+impl PartialEq for CompiledFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.instructions == other.instructions
+            && self.num_locals == other.num_locals
+            && self.num_parameters == other.num_parameters
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Closure(_) => "CLOSURE",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Hash(_) => "HASH",
+            // 埋め込み側の関数も組み込み関数と同じに見せる
+            Object::HostFunction(_) => "BUILTIN",
+            Object::Range(_) => "RANGE",
+            Object::Iteration(_) => "ITERATION",
+            Object::Module(_) => "MODULE",
+        }
+    }
+
+    // false と null 以外はすべて真
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    // 場所はあとで、エラーを起こしたノードの範囲を入れる
+    pub fn error(message: impl ToString) -> Object {
+        Object::Error(Box::new(RuntimeError::new(message)))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    // 添字をたどった先を value に置き換える。配列とハッシュは値として振る舞うので、
+    // ほかからも参照されていれば複製してから書き換える。途中の添字は読み出しと同じく
+    // 解釈する。書き換えた入れ物を返す
+    pub fn set_index(&mut self, indexes: &[Object], value: Object) -> Result<&Object, String> {
+        let Some((last, path)) = indexes.split_last() else {
+            *self = value;
+            return Ok(self);
+        };
+        let mut container = self;
+        for (i, index) in path.iter().enumerate() {
+            container = match container.element_mut(index)? {
+                Some(element) => element,
+                // 残りの添字は NULL をたどることになり、読み出しと同じくエラーになる
+                None => {
+                    let rest = &indexes[i + 1..];
+                    return Err(Object::Null
+                        .set_index(rest, value)
+                        .err()
+                        .unwrap_or_default());
+                }
+            };
+        }
+        match (&mut *container, last) {
+            (Object::Array(elements), Object::Integer(i)) => {
+                match usize::try_from(*i).ok().filter(|&i| i < elements.len()) {
+                    Some(i) => {
+                        Rc::make_mut(elements)[i] = value;
+                        Ok(container)
+                    }
+                    None => Err(format!(
+                        "index out of range: {} (len {})",
+                        i,
+                        elements.len()
+                    )),
+                }
+            }
+            (Object::Hash(hash), _) => {
+                let key = HashKey::from_object(last)?;
+                Rc::make_mut(hash).insert(key, value);
+                Ok(container)
+            }
+            (left, _) => Err(format!(
+                "index assignment not supported: {}[{}]",
+                left.type_name(),
+                last.type_name()
+            )),
+        }
+    }
+
+    // 書き換えるために添字の位置の要素を借りる。範囲外や無いキーなら None
+    fn element_mut(&mut self, index: &Object) -> Result<Option<&mut Object>, String> {
+        match (self, index) {
+            (Object::Array(elements), Object::Integer(i)) => {
+                match usize::try_from(*i).ok().filter(|&i| i < elements.len()) {
+                    Some(i) => Ok(Some(&mut Rc::make_mut(elements)[i])),
+                    None => Ok(None),
+                }
+            }
+            (Object::Hash(hash), _) => {
+                let key = HashKey::from_object(index)?;
+                if hash.get(&key).is_none() {
+                    return Ok(None);
+                }
+                Ok(Rc::make_mut(hash).get_mut(&key))
+            }
+            (left, _) => Err(format!(
+                "index operator not supported: {}[{}]",
+                left.type_name(),
+                index.type_name()
+            )),
+        }
+    }
+
+    // object.name で取り出せるのはモジュールが export した値だけ
+    pub fn member(&self, name: &str) -> Result<Object, String> {
+        match self {
+            Object::Module(module) => module.get(name),
+            other => Err(format!(
+                "member access not supported: {}",
+                other.type_name()
+            )),
+        }
+    }
+
+    // 形が合わなかったときのエラーに出す値の様子。配列は長さも添える
+    pub fn describe(&self) -> String {
+        match self {
+            Object::Array(elements) => format!("ARRAY of length {}", elements.len()),
+            _ => self.type_name().to_string(),
+        }
+    }
+
+    // パターンに書いたリテラルの値。ハッシュパターンのキーはこれで求める
+    pub fn from_literal(exp: &Expression) -> Option<Object> {
+        match exp {
+            Expression::IntegerLiteral(e) => Some(Object::Integer(e.value)),
+            Expression::StringLiteral(e) => Some(Object::String(e.value.as_str().into())),
+            Expression::Boolean(e) => Some(Object::Boolean(e.value)),
+            _ => None,
+        }
+    }
+
+    // 配列パターンの形に合うか。残りを受けるなら len 個以上でよい
+    pub fn matches_array(&self, len: usize, rest: bool) -> bool {
+        match self {
+            Object::Array(elements) if rest => elements.len() >= len,
+            Object::Array(elements) => elements.len() == len,
+            _ => false,
+        }
+    }
+
+    // ハッシュパターンの形に合うか。挙げたキーがすべてあればよい
+    pub fn has_keys(&self, keys: &[Object]) -> bool {
+        let Object::Hash(hash) = self else {
+            return false;
+        };
+        keys.iter()
+            .all(|key| HashKey::from_object(key).is_ok_and(|key| hash.get(&key).is_some()))
+    }
+
+    // 配列の start 番目から後ろを取り出す。パターンの残りを束縛するのに使う
+    pub fn slice_from(&self, start: usize) -> Object {
+        match self {
+            Object::Array(elements) => {
+                Object::Array(Rc::new(elements.get(start..).unwrap_or(&[]).to_vec()))
+            }
+            _ => Object::Null,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::Error(error) => write!(f, "ERROR: {}", error),
+            Object::Function(function) => {
+                let parameters: Vec<String> =
+                    function.parameters.iter().map(|p| p.to_string()).collect();
+                write!(
+                    f,
+                    "fn({}) {}",
+                    parameters.join(", "),
+                    function.body.to_string()
+                )
+            }
+            Object::CompiledFunction(function) => {
+                write!(f, "CompiledFunction[{:p}]", Rc::as_ptr(function))
+            }
+            Object::Closure(closure) => write!(f, "Closure[{:p}]", Rc::as_ptr(closure)),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Object::Hash(hash) => write!(f, "{}", hash),
+            Object::HostFunction(function) => write!(f, "builtin {}", function.name),
+            Object::Range(range) => write!(f, "{}", range),
+            Object::Iteration(iteration) => write!(f, "iteration over {}", iteration.iterable()),
+            Object::Module(module) => write!(f, "{}", module),
+        }
+    }
+}
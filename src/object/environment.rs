@@ -0,0 +1,69 @@
+use crate::object::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }
+    }
+
+    // 見つからなければ外側の環境を探す
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    // この環境で束縛している名前。外側の環境の名前は含まない
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.store.keys().map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: impl ToString, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    // 代入できるのは、いまの関数の変数と一番外側の変数だけ。VM のクロージャは
+    // 外側の関数の変数を値で取り込むので、書き換えても外へ伝わらない
+    pub fn check_assignable(&self, name: &str) -> Result<(), String> {
+        if self.store.contains_key(name) {
+            return Ok(());
+        }
+        let mut outer = self.outer.clone();
+        while let Some(env) = outer {
+            let env = env.borrow();
+            if env.store.contains_key(name) {
+                return match env.outer {
+                    None => Ok(()),
+                    Some(_) => Err(format!("cannot assign to captured variable: {}", name)),
+                };
+            }
+            outer = env.outer.clone();
+        }
+        Err(format!("cannot assign to undefined variable: {}", name))
+    }
+
+    // 名前を束縛している環境の値をその場で書き換える。束縛から値を取り出さないので、
+    // ほかに参照がなければ入れ物は複製されない
+    pub fn update<T>(&mut self, name: &str, f: impl FnOnce(&mut Object) -> T) -> Option<T> {
+        match self.store.get_mut(name) {
+            Some(slot) => Some(f(slot)),
+            None => self.outer.as_ref()?.borrow_mut().update(name, f),
+        }
+    }
+}
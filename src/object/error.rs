@@ -0,0 +1,89 @@
+use crate::token::Span;
+use std::fmt;
+use std::rc::Rc;
+
+// 実行時のエラー。起きた場所と、そこに至るまでの関数呼び出しを持つ
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub kind: ErrorKind,
+    // エラーを起こしたノードの範囲。デバッグ情報のないバイトコードでは分からない
+    pub span: Option<Span>,
+    // 呼び出し中だった関数。内側から順に並ぶ
+    pub stack: Vec<StackFrame>,
+    // span がどのモジュールのソースを指すか。None は実行したプログラムそのもの
+    pub module: Option<Rc<str>>,
+}
+
+// 埋め込み側がメッセージを読まずにエラーを見分けられるようにする
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ErrorKind {
+    #[default]
+    Runtime,
+    // Limits で決めた上限に達して実行を止めた
+    LimitExceeded(Limit),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Limit {
+    Steps,
+    CallDepth,
+    Allocation,
+    Deadline,
+}
+
+// 関数の名前と、それを呼び出した場所
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StackFrame {
+    // let で束縛していない関数には名前がない
+    pub function: Option<Rc<str>>,
+    pub span: Option<Span>,
+    // 呼び出した場所があるモジュール
+    pub module: Option<Rc<str>>,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl ToString) -> RuntimeError {
+        RuntimeError {
+            message: message.to_string(),
+            kind: ErrorKind::Runtime,
+            span: None,
+            stack: vec![],
+            module: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> RuntimeError {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> RuntimeError {
+        self.kind = kind;
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> RuntimeError {
+        RuntimeError::new(message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> RuntimeError {
+        RuntimeError::new(message)
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.function.as_deref().unwrap_or("<anonymous>"))
+    }
+}
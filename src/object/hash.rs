@@ -0,0 +1,116 @@
+use crate::object::Object;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// ハッシュのキーに使える値
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl HashKey {
+    pub fn from_object(object: &Object) -> Result<HashKey, String> {
+        match object {
+            Object::Integer(value) => Ok(HashKey::Integer(*value)),
+            Object::Boolean(value) => Ok(HashKey::Boolean(*value)),
+            Object::String(value) => Ok(HashKey::String(value.clone())),
+            other => Err(format!("unusable as hash key: {}", other.type_name())),
+        }
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            HashKey::Integer(value) => Object::Integer(*value),
+            HashKey::Boolean(value) => Object::Boolean(*value),
+            HashKey::String(value) => Object::String(value.clone()),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_object())
+    }
+}
+
+// 表示や反復の順序が決まるように、キーを入れた順に並べて持つ
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    pairs: Vec<(HashKey, Object)>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl Hash {
+    pub fn new() -> Hash {
+        Hash::default()
+    }
+
+    // 同じキーがあれば値だけ置き換え、位置は変えない
+    pub fn insert(&mut self, key: HashKey, value: Object) {
+        match self.index.get(&key) {
+            Some(&i) => self.pairs[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.pairs.len());
+                self.pairs.push((key, value));
+            }
+        }
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&Object> {
+        self.index.get(key).map(|&i| &self.pairs[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &HashKey) -> Option<&mut Object> {
+        self.index.get(key).map(|&i| &mut self.pairs[i].1)
+    }
+
+    // 入れた順で i 番目の組
+    pub fn get_index(&self, i: usize) -> Option<(&HashKey, &Object)> {
+        self.pairs.get(i).map(|(key, value)| (key, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&HashKey, &Object)> {
+        self.pairs.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl FromIterator<(HashKey, Object)> for Hash {
+    fn from_iter<I: IntoIterator<Item = (HashKey, Object)>>(iter: I) -> Hash {
+        let mut hash = Hash::new();
+        for (key, value) in iter {
+            hash.insert(key, value);
+        }
+        hash
+    }
+}
+
+// 並び順は比較しない
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs: Vec<String> = self
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        write!(f, "{{{}}}", pairs.join(", "))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::object::Object;
+use std::fmt;
+
+// 整数の範囲。要素は反復するときに一つずつ作る
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64, inclusive: bool) -> Range {
+        Range {
+            start,
+            end,
+            inclusive,
+        }
+    }
+
+    // 端はどちらも整数でなければならない
+    pub fn from_bounds(start: &Object, end: &Object, inclusive: bool) -> Result<Range, String> {
+        match (start, end) {
+            (Object::Integer(start), Object::Integer(end)) => {
+                Ok(Range::new(*start, *end, inclusive))
+            }
+            _ => Err(format!(
+                "range bounds must be INTEGER, got {}{}{}",
+                start.type_name(),
+                if inclusive { "..=" } else { ".." },
+                end.type_name()
+            )),
+        }
+    }
+
+    // i64 の全域にわたる範囲でもあふれないよう、広い型で数える
+    pub fn len(&self) -> u64 {
+        let end = self.end as i128 + self.inclusive as i128;
+        (end - self.start as i128).clamp(0, u64::MAX as i128) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: u64) -> Option<i64> {
+        (i < self.len()).then(|| self.start.wrapping_add(i as i64))
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{}{}", self.start, op, self.end)
+    }
+}
+
+// for 文が反復している値と、次に取り出す位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iteration {
+    iterable: Object,
+    index: u64,
+    // 文字列では次の文字のバイト位置
+    offset: usize,
+}
+
+impl Iteration {
+    pub fn new(iterable: Object) -> Result<Iteration, String> {
+        match iterable {
+            Object::Array(_) | Object::String(_) | Object::Hash(_) | Object::Range(_) => {
+                Ok(Iteration {
+                    iterable,
+                    index: 0,
+                    offset: 0,
+                })
+            }
+            other => Err(format!("not iterable: {}", other.type_name())),
+        }
+    }
+
+    pub fn iterable(&self) -> &Object {
+        &self.iterable
+    }
+
+    // 添字 (ハッシュならキー) と要素の組。尽きたら None
+    pub fn next_pair(&mut self) -> Option<(Object, Object)> {
+        let index = Object::Integer(self.index as i64);
+        let pair = match &self.iterable {
+            Object::Array(elements) => (index, elements.get(self.index as usize)?.clone()),
+            Object::String(s) => {
+                let c = s[self.offset..].chars().next()?;
+                self.offset += c.len_utf8();
+                (index, Object::String(c.to_string().into()))
+            }
+            Object::Hash(hash) => {
+                let (key, value) = hash.get_index(self.index as usize)?;
+                (key.to_object(), value.clone())
+            }
+            Object::Range(range) => (index, Object::Integer(range.get(self.index)?)),
+            _ => return None,
+        };
+        self.index += 1;
+        Some(pair)
+    }
+
+    // 変数が一つなら、配列・文字列・範囲は要素を、ハッシュはキーを取り出す
+    pub fn next_single(&mut self) -> Option<Object> {
+        let is_hash = matches!(self.iterable, Object::Hash(_));
+        self.next_pair()
+            .map(|(key, value)| if is_hash { key } else { value })
+    }
+}
@@ -0,0 +1,33 @@
+use crate::object::Object;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+// import で読み込んだモジュール。export した束縛を名前順に持つ
+#[derive(Debug)]
+pub struct Module {
+    pub name: Rc<str>,
+    pub exports: BTreeMap<String, Object>,
+}
+
+impl Module {
+    pub fn get(&self, name: &str) -> Result<Object, String> {
+        self.exports
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("module {} has no export {}", self.name, name))
+    }
+}
+
+// 同じモジュールは一度しか実行しないので、同じものかどうかで比べる
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "module {}", self.name)
+    }
+}
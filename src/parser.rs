@@ -1,14 +1,35 @@
 #[cfg(test)]
 mod test;
+mod trace;
 
 use crate::ast::{
-    BlockStatement, Boolean, Expression, ExpressionStatement, Identifier, IfExpression,
-    InfixExpression, IntegerLiteral, LetStatement, PrefixExpression, Program, ReturnStatement,
-    Statement,
+    is_assignable, ArrayLiteral, ArrayPattern, AssignExpression, BlockStatement, Boolean,
+    BreakStatement, CallExpression, ContinueStatement, ExportStatement, Expression,
+    ExpressionStatement, ForStatement, FunctionLiteral, HashLiteral, HashPattern, Identifier,
+    IfExpression, ImportStatement, IndexExpression, InfixExpression, IntegerLiteral, LetStatement,
+    MatchArm, MatchExpression, MemberExpression, Node, Pattern, PrefixExpression, Program,
+    RangeExpression, RestPattern, ReturnStatement, Statement, StringLiteral, WhileStatement,
+    WildcardPattern,
 };
-use crate::lexer::Lexer;
-use crate::token::{Token, TokenKind};
+use crate::lexer::{unquote, Lexer};
+use crate::parser::trace::{TraceGuard, Tracer};
+use crate::token::{Span, Token, TokenKind};
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// これより深い式は評価やコンパイルでスタックを使い切るので、構文解析の段階で断る。
+// 数えるのは前置演算子や括弧、右辺、呼び出しや添字のように木をたどる側が再帰するところ
+pub const MAX_NESTING: usize = 256;
+
+// 二項演算子を左につなげた数。木はその分だけ左に深くなるが、木をたどる処理はどれも
+// 左の枝を繰り返しでたどる。それでも JSON や CST のように入れ子のまま持つ表現があるので、
+// 既定の大きさのスレッドでも扱える数に抑える
+pub const MAX_OPERATORS: usize = 1024;
+
+// 呼び出しに渡す引数と、関数が受け取る引数の数の上限。VM は引数の数を 1 バイトで
+// 持つので、評価器でも同じところで断ってエンジンの間で受け付けるプログラムを揃える
+pub const MAX_ARGUMENTS: usize = u8::MAX as usize;
 
 type PrefixParseFn<'a> = fn(&mut Parser<'a>) -> Option<Expression>;
 type InfixParseFn<'a> = fn(&mut Parser<'a>, Option<Expression>) -> Option<Expression>;
@@ -17,20 +38,49 @@ pub struct Parser<'a> {
     l: &'a mut Lexer,
     cur_token: Token,
     peek_token: Token,
-    errors: Vec<String>,
+    errors: Vec<SyntaxError>,
+    // 実行はできるが、おそらく誤りであるもの
+    warnings: Vec<SyntaxError>,
     prefix_parse_fns: HashMap<TokenKind, PrefixParseFn<'a>>,
     infix_parse_fns: HashMap<TokenKind, InfixParseFn<'a>>,
+    tracer: Rc<Tracer>,
+    depth: usize,
+    operators: usize,
+    // 深すぎる式のエラーの位置。外側の式が重ねたエラーはあとで捨てる
+    nesting_error: Option<usize>,
+    // 囲んでいるループの数。関数の本体に入ると数え直す
+    loops: usize,
+    // 囲んでいるブロックの数。import と export は最上位にしか書けない
+    blocks: usize,
+    // match の腕の本体の先頭。ここの { はハッシュだが、ブロックと間違えやすい
+    arm_body: Option<Span>,
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
-enum Precedence {
+// 構文エラーと、それが見つかったトークンの範囲
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Precedence {
     Lowest,
+    Assign,      // = or +=
+    Range,       // .. or ..=
     Equals,      // ==
     LessGreater, // > or <
     Sum,         // +
     Product,     // *
     Prefix,      // -X or !X
     Call,        // myFunction(X)
+    Index,       // array[index] or object.property
 }
 
 impl<'a> Parser<'a> {
@@ -40,8 +90,16 @@ impl<'a> Parser<'a> {
             cur_token: Token::default(),
             peek_token: Token::default(),
             errors: Vec::new(),
+            warnings: Vec::new(),
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
+            tracer: Rc::new(Tracer::default()),
+            depth: 0,
+            operators: 0,
+            nesting_error: None,
+            loops: 0,
+            blocks: 0,
+            arm_body: None,
         };
 
         p.register_prefix(TokenKind::Ident, Parser::parse_identifier);
@@ -52,6 +110,11 @@ impl<'a> Parser<'a> {
         p.register_prefix(TokenKind::Minus, Parser::parse_prefix_expression);
         p.register_prefix(TokenKind::LParen, Parser::parse_grouped_expression);
         p.register_prefix(TokenKind::If, Parser::parse_if_expression);
+        p.register_prefix(TokenKind::Match, Parser::parse_match_expression);
+        p.register_prefix(TokenKind::Function, Parser::parse_function_literal);
+        p.register_prefix(TokenKind::String, Parser::parse_string_literal);
+        p.register_prefix(TokenKind::LBracket, Parser::parse_array_literal);
+        p.register_prefix(TokenKind::LBrace, Parser::parse_hash_literal);
 
         p.register_infix(TokenKind::Plus, Parser::parse_infix_expression);
         p.register_infix(TokenKind::Minus, Parser::parse_infix_expression);
@@ -61,6 +124,16 @@ impl<'a> Parser<'a> {
         p.register_infix(TokenKind::NotEq, Parser::parse_infix_expression);
         p.register_infix(TokenKind::Lt, Parser::parse_infix_expression);
         p.register_infix(TokenKind::Gt, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::LParen, Parser::parse_call_expression);
+        p.register_infix(TokenKind::LBracket, Parser::parse_index_expression);
+        p.register_infix(TokenKind::Dot, Parser::parse_member_expression);
+        p.register_infix(TokenKind::DotDot, Parser::parse_range_expression);
+        p.register_infix(TokenKind::DotDotEq, Parser::parse_range_expression);
+        p.register_infix(TokenKind::Assign, Parser::parse_assign_expression);
+        p.register_infix(TokenKind::PlusAssign, Parser::parse_assign_expression);
+        p.register_infix(TokenKind::MinusAssign, Parser::parse_assign_expression);
+        p.register_infix(TokenKind::AsteriskAssign, Parser::parse_assign_expression);
+        p.register_infix(TokenKind::SlashAssign, Parser::parse_assign_expression);
 
         p.next_token();
         p.next_token();
@@ -68,12 +141,25 @@ impl<'a> Parser<'a> {
         p
     }
 
-    fn get_precedence(k: TokenKind) -> Precedence {
+    pub fn with_trace(mut self, enabled: bool) -> Parser<'a> {
+        self.tracer = Rc::new(Tracer::new(enabled));
+        self
+    }
+
+    pub fn get_precedence(k: TokenKind) -> Precedence {
         match k {
+            TokenKind::Assign
+            | TokenKind::PlusAssign
+            | TokenKind::MinusAssign
+            | TokenKind::AsteriskAssign
+            | TokenKind::SlashAssign => Precedence::Assign,
+            TokenKind::DotDot | TokenKind::DotDotEq => Precedence::Range,
             TokenKind::Eq | TokenKind::NotEq => Precedence::Equals,
             TokenKind::Lt | TokenKind::Gt => Precedence::LessGreater,
             TokenKind::Plus | TokenKind::Minus => Precedence::Sum,
             TokenKind::Asterisk | TokenKind::Slash => Precedence::Product,
+            TokenKind::LParen => Precedence::Call,
+            TokenKind::LBracket | TokenKind::Dot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
@@ -96,19 +182,39 @@ impl<'a> Parser<'a> {
 
     fn no_prefix_parse_fn_error(&mut self, k: TokenKind) {
         let msg = format!("no prefix parse function for {} found", k);
-        self.errors.push(msg);
+        self.error(self.cur_token.span, msg);
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(SyntaxError { message, span });
     }
 
-    pub fn errors(&self) -> &Vec<String> {
+    pub fn errors(&self) -> &[SyntaxError] {
         &self.errors
     }
 
+    fn warning(&mut self, span: Span, message: String) {
+        self.warnings.push(SyntaxError { message, span });
+    }
+
+    pub fn warnings(&self) -> &[SyntaxError] {
+        &self.warnings
+    }
+
+    pub fn trace_lines(&self) -> Vec<String> {
+        self.tracer.lines()
+    }
+
+    fn trace(&self, name: &'static str) -> Option<TraceGuard> {
+        self.tracer.begin(name, &self.cur_token, None)
+    }
+
     fn peek_error(&mut self, k: TokenKind) {
         let msg = format!(
             "expected next token to be {}, got {} instead",
             k, self.peek_token.kind
         );
-        self.errors.push(msg)
+        self.error(self.peek_token.span, msg)
     }
 
     fn next_token(&mut self) {
@@ -149,53 +255,205 @@ impl<'a> Parser<'a> {
 
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.cur_token.kind {
-            TokenKind::Let => self.parse_let_statement(),
+            TokenKind::Let => self.parse_let_statement().map(Statement::from),
             TokenKind::Return => self.parse_return_statement(),
+            TokenKind::While => self.parse_while_statement(),
+            TokenKind::For => self.parse_for_statement(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
+            TokenKind::Import => self.parse_import_statement(),
+            TokenKind::Export => self.parse_export_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
-    fn parse_let_statement(&mut self) -> Option<Statement> {
+    fn parse_let_statement(&mut self) -> Option<LetStatement> {
+        let _trace = self.trace("parse_let_statement");
         let token = self.cur_token.clone();
 
-        if !self.expect_peek(TokenKind::Ident) {
-            return None;
-        }
-
-        let name = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+        let pattern = self.parse_binding()?;
 
         if !self.expect_peek(TokenKind::Assign) {
             return None;
         }
 
-        // TODO: セミコロンに遭遇するまで式を読み飛ばしてしまっている
-        while !self.cur_token_is(TokenKind::SemiColon) {
-            self.next_token()
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest);
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
         }
 
-        Some(Statement::LetStatement(LetStatement::new(
-            token,
-            name.clone(),
-            None,
-        )))
+        Some(LetStatement::new(token, pattern, value))
     }
 
     fn parse_return_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_return_statement");
         let token = self.cur_token.clone();
 
         self.next_token();
 
-        // TODO: セミコロンに遭遇するまで式を読み飛ばしてしまっている
-        while !self.cur_token_is(TokenKind::SemiColon) {
-            self.next_token()
+        let return_value = self.parse_expression(Precedence::Lowest);
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
         }
 
         Some(Statement::ReturnStatement(ReturnStatement::new(
-            token, None,
+            token,
+            return_value,
         )))
     }
 
+    fn parse_while_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_while_statement");
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest);
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        self.loops += 1;
+        let body = self.parse_loop_body();
+        self.loops -= 1;
+        let body = body?;
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        Some(WhileStatement::new(token, condition, body).into())
+    }
+
+    // for (x in xs) { ... } と for (k, v in h) { ... }
+    fn parse_for_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_for_statement");
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) || !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let mut variables = vec![Identifier::new(
+            self.cur_token.clone(),
+            &self.cur_token.literal,
+        )];
+
+        if self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            if !self.expect_peek(TokenKind::Ident) {
+                return None;
+            }
+            variables.push(Identifier::new(
+                self.cur_token.clone(),
+                &self.cur_token.literal,
+            ));
+        }
+
+        if !self.expect_peek(TokenKind::In) {
+            return None;
+        }
+
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest);
+
+        if !self.expect_peek(TokenKind::RParen) || !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        self.loops += 1;
+        let body = self.parse_loop_body();
+        self.loops -= 1;
+        let body = body?;
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        Some(ForStatement::new(token, variables, iterable, body).into())
+    }
+
+    // ループの本体も式と同じく深さを数える
+    fn parse_loop_body(&mut self) -> Option<BlockStatement> {
+        self.nested(|p| Some(p.parse_block_statement()))
+    }
+
+    // break と continue
+    fn parse_loop_control(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_loop_control");
+        let token = self.cur_token.clone();
+
+        if self.loops == 0 {
+            let msg = format!("{} outside of loop", token.literal);
+            self.error(token.span, msg);
+        }
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        Some(match token.kind {
+            TokenKind::Break => BreakStatement::new(token).into(),
+            _ => ContinueStatement::new(token).into(),
+        })
+    }
+
+    fn parse_import_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_import_statement");
+        let token = self.cur_token.clone();
+        self.check_top_level(&token);
+
+        if !self.expect_peek(TokenKind::String) {
+            return None;
+        }
+        let path = unquote(&self.cur_token.literal)
+            .map_err(|msg| self.error(self.cur_token.span, msg))
+            .ok()?;
+
+        if !self.expect_peek(TokenKind::As) || !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let alias = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        Some(ImportStatement::new(token, path, alias).into())
+    }
+
+    fn parse_export_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_export_statement");
+        let token = self.cur_token.clone();
+        self.check_top_level(&token);
+
+        if !self.expect_peek(TokenKind::Let) {
+            return None;
+        }
+
+        let stmt = self.parse_let_statement()?;
+        Some(ExportStatement::new(token, stmt).into())
+    }
+
+    fn check_top_level(&mut self, token: &Token) {
+        if self.blocks > 0 {
+            let msg = format!("{} is only allowed at the top level", token.literal);
+            self.error(token.span, msg);
+        }
+    }
+
     fn parse_expression_statement(&mut self) -> Option<Statement> {
+        let _trace = self.trace("parse_expression_statement");
         let stmt = ExpressionStatement::new(
             self.cur_token.clone(),
             self.parse_expression(Precedence::Lowest),
@@ -209,6 +467,28 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        self.nested(|p| p.parse_nested_expression(precedence))
+    }
+
+    // 入れ子を一段深くして読む。いちばん外側に戻ったら、深すぎたエラーのあとに
+    // 外側の構文が重ねたエラーを捨てる
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let (depth, operators) = (self.depth, self.operators);
+        let result = self.nest().and_then(|()| parse(self));
+        (self.depth, self.operators) = (depth, operators);
+        if depth == 0 {
+            if let Some(i) = self.nesting_error.take() {
+                self.errors.truncate(i + 1);
+            }
+        }
+        result
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        // 結合の強さは、式を読み始めるここでだけ記録する
+        let _trace = self
+            .tracer
+            .begin("parse_expression", &self.cur_token, Some(precedence));
         let prefix = match self.prefix_parse_fns.get(&self.cur_token.kind) {
             Some(f) => f,
             None => {
@@ -220,11 +500,19 @@ impl<'a> Parser<'a> {
 
         while !self.peek_token_is(TokenKind::SemiColon) && precedence < self.peek_precedence() {
             let infix = match self.infix_parse_fns.get(&self.peek_token.kind) {
-                Some(f) => f.clone(),
+                Some(f) => *f,
                 None => return left_exp,
             };
 
             self.next_token();
+            if is_infix_operator(self.cur_token.kind) {
+                self.operators += 1;
+                if self.operators > MAX_OPERATORS {
+                    return self.give_up("expression too long");
+                }
+            } else {
+                self.nest()?;
+            }
 
             left_exp = infix(self, left_exp);
         }
@@ -232,15 +520,37 @@ impl<'a> Parser<'a> {
         left_exp
     }
 
+    fn nest(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth <= MAX_NESTING {
+            return Some(());
+        }
+        self.give_up("expression nested too deeply")
+    }
+
+    // 残りの入力を読み捨てる。途中で止めると外側の式が同じ所から何度もやり直す
+    fn give_up<T>(&mut self, message: &str) -> Option<T> {
+        if self.nesting_error.is_none() {
+            self.nesting_error = Some(self.errors.len());
+            self.error(self.cur_token.span, message.to_string());
+        }
+        while !self.peek_token_is(TokenKind::EOF) {
+            self.next_token();
+        }
+        None
+    }
+
     fn parse_identifier(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_identifier");
         Some(Identifier::new(self.cur_token.clone(), self.cur_token.literal.clone()).into())
     }
 
     fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_integer_literal");
         let token = self.cur_token.clone();
         let value = self.cur_token.literal.parse::<i64>().ok().or_else(|| {
             let msg = format!("could not parse {} as integer", self.cur_token.literal);
-            self.errors.push(msg);
+            self.error(self.cur_token.span, msg);
             None
         })?;
 
@@ -248,10 +558,81 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_boolean(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_boolean");
         Some(Boolean::new(self.cur_token.clone(), self.cur_token_is(TokenKind::True)).into())
     }
 
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_string_literal");
+        let token = self.cur_token.clone();
+        let value = unquote(&token.literal)
+            .map_err(|msg| self.error(token.span, msg))
+            .ok()?;
+
+        Some(StringLiteral::new(token, value).into())
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_array_literal");
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(TokenKind::RBracket)?;
+
+        Some(ArrayLiteral::new(token, elements).into())
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_hash_literal");
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenKind::RBrace) {
+            let errors = self.errors.len();
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest);
+
+            let in_arm = pairs.is_empty() && self.arm_body == Some(token.span);
+            if in_arm && !self.peek_token_is(TokenKind::Colon) {
+                self.errors.truncate(errors);
+                return self.block_in_match_arm(token.span);
+            }
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest);
+            pairs.push((key?, value?));
+
+            if !self.peek_token_is(TokenKind::RBrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBrace) {
+            return None;
+        }
+
+        Some(HashLiteral::new(token, pairs).into())
+    }
+
+    // match の腕にブロックは書けない。対応する } まで読み飛ばして、括弧を使うよう知らせる
+    fn block_in_match_arm(&mut self, span: Span) -> Option<Expression> {
+        let msg = "match arm body cannot be a block; use parentheses around an expression instead of braces";
+        self.error(span, msg.to_string());
+        let mut depth = 1;
+        while depth > 0 && !self.peek_token_is(TokenKind::EOF) {
+            self.next_token();
+            match self.cur_token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
     fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_prefix_expression");
         let token = self.cur_token.clone();
         let operator = self.cur_token.literal.clone();
 
@@ -263,6 +644,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_infix_expression(&mut self, left: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_infix_expression");
         let token = self.cur_token.clone();
         let operator = self.cur_token.literal.clone();
         let precedence = self.cur_precedence();
@@ -274,7 +656,41 @@ impl<'a> Parser<'a> {
         Some(InfixExpression::new(token, left, operator, right).into())
     }
 
+    // 中置式と同じく左結合。a..b..c は範囲を端にした範囲になり、評価でエラーになる
+    fn parse_range_expression(&mut self, start: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_range_expression");
+        let token = self.cur_token.clone();
+        let inclusive = self.cur_token_is(TokenKind::DotDotEq);
+        let precedence = self.cur_precedence();
+
+        self.next_token();
+
+        let end = self.parse_expression(precedence);
+
+        Some(RangeExpression::new(token, start, end, inclusive).into())
+    }
+
+    // 右結合。a = b = c は b = c の値を a に入れる
+    fn parse_assign_expression(&mut self, target: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_assign_expression");
+        let token = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+
+        let target = target?;
+        if !is_assignable(&target) {
+            let msg = format!("invalid assignment target: {}", target.to_string());
+            self.error(token.span, msg);
+        }
+
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest);
+
+        Some(AssignExpression::new(token, target, operator, value).into())
+    }
+
     fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_grouped_expression");
         self.next_token();
 
         let exp = self.parse_expression(Precedence::Lowest);
@@ -287,6 +703,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_if_expression(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_if_expression");
         let token = self.cur_token.clone();
 
         if !self.expect_peek(TokenKind::LParen) {
@@ -310,6 +727,17 @@ impl<'a> Parser<'a> {
         if self.peek_token_is(TokenKind::Else) {
             self.next_token();
 
+            // else if はつなげた分だけ入れ子になる
+            if self.peek_token_is(TokenKind::If) {
+                self.next_token();
+                let Some(Expression::IfExpression(next)) = self.nested(Self::parse_if_expression)
+                else {
+                    return None;
+                };
+                expression.alternative = Some(Box::new(next.into_else_block()));
+                return Some(expression.into());
+            }
+
             if !self.expect_peek(TokenKind::LBrace) {
                 return None;
             }
@@ -320,10 +748,241 @@ impl<'a> Parser<'a> {
         Some(expression.into())
     }
 
+    // match (value) { pattern if guard => body, ... }
+    fn parse_match_expression(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_match_expression");
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        self.next_token();
+        let subject = self.parse_expression(Precedence::Lowest);
+
+        if !self.expect_peek(TokenKind::RParen) || !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        let mut arms = vec![];
+        while !self.peek_token_is(TokenKind::RBrace) {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+
+            let mut guard = None;
+            if self.peek_token_is(TokenKind::If) {
+                self.next_token();
+                self.next_token();
+                guard = self.parse_expression(Precedence::Lowest);
+            }
+
+            if !self.expect_peek(TokenKind::FatArrow) {
+                return None;
+            }
+            let arrow = self.cur_token.clone();
+
+            self.next_token();
+            self.arm_body = Some(self.cur_token.span);
+            let body = self.parse_expression(Precedence::Lowest);
+            arms.push(MatchArm::new(arrow, pattern, guard, body));
+
+            if !self.peek_token_is(TokenKind::RBrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBrace) {
+            return None;
+        }
+
+        let expression = MatchExpression::new(token, subject, arms);
+        self.check_exhaustiveness(&expression);
+        Some(expression.into())
+    }
+
+    // 条件のない _ や名前の腕より後ろには届かない。それがなければ、真偽値か
+    // リテラルだけを並べたときに限って漏れを知らせる
+    fn check_exhaustiveness(&mut self, e: &MatchExpression) {
+        let catch_all = e
+            .arms
+            .iter()
+            .position(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable());
+        if let Some(i) = catch_all {
+            for arm in &e.arms[i + 1..] {
+                self.warning(arm.pattern.span(), "unreachable match arm".to_string());
+            }
+            return;
+        }
+
+        let covered = |value: bool| {
+            e.arms.iter().any(|arm| {
+                arm.guard.is_none()
+                    && matches!(&arm.pattern, Pattern::Boolean(b) if b.value == value)
+            })
+        };
+        let all_booleans = !e.arms.is_empty()
+            && e.arms
+                .iter()
+                .all(|arm| matches!(arm.pattern, Pattern::Boolean(_)));
+        let message = if all_booleans {
+            match [true, false].into_iter().find(|&value| !covered(value)) {
+                Some(missing) => format!("non-exhaustive match: `{}` not covered", missing),
+                None => return,
+            }
+        } else if e.arms.iter().all(|arm| {
+            matches!(
+                arm.pattern,
+                Pattern::IntegerLiteral(_) | Pattern::StringLiteral(_) | Pattern::Boolean(_)
+            )
+        }) {
+            "non-exhaustive match: add a `_` arm to cover other values".to_string()
+        } else {
+            return;
+        };
+        self.warning(e.token.span, message);
+    }
+
+    // 式と同じく深さを数える
+    fn parse_pattern(&mut self) -> Option<Pattern> {
+        let _trace = self.trace("parse_pattern");
+        self.nested(Self::parse_nested_pattern)
+    }
+
+    fn parse_nested_pattern(&mut self) -> Option<Pattern> {
+        let token = self.cur_token.clone();
+        match token.kind {
+            TokenKind::Ident if token.literal == "_" => Some(WildcardPattern::new(token).into()),
+            TokenKind::Ident => Some(Identifier::new(token.clone(), token.literal).into()),
+            TokenKind::Int => match self.parse_integer_literal()? {
+                Expression::IntegerLiteral(e) => Some(e.into()),
+                _ => None,
+            },
+            // 負の数は一つのリテラルとして扱う
+            TokenKind::Minus if self.peek_token_is(TokenKind::Int) => {
+                self.next_token();
+                let literal = format!("-{}", self.cur_token.literal);
+                let span = Span::new(token.span.start, self.cur_token.span.end);
+                let Ok(value) = literal.parse::<i64>() else {
+                    let msg = format!("could not parse {} as integer", literal);
+                    self.error(span, msg);
+                    return None;
+                };
+                let token = Token::new(TokenKind::Int, literal).with_span(span);
+                Some(IntegerLiteral::new(token, value).into())
+            }
+            TokenKind::String => match self.parse_string_literal()? {
+                Expression::StringLiteral(e) => Some(e.into()),
+                _ => None,
+            },
+            TokenKind::True | TokenKind::False => {
+                Some(Boolean::new(token, self.cur_token_is(TokenKind::True)).into())
+            }
+            TokenKind::LBracket => self.parse_array_pattern(),
+            TokenKind::LBrace => self.parse_hash_pattern(),
+            kind => {
+                let msg = format!("expected pattern, got {} instead", kind);
+                self.error(token.span, msg);
+                None
+            }
+        }
+    }
+
+    // 残りの要素は最後にだけ書ける
+    fn parse_array_pattern(&mut self) -> Option<Pattern> {
+        let _trace = self.trace("parse_array_pattern");
+        let token = self.cur_token.clone();
+        let mut elements = vec![];
+        let mut rest = None;
+
+        while !self.peek_token_is(TokenKind::RBracket) {
+            self.next_token();
+
+            if self.cur_token_is(TokenKind::DotDot) {
+                let rest_token = self.cur_token.clone();
+                let mut name = None;
+                if self.peek_token_is(TokenKind::Ident) {
+                    self.next_token();
+                    if self.cur_token.literal != "_" {
+                        name = Some(Identifier::new(
+                            self.cur_token.clone(),
+                            &self.cur_token.literal,
+                        ));
+                    }
+                }
+                rest = Some(RestPattern::new(rest_token, name));
+
+                if self.peek_token_is(TokenKind::Comma) {
+                    self.next_token();
+                }
+                if !self.peek_token_is(TokenKind::RBracket) {
+                    let msg = "rest pattern must come last".to_string();
+                    self.error(self.peek_token.span, msg);
+                    return None;
+                }
+                break;
+            }
+
+            elements.push(self.parse_pattern()?);
+
+            if !self.peek_token_is(TokenKind::RBracket) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBracket) {
+            return None;
+        }
+
+        Some(ArrayPattern::new(token, elements, rest).into())
+    }
+
+    fn parse_hash_pattern(&mut self) -> Option<Pattern> {
+        let _trace = self.trace("parse_hash_pattern");
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenKind::RBrace) {
+            self.next_token();
+            let key = match self.parse_pattern()? {
+                Pattern::IntegerLiteral(e) => e.into(),
+                Pattern::StringLiteral(e) => e.into(),
+                Pattern::Boolean(e) => e.into(),
+                other => {
+                    let msg = format!(
+                        "hash pattern key must be a literal, got {}",
+                        other.to_string()
+                    );
+                    self.error(other.span(), msg);
+                    return None;
+                }
+            };
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_pattern()?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::RBrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBrace) {
+            return None;
+        }
+
+        Some(HashPattern::new(token, pairs).into())
+    }
+
     fn parse_block_statement(&mut self) -> BlockStatement {
+        let _trace = self.trace("parse_block_statement");
         let mut block = BlockStatement::new(self.cur_token.clone(), vec![]);
 
         self.next_token();
+        self.blocks += 1;
 
         while !self.cur_token_is(TokenKind::RBrace) && !self.cur_token_is(TokenKind::EOF) {
             let stmt = self.parse_statement();
@@ -333,6 +992,152 @@ impl<'a> Parser<'a> {
             self.next_token();
         }
 
+        self.blocks -= 1;
         block
     }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let _trace = self.trace("parse_function_literal");
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        // 関数の中からは外側のループを抜けられない
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.parse_block_statement();
+        self.loops = loops;
+
+        Some(FunctionLiteral::new(token, parameters, body).into())
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Pattern>> {
+        let paren = self.cur_token.span;
+        let mut parameters = vec![];
+
+        if self.peek_token_is(TokenKind::RParen) {
+            self.next_token();
+            return Some(parameters);
+        }
+
+        parameters.push(self.parse_binding()?);
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            parameters.push(self.parse_binding()?);
+        }
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        if parameters.len() > MAX_ARGUMENTS {
+            self.error(paren, format!("too many parameters: {}", parameters.len()));
+        }
+        let mut names: Vec<&str> = vec![];
+        for name in parameters.iter().flat_map(Pattern::bindings) {
+            if names.contains(&name.value.as_str()) {
+                let msg = format!("duplicate parameter {}", name.value);
+                self.error(name.token.span, msg);
+            }
+            names.push(&name.value);
+        }
+
+        Some(parameters)
+    }
+
+    // let と引数の束縛先。名前か配列・ハッシュの形に限り、リテラルは書けない
+    fn parse_binding(&mut self) -> Option<Pattern> {
+        if self.peek_token_is(TokenKind::LBracket) || self.peek_token_is(TokenKind::LBrace) {
+            self.next_token();
+        } else if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        self.parse_pattern()
+    }
+
+    fn parse_call_expression(&mut self, function: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_call_expression");
+        let token = self.cur_token.clone();
+        let arguments = self.parse_expression_list(TokenKind::RParen)?;
+        if arguments.len() > MAX_ARGUMENTS {
+            let msg = format!("too many arguments: {}", arguments.len());
+            self.error(token.span, msg);
+        }
+
+        Some(CallExpression::new(token, function?, arguments).into())
+    }
+
+    fn parse_index_expression(&mut self, left: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_index_expression");
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest);
+
+        if !self.expect_peek(TokenKind::RBracket) {
+            return None;
+        }
+
+        Some(IndexExpression::new(token, left?, index?).into())
+    }
+
+    fn parse_member_expression(&mut self, left: Option<Expression>) -> Option<Expression> {
+        let _trace = self.trace("parse_member_expression");
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let property = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+
+        Some(MemberExpression::new(token, left?, property).into())
+    }
+
+    // 呼び出しの引数や配列の要素のような、カンマ区切りの式の並び
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<Expression>> {
+        let mut list = vec![];
+
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(list)
+    }
+}
+
+// InfixExpression になる二項演算子。左につなげても入れ子とは数えない
+pub(crate) fn is_infix_operator(k: TokenKind) -> bool {
+    matches!(
+        k,
+        TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Asterisk
+            | TokenKind::Slash
+            | TokenKind::Eq
+            | TokenKind::NotEq
+            | TokenKind::Lt
+            | TokenKind::Gt
+    )
 }
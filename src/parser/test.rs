@@ -1,7 +1,15 @@
+mod roundtrip;
+
 use super::*;
+use crate::ast::json::ToJson;
+use crate::ast::sexp::ToSexp;
 use crate::ast::*;
+use crate::engine::{Engine, Interpreter};
 use crate::lexer::Lexer;
+use crate::resolver::Resolver;
+use crate::{cst, formatter, lint, typecheck};
 use std::any::Any;
+use std::thread;
 
 fn test_let_statement(s: &Statement, name: &str) {
     assert_eq!(s.token_literal(), "let");
@@ -9,8 +17,9 @@ fn test_let_statement(s: &Statement, name: &str) {
     assert!(matches!(s, Statement::LetStatement(_)));
     let let_stmt: LetStatement = s.try_into().unwrap();
 
-    assert_eq!(let_stmt.name.value, name);
-    assert_eq!(let_stmt.name.token_literal(), name);
+    let identifier = let_stmt.pattern.as_identifier().unwrap();
+    assert_eq!(identifier.value, name);
+    assert_eq!(identifier.token_literal(), name);
 }
 
 fn test_integer_literal(il: Expression, value: i64) {
@@ -66,7 +75,7 @@ macro_rules! test_infix_expression {
 
 fn check_parser_errors(p: &Parser) {
     let errors = p.errors();
-    if errors.len() == 0 {
+    if errors.is_empty() {
         return;
     }
 
@@ -91,13 +100,35 @@ let foobar = 838383;
     check_parser_errors(&p);
     assert_eq!(program.statements.len(), 3);
 
-    let tests = vec!["x", "y", "foobar"];
+    let tests = ["x", "y", "foobar"];
     for (i, tt) in tests.iter().enumerate() {
         let stmt = &program.statements[i];
         test_let_statement(stmt, tt);
     }
 }
 
+#[test]
+fn test_let_statement_values() {
+    let tests: Vec<(&str, &str, &dyn Any)> = vec![
+        ("let x = 5;", "x", &5),
+        ("let y = true;", "y", &true),
+        ("let foobar = y;", "foobar", &"y"),
+    ];
+
+    for (input, name, value) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(program.statements.len(), 1);
+
+        let stmt = &program.statements[0];
+        test_let_statement(stmt, name);
+        let let_stmt: LetStatement = stmt.try_into().unwrap();
+        test_literal_expression!(let_stmt.value.unwrap(), value);
+    }
+}
+
 #[test]
 fn test_return_statements() {
     let input = "
@@ -112,9 +143,201 @@ return 993322;
     check_parser_errors(&p);
     assert_eq!(program.statements.len(), 3);
 
-    for stmt in program.statements {
+    let values = [5, 10, 993322];
+    for (stmt, value) in program.statements.into_iter().zip(values) {
         assert!(matches!(stmt, Statement::ReturnStatement(_)));
         assert_eq!(stmt.token_literal(), "return");
+        let return_stmt: ReturnStatement = stmt.try_into().unwrap();
+        test_integer_literal(return_stmt.return_value.unwrap(), value);
+    }
+}
+
+#[test]
+fn test_while_statement() {
+    let input = "while (x < 10) { if (x == 5) { break; } continue }; y";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert_eq!(program.statements.len(), 2);
+
+    let stmt: WhileStatement = (&program.statements[0]).try_into().unwrap();
+    test_infix_expression!(stmt.condition.clone().unwrap(), &"x", "<", &10);
+    assert_eq!(stmt.body.statements.len(), 2);
+    assert!(matches!(
+        stmt.body.statements[1],
+        Statement::ContinueStatement(_)
+    ));
+    assert_eq!(
+        program.statements[0].to_string(),
+        "while (x < 10) { if (x == 5) { break; };continue; }"
+    );
+}
+
+#[test]
+fn test_for_statement() {
+    let tests = vec![
+        ("for (x in xs) { x }", vec!["x"], "for (x in xs) { x }"),
+        (
+            "for (k, v in {1: 2}) { break; };",
+            vec!["k", "v"],
+            "for (k, v in {1: 2}) { break; }",
+        ),
+        (
+            "for (i in 0..=10) { continue }",
+            vec!["i"],
+            "for (i in (0..=10)) { continue; }",
+        ),
+    ];
+
+    for (input, variables, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(program.statements.len(), 1, "input: {}", input);
+
+        let stmt: ForStatement = (&program.statements[0]).try_into().unwrap();
+        let names: Vec<&str> = stmt.variables.iter().map(|v| v.value.as_str()).collect();
+        assert_eq!(names, variables, "input: {}", input);
+        assert_eq!(program.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_for_statement_errors() {
+    let tests = vec![
+        (
+            "for (x xs) {}",
+            "expected next token to be IN, got IDENT instead",
+        ),
+        (
+            "for (a, b, c in xs) {}",
+            "expected next token to be IN, got , instead",
+        ),
+        (
+            "for (1 in xs) {}",
+            "expected next token to be IDENT, got INT instead",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        assert_eq!(
+            p.errors().first().map(|e| e.message.as_str()),
+            Some(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_assign_expression_errors() {
+    let tests = vec![
+        ("1 = 2", "invalid assignment target: 1"),
+        ("f(x) += 1", "invalid assignment target: f(x)"),
+        ("a + b = c", "invalid assignment target: (a + b)"),
+        ("[1][0] = 2", "invalid assignment target: ([1][0])"),
+        ("lib.x = 1", "invalid assignment target: (lib.x)"),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        assert_eq!(
+            p.errors().first().map(|e| e.message.as_str()),
+            Some(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+// 関数の本体からは外側のループを抜けられない
+#[test]
+fn test_loop_control_outside_of_loop() {
+    let tests = vec![
+        ("break;", vec!["break outside of loop"]),
+        ("if (x) { continue }", vec!["continue outside of loop"]),
+        (
+            "while (x) { fn() { break; continue; } }",
+            vec!["break outside of loop", "continue outside of loop"],
+        ),
+        ("while (x) { fn() { while (y) { break } } }", vec![]),
+        ("for (x in xs) { if (x) { break } }", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let errors: Vec<_> = p.errors().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors, expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_module_statements() {
+    let input = r#"import "lib/math.monkey" as math; export let [a, b] = math.pair;"#;
+
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert_eq!(program.statements.len(), 2);
+
+    let import: ImportStatement = (&program.statements[0]).try_into().unwrap();
+    assert_eq!(import.path, "lib/math.monkey");
+    assert_eq!(import.alias.value, "math");
+
+    let export: ExportStatement = (&program.statements[1]).try_into().unwrap();
+    assert_eq!(export.statement.pattern.to_string(), "[a, b]");
+    let member: MemberExpression = export.statement.value.clone().unwrap().try_into().unwrap();
+    test_identifier(*member.object, "math".to_string());
+    assert_eq!(member.property.value, "pair");
+}
+
+// import と export は関数やブロックの中には書けない
+#[test]
+fn test_module_statement_errors() {
+    let tests = vec![
+        (
+            r#"fn() { import "a" as a; }"#,
+            vec!["import is only allowed at the top level"],
+        ),
+        (
+            "if (x) { export let y = 1; }",
+            vec!["export is only allowed at the top level"],
+        ),
+        (
+            "import lib;",
+            vec!["expected next token to be STRING, got IDENT instead"],
+        ),
+        (
+            r#"import "a" b"#,
+            vec!["expected next token to be AS, got IDENT instead"],
+        ),
+        (
+            "export y = 1",
+            vec!["expected next token to be LET, got IDENT instead"],
+        ),
+        (
+            "a.1",
+            vec!["expected next token to be IDENT, got INT instead"],
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let errors: Vec<_> = p.errors().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors, expected, "input: {}", input);
     }
 }
 
@@ -208,7 +431,7 @@ fn test_operator_precedence_parsing() {
         ("a * b / c", "((a * b) / c)"),
         ("a + b / c", "(a + (b / c))"),
         ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
-        ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+        ("3 + 4; -5 * 5", "(3 + 4);((-5) * 5)"),
         ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
         ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
         (
@@ -224,6 +447,39 @@ fn test_operator_precedence_parsing() {
         ("2 / (5 + 5)", "(2 / (5 + 5))"),
         ("-(5 + 5)", "(-(5 + 5))"),
         ("!(true == true)", "(!(true == true))"),
+        ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+        (
+            "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+            "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+        ),
+        (
+            "add(a + b + c * d / f + g)",
+            "add((((a + b) + ((c * d) / f)) + g))",
+        ),
+        (
+            "a * [1, 2, 3, 4][b * c] * d",
+            "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+        ),
+        (
+            "add(a * b[2], b[1], 2 * [1, 2][1])",
+            "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+        ),
+        ("-a[0]", "(-(a[0]))"),
+        ("f(x)[0][1]", "((f(x)[0])[1])"),
+        ("0..n + 1", "(0..(n + 1))"),
+        ("a..=b * 2", "(a..=(b * 2))"),
+        ("x == 0..3", "((x == 0)..3)"),
+        ("a..b..c", "((a..b)..c)"),
+        ("-1..len(xs)", "((-1)..len(xs))"),
+        ("x = 1 + 2", "(x = (1 + 2))"),
+        ("a = b = c", "(a = (b = c))"),
+        ("a += b -= c", "(a += (b -= c))"),
+        ("x *= y == 0..3", "(x *= ((y == 0)..3))"),
+        ("a[i][j] /= 2", "(((a[i])[j]) /= 2)"),
+        ("(a[0]) = fn(x) { x }", "((a[0]) = fn(x) { x })"),
+        ("-a.b * c", "((-(a.b)) * c)"),
+        ("a.b.c(d)[0]", "(((a.b).c)(d)[0])"),
+        ("x = lib.f(1).y", "(x = ((lib.f)(1).y))"),
     ];
 
     for (input, expected) in tests {
@@ -274,3 +530,721 @@ fn test_if_else_expression() {
         .unwrap();
     test_identifier(alternative.expression.unwrap(), "y".to_string());
 }
+
+#[test]
+fn test_else_if_expression() {
+    let input = "if (x < y) { x } else if (x > y) { y } else { z }";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert_eq!(program.statements.len(), 1);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let exp: IfExpression = stmt.expression.unwrap().try_into().unwrap();
+    let next = exp.else_if().expect("else if");
+    test_infix_expression!(*next.condition.as_ref().unwrap().clone(), &"x", ">", &"y");
+    let consequence: ExpressionStatement = (&next.consequence.statements[0]).try_into().unwrap();
+    test_identifier(consequence.expression.unwrap(), "y".to_string());
+    let alternative: ExpressionStatement = (&next.alternative.as_ref().unwrap().statements[0])
+        .try_into()
+        .unwrap();
+    test_identifier(alternative.expression.unwrap(), "z".to_string());
+    assert_eq!(program.to_string(), input);
+}
+
+// else if は入れ子の if と同じ AST になり、to_string からパースし直しても変わらない
+#[test]
+fn test_else_if_chains() {
+    let tests = vec![
+        (
+            "if (a) { 1 } else if (b) { 2 }",
+            "if (a) { 1 } else if (b) { 2 }",
+        ),
+        (
+            "if (a) { 1 } else if (b) { 2 } else if (c) { 3 } else { 4 }",
+            "if (a) { 1 } else if (b) { 2 } else if (c) { 3 } else { 4 }",
+        ),
+        (
+            "if (a) { 1 } else { if (b) { 2 } }",
+            "if (a) { 1 } else if (b) { 2 }",
+        ),
+        (
+            "let x = if (a) { 1 } else if (b) { 2 } else { 3 } + 1;",
+            "let x = (if (a) { 1 } else if (b) { 2 } else { 3 } + 1);",
+        ),
+        (
+            "if (a) { 1 } else if (b) { 2 } let y = 3;",
+            "if (a) { 1 } else if (b) { 2 };let y = 3;",
+        ),
+        (
+            "if (a) { 1 } else { if (b) { 2 }; 3 }",
+            "if (a) { 1 } else { if (b) { 2 };3 }",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(program.to_string(), expected, "input: {}", input);
+
+        let mut l = Lexer::new(expected);
+        let mut p = Parser::new(&mut l);
+        let reparsed = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(reparsed.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_match_expression() {
+    let input = "match (x) { [a, ..rest] if a > 0 => a, {\"k\": -1} => 0, _ => 1, }";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert!(p.warnings().is_empty());
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let exp: MatchExpression = stmt.expression.unwrap().try_into().unwrap();
+    test_identifier(*exp.subject.unwrap(), "x".to_string());
+    assert_eq!(exp.arms.len(), 3);
+
+    let array: ArrayPattern = exp.arms[0].pattern.clone().try_into().unwrap();
+    assert_eq!(array.elements.len(), 1);
+    assert_eq!(array.rest.unwrap().name.unwrap().value, "rest");
+    test_infix_expression!(exp.arms[0].guard.clone().unwrap(), &"a", ">", &0);
+    test_identifier(exp.arms[0].body.clone().unwrap(), "a".to_string());
+
+    let hash: HashPattern = exp.arms[1].pattern.clone().try_into().unwrap();
+    let literal: IntegerLiteral = hash.pairs[0].1.clone().try_into().unwrap();
+    assert_eq!(literal.value, -1);
+    assert!(exp.arms[1].guard.is_none());
+
+    assert!(matches!(exp.arms[2].pattern, Pattern::WildcardPattern(_)));
+}
+
+// パターンも to_string からパースし直して変わらない
+#[test]
+fn test_match_patterns() {
+    let tests = vec![
+        ("match (x) {}", "match (x) { }"),
+        ("match (x) { 1 => a, }", "match (x) { 1 => a }"),
+        (
+            r#"match (x) { -5 => 1, "s" => 2, true => 3, y => y }"#,
+            r#"match (x) { -5 => 1, "s" => 2, true => 3, y => y }"#,
+        ),
+        (
+            "match (x) { [] => 0, [a, [b, _]] => 1, [..] => 2, [h, .._] => 3, [.., ] => 4 }",
+            "match (x) { [] => 0, [a, [b, _]] => 1, [..] => 2, [h, ..] => 3, [..] => 4 }",
+        ),
+        (
+            r#"match (h) { {} => 0, {"a": [x, ..t], 1: {true: _}} if x => t }"#,
+            r#"match (h) { {} => 0, {"a": [x, ..t], 1: {true: _}} if x => t }"#,
+        ),
+        (
+            "match (x) { _ => {1: 2} } + 1",
+            "(match (x) { _ => {1: 2} } + 1)",
+        ),
+        (
+            "match (x) { a if a == 1 => match (a) { _ => 2 }, _ => fn() { 3 } }",
+            "match (x) { a if (a == 1) => match (a) { _ => 2 }, _ => fn() { 3 } }",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(program.to_string(), expected, "input: {}", input);
+
+        let mut l = Lexer::new(expected);
+        let mut p = Parser::new(&mut l);
+        let reparsed = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(reparsed.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_match_errors() {
+    let tests = vec![
+        (
+            "match x { _ => 1 }",
+            "expected next token to be (, got IDENT instead",
+        ),
+        (
+            "match (x) _ => 1",
+            "expected next token to be {, got IDENT instead",
+        ),
+        (
+            "match (x) { 1 + 2 => 3 }",
+            "expected next token to be =>, got + instead",
+        ),
+        (
+            "match (x) { _ 1 }",
+            "expected next token to be =>, got INT instead",
+        ),
+        (
+            "match (x) { _ => 1 _ => 2 }",
+            "expected next token to be ,, got IDENT instead",
+        ),
+        ("match (x) { (a) => 1 }", "expected pattern, got ( instead"),
+        ("match (x) { [..a, b] => 1 }", "rest pattern must come last"),
+        (
+            "match (x) { {a: 1} => 1 }",
+            "hash pattern key must be a literal, got a",
+        ),
+        (
+            "match (x) { {[1]: 1} => 1 }",
+            "hash pattern key must be a literal, got [1]",
+        ),
+        (
+            "match (x) { -99999999999999999999 => 1 }",
+            "could not parse -99999999999999999999 as integer",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        assert_eq!(
+            p.errors().first().map(|e| e.message.as_str()),
+            Some(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+// => のあとの { はハッシュ。ブロックのつもりで書いたものは、ひとつのエラーにまとめる
+#[test]
+fn test_block_in_match_arm() {
+    let msg =
+        "match arm body cannot be a block; use parentheses around an expression instead of braces";
+    let tests = vec![
+        ("match (x) { _ => { s = 5 } }", vec![(msg, (17, 18))]),
+        (
+            "match (x) { 1 => { let y = 1; { y } }, _ => { f() } }; 2",
+            vec![(msg, (17, 18)), (msg, (44, 45))],
+        ),
+        ("match (x) { _ => (s = 5) }", vec![]),
+        (r#"match (x) { _ => { "a": 1 }["a"] }"#, vec![]),
+        ("match (x) { _ => {} }", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let errors: Vec<(&str, (usize, usize))> = p
+            .errors()
+            .iter()
+            .map(|e| (e.message.as_str(), (e.span.start, e.span.end)))
+            .collect();
+        assert_eq!(errors, expected, "input: {}", input);
+    }
+}
+
+// VM と同じく、引数は MAX_ARGUMENTS 個まで
+#[test]
+fn test_argument_limit() {
+    let list = |n: usize, item: &str| vec![item; n].join(", ");
+    let tests = vec![
+        (format!("f({})", list(MAX_ARGUMENTS, "1")), None),
+        (
+            format!("f({})", list(MAX_ARGUMENTS + 1, "1")),
+            Some(("too many arguments: 256", (1, 2))),
+        ),
+        (format!("fn({}) {{}}", list(MAX_ARGUMENTS, "_")), None),
+        (
+            format!("fn({}) {{}}", list(MAX_ARGUMENTS + 1, "_")),
+            Some(("too many parameters: 256", (2, 3))),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(&input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let error = p
+            .errors()
+            .first()
+            .map(|e| (e.message.as_str(), (e.span.start, e.span.end)));
+        assert_eq!(error, expected, "input: {}", input);
+    }
+}
+
+// let と引数には名前のほかに配列とハッシュの形を書ける
+#[test]
+fn test_binding_patterns() {
+    let tests = vec![
+        ("let [a, b] = pair;", "let [a, b] = pair;"),
+        ("let [head, ..tail] = list", "let [head, ..tail] = list;"),
+        (
+            r#"let {"x": x, "y": [y, ..]} = point;"#,
+            r#"let {"x": x, "y": [y, ..]} = point;"#,
+        ),
+        ("let _ = f();", "let _ = f();"),
+        (
+            r#"fn([a, _], {"k": v}, c) { a }"#,
+            r#"fn([a, _], {"k": v}, c) { a }"#,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+        assert_eq!(program.to_string(), expected, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_binding_pattern_errors() {
+    let tests = vec![
+        (
+            "let 1 = x;",
+            "expected next token to be IDENT, got INT instead",
+        ),
+        (
+            r#"let "a" = x;"#,
+            "expected next token to be IDENT, got STRING instead",
+        ),
+        (
+            "let [a, ..] x;",
+            "expected next token to be =, got IDENT instead",
+        ),
+        (
+            "fn(true) {}",
+            "expected next token to be IDENT, got TRUE instead",
+        ),
+        ("fn(a, [b, a]) {}", "duplicate parameter a"),
+        (r#"fn({"k": c, 1: [c]}) {}"#, "duplicate parameter c"),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        assert_eq!(
+            p.errors().first().map(|e| e.message.as_str()),
+            Some(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_match_warnings() {
+    let tests = vec![
+        ("match (x) { _ => 1 }", vec![]),
+        ("match (x) { [a] => 1 }", vec![]),
+        ("match (x) { true => 1, false => 2 }", vec![]),
+        (
+            "match (x) { true => 1 }",
+            vec!["non-exhaustive match: `false` not covered"],
+        ),
+        (
+            "match (x) { true => 1, false if y => 2 }",
+            vec!["non-exhaustive match: `false` not covered"],
+        ),
+        (
+            r#"match (x) { 1 => 1, "a" => 2 }"#,
+            vec!["non-exhaustive match: add a `_` arm to cover other values"],
+        ),
+        ("match (x) { a if a => 1, 1 => 2 }", vec![]),
+        (
+            "match (x) { a => 1, _ => 2, 3 => 3 }",
+            vec!["unreachable match arm", "unreachable match arm"],
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        check_parser_errors(&p);
+        let warnings: Vec<&str> = p.warnings().iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(warnings, expected, "input: {}", input);
+    }
+
+    let mut l = Lexer::new("let y = match (x) { a => 1, 2 => 2 };");
+    let mut p = Parser::new(&mut l);
+    p.parse_program();
+    assert_eq!(p.warnings()[0].span, Span::new(28, 29));
+}
+
+#[test]
+fn test_else_if_errors() {
+    let tests = vec![
+        (
+            "if (a) { 1 } else if { 2 }",
+            "expected next token to be (, got { instead",
+        ),
+        (
+            "if (a) { 1 } else if (b) 2",
+            "expected next token to be {, got INT instead",
+        ),
+        (
+            "if (a) { 1 } else x",
+            "expected next token to be {, got IDENT instead",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        assert_eq!(
+            p.errors().first().map(|e| e.message.as_str()),
+            Some(expected),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_function_literal_parsing() {
+    let input = "fn(x, y) { x + y; }";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert_eq!(program.statements.len(), 1);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let function: FunctionLiteral = stmt.expression.unwrap().try_into().unwrap();
+    assert_eq!(function.parameters.len(), 2);
+    let parameter = function.parameters[0].as_identifier().unwrap().clone();
+    test_literal_expression!(parameter.into(), &"x");
+    let parameter = function.parameters[1].as_identifier().unwrap().clone();
+    test_literal_expression!(parameter.into(), &"y");
+    assert_eq!(function.body.statements.len(), 1);
+    let body: ExpressionStatement = (&function.body.statements[0]).try_into().unwrap();
+    let exp = body.expression.unwrap();
+    test_infix_expression!(exp, &"x", "+", &"y");
+}
+
+#[test]
+fn test_function_parameter_parsing() {
+    let tests = vec![
+        ("fn() {};", vec![]),
+        ("fn(x) {};", vec!["x"]),
+        ("fn(x, y, z) {};", vec!["x", "y", "z"]),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+
+        let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+        let function: FunctionLiteral = stmt.expression.unwrap().try_into().unwrap();
+        let names: Vec<String> = function.parameters.iter().map(|p| p.to_string()).collect();
+        assert_eq!(names, expected);
+    }
+}
+
+#[test]
+fn test_call_expression_parsing() {
+    let input = "add(1, 2 * 3, 4 + 5);";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    assert_eq!(program.statements.len(), 1);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let exp: CallExpression = stmt.expression.unwrap().try_into().unwrap();
+    test_identifier(*exp.function, "add".to_string());
+    assert_eq!(exp.arguments.len(), 3);
+    test_literal_expression!(exp.arguments[0].clone(), &1);
+    test_infix_expression!(&exp.arguments[1], &2, "*", &3);
+    test_infix_expression!(&exp.arguments[2], &4, "+", &5);
+}
+
+#[test]
+fn test_string_literal_expression() {
+    let input = r#""hello \"world\"\n";"#;
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let literal: StringLiteral = stmt.expression.unwrap().try_into().unwrap();
+    assert_eq!(literal.value, "hello \"world\"\n");
+}
+
+// 入れ子の上限を超えたら、閉じ括弧が足りないというエラーは重ねない。
+// 左につなげた二項演算子は入れ子と数えない
+#[test]
+fn test_nesting_limit() {
+    let n = MAX_NESTING;
+    let nested = Some("expression nested too deeply");
+    let tests = vec![
+        (format!("{}1{}", "(".repeat(n - 1), ")".repeat(n - 1)), None),
+        (format!("{}1{}; 2", "(".repeat(n), ")".repeat(n)), nested),
+        (format!("{}1", "-".repeat(n + 1)), nested),
+        (
+            format!("{}{}", "[".repeat(n + 1), "]".repeat(n + 1)),
+            nested,
+        ),
+        (format!("1{}", "+1".repeat(n * 4)), None),
+        (format!("f{}", "()".repeat(n - 1)), None),
+        (format!("f{}", "()".repeat(n)), nested),
+        (format!("a{}", "[0]".repeat(n)), nested),
+        (format!("1{}", "+(1".repeat(n)), nested),
+        (
+            format!("if (x) {{}}{}", " else if (x) {{}}".repeat(n / 2)),
+            None,
+        ),
+        (
+            format!("if (x) {{}}{}", " else if (x) {{}}".repeat(30000)),
+            nested,
+        ),
+        (
+            format!("{}{}", "while (x) {".repeat(n - 1), "}".repeat(n - 1)),
+            None,
+        ),
+        (
+            format!("{}{}", "while (x) {".repeat(5000), "}".repeat(5000)),
+            nested,
+        ),
+        (
+            format!("{}{}", "for (x in y) {".repeat(5000), "}".repeat(5000)),
+            nested,
+        ),
+        (
+            format!("1{}", "+1".repeat(MAX_OPERATORS + 1)),
+            Some("expression too long"),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(&input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let errors: Vec<_> = p.errors().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors, Vec::from_iter(expected), "input: {}", input);
+    }
+}
+
+// 上限いっぱいの式でも、構文解析の後に木をたどる処理がどれも既定の大きさのスレッドで動く
+#[test]
+fn test_longest_expressions_in_every_pass() {
+    let arrays = MAX_NESTING - 2;
+    let inputs = [
+        format!(
+            "let f = fn() {{ f }};\n{}1{}{}",
+            "[".repeat(arrays),
+            " + 1".repeat(MAX_OPERATORS),
+            "]".repeat(arrays)
+        ),
+        format!(
+            "let f = fn() {{ f }};\n{}f{}{}",
+            "[".repeat(arrays),
+            " == f".repeat(MAX_OPERATORS),
+            "]".repeat(arrays)
+        ),
+        format!(
+            "let f = fn() {{ f }};\nf{} == f",
+            "()".repeat(MAX_NESTING - 2)
+        ),
+    ];
+
+    for input in inputs {
+        thread::spawn(move || {
+            let mut l = Lexer::new(&input);
+            let mut p = Parser::new(&mut l);
+            let program = p.parse_program();
+            check_parser_errors(&p);
+
+            let json = program.to_json();
+            json.pretty();
+            json.to_string();
+            assert_eq!(Program::from_json(&json).as_ref(), Ok(&program));
+            program.to_sexp();
+            program.to_string();
+
+            formatter::format(&input).unwrap();
+            let resolution = Resolver::new().resolve(&program);
+            assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+            typecheck::check(&program, &resolution);
+            lint::lint(&input, &lint::Config::default()).unwrap();
+
+            let parse = cst::parse(&input);
+            assert_eq!(parse.root.to_program(), program);
+            assert_eq!(parse.root.text(), input);
+            parse.root.dump();
+
+            let results = [Engine::Eval, Engine::Vm].map(|engine| {
+                Interpreter::new(engine)
+                    .run(&program)
+                    .map(|r| r.to_string())
+            });
+            assert_eq!(results[0], results[1]);
+        })
+        .join()
+        .expect("a pass overflowed the thread's stack");
+    }
+}
+
+#[test]
+fn test_string_literal_errors() {
+    let tests = vec![
+        (r#""abc"#, "unterminated string literal"),
+        (r#""a\qb""#, "unknown escape sequence \\q"),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let errors: Vec<_> = p.errors().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors, vec![expected], "input: {}", input);
+    }
+}
+
+#[test]
+fn test_array_literal_parsing() {
+    let input = "[1, 2 * 2, 3 + 3]";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let array: ArrayLiteral = stmt.expression.unwrap().try_into().unwrap();
+    assert_eq!(array.elements.len(), 3);
+    test_literal_expression!(array.elements[0].clone(), &1);
+    test_infix_expression!(&array.elements[1], &2, "*", &2);
+    test_infix_expression!(&array.elements[2], &3, "+", &3);
+}
+
+#[test]
+fn test_index_expression_parsing() {
+    let input = "myArray[1 + 1]";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+    let exp: IndexExpression = stmt.expression.unwrap().try_into().unwrap();
+    test_identifier(*exp.left, "myArray".to_string());
+    test_infix_expression!(&*exp.index, &1, "+", &1);
+}
+
+#[test]
+fn test_hash_literal_parsing() {
+    let tests = vec![
+        ("{}", "{}"),
+        (r#"{"one": 1, "two": 2}"#, r#"{"one": 1, "two": 2}"#),
+        ("{true: 1, 2: fn(x) { x }}", "{true: 1, 2: fn(x) { x }}"),
+        (
+            r#"{"one": 0 + 1, "two": 10 - 8,}"#,
+            r#"{"one": (0 + 1), "two": (10 - 8)}"#,
+        ),
+        (r#"{"a": 1}["a"]"#, r#"({"a": 1}["a"])"#),
+    ];
+
+    for (input, expected) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+
+        let stmt: ExpressionStatement = (&program.statements[0]).try_into().unwrap();
+        let exp = stmt.expression.unwrap();
+        assert_eq!(exp.to_string(), expected, "input: {}", input);
+    }
+
+    let mut l = Lexer::new(r#"{"a" 1}"#);
+    let mut p = Parser::new(&mut l);
+    p.parse_program();
+    assert_eq!(
+        p.errors()[0].message,
+        "expected next token to be :, got INT instead"
+    );
+}
+
+#[test]
+fn test_trace() {
+    let input = "1 + 2 * 3";
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l).with_trace(true);
+    p.parse_program();
+    check_parser_errors(&p);
+
+    let expected = vec![
+        "BEGIN parse_expression_statement (token: INT \"1\")",
+        "  BEGIN parse_expression (token: INT \"1\", precedence: Lowest)",
+        "    BEGIN parse_integer_literal (token: INT \"1\")",
+        "    END parse_integer_literal",
+        "    BEGIN parse_infix_expression (token: + \"+\")",
+        "      BEGIN parse_expression (token: INT \"2\", precedence: Sum)",
+        "        BEGIN parse_integer_literal (token: INT \"2\")",
+        "        END parse_integer_literal",
+        "        BEGIN parse_infix_expression (token: * \"*\")",
+        "          BEGIN parse_expression (token: INT \"3\", precedence: Product)",
+        "            BEGIN parse_integer_literal (token: INT \"3\")",
+        "            END parse_integer_literal",
+        "          END parse_expression",
+        "        END parse_infix_expression",
+        "      END parse_expression",
+        "    END parse_infix_expression",
+        "  END parse_expression",
+        "END parse_expression_statement",
+    ];
+    assert_eq!(p.trace_lines(), expected);
+}
+
+#[test]
+fn test_trace_disabled_by_default() {
+    let mut l = Lexer::new("1 + 2");
+    let mut p = Parser::new(&mut l);
+    p.parse_program();
+    assert!(p.trace_lines().is_empty());
+}
+
+#[test]
+fn test_error_spans() {
+    let tests = vec![
+        (
+            "if (x { x }",
+            "expected next token to be ), got { instead",
+            (6, 7),
+        ),
+        (
+            "let = 5;",
+            "expected next token to be IDENT, got = instead",
+            (4, 5),
+        ),
+        ("1 + ;", "no prefix parse function for ; found", (4, 5)),
+        (r#"x + "a\qb""#, "unknown escape sequence \\q", (4, 10)),
+        (
+            "while (x) { }; continue",
+            "continue outside of loop",
+            (15, 23),
+        ),
+    ];
+
+    for (input, message, (start, end)) in tests {
+        let mut l = Lexer::new(input);
+        let mut p = Parser::new(&mut l);
+        p.parse_program();
+        let error = &p.errors()[0];
+        assert_eq!(error.message, message, "input: {}", input);
+        assert_eq!(error.span, Span::new(start, end), "input: {}", input);
+    }
+}
@@ -0,0 +1,926 @@
+// Node::to_string の出力をパースし直すと元の AST に戻ることをランダムな Program で確かめる
+use crate::ast::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::{Token, TokenKind};
+
+const CASES: u64 = 2000;
+const MAX_DEPTH: u32 = 4;
+
+const IDENTS: [&str; 6] = ["a", "b", "foo", "bar", "snake_case", "camelCase"];
+const STRINGS: [&str; 6] = [
+    "",
+    "hello",
+    "a \"quoted\" word",
+    "back\\slash",
+    "line\nbreak",
+    "// not a comment",
+];
+const PREFIX_OPERATORS: [(TokenKind, &str); 2] = [(TokenKind::Minus, "-"), (TokenKind::Bang, "!")];
+const INFIX_OPERATORS: [(TokenKind, &str); 8] = [
+    (TokenKind::Plus, "+"),
+    (TokenKind::Minus, "-"),
+    (TokenKind::Asterisk, "*"),
+    (TokenKind::Slash, "/"),
+    (TokenKind::Lt, "<"),
+    (TokenKind::Gt, ">"),
+    (TokenKind::Eq, "=="),
+    (TokenKind::NotEq, "!="),
+];
+const ASSIGN_OPERATORS: [(TokenKind, &str); 5] = [
+    (TokenKind::Assign, "="),
+    (TokenKind::PlusAssign, "+="),
+    (TokenKind::MinusAssign, "-="),
+    (TokenKind::AsteriskAssign, "*="),
+    (TokenKind::SlashAssign, "/="),
+];
+
+// xorshift64
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+fn identifier(name: &str) -> Identifier {
+    Identifier::new(Token::new(TokenKind::Ident, name), name)
+}
+
+fn integer(value: i64) -> Expression {
+    IntegerLiteral::new(Token::new(TokenKind::Int, value), value).into()
+}
+
+fn boolean(value: bool) -> Expression {
+    let kind = if value {
+        TokenKind::True
+    } else {
+        TokenKind::False
+    };
+    Boolean::new(Token::new(kind, value), value).into()
+}
+
+fn string(value: &str) -> Expression {
+    StringLiteral::new(Token::new(TokenKind::String, value), value).into()
+}
+
+fn block(statements: Vec<Statement>) -> BlockStatement {
+    BlockStatement::new(Token::new(TokenKind::LBrace, "{"), statements)
+}
+
+fn gen_program(rng: &mut Rng) -> Program {
+    let len = 1 + rng.below(4);
+    Program {
+        statements: (0..len).map(|_| gen_top_level(rng)).collect(),
+    }
+}
+
+// import と export は最上位にしか書けない
+fn gen_top_level(rng: &mut Rng) -> Statement {
+    match rng.below(10) {
+        0 => ImportStatement::new(
+            Token::new(TokenKind::Import, "import"),
+            STRINGS[rng.below(STRINGS.len())],
+            identifier(IDENTS[rng.below(IDENTS.len())]),
+        )
+        .into(),
+        1 => ExportStatement::new(
+            Token::new(TokenKind::Export, "export"),
+            LetStatement::new(
+                Token::new(TokenKind::Let, "let"),
+                gen_binding(rng, 0),
+                Some(gen_expression(rng, 0)),
+            ),
+        )
+        .into(),
+        _ => gen_statement(rng, 0),
+    }
+}
+
+// Statement::BlockStatement は単独では書けないので、if の本体として生成する
+fn gen_statement(rng: &mut Rng, depth: u32) -> Statement {
+    let choices = if depth >= MAX_DEPTH { 3 } else { 5 };
+    match rng.below(choices) {
+        0 => LetStatement::new(
+            Token::new(TokenKind::Let, "let"),
+            gen_binding(rng, depth),
+            Some(gen_expression(rng, depth)),
+        )
+        .into(),
+        1 => ReturnStatement::new(
+            Token::new(TokenKind::Return, "return"),
+            Some(gen_expression(rng, depth)),
+        )
+        .into(),
+        2 => {
+            let expression = gen_expression(rng, depth);
+            ExpressionStatement::new(Token::default(), Some(expression)).into()
+        }
+        3 => gen_while(rng, depth + 1),
+        _ => gen_for(rng, depth + 1),
+    }
+}
+
+// break と continue は、ループの外に出てしまわないよう本体の直下にだけ置く
+fn gen_while(rng: &mut Rng, depth: u32) -> Statement {
+    let condition = gen_expression(rng, depth);
+    let mut body = gen_block(rng, depth);
+    match rng.below(3) {
+        0 => body
+            .statements
+            .push(BreakStatement::new(Token::new(TokenKind::Break, "break")).into()),
+        1 => body
+            .statements
+            .push(ContinueStatement::new(Token::new(TokenKind::Continue, "continue")).into()),
+        _ => {}
+    }
+    WhileStatement::new(Token::new(TokenKind::While, "while"), Some(condition), body).into()
+}
+
+fn gen_for(rng: &mut Rng, depth: u32) -> Statement {
+    let variables = (0..1 + rng.below(2))
+        .map(|_| identifier(IDENTS[rng.below(IDENTS.len())]))
+        .collect();
+    let iterable = gen_expression(rng, depth);
+    let body = gen_block(rng, depth);
+    ForStatement::new(
+        Token::new(TokenKind::For, "for"),
+        variables,
+        Some(iterable),
+        body,
+    )
+    .into()
+}
+
+fn gen_block(rng: &mut Rng, depth: u32) -> BlockStatement {
+    let len = rng.below(3);
+    block((0..len).map(|_| gen_statement(rng, depth)).collect())
+}
+
+fn gen_expression(rng: &mut Rng, depth: u32) -> Expression {
+    let choices = if depth >= MAX_DEPTH { 4 } else { 16 };
+
+    match rng.below(choices) {
+        0 => identifier(IDENTS[rng.below(IDENTS.len())]).into(),
+        1 => integer(if rng.chance(10) {
+            i64::MAX
+        } else {
+            rng.below(1000) as i64
+        }),
+        2 => boolean(rng.chance(50)),
+        3 => string(STRINGS[rng.below(STRINGS.len())]),
+        4 => {
+            let (kind, op) = PREFIX_OPERATORS[rng.below(PREFIX_OPERATORS.len())];
+            let right = gen_expression(rng, depth + 1);
+            PrefixExpression::new(Token::new(kind, op), op, Some(right)).into()
+        }
+        5 => {
+            let (kind, op) = INFIX_OPERATORS[rng.below(INFIX_OPERATORS.len())];
+            let left = gen_expression(rng, depth + 1);
+            let right = gen_expression(rng, depth + 1);
+            InfixExpression::new(Token::new(kind, op), Some(left), op, Some(right)).into()
+        }
+        6 => {
+            // 同じ名前の引数は構文エラーになるので、重なったら _ にする
+            let mut parameters: Vec<Pattern> = vec![];
+            for _ in 0..rng.below(3) {
+                let parameter = gen_binding(rng, depth + 1);
+                let mut names: Vec<&str> = parameters
+                    .iter()
+                    .flat_map(Pattern::bindings)
+                    .map(|name| name.value.as_str())
+                    .collect();
+                let taken = parameter.bindings().iter().any(|name| {
+                    let taken = names.contains(&name.value.as_str());
+                    names.push(&name.value);
+                    taken
+                });
+                parameters.push(if taken {
+                    WildcardPattern::new(Token::new(TokenKind::Ident, "_")).into()
+                } else {
+                    parameter
+                });
+            }
+            let body = gen_block(rng, depth + 1);
+            FunctionLiteral::new(Token::new(TokenKind::Function, "fn"), parameters, body).into()
+        }
+        7 => {
+            let function = gen_expression(rng, depth + 1);
+            let arguments = (0..rng.below(3))
+                .map(|_| gen_expression(rng, depth + 1))
+                .collect();
+            CallExpression::new(Token::new(TokenKind::LParen, "("), function, arguments).into()
+        }
+        8 => {
+            let elements = (0..rng.below(3))
+                .map(|_| gen_expression(rng, depth + 1))
+                .collect();
+            ArrayLiteral::new(Token::new(TokenKind::LBracket, "["), elements).into()
+        }
+        9 => {
+            let left = gen_expression(rng, depth + 1);
+            let index = gen_expression(rng, depth + 1);
+            IndexExpression::new(Token::new(TokenKind::LBracket, "["), left, index).into()
+        }
+        10 => {
+            let pairs = (0..rng.below(3))
+                .map(|_| {
+                    (
+                        gen_expression(rng, depth + 1),
+                        gen_expression(rng, depth + 1),
+                    )
+                })
+                .collect();
+            HashLiteral::new(Token::new(TokenKind::LBrace, "{"), pairs).into()
+        }
+        11 => {
+            let inclusive = rng.chance(50);
+            let token = if inclusive {
+                Token::new(TokenKind::DotDotEq, "..=")
+            } else {
+                Token::new(TokenKind::DotDot, "..")
+            };
+            let start = gen_expression(rng, depth + 1);
+            let end = gen_expression(rng, depth + 1);
+            RangeExpression::new(token, Some(start), Some(end), inclusive).into()
+        }
+        12 => {
+            let (kind, op) = ASSIGN_OPERATORS[rng.below(ASSIGN_OPERATORS.len())];
+            let target = gen_assign_target(rng, depth + 1);
+            let value = gen_expression(rng, depth + 1);
+            AssignExpression::new(Token::new(kind, op), target, op, Some(value)).into()
+        }
+        13 => gen_match(rng, depth + 1),
+        14 => {
+            let object = gen_expression(rng, depth + 1);
+            let property = identifier(IDENTS[rng.below(IDENTS.len())]);
+            MemberExpression::new(Token::new(TokenKind::Dot, "."), object, property).into()
+        }
+        _ => gen_if(rng, depth + 1).into(),
+    }
+}
+
+fn gen_if(rng: &mut Rng, depth: u32) -> IfExpression {
+    let condition = gen_expression(rng, depth);
+    let consequence = gen_block(rng, depth);
+    let alternative = match rng.below(3) {
+        0 => None,
+        1 => Some(gen_block(rng, depth)),
+        _ => Some(gen_if(rng, depth + 1).into_else_block()),
+    };
+    IfExpression::new(
+        Token::new(TokenKind::If, "if"),
+        Some(condition),
+        consequence,
+        alternative,
+    )
+}
+
+fn gen_match(rng: &mut Rng, depth: u32) -> Expression {
+    let subject = gen_expression(rng, depth);
+    let arms = (0..rng.below(3))
+        .map(|_| {
+            let pattern = gen_pattern(rng, depth);
+            let guard = rng.chance(30).then(|| gen_expression(rng, depth));
+            let body = gen_expression(rng, depth);
+            MatchArm::new(
+                Token::new(TokenKind::FatArrow, "=>"),
+                pattern,
+                guard,
+                Some(body),
+            )
+        })
+        .collect();
+    MatchExpression::new(Token::new(TokenKind::Match, "match"), Some(subject), arms).into()
+}
+
+// let と引数の束縛先。リテラルは書けない
+fn gen_binding(rng: &mut Rng, depth: u32) -> Pattern {
+    if rng.below(3) > 0 {
+        return identifier(IDENTS[rng.below(IDENTS.len())]).into();
+    }
+    loop {
+        match gen_pattern(rng, depth) {
+            Pattern::IntegerLiteral(_) | Pattern::StringLiteral(_) | Pattern::Boolean(_) => {}
+            pattern => return pattern,
+        }
+    }
+}
+
+fn gen_pattern(rng: &mut Rng, depth: u32) -> Pattern {
+    let choices = if depth >= MAX_DEPTH { 5 } else { 7 };
+    match rng.below(choices) {
+        0 => WildcardPattern::new(Token::new(TokenKind::Ident, "_")).into(),
+        1 => identifier(IDENTS[rng.below(IDENTS.len())]).into(),
+        2..=4 => match gen_literal(rng) {
+            Expression::IntegerLiteral(e) => e.into(),
+            Expression::StringLiteral(e) => e.into(),
+            Expression::Boolean(e) => e.into(),
+            _ => unreachable!(),
+        },
+        5 => {
+            let elements = (0..rng.below(3))
+                .map(|_| gen_pattern(rng, depth + 1))
+                .collect();
+            let rest = match rng.below(3) {
+                0 => None,
+                1 => Some(RestPattern::new(Token::new(TokenKind::DotDot, ".."), None)),
+                _ => Some(RestPattern::new(
+                    Token::new(TokenKind::DotDot, ".."),
+                    Some(identifier(IDENTS[rng.below(IDENTS.len())])),
+                )),
+            };
+            ArrayPattern::new(Token::new(TokenKind::LBracket, "["), elements, rest).into()
+        }
+        _ => {
+            let pairs = (0..rng.below(3))
+                .map(|_| (gen_literal(rng), gen_pattern(rng, depth + 1)))
+                .collect();
+            HashPattern::new(Token::new(TokenKind::LBrace, "{"), pairs).into()
+        }
+    }
+}
+
+// パターンに書けるリテラル。整数は負の数も作る
+fn gen_literal(rng: &mut Rng) -> Expression {
+    match rng.below(3) {
+        0 => integer(rng.below(1000) as i64 - 500),
+        1 => string(STRINGS[rng.below(STRINGS.len())]),
+        _ => boolean(rng.chance(50)),
+    }
+}
+
+// 代入先は名前か、名前から始まる添字式
+fn gen_assign_target(rng: &mut Rng, depth: u32) -> Expression {
+    let mut target: Expression = identifier(IDENTS[rng.below(IDENTS.len())]).into();
+    for _ in 0..rng.below(3) {
+        let index = gen_expression(rng, depth + 1);
+        target = IndexExpression::new(Token::new(TokenKind::LBracket, "["), target, index).into();
+    }
+    target
+}
+
+// トークンは比較対象にしないので、すべて既定値に置き換える
+fn strip_program(program: &Program) -> Program {
+    Program {
+        statements: program.statements.iter().map(strip_statement).collect(),
+    }
+}
+
+fn strip_statement(stmt: &Statement) -> Statement {
+    match stmt {
+        Statement::LetStatement(s) => LetStatement::new(
+            Token::default(),
+            strip_pattern(&s.pattern),
+            s.value.as_ref().map(strip_expression),
+        )
+        .into(),
+        Statement::ReturnStatement(s) => ReturnStatement::new(
+            Token::default(),
+            s.return_value.as_ref().map(strip_expression),
+        )
+        .into(),
+        Statement::ExpressionStatement(s) => ExpressionStatement::new(
+            Token::default(),
+            s.expression.as_ref().map(strip_expression),
+        )
+        .into(),
+        Statement::BlockStatement(s) => strip_block(s).into(),
+        Statement::WhileStatement(s) => WhileStatement::new(
+            Token::default(),
+            s.condition.as_ref().map(strip_expression),
+            strip_block(&s.body),
+        )
+        .into(),
+        Statement::ForStatement(s) => ForStatement::new(
+            Token::default(),
+            s.variables
+                .iter()
+                .map(|v| Identifier::new(Token::default(), &v.value))
+                .collect(),
+            s.iterable.as_ref().map(strip_expression),
+            strip_block(&s.body),
+        )
+        .into(),
+        Statement::BreakStatement(_) => BreakStatement::new(Token::default()).into(),
+        Statement::ContinueStatement(_) => ContinueStatement::new(Token::default()).into(),
+        Statement::ImportStatement(s) => ImportStatement::new(
+            Token::default(),
+            &s.path,
+            Identifier::new(Token::default(), &s.alias.value),
+        )
+        .into(),
+        Statement::ExportStatement(s) => {
+            let Statement::LetStatement(stmt) = strip_statement(&(*s.statement).clone().into())
+            else {
+                unreachable!()
+            };
+            ExportStatement::new(Token::default(), stmt).into()
+        }
+    }
+}
+
+fn strip_block(block: &BlockStatement) -> BlockStatement {
+    BlockStatement::new(
+        Token::default(),
+        block.statements.iter().map(strip_statement).collect(),
+    )
+}
+
+fn strip_expression(exp: &Expression) -> Expression {
+    match exp {
+        Expression::Identifier(e) => Identifier::new(Token::default(), &e.value).into(),
+        Expression::IntegerLiteral(e) => IntegerLiteral::new(Token::default(), e.value).into(),
+        Expression::Boolean(e) => Boolean::new(Token::default(), e.value).into(),
+        Expression::PrefixExpression(e) => PrefixExpression::new(
+            Token::default(),
+            &e.operator,
+            e.right.as_deref().map(strip_expression),
+        )
+        .into(),
+        Expression::InfixExpression(e) => InfixExpression::new(
+            Token::default(),
+            e.left.as_deref().map(strip_expression),
+            &e.operator,
+            e.right.as_deref().map(strip_expression),
+        )
+        .into(),
+        Expression::IfExpression(e) => IfExpression::new(
+            Token::default(),
+            e.condition.as_deref().map(strip_expression),
+            strip_block(&e.consequence),
+            e.alternative.as_deref().map(strip_block),
+        )
+        .into(),
+        Expression::FunctionLiteral(e) => FunctionLiteral::new(
+            Token::default(),
+            e.parameters.iter().map(strip_pattern).collect(),
+            strip_block(&e.body),
+        )
+        .into(),
+        Expression::CallExpression(e) => CallExpression::new(
+            Token::default(),
+            strip_expression(&e.function),
+            e.arguments.iter().map(strip_expression).collect(),
+        )
+        .into(),
+        Expression::StringLiteral(e) => StringLiteral::new(Token::default(), &e.value).into(),
+        Expression::ArrayLiteral(e) => ArrayLiteral::new(
+            Token::default(),
+            e.elements.iter().map(strip_expression).collect(),
+        )
+        .into(),
+        Expression::IndexExpression(e) => IndexExpression::new(
+            Token::default(),
+            strip_expression(&e.left),
+            strip_expression(&e.index),
+        )
+        .into(),
+        Expression::MemberExpression(e) => MemberExpression::new(
+            Token::default(),
+            strip_expression(&e.object),
+            Identifier::new(Token::default(), &e.property.value),
+        )
+        .into(),
+        Expression::HashLiteral(e) => HashLiteral::new(
+            Token::default(),
+            e.pairs
+                .iter()
+                .map(|(k, v)| (strip_expression(k), strip_expression(v)))
+                .collect(),
+        )
+        .into(),
+        Expression::RangeExpression(e) => RangeExpression::new(
+            Token::default(),
+            e.start.as_deref().map(strip_expression),
+            e.end.as_deref().map(strip_expression),
+            e.inclusive,
+        )
+        .into(),
+        Expression::AssignExpression(e) => AssignExpression::new(
+            Token::default(),
+            strip_expression(&e.target),
+            &e.operator,
+            e.value.as_deref().map(strip_expression),
+        )
+        .into(),
+        Expression::MatchExpression(e) => MatchExpression::new(
+            Token::default(),
+            e.subject.as_deref().map(strip_expression),
+            e.arms
+                .iter()
+                .map(|arm| {
+                    MatchArm::new(
+                        Token::default(),
+                        strip_pattern(&arm.pattern),
+                        arm.guard.as_ref().map(strip_expression),
+                        arm.body.as_ref().map(strip_expression),
+                    )
+                })
+                .collect(),
+        )
+        .into(),
+    }
+}
+
+fn strip_pattern(pattern: &Pattern) -> Pattern {
+    match pattern {
+        Pattern::WildcardPattern(_) => WildcardPattern::new(Token::default()).into(),
+        Pattern::Identifier(p) => Identifier::new(Token::default(), &p.value).into(),
+        Pattern::IntegerLiteral(p) => IntegerLiteral::new(Token::default(), p.value).into(),
+        Pattern::StringLiteral(p) => StringLiteral::new(Token::default(), &p.value).into(),
+        Pattern::Boolean(p) => Boolean::new(Token::default(), p.value).into(),
+        Pattern::ArrayPattern(p) => ArrayPattern::new(
+            Token::default(),
+            p.elements.iter().map(strip_pattern).collect(),
+            p.rest.as_ref().map(|r| {
+                RestPattern::new(
+                    Token::default(),
+                    r.name
+                        .as_ref()
+                        .map(|n| Identifier::new(Token::default(), &n.value)),
+                )
+            }),
+        )
+        .into(),
+        Pattern::HashPattern(p) => HashPattern::new(
+            Token::default(),
+            p.pairs
+                .iter()
+                .map(|(k, v)| (strip_expression(k), strip_pattern(v)))
+                .collect(),
+        )
+        .into(),
+    }
+}
+
+// 失敗した場合はエラーの説明を返す
+fn check_roundtrip(program: &Program) -> Result<(), String> {
+    let input = program.to_string();
+    let mut l = Lexer::new(&input);
+    let mut p = Parser::new(&mut l);
+    let parsed = p.parse_program();
+
+    if !p.errors().is_empty() {
+        return Err(format!("parser errors: {:?}", p.errors()));
+    }
+    if strip_program(&parsed) != strip_program(program) {
+        return Err(format!("parsed back as: {}", parsed.to_string()));
+    }
+    Ok(())
+}
+
+fn shrink_program(program: &Program) -> Vec<Program> {
+    let mut candidates = vec![];
+    for (i, stmt) in program.statements.iter().enumerate() {
+        let mut statements = program.statements.clone();
+        statements.remove(i);
+        candidates.push(Program { statements });
+
+        for shrunk in shrink_statement(stmt) {
+            let mut statements = program.statements.clone();
+            statements[i] = shrunk;
+            candidates.push(Program { statements });
+        }
+    }
+    candidates
+}
+
+fn shrink_statement(stmt: &Statement) -> Vec<Statement> {
+    match stmt {
+        Statement::LetStatement(s) => {
+            let mut candidates = vec![];
+            if s.pattern
+                .as_identifier()
+                .is_none_or(|name| name.value != "a")
+            {
+                candidates.push(
+                    LetStatement::new(s.token.clone(), identifier("a"), s.value.clone()).into(),
+                );
+            }
+            for value in s.value.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    LetStatement::new(s.token.clone(), (*s.pattern).clone(), Some(value)).into(),
+                );
+            }
+            candidates
+        }
+        Statement::ReturnStatement(s) => s
+            .return_value
+            .iter()
+            .flat_map(shrink_expression)
+            .map(|v| ReturnStatement::new(s.token.clone(), Some(v)).into())
+            .collect(),
+        Statement::ExpressionStatement(s) => s
+            .expression
+            .iter()
+            .flat_map(shrink_expression)
+            .map(|e| ExpressionStatement::new(s.token.clone(), Some(e)).into())
+            .collect(),
+        Statement::BlockStatement(s) => shrink_block(s).into_iter().map(Statement::from).collect(),
+        Statement::WhileStatement(s) => {
+            let mut candidates = vec![];
+            for c in s.condition.iter().flat_map(shrink_expression) {
+                candidates
+                    .push(WhileStatement::new(s.token.clone(), Some(c), (*s.body).clone()).into());
+            }
+            for body in shrink_block(&s.body) {
+                candidates
+                    .push(WhileStatement::new(s.token.clone(), s.condition.clone(), body).into());
+            }
+            candidates
+        }
+        Statement::ForStatement(s) => {
+            let rebuild = |iterable: Option<Expression>, body: BlockStatement| {
+                Statement::from(ForStatement::new(
+                    s.token.clone(),
+                    s.variables.clone(),
+                    iterable,
+                    body,
+                ))
+            };
+            let mut candidates = vec![];
+            for i in s.iterable.iter().flat_map(shrink_expression) {
+                candidates.push(rebuild(Some(i), (*s.body).clone()));
+            }
+            for body in shrink_block(&s.body) {
+                candidates.push(rebuild(s.iterable.clone(), body));
+            }
+            candidates
+        }
+        Statement::ExportStatement(s) => shrink_statement(&(*s.statement).clone().into())
+            .into_iter()
+            .filter_map(|shrunk| match shrunk {
+                Statement::LetStatement(stmt) => {
+                    Some(ExportStatement::new(s.token.clone(), stmt).into())
+                }
+                _ => None,
+            })
+            .collect(),
+        Statement::BreakStatement(_)
+        | Statement::ContinueStatement(_)
+        | Statement::ImportStatement(_) => vec![],
+    }
+}
+
+fn shrink_block(block: &BlockStatement) -> Vec<BlockStatement> {
+    let program = Program {
+        statements: block.statements.clone(),
+    };
+    shrink_program(&program)
+        .into_iter()
+        .map(|p| BlockStatement::new(block.token.clone(), p.statements))
+        .collect()
+}
+
+// 部分式そのものを先に試し、次に部分式を縮めたものを試す
+fn shrink_expression(exp: &Expression) -> Vec<Expression> {
+    match exp {
+        Expression::Identifier(e) if e.value != "a" => vec![identifier("a").into()],
+        Expression::IntegerLiteral(e) if e.value != 0 => vec![integer(0)],
+        Expression::Boolean(e) if e.value => vec![boolean(false)],
+        Expression::StringLiteral(e) if !e.value.is_empty() => vec![string("")],
+        Expression::PrefixExpression(e) => {
+            let right = e.right.as_deref().cloned();
+            let mut candidates: Vec<Expression> = right.iter().cloned().collect();
+            for r in right.iter().flat_map(shrink_expression) {
+                candidates
+                    .push(PrefixExpression::new(e.token.clone(), &e.operator, Some(r)).into());
+            }
+            candidates
+        }
+        Expression::InfixExpression(e) => {
+            let left = e.left.as_deref().cloned();
+            let right = e.right.as_deref().cloned();
+            let mut candidates: Vec<Expression> =
+                left.iter().chain(right.iter()).cloned().collect();
+            for l in left.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    InfixExpression::new(e.token.clone(), Some(l), &e.operator, right.clone())
+                        .into(),
+                );
+            }
+            for r in right.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    InfixExpression::new(e.token.clone(), left.clone(), &e.operator, Some(r))
+                        .into(),
+                );
+            }
+            candidates
+        }
+        Expression::IfExpression(e) => {
+            let condition = e.condition.as_deref().cloned();
+            let consequence = (*e.consequence).clone();
+            let alternative = e.alternative.as_deref().cloned();
+            let rebuild =
+                |c: Option<Expression>, cons: BlockStatement, alt: Option<BlockStatement>| {
+                    Expression::from(IfExpression::new(e.token.clone(), c, cons, alt))
+                };
+
+            let mut candidates: Vec<Expression> = condition.iter().cloned().collect();
+            if alternative.is_some() {
+                candidates.push(rebuild(condition.clone(), consequence.clone(), None));
+            }
+            for c in condition.iter().flat_map(shrink_expression) {
+                candidates.push(rebuild(Some(c), consequence.clone(), alternative.clone()));
+            }
+            for cons in shrink_block(&consequence) {
+                candidates.push(rebuild(condition.clone(), cons, alternative.clone()));
+            }
+            for alt in alternative.iter().flat_map(shrink_block) {
+                candidates.push(rebuild(condition.clone(), consequence.clone(), Some(alt)));
+            }
+            candidates
+        }
+        Expression::FunctionLiteral(e) => {
+            let mut candidates = vec![];
+            for i in 0..e.parameters.len() {
+                let mut parameters = e.parameters.clone();
+                parameters.remove(i);
+                candidates.push(
+                    FunctionLiteral::new(e.token.clone(), parameters, (*e.body).clone()).into(),
+                );
+            }
+            for body in shrink_block(&e.body) {
+                candidates
+                    .push(FunctionLiteral::new(e.token.clone(), e.parameters.clone(), body).into());
+            }
+            candidates
+        }
+        Expression::CallExpression(e) => {
+            let mut candidates: Vec<Expression> = vec![(*e.function).clone()];
+            candidates.extend(e.arguments.iter().cloned());
+            for i in 0..e.arguments.len() {
+                let mut arguments = e.arguments.clone();
+                arguments.remove(i);
+                candidates.push(
+                    CallExpression::new(e.token.clone(), (*e.function).clone(), arguments).into(),
+                );
+            }
+            for f in shrink_expression(&e.function) {
+                candidates
+                    .push(CallExpression::new(e.token.clone(), f, e.arguments.clone()).into());
+            }
+            for (i, arg) in e.arguments.iter().enumerate() {
+                for a in shrink_expression(arg) {
+                    let mut arguments = e.arguments.clone();
+                    arguments[i] = a;
+                    candidates.push(
+                        CallExpression::new(e.token.clone(), (*e.function).clone(), arguments)
+                            .into(),
+                    );
+                }
+            }
+            candidates
+        }
+        Expression::ArrayLiteral(e) => {
+            let mut candidates: Vec<Expression> = e.elements.clone();
+            for i in 0..e.elements.len() {
+                let mut elements = e.elements.clone();
+                elements.remove(i);
+                candidates.push(ArrayLiteral::new(e.token.clone(), elements).into());
+            }
+            for (i, element) in e.elements.iter().enumerate() {
+                for shrunk in shrink_expression(element) {
+                    let mut elements = e.elements.clone();
+                    elements[i] = shrunk;
+                    candidates.push(ArrayLiteral::new(e.token.clone(), elements).into());
+                }
+            }
+            candidates
+        }
+        Expression::IndexExpression(e) => {
+            let mut candidates: Vec<Expression> = vec![(*e.left).clone(), (*e.index).clone()];
+            for l in shrink_expression(&e.left) {
+                candidates
+                    .push(IndexExpression::new(e.token.clone(), l, (*e.index).clone()).into());
+            }
+            for i in shrink_expression(&e.index) {
+                candidates.push(IndexExpression::new(e.token.clone(), (*e.left).clone(), i).into());
+            }
+            candidates
+        }
+        Expression::MemberExpression(e) => {
+            let mut candidates: Vec<Expression> = vec![(*e.object).clone()];
+            for o in shrink_expression(&e.object) {
+                candidates
+                    .push(MemberExpression::new(e.token.clone(), o, e.property.clone()).into());
+            }
+            candidates
+        }
+        Expression::HashLiteral(e) => {
+            let mut candidates: Vec<Expression> = vec![];
+            for (k, v) in &e.pairs {
+                candidates.push(k.clone());
+                candidates.push(v.clone());
+            }
+            for i in 0..e.pairs.len() {
+                let mut pairs = e.pairs.clone();
+                pairs.remove(i);
+                candidates.push(HashLiteral::new(e.token.clone(), pairs).into());
+            }
+            candidates
+        }
+        Expression::RangeExpression(e) => {
+            let start = e.start.as_deref().cloned();
+            let end = e.end.as_deref().cloned();
+            let mut candidates: Vec<Expression> = start.iter().chain(end.iter()).cloned().collect();
+            for s in start.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    RangeExpression::new(e.token.clone(), Some(s), end.clone(), e.inclusive).into(),
+                );
+            }
+            for n in end.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    RangeExpression::new(e.token.clone(), start.clone(), Some(n), e.inclusive)
+                        .into(),
+                );
+            }
+            candidates
+        }
+        // 代入先を縮めると代入できない式になりうるので、値だけを縮める
+        Expression::AssignExpression(e) => {
+            let value = e.value.as_deref().cloned();
+            let mut candidates: Vec<Expression> = value.iter().cloned().collect();
+            for v in value.iter().flat_map(shrink_expression) {
+                candidates.push(
+                    AssignExpression::new(
+                        e.token.clone(),
+                        (*e.target).clone(),
+                        &e.operator,
+                        Some(v),
+                    )
+                    .into(),
+                );
+            }
+            candidates
+        }
+        // パターンはそのままにして、値と腕を減らす
+        Expression::MatchExpression(e) => {
+            let subject = e.subject.as_deref().cloned();
+            let mut candidates: Vec<Expression> = subject.iter().cloned().collect();
+            candidates.extend(e.arms.iter().filter_map(|arm| arm.body.clone()));
+            for i in 0..e.arms.len() {
+                let mut arms = e.arms.clone();
+                arms.remove(i);
+                candidates
+                    .push(MatchExpression::new(e.token.clone(), subject.clone(), arms).into());
+            }
+            for s in subject.iter().flat_map(shrink_expression) {
+                candidates
+                    .push(MatchExpression::new(e.token.clone(), Some(s), e.arms.clone()).into());
+            }
+            candidates
+        }
+        _ => vec![],
+    }
+}
+
+// 失敗し続ける限り、より小さな候補に置き換えていく
+fn shrink(mut program: Program) -> Program {
+    'outer: loop {
+        for candidate in shrink_program(&program) {
+            if check_roundtrip(&candidate).is_err() {
+                program = candidate;
+                continue 'outer;
+            }
+        }
+        return program;
+    }
+}
+
+#[test]
+fn test_roundtrip() {
+    for seed in 0..CASES {
+        let mut rng = Rng::new(seed);
+        let program = gen_program(&mut rng);
+
+        if check_roundtrip(&program).is_err() {
+            let minimal = shrink(program);
+            let reason = check_roundtrip(&minimal).unwrap_err();
+            panic!(
+                "roundtrip failed (seed {})\n  input: {}\n  {}\n  ast: {:?}",
+                seed,
+                minimal.to_string(),
+                reason,
+                strip_program(&minimal)
+            );
+        }
+    }
+}
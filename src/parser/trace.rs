@@ -0,0 +1,69 @@
+use crate::parser::Precedence;
+use crate::token::Token;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+const INDENT: &str = "  ";
+
+// パーサの関数の出入りを字下げ付きで記録する
+#[derive(Default)]
+pub struct Tracer {
+    enabled: bool,
+    depth: Cell<usize>,
+    lines: RefCell<Vec<String>>,
+}
+
+// スコープを抜けるときに END を記録する
+pub struct TraceGuard {
+    tracer: Rc<Tracer>,
+    name: &'static str,
+}
+
+impl Tracer {
+    pub fn new(enabled: bool) -> Tracer {
+        Tracer {
+            enabled,
+            ..Tracer::default()
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.borrow().clone()
+    }
+
+    pub fn begin(
+        self: &Rc<Self>,
+        name: &'static str,
+        token: &Token,
+        precedence: Option<Precedence>,
+    ) -> Option<TraceGuard> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut msg = format!("BEGIN {} (token: {} {:?}", name, token.kind, token.literal);
+        if let Some(precedence) = precedence {
+            msg += &format!(", precedence: {:?}", precedence);
+        }
+        self.print(msg + ")");
+        self.depth.set(self.depth.get() + 1);
+
+        Some(TraceGuard {
+            tracer: Rc::clone(self),
+            name,
+        })
+    }
+
+    fn print(&self, msg: String) {
+        let indent = INDENT.repeat(self.depth.get());
+        self.lines.borrow_mut().push(format!("{}{}", indent, msg));
+    }
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        let depth = &self.tracer.depth;
+        depth.set(depth.get() - 1);
+        self.tracer.print(format!("END {}", self.name));
+    }
+}
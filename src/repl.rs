@@ -1,32 +1,122 @@
+#[cfg(test)]
+mod test;
+
+use crate::builtins::Output;
+use crate::disasm::disassemble;
+use crate::engine::{Engine, Interpreter};
+use crate::evaluator::DEFAULT_STACK_SIZE;
 use crate::lexer::Lexer;
-use crate::token::TokenKind;
+use crate::parser::{Parser, SyntaxError};
+use std::cell::RefCell;
 use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+
+const PROMPT: &str = ">> ";
+
+pub fn start(buf_in: impl Read, buf_out: impl Write) {
+    start_with_engine(buf_in, buf_out, Engine::default());
+}
 
-const PROMPT: &'static str = ">> ";
+pub fn start_with_engine(buf_in: impl Read, buf_out: impl Write, engine: Engine) {
+    start_with_stack_size(buf_in, buf_out, engine, DEFAULT_STACK_SIZE);
+}
 
-pub fn start(buf_in: impl Read, mut buf_out: impl Write) {
+// stack_size は REPL を動かすスレッドのスタックの大きさ
+pub fn start_with_stack_size(
+    buf_in: impl Read,
+    mut buf_out: impl Write,
+    engine: Engine,
+    stack_size: usize,
+) {
     let mut reader = BufReader::new(buf_in);
+    let mut trace = false;
+    // puts の出力はいったん溜めて、結果の前に書き出す
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let new_interpreter = |engine| {
+        Interpreter::new(engine)
+            .with_output(output.clone() as Output)
+            .with_stack_size(stack_size)
+    };
+    let mut interpreter = new_interpreter(engine);
 
     loop {
         buf_out
-            .write(PROMPT.as_bytes())
+            .write_all(PROMPT.as_bytes())
             .and_then(|_| buf_out.flush())
             .expect("failed to write prompt");
 
         let mut line = String::new();
-        reader.read_line(&mut line).expect("failed to read line");
+        if reader.read_line(&mut line).expect("failed to read line") == 0 {
+            return;
+        }
+
+        match line.trim() {
+            ":trace on" => {
+                trace = true;
+                continue;
+            }
+            ":trace off" => {
+                trace = false;
+                continue;
+            }
+            // エンジンを切り替えると、それまでの束縛は捨てる
+            command if command.starts_with(":engine") => {
+                let message = match command[":engine".len()..].trim() {
+                    "" => format!("engine: {}", interpreter.engine()),
+                    name => match name.parse::<Engine>() {
+                        Ok(engine) => {
+                            interpreter = new_interpreter(engine);
+                            format!("switched to {} engine", engine)
+                        }
+                        Err(e) => e,
+                    },
+                };
+                writeln!(buf_out, "{}", message).expect("failed to write output");
+                continue;
+            }
+            _ => {}
+        }
 
-        let mut lexer = Lexer::new(&line);
+        // :bytecode の後ろの式はコンパイルして逆アセンブルするだけで実行しない
+        let (source, bytecode_only) = match line.trim().strip_prefix(":bytecode") {
+            Some(rest) => (rest.to_string(), true),
+            None => (line, false),
+        };
 
-        loop {
-            let token = lexer.next_token();
-            if token.kind == TokenKind::EOF {
-                break;
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(&mut lexer).with_trace(trace);
+        let program = parser.parse_program();
+
+        for msg in parser.trace_lines() {
+            writeln!(buf_out, "{}", msg).expect("failed to write output");
+        }
+
+        if !parser.errors().is_empty() {
+            print_parser_errors(&mut buf_out, parser.errors());
+            continue;
+        }
+
+        if bytecode_only {
+            match interpreter.compile(&program) {
+                Ok(bytecode) => write!(buf_out, "{}", disassemble(&bytecode)),
+                Err(e) => writeln!(buf_out, "ERROR: {}", e),
             }
+            .expect("failed to write output");
+            continue;
+        }
 
-            buf_out
-                .write_fmt(format_args!("{:?}\n", token))
-                .expect("failed to write output");
+        let result = interpreter.run(&program);
+        buf_out
+            .write_all(&output.borrow_mut().split_off(0))
+            .expect("failed to write output");
+        if let Some(result) = result {
+            writeln!(buf_out, "{}", result).expect("failed to write output");
         }
     }
 }
+
+fn print_parser_errors(buf_out: &mut impl Write, errors: &[SyntaxError]) {
+    for msg in errors {
+        writeln!(buf_out, "\t{}", msg).expect("failed to write output");
+    }
+}
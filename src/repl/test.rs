@@ -0,0 +1,91 @@
+use super::*;
+
+fn run(input: &str) -> String {
+    let mut out = Vec::new();
+    start(input.as_bytes(), &mut out);
+    String::from_utf8(out).unwrap()
+}
+
+fn run_with_engine(input: &str, engine: Engine) -> String {
+    let mut out = Vec::new();
+    start_with_engine(input.as_bytes(), &mut out, engine);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_eval_and_print() {
+    for engine in [Engine::Eval, Engine::Vm] {
+        assert_eq!(
+            run_with_engine("1 + 2 * 3\nlet x = 5;\nx * 2\ny\nx\n", engine),
+            ">> 7\n>> >> 10\n>> ERROR: identifier not found: y\n>> 5\n>> ",
+            "engine: {}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn test_engine_switch() {
+    assert_eq!(
+        run(":engine\nlet x = 1;\n:engine eval\nx\n:engine jit\n"),
+        ">> engine: vm\n>> >> switched to eval engine\n>> ERROR: identifier not found: x\n>> unknown engine: jit (expected eval or vm)\n>> "
+    );
+}
+
+#[test]
+fn test_parser_errors() {
+    assert_eq!(
+        run("let = 5\n"),
+        ">> \texpected next token to be IDENT, got = instead\n\tno prefix parse function for = found\n>> "
+    );
+}
+
+#[test]
+fn test_trace_toggle() {
+    let out = run(":trace on\n-1\n:trace off\n-1\n");
+    assert_eq!(
+        out,
+        ">> >> BEGIN parse_expression_statement (token: - \"-\")
+  BEGIN parse_expression (token: - \"-\", precedence: Lowest)
+    BEGIN parse_prefix_expression (token: - \"-\")
+      BEGIN parse_expression (token: INT \"1\", precedence: Prefix)
+        BEGIN parse_integer_literal (token: INT \"1\")
+        END parse_integer_literal
+      END parse_expression
+    END parse_prefix_expression
+  END parse_expression
+END parse_expression_statement
+-1
+>> >> -1
+>> "
+    );
+}
+
+#[test]
+fn test_bytecode_command() {
+    assert_eq!(
+        run("let x = 1;\n:bytecode x + 2\n:bytecode y\n"),
+        ">> >> main:
+  0000 OpGetGlobal 0
+  0003 OpConstant 1  ; 2
+  0006 OpAdd
+  0007 OpPop
+constants:
+  0 INTEGER 1
+  1 INTEGER 2
+>> ERROR: identifier not found: y
+>> "
+    );
+}
+
+#[test]
+fn test_puts_writes_to_repl_output() {
+    for engine in [Engine::Eval, Engine::Vm] {
+        assert_eq!(
+            run_with_engine("puts(\"hi\", 1)\nlet x = puts(2);\n", engine),
+            ">> hi\n1\nnull\n>> 2\n>> ",
+            "engine: {}",
+            engine
+        );
+    }
+}
@@ -0,0 +1,365 @@
+#[cfg(test)]
+mod test;
+
+use crate::ast::{
+    BlockStatement, Expression, FunctionLiteral, Identifier, InfixExpression, LetStatement,
+    Pattern, Program, Statement,
+};
+use crate::builtins;
+use crate::compiler::SymbolScope;
+use crate::parser::SyntaxError;
+use crate::token::Span;
+use std::collections::HashMap;
+
+// 名前を束縛した構文
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BindingKind {
+    Let,
+    Parameter,
+    For,
+    Match,
+    Import,
+    Builtin,
+}
+
+// ソース中に名前が現れた場所と、その名前が指す束縛。
+// 束縛する側の名前は definition が自分自身の場所になる
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reference {
+    pub span: Span,
+    pub name: String,
+    pub kind: BindingKind,
+    pub scope: SymbolScope,
+    // 組み込み関数には束縛した場所がない
+    pub definition: Option<Span>,
+}
+
+impl Reference {
+    pub fn is_definition(&self) -> bool {
+        self.definition == Some(self.span)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    // 現れた順に並ぶ
+    pub references: Vec<Reference>,
+    pub errors: Vec<SyntaxError>,
+    pub warnings: Vec<SyntaxError>,
+}
+
+impl Resolution {
+    // offset の位置にある名前
+    pub fn at(&self, offset: usize) -> Option<&Reference> {
+        self.references
+            .iter()
+            .find(|r| r.span.start <= offset && offset < r.span.end)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(|r| r.is_definition())
+    }
+
+    // definition で束縛した名前が現れる場所。束縛した場所も含む
+    pub fn references_to(&self, definition: Span) -> impl Iterator<Item = &Reference> {
+        self.references
+            .iter()
+            .filter(move |r| r.definition == Some(definition))
+    }
+}
+
+// 関数ひとつ分のスコープ。ブロックはスコープを作らない
+#[derive(Default)]
+struct Scope {
+    names: HashMap<String, (BindingKind, Span)>,
+    // let で束縛している関数の名前。本体からは自分自身を指す
+    function: Option<(String, Span)>,
+}
+
+impl Scope {
+    fn binds(&self, name: &str) -> bool {
+        self.names.contains_key(name) || self.function.as_ref().is_some_and(|(f, _)| f == name)
+    }
+}
+
+// 実行する前に、名前がどの束縛を指すかを決める。スコープの決め方はコンパイラと同じで、
+// 名前は束縛したあとからしか見えない。ただし let で束縛する関数は本体から自分を参照できる
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    // 次に解決する関数リテラルを束縛する名前
+    function_name: Option<(String, Span)>,
+    resolution: Resolution,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: vec![Scope::default()],
+            function_name: None,
+            resolution: Resolution::default(),
+        }
+    }
+
+    // すでに束縛してあるグローバルの名前を加える。REPL の前の行や埋め込み側が定義した
+    // 名前で、束縛した場所はこのプログラムの中にない
+    pub fn with_globals<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Resolver {
+        for name in names {
+            self.scopes[0]
+                .names
+                .insert(name.to_string(), (BindingKind::Let, Span::default()));
+        }
+        self
+    }
+
+    pub fn resolve(mut self, program: &Program) -> Resolution {
+        for stmt in &program.statements {
+            self.resolve_statement(stmt);
+        }
+        self.resolution
+    }
+
+    fn resolve_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::LetStatement(s) => self.resolve_let(s),
+            Statement::ExportStatement(s) => self.resolve_let(&s.statement),
+            Statement::ReturnStatement(s) => self.resolve_optional(s.return_value.as_ref()),
+            Statement::ExpressionStatement(s) => self.resolve_optional(s.expression.as_ref()),
+            Statement::BlockStatement(s) => self.resolve_block(s),
+            Statement::WhileStatement(s) => {
+                self.resolve_optional(s.condition.as_ref());
+                self.resolve_block(&s.body);
+            }
+            Statement::ForStatement(s) => {
+                self.resolve_optional(s.iterable.as_ref());
+                for variable in &s.variables {
+                    self.define(variable, BindingKind::For);
+                }
+                self.resolve_block(&s.body);
+            }
+            Statement::ImportStatement(s) => self.define(&s.alias, BindingKind::Import),
+            Statement::BreakStatement(_) | Statement::ContinueStatement(_) => {}
+        }
+    }
+
+    fn resolve_let(&mut self, s: &LetStatement) {
+        if let (Some(Expression::FunctionLiteral(_)), Some(name)) =
+            (&s.value, s.pattern.as_identifier())
+        {
+            self.function_name = Some((name.value.clone(), name.token.span));
+        }
+        self.resolve_optional(s.value.as_ref());
+        self.define_pattern(&s.pattern, BindingKind::Let);
+    }
+
+    fn resolve_block(&mut self, block: &BlockStatement) {
+        for stmt in &block.statements {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    fn resolve_optional(&mut self, exp: Option<&Expression>) {
+        if let Some(exp) = exp {
+            self.resolve_expression(exp);
+        }
+    }
+
+    fn resolve_expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::Identifier(e) => match self.lookup(e) {
+                Some(reference) => self.resolution.references.push(reference),
+                None => {
+                    let msg = format!("identifier not found: {}", e.value);
+                    self.error(e.token.span, msg);
+                }
+            },
+            Expression::IntegerLiteral(_)
+            | Expression::Boolean(_)
+            | Expression::StringLiteral(_) => {}
+            Expression::PrefixExpression(e) => self.resolve_optional(e.right.as_deref()),
+            Expression::InfixExpression(e) => self.resolve_infix(e),
+            Expression::IfExpression(e) => {
+                self.resolve_optional(e.condition.as_deref());
+                self.resolve_block(&e.consequence);
+                if let Some(alternative) = &e.alternative {
+                    self.resolve_block(alternative);
+                }
+            }
+            Expression::FunctionLiteral(e) => self.resolve_function(e),
+            Expression::CallExpression(e) => {
+                self.resolve_expression(&e.function);
+                for argument in &e.arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expression::ArrayLiteral(e) => {
+                for element in &e.elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expression::IndexExpression(e) => {
+                self.resolve_expression(&e.left);
+                self.resolve_expression(&e.index);
+            }
+            // プロパティはモジュールの中の名前なので、ここでは解決しない
+            Expression::MemberExpression(e) => self.resolve_expression(&e.object),
+            Expression::HashLiteral(e) => {
+                for (key, value) in &e.pairs {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            Expression::RangeExpression(e) => {
+                self.resolve_optional(e.start.as_deref());
+                self.resolve_optional(e.end.as_deref());
+            }
+            // 代入先は添字をたどった先の名前。組み込み関数には代入できない
+            Expression::AssignExpression(e) => {
+                let mut indexes = vec![];
+                let mut target = &*e.target;
+                while let Expression::IndexExpression(index) = target {
+                    indexes.push(&*index.index);
+                    target = &index.left;
+                }
+                match target {
+                    Expression::Identifier(name) => match self.lookup(name) {
+                        Some(reference) if reference.scope != SymbolScope::Builtin => {
+                            self.resolution.references.push(reference)
+                        }
+                        _ => {
+                            let msg =
+                                format!("cannot assign to undefined variable: {}", name.value);
+                            self.error(name.token.span, msg);
+                        }
+                    },
+                    target => self.resolve_expression(target),
+                }
+                for index in indexes.into_iter().rev() {
+                    self.resolve_expression(index);
+                }
+                self.resolve_optional(e.value.as_deref());
+            }
+            Expression::MatchExpression(e) => {
+                self.resolve_optional(e.subject.as_deref());
+                for arm in &e.arms {
+                    self.define_pattern(&arm.pattern, BindingKind::Match);
+                    self.resolve_optional(arm.guard.as_ref());
+                    self.resolve_optional(arm.body.as_ref());
+                }
+            }
+        }
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順に解決する
+    fn resolve_infix(&mut self, e: &InfixExpression) {
+        let mut rights = vec![e.right.as_deref()];
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            rights.push(inner.right.as_deref());
+            leftmost = inner.left.as_deref();
+        }
+
+        self.resolve_optional(leftmost);
+        for right in rights.into_iter().rev() {
+            self.resolve_optional(right);
+        }
+    }
+
+    fn resolve_function(&mut self, e: &FunctionLiteral) {
+        let function = self.function_name.take();
+        self.scopes.push(Scope {
+            function,
+            ..Scope::default()
+        });
+
+        let mut names: Vec<&str> = vec![];
+        for name in e.parameters.iter().flat_map(Pattern::bindings) {
+            if names.contains(&name.value.as_str()) {
+                let msg = format!("duplicate parameter {}", name.value);
+                self.error(name.token.span, msg);
+            }
+            names.push(&name.value);
+        }
+        for parameter in &e.parameters {
+            self.define_pattern(parameter, BindingKind::Parameter);
+        }
+        self.resolve_block(&e.body);
+
+        self.scopes.pop();
+    }
+
+    fn define_pattern(&mut self, pattern: &Pattern, kind: BindingKind) {
+        for name in pattern.bindings() {
+            self.define(name, kind);
+        }
+    }
+
+    // 外側の関数やグローバルの名前を隠すときは警告する。同じスコープでの定義し直しは隠さない
+    fn define(&mut self, name: &Identifier, kind: BindingKind) {
+        let span = name.token.span;
+        let depth = self.scopes.len() - 1;
+        // 同じスコープにまだない名前がどこかで束縛されていれば、外側のものを隠す
+        let redefined = self.scopes[depth].names.contains_key(&name.value);
+        if depth > 0 && !redefined && self.scopes.iter().any(|s| s.binds(&name.value)) {
+            let msg = format!("{} shadows a binding from an outer scope", name.value);
+            self.resolution
+                .warnings
+                .push(SyntaxError { message: msg, span });
+        }
+
+        let scope = if depth == 0 {
+            SymbolScope::Global
+        } else {
+            SymbolScope::Local
+        };
+        self.scopes[depth]
+            .names
+            .insert(name.value.clone(), (kind, span));
+        self.resolution.references.push(Reference {
+            span,
+            name: name.value.clone(),
+            kind,
+            scope,
+            definition: Some(span),
+        });
+    }
+
+    // 内側のスコープから順に探す。ほかの関数の局所変数は取り込んで使う
+    fn lookup(&self, name: &Identifier) -> Option<Reference> {
+        let depth = self.scopes.len() - 1;
+        let found = self.scopes.iter().enumerate().rev().find_map(|(i, scope)| {
+            if let Some((kind, span)) = scope.names.get(&name.value) {
+                return Some((i, *kind, *span, false));
+            }
+            let (function, span) = scope.function.as_ref()?;
+            (*function == name.value).then_some((i, BindingKind::Let, *span, true))
+        });
+
+        let (kind, scope, definition) = match found {
+            Some((0, kind, span, _)) => (kind, SymbolScope::Global, Some(span)),
+            Some((i, kind, span, true)) if i == depth => (kind, SymbolScope::Function, Some(span)),
+            Some((i, kind, span, _)) if i == depth => (kind, SymbolScope::Local, Some(span)),
+            Some((_, kind, span, _)) => (kind, SymbolScope::Free, Some(span)),
+            None => {
+                builtins::lookup(&name.value)?;
+                (BindingKind::Builtin, SymbolScope::Builtin, None)
+            }
+        };
+        Some(Reference {
+            span: name.token.span,
+            name: name.value.clone(),
+            kind,
+            scope,
+            definition,
+        })
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.resolution.errors.push(SyntaxError { message, span });
+    }
+}
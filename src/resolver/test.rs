@@ -0,0 +1,202 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn parse(input: &str) -> (Program, Vec<SyntaxError>) {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    (program, p.errors().to_vec())
+}
+
+fn resolve(input: &str) -> Resolution {
+    let (program, errors) = parse(input);
+    assert!(errors.is_empty(), "parser errors: {:?}", errors);
+    Resolver::new().resolve(&program)
+}
+
+// 名前が現れた順に (名前, スコープ, 束縛した場所の先頭)
+fn scopes(resolution: &Resolution) -> Vec<(&str, SymbolScope, Option<usize>)> {
+    resolution
+        .references
+        .iter()
+        .map(|r| (r.name.as_str(), r.scope, r.definition.map(|d| d.start)))
+        .collect()
+}
+
+fn messages(errors: &[SyntaxError]) -> Vec<(&str, Span)> {
+    errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span))
+        .collect()
+}
+
+#[test]
+fn test_scopes() {
+    let resolution = resolve(
+        "let a = 1;
+let f = fn(b) { let c = a + b; fn() { b + c + f(len) } };",
+    );
+    assert!(resolution.errors.is_empty());
+    assert!(resolution.warnings.is_empty());
+    assert_eq!(
+        scopes(&resolution),
+        vec![
+            ("a", SymbolScope::Global, Some(4)),
+            ("b", SymbolScope::Local, Some(22)),
+            ("a", SymbolScope::Global, Some(4)),
+            ("b", SymbolScope::Local, Some(22)),
+            ("c", SymbolScope::Local, Some(31)),
+            ("b", SymbolScope::Free, Some(22)),
+            ("c", SymbolScope::Free, Some(31)),
+            ("f", SymbolScope::Free, Some(15)),
+            ("len", SymbolScope::Builtin, None),
+            ("f", SymbolScope::Global, Some(15)),
+        ]
+    );
+
+    // 関数の中から自分を指す名前は Function になる
+    let resolution = resolve("fn() { let g = fn(n) { g(n) }; g }");
+    assert_eq!(
+        scopes(&resolution),
+        vec![
+            ("n", SymbolScope::Local, Some(18)),
+            ("g", SymbolScope::Function, Some(11)),
+            ("n", SymbolScope::Local, Some(18)),
+            ("g", SymbolScope::Local, Some(11)),
+            ("g", SymbolScope::Local, Some(11)),
+        ]
+    );
+}
+
+#[test]
+fn test_binding_kinds() {
+    let resolution = resolve(
+        "import \"lib\" as m;
+for (i, x in [1]) { match (x) { [y] if y => i, _ => m.x } }",
+    );
+    assert!(resolution.errors.is_empty());
+    let kinds: Vec<_> = resolution
+        .definitions()
+        .map(|r| (r.name.as_str(), r.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("m", BindingKind::Import),
+            ("i", BindingKind::For),
+            ("x", BindingKind::For),
+            ("y", BindingKind::Match),
+        ]
+    );
+    // プロパティは解決しない
+    let last = resolution.references.last().unwrap();
+    assert_eq!((last.name.as_str(), last.kind), ("m", BindingKind::Import));
+
+    let resolution = resolve("let [a, {\"k\": b}] = [1]; fn([c], d) { a + b + c + d }");
+    let kinds: Vec<_> = resolution
+        .definitions()
+        .map(|r| (r.name.as_str(), r.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("a", BindingKind::Let),
+            ("b", BindingKind::Let),
+            ("c", BindingKind::Parameter),
+            ("d", BindingKind::Parameter),
+        ]
+    );
+}
+
+#[test]
+fn test_references() {
+    let input = "let x = 1; let y = x + x; fn(z) { x + z }";
+    let resolution = resolve(input);
+
+    let x = resolution.at(4).unwrap();
+    assert!(x.is_definition());
+    let found: Vec<_> = resolution
+        .references_to(x.span)
+        .map(|r| r.span.start)
+        .collect();
+    assert_eq!(found, vec![4, 19, 23, 34]);
+
+    let use_of_x = resolution.at(19).unwrap();
+    assert!(!use_of_x.is_definition());
+    assert_eq!(use_of_x.definition, Some(Span::new(4, 5)));
+    assert_eq!(resolution.at(8), None);
+}
+
+#[test]
+fn test_errors() {
+    let tests = vec![
+        ("x", vec![("identifier not found: x", Span::new(0, 1))]),
+        // 束縛する前の名前は見えない
+        (
+            "let f = fn() { g() }; let g = 1;",
+            vec![("identifier not found: g", Span::new(15, 16))],
+        ),
+        (
+            "let a = a;",
+            vec![("identifier not found: a", Span::new(8, 9))],
+        ),
+        // ブロックはスコープを作らない
+        ("if (true) { let a = 1; }; a", vec![]),
+        (
+            "y = 1; len = 2; let z = [1]; z[w] = 3;",
+            vec![
+                ("cannot assign to undefined variable: y", Span::new(0, 1)),
+                ("cannot assign to undefined variable: len", Span::new(7, 10)),
+                ("identifier not found: w", Span::new(31, 32)),
+            ],
+        ),
+        ("import \"lib\" as m; m.missing", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        let resolution = resolve(input);
+        assert_eq!(messages(&resolution.errors), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_duplicate_parameters() {
+    // 構文解析でも見つかるが、構文木だけを渡されたときのために解決でも調べる
+    let (program, errors) = parse("fn(a, [b, a], b) { a }");
+    let expected = vec![
+        ("duplicate parameter a", Span::new(10, 11)),
+        ("duplicate parameter b", Span::new(14, 15)),
+    ];
+    assert_eq!(messages(&errors), expected);
+    let resolution = Resolver::new().resolve(&program);
+    assert_eq!(messages(&resolution.errors), expected);
+}
+
+#[test]
+fn test_shadowing() {
+    let tests = vec![
+        (
+            "let x = 1; fn(x) { x }",
+            vec![("x shadows a binding from an outer scope", Span::new(14, 15))],
+        ),
+        (
+            "let f = fn() { let f = 1; fn() { let a = 1; fn() { let a = 2; } } };",
+            vec![
+                ("f shadows a binding from an outer scope", Span::new(19, 20)),
+                ("a shadows a binding from an outer scope", Span::new(55, 56)),
+            ],
+        ),
+        // 同じスコープでの定義し直しや、組み込み関数を隠すのは警告しない
+        (
+            "let x = 1; let x = 2; fn() { let y = 1; let y = 2; }",
+            vec![],
+        ),
+        ("let len = 1; fn(first) { first }", vec![]),
+    ];
+
+    for (input, expected) in tests {
+        let resolution = resolve(input);
+        assert_eq!(messages(&resolution.warnings), expected, "{}", input);
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::Formatter;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Default, Debug, Eq, PartialEq, Copy, Clone, Hash)]
 pub enum TokenKind {
     #[default]
@@ -9,14 +10,19 @@ pub enum TokenKind {
     // 識別子 + リテラル
     Ident,
     Int,
+    String,
 
     // 演算子
-    Assign,   // =
-    Plus,     // +
-    Minus,    // -
-    Bang,     // !
-    Asterisk, // *
-    Slash,    // /
+    Assign,         // =
+    PlusAssign,     // +=
+    MinusAssign,    // -=
+    AsteriskAssign, // *=
+    SlashAssign,    // /=
+    Plus,           // +
+    Minus,          // -
+    Bang,           // !
+    Asterisk,       // *
+    Slash,          // /
 
     Lt,    // <
     Gt,    // >
@@ -26,11 +32,18 @@ pub enum TokenKind {
     // デリミタ
     Comma,     // ,
     SemiColon, // ;
+    Colon,     // :
+    Dot,       // .
+    DotDot,    // ..
+    DotDotEq,  // ..=
+    FatArrow,  // =>
 
-    LParen, // (
-    RParen, // )
-    LBrace, // {
-    RBrace, // }
+    LParen,   // (
+    RParen,   // )
+    LBrace,   // {
+    RBrace,   // }
+    LBracket, // [
+    RBracket, // ]
 
     // キーワード
     Function,
@@ -40,6 +53,15 @@ pub enum TokenKind {
     If,
     Else,
     Return,
+    While,
+    Break,
+    Continue,
+    For,
+    In,
+    Match,
+    Import,
+    Export,
+    As,
 }
 
 impl std::fmt::Display for TokenKind {
@@ -49,7 +71,12 @@ impl std::fmt::Display for TokenKind {
             TokenKind::EOF => "EOF",
             TokenKind::Ident => "IDENT",
             TokenKind::Int => "INT",
+            TokenKind::String => "STRING",
             TokenKind::Assign => "=",
+            TokenKind::PlusAssign => "+=",
+            TokenKind::MinusAssign => "-=",
+            TokenKind::AsteriskAssign => "*=",
+            TokenKind::SlashAssign => "/=",
             TokenKind::Plus => "+",
             TokenKind::Minus => "-",
             TokenKind::Bang => "!",
@@ -61,10 +88,17 @@ impl std::fmt::Display for TokenKind {
             TokenKind::Gt => ">",
             TokenKind::Comma => ",",
             TokenKind::SemiColon => ";",
+            TokenKind::Colon => ":",
+            TokenKind::Dot => ".",
+            TokenKind::DotDot => "..",
+            TokenKind::DotDotEq => "..=",
+            TokenKind::FatArrow => "=>",
             TokenKind::LParen => "(",
             TokenKind::RParen => ")",
             TokenKind::LBrace => "{",
             TokenKind::RBrace => "}",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
             TokenKind::Function => "FUNCTION",
             TokenKind::Let => "LET",
             TokenKind::True => "TRUE",
@@ -72,6 +106,15 @@ impl std::fmt::Display for TokenKind {
             TokenKind::If => "IF",
             TokenKind::Else => "ELSE",
             TokenKind::Return => "RETURN",
+            TokenKind::While => "WHILE",
+            TokenKind::Break => "BREAK",
+            TokenKind::Continue => "CONTINUE",
+            TokenKind::For => "FOR",
+            TokenKind::In => "IN",
+            TokenKind::Match => "MATCH",
+            TokenKind::Import => "IMPORT",
+            TokenKind::Export => "EXPORT",
+            TokenKind::As => "AS",
         };
         write!(f, "{}", s)
     }
@@ -87,15 +130,38 @@ impl TokenKind {
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
             "return" => TokenKind::Return,
+            "while" => TokenKind::While,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "match" => TokenKind::Match,
+            "import" => TokenKind::Import,
+            "export" => TokenKind::Export,
+            "as" => TokenKind::As,
             _ => TokenKind::Ident,
         }
     }
 }
 
+// 入力中の位置 (文字単位, end は含まない)
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub literal: String,
+    pub span: Span,
 }
 
 impl Token {
@@ -103,6 +169,18 @@ impl Token {
         Token {
             kind,
             literal: literal.to_string(),
+            span: Span::default(),
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Token {
+        self.span = span;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
 }
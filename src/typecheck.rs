@@ -0,0 +1,718 @@
+#[cfg(test)]
+mod test;
+mod types;
+
+use crate::ast::{
+    BlockStatement, Expression, ForStatement, FunctionLiteral, InfixExpression, LetStatement, Node,
+    Pattern, Program, Statement,
+};
+use crate::parser::SyntaxError;
+use crate::resolver::{Reference, Resolution};
+use crate::token::Span;
+use std::collections::HashMap;
+
+pub use types::Type;
+
+// 型の検査の結果。実行を止めるものではないので、見つけたものはすべて警告にする
+#[derive(Debug, Clone, Default)]
+pub struct Typing {
+    pub warnings: Vec<SyntaxError>,
+    // 束縛した場所ごとの型
+    bindings: HashMap<Span, Type>,
+}
+
+impl Typing {
+    pub fn type_of(&self, definition: Span) -> Option<&Type> {
+        self.bindings.get(&definition)
+    }
+}
+
+// 名前の解決の結果を使って、束縛した場所ごとに型を推論する (Hindley–Milner)。
+// let で束縛した関数リテラルは多相にする
+pub fn check(program: &Program, resolution: &Resolution) -> Typing {
+    let mut checker = Checker {
+        references: resolution.references.iter().map(|r| (r.span, r)).collect(),
+        env: HashMap::new(),
+        vars: vec![],
+        level: 0,
+        returns: vec![],
+        additions: vec![],
+        warnings: vec![],
+    };
+    for stmt in &program.statements {
+        checker.statement(stmt);
+    }
+    checker.finish()
+}
+
+// 束縛した名前の型。quantified の型変数は使うたびに新しくする
+struct Scheme {
+    quantified: Vec<usize>,
+    ty: Type,
+}
+
+impl Scheme {
+    fn mono(ty: Type) -> Scheme {
+        Scheme {
+            quantified: vec![],
+            ty,
+        }
+    }
+}
+
+// 型変数。level は作ったときの let の深さで、一般化できるかを決める
+#[derive(Clone)]
+struct Var {
+    binding: Option<Type>,
+    level: usize,
+}
+
+struct Checker<'a> {
+    // 名前が現れた場所から、その名前が指す束縛へ
+    references: HashMap<Span, &'a Reference>,
+    env: HashMap<Span, Scheme>,
+    vars: Vec<Var>,
+    level: usize,
+    // 検査している関数ごとの、return した値の型
+    returns: Vec<Type>,
+    // 両辺の型が決まらなかった +。整数か文字列になったかを最後に確かめる
+    additions: Vec<(Type, Span)>,
+    warnings: Vec<SyntaxError>,
+}
+
+impl Checker<'_> {
+    fn finish(mut self) -> Typing {
+        for (ty, span) in std::mem::take(&mut self.additions) {
+            let ty = self.zonk(&ty);
+            if !matches!(ty, Type::Int | Type::String | Type::Var(_) | Type::Dynamic) {
+                self.warn(span, format!("unknown operator: {} + {}", ty, ty));
+            }
+        }
+        let bindings = self
+            .env
+            .iter()
+            .map(|(span, scheme)| (*span, self.zonk(&scheme.ty)))
+            .collect();
+        self.warnings.sort_by_key(|w| w.span.start);
+        Typing {
+            warnings: self.warnings,
+            bindings,
+        }
+    }
+
+    fn warn(&mut self, span: Span, message: String) {
+        self.warnings.push(SyntaxError { message, span });
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.vars.push(Var {
+            binding: None,
+            level: self.level,
+        });
+        Type::Var(self.vars.len() - 1)
+    }
+
+    // 束縛済みの型変数をたどった先
+    fn prune(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(id) = ty {
+            match &self.vars[id].binding {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    // 中まですべてたどった型
+    fn zonk(&self, ty: &Type) -> Type {
+        match self.prune(ty) {
+            Type::Array(element) => Type::array(self.zonk(&element)),
+            Type::Hash(key, value) => Type::hash(self.zonk(&key), self.zonk(&value)),
+            Type::Function(parameters, result) => Type::function(
+                parameters.iter().map(|p| self.zonk(p)).collect(),
+                self.zonk(&result),
+            ),
+            ty => ty,
+        }
+    }
+
+    // id が ty の中に現れるか。現れなければ、ty の中の型変数を id と同じ深さまで下げる
+    fn occurs(&mut self, id: usize, ty: &Type) -> bool {
+        match self.prune(ty) {
+            Type::Var(other) if other == id => true,
+            Type::Var(other) => {
+                let level = self.vars[id].level;
+                let var = &mut self.vars[other];
+                var.level = var.level.min(level);
+                false
+            }
+            Type::Array(element) => self.occurs(id, &element),
+            Type::Hash(key, value) => self.occurs(id, &key) || self.occurs(id, &value),
+            Type::Function(parameters, result) => {
+                parameters.iter().any(|p| self.occurs(id, p)) || self.occurs(id, &result)
+            }
+            _ => false,
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), ()> {
+        match (self.prune(a), self.prune(b)) {
+            (Type::Var(x), Type::Var(y)) if x == y => Ok(()),
+            // dynamic と合わせた型変数は dynamic になる
+            (Type::Var(id), ty) | (ty, Type::Var(id)) => {
+                if self.occurs(id, &ty) {
+                    return Err(());
+                }
+                self.vars[id].binding = Some(ty);
+                Ok(())
+            }
+            (Type::Array(a), Type::Array(b)) => self.unify(&a, &b),
+            (Type::Hash(k1, v1), Type::Hash(k2, v2)) => {
+                self.unify(&k1, &k2)?;
+                self.unify(&v1, &v2)
+            }
+            (Type::Function(p1, r1), Type::Function(p2, r2)) if p1.len() == p2.len() => {
+                for (a, b) in p1.iter().zip(&p2) {
+                    self.unify(a, b)?;
+                }
+                self.unify(&r1, &r2)
+            }
+            (Type::Dynamic, _) | (_, Type::Dynamic) => Ok(()),
+            (a, b) if a == b => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    // 合わなければ、合わせようとしたことを取り消す
+    fn try_unify(&mut self, a: &Type, b: &Type) -> bool {
+        let snapshot = self.vars.clone();
+        let ok = self.unify(a, b).is_ok();
+        if !ok {
+            self.vars = snapshot;
+        }
+        ok
+    }
+
+    fn expect(&mut self, expected: &Type, actual: &Type, span: Span) {
+        if !self.try_unify(expected, actual) {
+            let message = format!(
+                "expected {}, got {}",
+                self.zonk(expected),
+                self.zonk(actual)
+            );
+            self.warn(span, message);
+        }
+    }
+
+    // 配列の要素や if の腕のように、いくつかの値が合流するところの型。
+    // 合わなければエラーにせず dynamic にする
+    fn join(&mut self, a: &Type, b: &Type) -> Type {
+        let either_dynamic = [a, b].iter().any(|t| self.prune(t) == Type::Dynamic);
+        if either_dynamic || !self.try_unify(a, b) {
+            return Type::Dynamic;
+        }
+        a.clone()
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let fresh: HashMap<usize, Type> = scheme
+            .quantified
+            .iter()
+            .map(|&id| (id, self.fresh()))
+            .collect();
+        self.substitute(&scheme.ty, &fresh)
+    }
+
+    fn substitute(&self, ty: &Type, fresh: &HashMap<usize, Type>) -> Type {
+        match self.prune(ty) {
+            Type::Var(id) => fresh.get(&id).cloned().unwrap_or(Type::Var(id)),
+            Type::Array(element) => Type::array(self.substitute(&element, fresh)),
+            Type::Hash(key, value) => {
+                Type::hash(self.substitute(&key, fresh), self.substitute(&value, fresh))
+            }
+            Type::Function(parameters, result) => Type::function(
+                parameters
+                    .iter()
+                    .map(|p| self.substitute(p, fresh))
+                    .collect(),
+                self.substitute(&result, fresh),
+            ),
+            ty => ty,
+        }
+    }
+
+    // いまの let より内側で作り、外に漏れていない型変数を一般化する
+    fn generalize(&self, ty: &Type) -> Scheme {
+        fn collect(checker: &Checker, ty: &Type, out: &mut Vec<usize>) {
+            match checker.prune(ty) {
+                Type::Var(id) if checker.vars[id].level > checker.level && !out.contains(&id) => {
+                    out.push(id)
+                }
+                Type::Array(element) => collect(checker, &element, out),
+                Type::Hash(key, value) => {
+                    collect(checker, &key, out);
+                    collect(checker, &value, out);
+                }
+                Type::Function(parameters, result) => {
+                    for parameter in &parameters {
+                        collect(checker, parameter, out);
+                    }
+                    collect(checker, &result, out);
+                }
+                _ => {}
+            }
+        }
+        let mut quantified = vec![];
+        collect(self, ty, &mut quantified);
+        Scheme {
+            quantified,
+            ty: ty.clone(),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Type {
+        match stmt {
+            Statement::LetStatement(s) => self.let_statement(s),
+            Statement::ExportStatement(s) => self.let_statement(&s.statement),
+            Statement::ReturnStatement(s) => {
+                let value = self.optional(s.return_value.as_ref());
+                if let Some(result) = self.returns.pop() {
+                    let joined = self.join(&result, &value);
+                    self.returns.push(joined);
+                }
+                // ここから先には進まないので、どんな型とも合う
+                return self.fresh();
+            }
+            Statement::ExpressionStatement(s) => return self.optional(s.expression.as_ref()),
+            Statement::BlockStatement(s) => return self.block(s),
+            Statement::WhileStatement(s) => {
+                self.optional(s.condition.as_ref());
+                self.block(&s.body);
+            }
+            Statement::ForStatement(s) => self.for_statement(s),
+            Statement::BreakStatement(_) | Statement::ContinueStatement(_) => return self.fresh(),
+            // モジュールの中の名前の型は分からない
+            Statement::ImportStatement(s) => {
+                let scheme = Scheme::mono(Type::Dynamic);
+                self.env.insert(s.alias.token.span, scheme);
+            }
+        }
+        Type::Null
+    }
+
+    // ブロックの値は最後の文の値
+    fn block(&mut self, block: &BlockStatement) -> Type {
+        let mut ty = Type::Null;
+        for stmt in &block.statements {
+            ty = self.statement(stmt);
+        }
+        ty
+    }
+
+    fn let_statement(&mut self, s: &LetStatement) {
+        match (&s.value, s.pattern.as_identifier()) {
+            (Some(Expression::FunctionLiteral(function)), Some(name)) => {
+                // 本体からは自分自身を単相として参照できる
+                self.level += 1;
+                let ty = self.fresh();
+                self.env.insert(name.token.span, Scheme::mono(ty.clone()));
+                let function = self.function(function);
+                self.expect(&ty, &function, name.token.span);
+                self.level -= 1;
+                let scheme = self.generalize(&ty);
+                self.env.insert(name.token.span, scheme);
+            }
+            _ => {
+                let value = self.optional(s.value.as_ref());
+                self.bind_pattern(&s.pattern, &value, true);
+            }
+        }
+    }
+
+    fn for_statement(&mut self, s: &ForStatement) {
+        let iterable = self.optional(s.iterable.as_ref());
+        let (key, element) = match self.prune(&iterable) {
+            Type::Array(element) => (Type::Int, *element),
+            Type::String => (Type::Int, Type::String),
+            Type::Range => (Type::Int, Type::Int),
+            Type::Hash(key, value) => (*key, *value),
+            Type::Var(_) | Type::Dynamic => (Type::Dynamic, Type::Dynamic),
+            other => {
+                let span = s.iterable.as_ref().map_or(s.token.span, Expression::span);
+                self.warn(span, format!("not iterable: {}", self.zonk(&other)));
+                (Type::Dynamic, Type::Dynamic)
+            }
+        };
+        // 変数が一つなら、ハッシュはキーを、ほかは要素を取り出す
+        let is_hash = matches!(self.prune(&iterable), Type::Hash(_, _));
+        let types = match s.variables.len() {
+            1 if is_hash => vec![key],
+            1 => vec![element],
+            _ => vec![key, element],
+        };
+        for (variable, ty) in s.variables.iter().zip(types) {
+            self.env.insert(variable.token.span, Scheme::mono(ty));
+        }
+        self.block(&s.body);
+    }
+
+    // パターンが束縛する名前に型を付ける。strict なら、形が値の型に合わないときに警告する。
+    // 合わなければ名前は dynamic にする
+    fn bind_pattern(&mut self, pattern: &Pattern, value: &Type, strict: bool) {
+        let mut names = vec![];
+        let ty = self.pattern_type(pattern, &mut names);
+        if !self.try_unify(&ty, value) {
+            if strict {
+                let message = format!(
+                    "pattern {} does not match {}",
+                    pattern.to_string(),
+                    self.zonk(value)
+                );
+                self.warn(pattern.span(), message);
+            }
+            for (_, ty) in &mut names {
+                *ty = Type::Dynamic;
+            }
+        }
+        for (span, ty) in names {
+            self.env.insert(span, Scheme::mono(ty));
+        }
+    }
+
+    fn pattern_type(&mut self, pattern: &Pattern, names: &mut Vec<(Span, Type)>) -> Type {
+        match pattern {
+            Pattern::WildcardPattern(_) => self.fresh(),
+            Pattern::Identifier(p) => {
+                let ty = self.fresh();
+                names.push((p.token.span, ty.clone()));
+                ty
+            }
+            Pattern::IntegerLiteral(_) => Type::Int,
+            Pattern::StringLiteral(_) => Type::String,
+            Pattern::Boolean(_) => Type::Bool,
+            Pattern::ArrayPattern(p) => {
+                let mut element = self.fresh();
+                for pattern in &p.elements {
+                    let ty = self.pattern_type(pattern, names);
+                    element = self.join(&element, &ty);
+                }
+                if let Some(name) = p.rest.as_ref().and_then(|r| r.name.as_ref()) {
+                    names.push((name.token.span, Type::array(element.clone())));
+                }
+                Type::array(element)
+            }
+            Pattern::HashPattern(p) => {
+                let (mut key, mut value) = (self.fresh(), self.fresh());
+                for (k, v) in &p.pairs {
+                    let k = self.expression(k);
+                    key = self.join(&key, &k);
+                    let v = self.pattern_type(v, names);
+                    value = self.join(&value, &v);
+                }
+                Type::hash(key, value)
+            }
+        }
+    }
+
+    fn function(&mut self, e: &FunctionLiteral) -> Type {
+        let mut parameters = vec![];
+        for parameter in &e.parameters {
+            let mut names = vec![];
+            parameters.push(self.pattern_type(parameter, &mut names));
+            for (span, ty) in names {
+                self.env.insert(span, Scheme::mono(ty));
+            }
+        }
+        let result = self.fresh();
+        self.returns.push(result);
+        let value = self.block(&e.body);
+        let result = self.returns.pop().unwrap_or(Type::Dynamic);
+        let result = self.join(&result, &value);
+        Type::function(parameters, result)
+    }
+
+    fn optional(&mut self, exp: Option<&Expression>) -> Type {
+        match exp {
+            Some(exp) => self.expression(exp),
+            None => Type::Dynamic,
+        }
+    }
+
+    fn expression(&mut self, exp: &Expression) -> Type {
+        match exp {
+            Expression::Identifier(e) => {
+                let Some(reference) = self.references.get(&e.token.span).copied() else {
+                    return Type::Dynamic;
+                };
+                match reference.definition {
+                    Some(definition) => match self.env.remove(&definition) {
+                        Some(scheme) => {
+                            let ty = self.instantiate(&scheme);
+                            self.env.insert(definition, scheme);
+                            ty
+                        }
+                        None => Type::Dynamic,
+                    },
+                    None => self.builtin(&reference.name),
+                }
+            }
+            Expression::IntegerLiteral(_) => Type::Int,
+            Expression::Boolean(_) => Type::Bool,
+            Expression::StringLiteral(_) => Type::String,
+            Expression::PrefixExpression(e) => {
+                let right = self.optional(e.right.as_deref());
+                match e.operator.as_str() {
+                    "-" => {
+                        if !self.try_unify(&Type::Int, &right) {
+                            let message = format!("unknown operator: -{}", self.zonk(&right));
+                            self.warn(e.token.span, message);
+                        }
+                        Type::Int
+                    }
+                    _ => Type::Bool,
+                }
+            }
+            Expression::InfixExpression(e) => self.infix(e),
+            Expression::IfExpression(e) => {
+                self.optional(e.condition.as_deref());
+                let consequence = self.block(&e.consequence);
+                let alternative = match &e.alternative {
+                    Some(alternative) => self.block(alternative),
+                    None => Type::Null,
+                };
+                self.join(&consequence, &alternative)
+            }
+            Expression::FunctionLiteral(e) => self.function(e),
+            Expression::CallExpression(e) => {
+                let function = self.expression(&e.function);
+                let arguments: Vec<Type> = e.arguments.iter().map(|a| self.expression(a)).collect();
+                match self.prune(&function) {
+                    Type::Function(parameters, result) => {
+                        if parameters.len() != arguments.len() {
+                            let message = format!(
+                                "wrong number of arguments: want={}, got={}",
+                                parameters.len(),
+                                arguments.len()
+                            );
+                            self.warn(e.token.span, message);
+                            return *result;
+                        }
+                        for ((parameter, argument), exp) in
+                            parameters.iter().zip(&arguments).zip(&e.arguments)
+                        {
+                            self.expect(parameter, argument, exp.span());
+                        }
+                        *result
+                    }
+                    Type::Var(_) => {
+                        let result = self.fresh();
+                        let ty = Type::function(arguments, result.clone());
+                        self.expect(&function, &ty, e.token.span);
+                        result
+                    }
+                    Type::Dynamic => Type::Dynamic,
+                    other => {
+                        let span = e.function.span();
+                        self.warn(span, format!("not a function: {}", self.zonk(&other)));
+                        Type::Dynamic
+                    }
+                }
+            }
+            Expression::ArrayLiteral(e) => {
+                let mut element = self.fresh();
+                for exp in &e.elements {
+                    let ty = self.expression(exp);
+                    element = self.join(&element, &ty);
+                }
+                Type::array(element)
+            }
+            Expression::IndexExpression(e) => {
+                let left = self.expression(&e.left);
+                let index = self.expression(&e.index);
+                self.index(&left, &index, e.token.span)
+            }
+            Expression::HashLiteral(e) => {
+                let (mut key, mut value) = (self.fresh(), self.fresh());
+                for (k, v) in &e.pairs {
+                    let k = self.expression(k);
+                    key = self.join(&key, &k);
+                    let v = self.expression(v);
+                    value = self.join(&value, &v);
+                }
+                Type::hash(key, value)
+            }
+            Expression::RangeExpression(e) => {
+                let start = self.optional(e.start.as_deref());
+                let end = self.optional(e.end.as_deref());
+                if !(self.try_unify(&Type::Int, &start) & self.try_unify(&Type::Int, &end)) {
+                    let op = if e.inclusive { "..=" } else { ".." };
+                    let message = format!(
+                        "range bounds must be int, got {}{}{}",
+                        self.zonk(&start),
+                        op,
+                        self.zonk(&end)
+                    );
+                    self.warn(e.token.span, message);
+                }
+                Type::Range
+            }
+            Expression::AssignExpression(e) => {
+                // 添字をたどって、書き換える場所の型を求める
+                let mut indexes = vec![];
+                let mut target = &*e.target;
+                while let Expression::IndexExpression(index) = target {
+                    indexes.push(index);
+                    target = &index.left;
+                }
+                let mut slot = self.expression(target);
+                for index in indexes.into_iter().rev() {
+                    let key = self.expression(&index.index);
+                    slot = self.index(&slot, &key, index.token.span);
+                }
+
+                let value = self.optional(e.value.as_deref());
+                match e.arithmetic_operator() {
+                    // 演算子の両辺を検査すれば、結果も書き換える場所に合う
+                    Some(op) => self.operator(op, &slot, &value, e.token.span),
+                    None => {
+                        let span = e.value.as_deref().map_or(e.token.span, Expression::span);
+                        self.expect(&slot, &value, span);
+                        value
+                    }
+                }
+            }
+            Expression::MatchExpression(e) => {
+                let subject = self.optional(e.subject.as_deref());
+                let mut result = self.fresh();
+                for arm in &e.arms {
+                    // 形の合わない腕は選ばれないだけなので、警告しない
+                    self.bind_pattern(&arm.pattern, &subject, false);
+                    self.optional(arm.guard.as_ref());
+                    let body = self.optional(arm.body.as_ref());
+                    result = self.join(&result, &body);
+                }
+                result
+            }
+            Expression::MemberExpression(e) => {
+                let object = self.expression(&e.object);
+                match self.prune(&object) {
+                    Type::Var(_) | Type::Dynamic => {}
+                    other => {
+                        let message = format!("member access not supported: {}", self.zonk(&other));
+                        self.warn(e.token.span, message);
+                    }
+                }
+                Type::Dynamic
+            }
+        }
+    }
+
+    // 左につなげた演算子は木の左側に深く伸びるので、再帰せずに左から順に検査する
+    fn infix(&mut self, e: &InfixExpression) -> Type {
+        let mut chain = vec![e];
+        let mut leftmost = e.left.as_deref();
+        while let Some(Expression::InfixExpression(inner)) = leftmost {
+            chain.push(inner);
+            leftmost = inner.left.as_deref();
+        }
+
+        let mut left = self.optional(leftmost);
+        for e in chain.into_iter().rev() {
+            let right = self.optional(e.right.as_deref());
+            left = self.operator(&e.operator, &left, &right, e.token.span);
+        }
+        left
+    }
+
+    // 演算子は整数どうし、+ は文字列どうしにも使える。== と != はどんな値でも比べられる
+    fn operator(&mut self, op: &str, left: &Type, right: &Type, span: Span) -> Type {
+        let result = match op {
+            "==" | "!=" => return Type::Bool,
+            "+" => match (self.prune(left), self.prune(right)) {
+                (Type::Dynamic, _) | (_, Type::Dynamic) => return Type::Dynamic,
+                (Type::String, _) | (_, Type::String) => Type::String,
+                (Type::Var(_), Type::Var(_)) => {
+                    self.unify(left, right).ok();
+                    self.additions.push((left.clone(), span));
+                    return left.clone();
+                }
+                _ => Type::Int,
+            },
+            "-" | "*" | "/" => Type::Int,
+            "<" | ">" => {
+                self.operands(op, left, right, &Type::Int, span);
+                return Type::Bool;
+            }
+            _ => return Type::Dynamic,
+        };
+        self.operands(op, left, right, &result, span);
+        result
+    }
+
+    // 両辺を operand に合わせる。合わなければ実行したときと同じ言い方で警告する
+    fn operands(&mut self, op: &str, left: &Type, right: &Type, operand: &Type, span: Span) {
+        if !(self.try_unify(operand, left) & self.try_unify(operand, right)) {
+            let (left, right) = (self.zonk(left), self.zonk(right));
+            let message = if left.kind() == right.kind() {
+                format!("unknown operator: {} {} {}", left, op, right)
+            } else {
+                format!("type mismatch: {} {} {}", left, op, right)
+            };
+            self.warn(span, message);
+        }
+    }
+
+    // 範囲外の添字や無いキーは null になるが、要素の型とみなす
+    fn index(&mut self, left: &Type, index: &Type, span: Span) -> Type {
+        match self.prune(left) {
+            Type::Array(element) => {
+                if !self.try_unify(&Type::Int, index) {
+                    let message = format!(
+                        "index operator not supported: {}[{}]",
+                        self.zonk(left),
+                        self.zonk(index)
+                    );
+                    self.warn(span, message);
+                }
+                *element
+            }
+            // 型の違うキーは null になるだけなので警告しない
+            Type::Hash(key, value) => {
+                self.try_unify(&key, index);
+                *value
+            }
+            // 文字列で引けるのはハッシュだけ
+            Type::Var(_) if self.prune(index) == Type::String => {
+                let value = self.fresh();
+                self.unify(left, &Type::hash(Type::String, value.clone()))
+                    .ok();
+                value
+            }
+            Type::Var(_) | Type::Dynamic => Type::Dynamic,
+            other => {
+                let message = format!(
+                    "index operator not supported: {}[{}]",
+                    self.zonk(&other),
+                    self.zonk(index)
+                );
+                self.warn(span, message);
+                Type::Dynamic
+            }
+        }
+    }
+
+    // 組み込み関数の型。使うたびに新しい型変数で作る
+    fn builtin(&mut self, name: &str) -> Type {
+        let a = self.fresh();
+        match name {
+            "len" => Type::function(vec![a], Type::Int),
+            "first" | "last" => Type::function(vec![Type::array(a.clone())], a),
+            "rest" => Type::function(vec![Type::array(a.clone())], Type::array(a)),
+            "push" => Type::function(vec![Type::array(a.clone()), a.clone()], Type::array(a)),
+            "type" | "str" => Type::function(vec![a], Type::String),
+            // puts はいくつでも引数を取る
+            _ => Type::Dynamic,
+        }
+    }
+}
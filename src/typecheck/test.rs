@@ -0,0 +1,251 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+
+fn typecheck(input: &str) -> (Resolution, Typing) {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+    let resolution = Resolver::new().resolve(&program);
+    assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+    let typing = check(&program, &resolution);
+    (resolution, typing)
+}
+
+// 束縛した順に (名前, 型)
+fn types(input: &str) -> Vec<(String, String)> {
+    let (resolution, typing) = typecheck(input);
+    assert!(typing.warnings.is_empty(), "{:?}", typing.warnings);
+    let mut definitions: Vec<&Reference> = resolution.definitions().collect();
+    definitions.sort_by_key(|r| r.span.start);
+    definitions
+        .into_iter()
+        .map(|r| {
+            let ty = typing.type_of(r.span).map(Type::to_string);
+            (r.name.clone(), ty.unwrap_or_default())
+        })
+        .collect()
+}
+
+fn warnings(input: &str) -> Vec<(String, Span)> {
+    let (_, typing) = typecheck(input);
+    typing
+        .warnings
+        .into_iter()
+        .map(|w| (w.message, w.span))
+        .collect()
+}
+
+fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+    expected
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_inference() {
+    let tests = [
+        (
+            "let a = 1; let b = \"x\" + \"y\"; let c = a < 2; let d = 1..a;",
+            vec![("a", "int"), ("b", "string"), ("c", "bool"), ("d", "range")],
+        ),
+        (
+            "let add = fn(x, y) { x + y * 2 }; let n = add(1, 2);",
+            vec![
+                ("add", "fn(int, int) -> int"),
+                ("x", "int"),
+                ("y", "int"),
+                ("n", "int"),
+            ],
+        ),
+        (
+            "let compose = fn(f, g) { fn(x) { f(g(x)) } };",
+            vec![
+                ("compose", "fn(fn('a) -> 'b, fn('c) -> 'a) -> fn('c) -> 'b"),
+                ("f", "fn('a) -> 'b"),
+                ("g", "fn('a) -> 'b"),
+                ("x", "'a"),
+            ],
+        ),
+        (
+            "let fact = fn(n) { if (n < 2) { return 1; } n * fact(n - 1) };",
+            vec![("fact", "fn(int) -> int"), ("n", "int")],
+        ),
+        (
+            "let s = fn(x) { x + \"!\" }; let t = fn(a) { puts(a); let b = a; };",
+            vec![
+                ("s", "fn(string) -> string"),
+                ("x", "string"),
+                ("t", "fn('a) -> null"),
+                ("a", "'a"),
+                ("b", "'a"),
+            ],
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(types(input), pairs(&expected), "{}", input);
+    }
+}
+
+#[test]
+fn test_let_polymorphism() {
+    let input = "let id = fn(x) { x };
+let a = id(1);
+let b = id(\"s\");
+let pair = fn(x) { [id(x), id(x)] };
+let c = pair(true);";
+    assert_eq!(
+        types(input),
+        pairs(&[
+            ("id", "fn('a) -> 'a"),
+            ("x", "'a"),
+            ("a", "int"),
+            ("b", "string"),
+            ("pair", "fn('a) -> ['a]"),
+            ("x", "'a"),
+            ("c", "[bool]"),
+        ])
+    );
+
+    // 引数は関数の中では単相
+    assert_eq!(
+        warnings("let f = fn(g) { [g(1), g(\"s\")] }; let h = fn(g) { g(1) + g(\"s\") };"),
+        vec![
+            ("expected int, got string".to_string(), Span::new(25, 28)),
+            ("expected int, got string".to_string(), Span::new(59, 62)),
+        ]
+    );
+}
+
+#[test]
+fn test_parametric_types() {
+    let input = "let xs = [1, 2, 3];
+let ys = push(rest(xs), 4);
+let y = first(ys);
+let h = {\"a\": [1], \"b\": []};
+let v = h[\"a\"];
+let get = fn(m, k) { m[k] };
+let lookup = fn(m) { m[\"key\"] };";
+    assert_eq!(
+        types(input),
+        pairs(&[
+            ("xs", "[int]"),
+            ("ys", "[int]"),
+            ("y", "int"),
+            ("h", "{string: [int]}"),
+            ("v", "[int]"),
+            ("get", "fn('a, 'b) -> dynamic"),
+            ("m", "'a"),
+            ("k", "'a"),
+            ("lookup", "fn({string: 'a}) -> 'a"),
+            ("m", "{string: 'a}"),
+        ])
+    );
+}
+
+#[test]
+fn test_dynamic() {
+    // 型の合わない値が合流するところは dynamic にして、警告しない
+    let input = "let xs = [1, \"a\", true];
+let x = if (len(xs) > 1) { 1 } else { \"one\" };
+let y = xs[0] + 1;
+let z = fn(flag) { if (flag) { return 1; } \"s\" };
+import \"math\" as m;
+let w = m.pi * 2;";
+    assert_eq!(
+        types(input),
+        pairs(&[
+            ("xs", "[dynamic]"),
+            ("x", "dynamic"),
+            ("y", "dynamic"),
+            ("z", "fn('a) -> dynamic"),
+            ("flag", "'a"),
+            ("m", "dynamic"),
+            ("w", "int"),
+        ])
+    );
+}
+
+#[test]
+fn test_warnings() {
+    let tests = [
+        ("1 + \"a\"", vec![("type mismatch: int + string", (2, 3))]),
+        (
+            "true - false",
+            vec![("unknown operator: bool - bool", (5, 6))],
+        ),
+        ("-\"a\"", vec![("unknown operator: -string", (0, 1))]),
+        (
+            "let f = fn(x) { x * 2 }; f(\"a\")",
+            vec![("expected int, got string", (27, 30))],
+        ),
+        (
+            "let f = fn(x, y) { x }; f(1)",
+            vec![("wrong number of arguments: want=2, got=1", (25, 26))],
+        ),
+        ("let a = 1; a(2)", vec![("not a function: int", (11, 12))]),
+        (
+            "let a = [1]; a[\"x\"]",
+            vec![("index operator not supported: [int][string]", (14, 15))],
+        ),
+        (
+            "5[0]",
+            vec![("index operator not supported: int[int]", (1, 2))],
+        ),
+        ("for (x in 5) {}", vec![("not iterable: int", (10, 11))]),
+        (
+            "1..\"a\"",
+            vec![("range bounds must be int, got int..string", (1, 3))],
+        ),
+        (
+            "let [a] = 1;",
+            vec![("pattern [a] does not match int", (4, 5))],
+        ),
+        (
+            "let a = 1; a = \"s\";",
+            vec![("expected int, got string", (15, 18))],
+        ),
+        (
+            "let a = [1]; a[0] += \"s\";",
+            vec![("type mismatch: int + string", (18, 20))],
+        ),
+        (
+            "let a = 1; a.b",
+            vec![("member access not supported: int", (12, 13))],
+        ),
+    ];
+    for (input, expected) in tests {
+        let expected: Vec<(String, Span)> = expected
+            .into_iter()
+            .map(|(m, (s, e))| (m.to_string(), Span::new(s, e)))
+            .collect();
+        assert_eq!(warnings(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_patterns_and_loops() {
+    let input = "let [a, b, ..rest] = [1, 2, 3];
+let {\"k\": v} = {\"k\": \"s\"};
+for (i, s in [\"a\"]) { i + 1; s + \"!\" }
+for (k in {\"x\": 1}) { k + \"\" }
+let r = match (a) { 0 => \"zero\", n if n > 0 => \"positive\", [x] => \"list\", _ => \"negative\" };";
+    assert_eq!(
+        types(input),
+        pairs(&[
+            ("a", "int"),
+            ("b", "int"),
+            ("rest", "[int]"),
+            ("v", "string"),
+            ("i", "int"),
+            ("s", "string"),
+            ("k", "string"),
+            ("r", "string"),
+            ("n", "int"),
+            ("x", "dynamic"),
+        ])
+    );
+}
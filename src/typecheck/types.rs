@@ -0,0 +1,96 @@
+use std::fmt;
+
+// 推論した型。Dynamic はどんな型とも合う逃げ道で、型の付けられない式に使う
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    Null,
+    Range,
+    Array(Box<Type>),
+    Hash(Box<Type>, Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    Var(usize),
+    Dynamic,
+}
+
+impl Type {
+    pub fn array(element: Type) -> Type {
+        Type::Array(Box::new(element))
+    }
+
+    pub fn hash(key: Type, value: Type) -> Type {
+        Type::Hash(Box::new(key), Box::new(value))
+    }
+
+    pub fn function(parameters: Vec<Type>, result: Type) -> Type {
+        Type::Function(parameters, Box::new(result))
+    }
+
+    // 演算子のエラーで、両辺が同じ種類の値かを見分けるのに使う
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Null => "null",
+            Type::Range => "range",
+            Type::Array(_) => "array",
+            Type::Hash(_, _) => "hash",
+            Type::Function(_, _) => "function",
+            Type::Var(_) => "var",
+            Type::Dynamic => "dynamic",
+        }
+    }
+
+    // 型変数は現れた順に 'a, 'b, ... と名付ける
+    fn write(&self, f: &mut fmt::Formatter, names: &mut Vec<usize>) -> fmt::Result {
+        match self {
+            Type::Array(element) => {
+                write!(f, "[")?;
+                element.write(f, names)?;
+                write!(f, "]")
+            }
+            Type::Hash(key, value) => {
+                write!(f, "{{")?;
+                key.write(f, names)?;
+                write!(f, ": ")?;
+                value.write(f, names)?;
+                write!(f, "}}")
+            }
+            Type::Function(parameters, result) => {
+                write!(f, "fn(")?;
+                for (i, parameter) in parameters.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    parameter.write(f, names)?;
+                }
+                write!(f, ") -> ")?;
+                result.write(f, names)
+            }
+            Type::Var(id) => {
+                let index = match names.iter().position(|n| n == id) {
+                    Some(index) => index,
+                    None => {
+                        names.push(*id);
+                        names.len() - 1
+                    }
+                };
+                let letter = (b'a' + (index % 26) as u8) as char;
+                match index / 26 {
+                    0 => write!(f, "'{}", letter),
+                    n => write!(f, "'{}{}", letter, n),
+                }
+            }
+            other => write!(f, "{}", other.kind()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut vec![])
+    }
+}
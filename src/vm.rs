@@ -0,0 +1,541 @@
+mod frame;
+#[cfg(test)]
+mod test;
+
+use crate::builtins::{self, Output, BUILTINS};
+use crate::code::{read_u16, Opcode};
+use crate::compiler::Bytecode;
+use crate::limits::{Limits, Meter};
+use crate::object::{
+    Builtin, Closure, CompiledFunction, Hash, HashKey, HostFunction, Iteration, Module, Object,
+    Range, RuntimeError, StackFrame,
+};
+use frame::Frame;
+use std::mem::size_of;
+use std::rc::Rc;
+
+const STACK_SIZE: usize = 2048;
+pub const MAX_FRAMES: usize = 1024;
+
+pub struct Vm {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    // 次に積む位置。stack[sp - 1] が一番上
+    sp: usize,
+    globals: Vec<Object>,
+    frames: Vec<Frame>,
+    output: Output,
+    limits: Limits,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Vm {
+        Vm::new_with_globals(bytecode, vec![])
+    }
+
+    // REPL では前の行までのグローバル変数を引き継ぐ
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Object>) -> Vm {
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            positions: bytecode.positions,
+            ..CompiledFunction::default()
+        };
+        let main = Closure {
+            function: Rc::new(main),
+            free: vec![],
+        };
+        let mut frames = Vec::with_capacity(MAX_FRAMES);
+        frames.push(Frame::new(Rc::new(main), 0));
+
+        Vm {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            globals,
+            frames,
+            output: builtins::stdout(),
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn with_output(mut self, output: Output) -> Vm {
+        self.output = output;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Vm {
+        self.limits = limits;
+        self
+    }
+
+    pub fn into_globals(self) -> Vec<Object> {
+        self.globals
+    }
+
+    // 最後に取り除いた値が、式文の評価結果になる
+    pub fn last_popped_stack_elem(&self) -> Object {
+        self.stack[self.sp].clone()
+    }
+
+    // エラーには、それを起こした命令の場所と呼び出し中の関数を付ける
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        self.execute().map_err(|error| RuntimeError {
+            span: self.frames.last().and_then(Frame::span),
+            stack: self
+                .frames
+                .windows(2)
+                .rev()
+                .map(|pair| StackFrame {
+                    function: pair[1].closure.function.name.clone(),
+                    span: pair[0].span(),
+                    module: pair[0].closure.function.module.clone(),
+                })
+                .collect(),
+            module: self
+                .frames
+                .last()
+                .and_then(|frame| frame.closure.function.module.clone()),
+            ..error
+        })
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        let mut meter = Meter::start(self.limits);
+        loop {
+            // 命令をひとつ読み、オペランドを取り出してから実行する
+            let frame = self.frames.last_mut().expect("no frame");
+            let ins = &frame.closure.function.instructions;
+            let Some(&byte) = ins.get(frame.ip) else {
+                return Ok(());
+            };
+            let op = Opcode::from_byte(byte).ok_or_else(|| format!("unknown opcode: {}", byte))?;
+            let (operand, operand2) = match op.definition().operand_widths {
+                [2] => (read_u16(&ins[frame.ip + 1..]) as usize, 0),
+                [1] => (ins[frame.ip + 1] as usize, 0),
+                [2, 1] => (
+                    read_u16(&ins[frame.ip + 1..]) as usize,
+                    ins[frame.ip + 3] as usize,
+                ),
+                [1, 1] => (ins[frame.ip + 1] as usize, ins[frame.ip + 2] as usize),
+                _ => (0, 0),
+            };
+            frame.ip += 1 + op.definition().operand_widths.iter().sum::<usize>();
+            let base_pointer = frame.base_pointer;
+            meter.step()?;
+
+            match op {
+                Opcode::Constant => self.push(self.constants[operand].clone())?,
+                // 最後に取り除いた値は結果として読めるよう、スタックに残しておく
+                Opcode::Pop => self.sp -= 1,
+                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                    self.execute_binary_operation(op, &meter)?
+                }
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::Equal | Opcode::NotEqual | Opcode::GreaterThan | Opcode::LessThan => {
+                    self.execute_comparison(op)?
+                }
+                Opcode::Bang => {
+                    let operand = self.pop();
+                    self.push(Object::Boolean(!operand.is_truthy()))?;
+                }
+                Opcode::Minus => match self.pop() {
+                    Object::Integer(value) => self.push(Object::Integer(value.wrapping_neg()))?,
+                    operand => {
+                        return Err(format!("unknown operator: -{}", operand.type_name()).into())
+                    }
+                },
+                Opcode::Jump => self.current_frame().ip = operand,
+                Opcode::JumpNotTruthy => {
+                    if !self.pop().is_truthy() {
+                        self.current_frame().ip = operand;
+                    }
+                }
+                Opcode::SetGlobal => {
+                    if operand >= self.globals.len() {
+                        self.globals.resize(operand + 1, Object::Null);
+                    }
+                    self.globals[operand] = self.pop();
+                }
+                Opcode::GetGlobal => {
+                    let value = self.globals.get(operand).cloned().unwrap_or(Object::Null);
+                    self.push(value)?;
+                }
+                Opcode::SetLocal => self.stack[base_pointer + operand] = self.pop(),
+                Opcode::GetLocal => self.push(self.stack[base_pointer + operand].clone())?,
+                Opcode::Call => self.call_function(operand, &meter)?,
+                Opcode::ReturnValue => {
+                    let value = self.pop();
+                    if !self.return_from_frame(value)? {
+                        return Ok(());
+                    }
+                }
+                Opcode::Return => {
+                    if !self.return_from_frame(Object::Null)? {
+                        return Ok(());
+                    }
+                }
+                Opcode::Closure => self.push_closure(operand, operand2)?,
+                Opcode::GetFree => {
+                    let value = self.current_frame().closure.free.get(operand).cloned();
+                    let value =
+                        value.ok_or_else(|| format!("free variable out of range: {}", operand))?;
+                    self.push(value)?;
+                }
+                Opcode::CurrentClosure => {
+                    let closure = self.current_frame().closure.clone();
+                    self.push(Object::Closure(closure))?;
+                }
+                Opcode::Array => {
+                    meter.check_allocation(operand * size_of::<Object>())?;
+                    let elements = self.stack[self.sp - operand..self.sp].to_vec();
+                    self.truncate(self.sp - operand);
+                    self.push(Object::Array(Rc::new(elements)))?;
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.execute_index_expression(left, index)?;
+                }
+                Opcode::GetBuiltin => {
+                    let builtin = BUILTINS
+                        .get(operand)
+                        .ok_or_else(|| format!("unknown builtin: {}", operand))?;
+                    self.push(Object::Builtin(*builtin))?;
+                }
+                Opcode::Hash => {
+                    meter.check_allocation(operand * size_of::<Object>())?;
+                    let hash = self.build_hash(self.sp - operand, self.sp)?;
+                    self.truncate(self.sp - operand);
+                    self.push(Object::Hash(Rc::new(hash)))?;
+                }
+                Opcode::Range => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let range = Range::from_bounds(&start, &end, operand == 1)?;
+                    self.push(Object::Range(range))?;
+                }
+                Opcode::IterInit => {
+                    let iteration = Iteration::new(self.pop())?;
+                    self.push(Object::Iteration(Box::new(iteration)))?;
+                }
+                Opcode::IterNext => self.iterate(operand)?,
+                Opcode::Dup => {
+                    for i in self.sp - operand..self.sp {
+                        self.push(self.stack[i].clone())?;
+                    }
+                }
+                Opcode::IndexPath => {
+                    let mut value = self.pop();
+                    for i in self.sp - operand..self.sp {
+                        self.execute_index_expression(value, self.stack[i].clone())?;
+                        value = self.pop();
+                    }
+                    self.push(value)?;
+                }
+                Opcode::SetGlobalIndex => {
+                    let value = self.pop();
+                    if operand >= self.globals.len() {
+                        self.globals.resize(operand + 1, Object::Null);
+                    }
+                    let keys = &self.stack[self.sp - operand2..self.sp];
+                    let container = self.globals[operand].set_index(keys, value.clone())?;
+                    meter.check_object(container)?;
+                    self.truncate(self.sp - operand2);
+                    self.push(value)?;
+                }
+                Opcode::SetLocalIndex => {
+                    let value = self.pop();
+                    let (locals, keys) = self.stack.split_at_mut(self.sp - operand2);
+                    let container = locals[base_pointer + operand]
+                        .set_index(&keys[..operand2], value.clone())?;
+                    meter.check_object(container)?;
+                    self.truncate(self.sp - operand2);
+                    self.push(value)?;
+                }
+                Opcode::MatchArray => {
+                    let matched = self.pop().matches_array(operand, operand2 == 1);
+                    self.push(Object::Boolean(matched))?;
+                }
+                Opcode::MatchHash => {
+                    let matched = self.stack[self.sp - operand - 1]
+                        .has_keys(&self.stack[self.sp - operand..self.sp]);
+                    self.truncate(self.sp - operand - 1);
+                    self.push(Object::Boolean(matched))?;
+                }
+                Opcode::Slice => {
+                    let rest = self.pop().slice_from(operand);
+                    self.push(rest)?;
+                }
+                Opcode::NoMatch => {
+                    return Err(format!("no match arm for {}", self.pop().type_name()).into());
+                }
+                Opcode::Mismatch => {
+                    let value = self.pop();
+                    let pattern = self.pop();
+                    let message =
+                        format!("pattern {} does not match {}", pattern, value.describe());
+                    return Err(message.into());
+                }
+                Opcode::Module => {
+                    let module = self.build_module(self.sp - operand * 2 - 1, self.sp);
+                    self.truncate(self.sp - operand * 2 - 1);
+                    self.push(Object::Module(Rc::new(module)))?;
+                }
+                Opcode::Member => {
+                    let name = self.pop();
+                    let value = self.pop().member(&name.to_string())?;
+                    self.push(value)?;
+                }
+            }
+        }
+    }
+
+    fn current_frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no frame")
+    }
+
+    fn call_function(&mut self, num_args: usize, meter: &Meter) -> Result<(), RuntimeError> {
+        let closure = match &self.stack[self.sp - 1 - num_args] {
+            Object::Closure(closure) => closure.clone(),
+            Object::Builtin(builtin) => return self.call_builtin(*builtin, num_args, meter),
+            Object::HostFunction(host) => {
+                return self.call_host_function(host.clone(), num_args, meter)
+            }
+            other => return Err(format!("not a function: {}", other.type_name()).into()),
+        };
+
+        let function = &closure.function;
+        if num_args != function.num_parameters {
+            return Err(format!(
+                "wrong number of arguments: want={}, got={}",
+                function.num_parameters, num_args
+            )
+            .into());
+        }
+        // 一番下のフレームはトップレベルなので数えない
+        meter.check_call_depth(self.frames.len())?;
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("stack overflow".into());
+        }
+
+        // 引数はそのまま局所変数の先頭になる
+        let base_pointer = self.sp - num_args;
+        let sp = base_pointer + function.num_locals;
+        if sp >= STACK_SIZE {
+            return Err("stack overflow".into());
+        }
+        for slot in &mut self.stack[self.sp..sp] {
+            *slot = Object::Null;
+        }
+        self.sp = sp;
+        self.frames.push(Frame::new(closure, base_pointer));
+        Ok(())
+    }
+
+    // 組み込み関数はフレームを作らずにその場で呼ぶ
+    fn call_builtin(
+        &mut self,
+        builtin: Builtin,
+        num_args: usize,
+        meter: &Meter,
+    ) -> Result<(), RuntimeError> {
+        let args = &self.stack[self.sp - num_args..self.sp];
+        let result = (builtin.function)(args, &mut *self.output.borrow_mut())?;
+        meter.check_object(&result)?;
+        self.truncate(self.sp - num_args - 1);
+        Ok(self.push(result)?)
+    }
+
+    fn call_host_function(
+        &mut self,
+        host: HostFunction,
+        num_args: usize,
+        meter: &Meter,
+    ) -> Result<(), RuntimeError> {
+        let args = &self.stack[self.sp - num_args..self.sp];
+        let result = (host.function)(args).map_err(|e| e.message)?;
+        meter.check_object(&result)?;
+        self.truncate(self.sp - num_args - 1);
+        Ok(self.push(result)?)
+    }
+
+    // スタックに積まれた自由変数を取り込んでクロージャを作る
+    fn push_closure(&mut self, index: usize, num_free: usize) -> Result<(), String> {
+        let function = match &self.constants[index] {
+            Object::CompiledFunction(function) => function.clone(),
+            other => return Err(format!("not a function: {}", other.type_name())),
+        };
+
+        let free = self.stack[self.sp - num_free..self.sp].to_vec();
+        self.truncate(self.sp - num_free);
+        self.push(Object::Closure(Rc::new(Closure { function, free })))
+    }
+
+    // トップレベルでの return なら false を返して実行を終える
+    fn return_from_frame(&mut self, value: Object) -> Result<bool, String> {
+        if self.frames.len() == 1 {
+            self.push(value)?;
+            self.sp -= 1;
+            return Ok(false);
+        }
+
+        let frame = self.frames.pop().expect("no frame");
+        // 呼び出した関数自身も取り除く
+        self.truncate(frame.base_pointer - 1);
+        self.push(value)?;
+        Ok(true)
+    }
+
+    fn execute_binary_operation(&mut self, op: Opcode, meter: &Meter) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+
+        let (left, right) = match (left, right) {
+            (Object::Integer(l), Object::Integer(r)) => (l, r),
+            (Object::String(l), Object::String(r)) if op == Opcode::Add => {
+                meter.check_allocation(l.len() + r.len())?;
+                return Ok(self.push(Object::String(format!("{}{}", l, r).into()))?);
+            }
+            (left, right) => {
+                let operator = match op {
+                    Opcode::Add => "+",
+                    Opcode::Sub => "-",
+                    Opcode::Mul => "*",
+                    _ => "/",
+                };
+                return Err(mismatch_error(&left, operator, &right).into());
+            }
+        };
+
+        let result = match op {
+            Opcode::Add => left.wrapping_add(right),
+            Opcode::Sub => left.wrapping_sub(right),
+            Opcode::Mul => left.wrapping_mul(right),
+            _ if right == 0 => return Err("division by zero".into()),
+            _ => left.wrapping_div(right),
+        };
+        Ok(self.push(Object::Integer(result))?)
+    }
+
+    fn execute_comparison(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+
+        let result = match (op, &left, &right) {
+            (Opcode::GreaterThan, Object::Integer(l), Object::Integer(r)) => l > r,
+            (Opcode::LessThan, Object::Integer(l), Object::Integer(r)) => l < r,
+            (Opcode::Equal, _, _) => left == right,
+            (Opcode::NotEqual, _, _) => left != right,
+            (Opcode::GreaterThan, _, _) => return Err(mismatch_error(&left, ">", &right)),
+            _ => return Err(mismatch_error(&left, "<", &right)),
+        };
+        self.push(Object::Boolean(result))
+    }
+
+    // 一番上の反復の状態を進める。尽きていれば変数の数だけ null を積む
+    fn iterate(&mut self, count: usize) -> Result<(), String> {
+        let Object::Iteration(iteration) = &mut self.stack[self.sp - 1] else {
+            return Err("not an iteration".to_string());
+        };
+        let next = match count {
+            1 => iteration.next_single().map(|value| (None, value)),
+            2 => iteration.next_pair().map(|(key, value)| (Some(key), value)),
+            _ => return Err(format!("cannot bind {} loop variables", count)),
+        };
+
+        let has_next = next.is_some();
+        let (key, value) = next.unwrap_or(((count == 2).then_some(Object::Null), Object::Null));
+        if let Some(key) = key {
+            self.push(key)?;
+        }
+        self.push(value)?;
+        self.push(Object::Boolean(has_next))
+    }
+
+    // スタックにはキーと値が交互に積まれている
+    fn build_hash(&self, start: usize, end: usize) -> Result<Hash, String> {
+        let mut hash = Hash::new();
+        for pair in self.stack[start..end].chunks(2) {
+            let key = HashKey::from_object(&pair[0])?;
+            hash.insert(key, pair[1].clone());
+        }
+        Ok(hash)
+    }
+
+    // モジュールの名前に続いて、export した名前と値が交互に積まれている
+    fn build_module(&self, start: usize, end: usize) -> Module {
+        let exports = self.stack[start + 1..end]
+            .chunks(2)
+            .map(|pair| (pair[0].to_string(), pair[1].clone()))
+            .collect();
+        Module {
+            name: self.stack[start].to_string().into(),
+            exports,
+        }
+    }
+
+    // 範囲外の添字は null になる
+    fn execute_index_expression(&mut self, left: Object, index: Object) -> Result<(), String> {
+        match (&left, &index) {
+            (Object::Array(elements), Object::Integer(i)) => {
+                let value = usize::try_from(*i)
+                    .ok()
+                    .and_then(|i| elements.get(i).cloned())
+                    .unwrap_or(Object::Null);
+                self.push(value)
+            }
+            (Object::Hash(hash), _) => {
+                let key = HashKey::from_object(&index)?;
+                self.push(hash.get(&key).cloned().unwrap_or(Object::Null))
+            }
+            _ => Err(format!(
+                "index operator not supported: {}[{}]",
+                left.type_name(),
+                index.type_name()
+            )),
+        }
+    }
+
+    fn push(&mut self, obj: Object) -> Result<(), String> {
+        if self.sp >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+        self.stack[self.sp] = obj;
+        self.sp += 1;
+        Ok(())
+    }
+
+    // 取り除いた値はスタックに残さない。残っていると、入れ物をその場で書き換えるときに
+    // ほかからの参照に数えられて複製されてしまう
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        std::mem::replace(&mut self.stack[self.sp], Object::Null)
+    }
+
+    fn truncate(&mut self, sp: usize) {
+        for slot in &mut self.stack[sp..self.sp] {
+            *slot = Object::Null;
+        }
+        self.sp = sp;
+    }
+}
+
+fn mismatch_error(left: &Object, operator: &str, right: &Object) -> String {
+    if left.type_name() != right.type_name() {
+        format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )
+    } else {
+        format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )
+    }
+}
@@ -0,0 +1,28 @@
+use crate::object::Closure;
+use crate::token::Span;
+use std::rc::Rc;
+
+// 関数呼び出しひとつ分の実行状態
+pub struct Frame {
+    pub closure: Rc<Closure>,
+    pub ip: usize,
+    pub base_pointer: usize,
+}
+
+impl Frame {
+    pub fn new(closure: Rc<Closure>, base_pointer: usize) -> Frame {
+        Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+        }
+    }
+
+    // 最後に読んだ命令のソース上の範囲。デバッグ情報がなければ分からない
+    pub fn span(&self) -> Option<Span> {
+        let offset = self.ip.checked_sub(1)?;
+        let positions = &self.closure.function.positions;
+        let i = positions.partition_point(|(position, _)| *position <= offset);
+        positions.get(i.checked_sub(1)?).map(|(_, span)| *span)
+    }
+}
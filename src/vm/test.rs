@@ -0,0 +1,393 @@
+use super::*;
+use crate::compiler::Compiler;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn run_vm(input: &str) -> Result<Object, String> {
+    let mut l = Lexer::new(input);
+    let mut p = Parser::new(&mut l);
+    let program = p.parse_program();
+    assert!(p.errors().is_empty(), "parser errors: {:?}", p.errors());
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).map_err(|e| e.message)?;
+    let mut vm = Vm::new(compiler.bytecode());
+    vm.run().map_err(|e| e.message)?;
+    Ok(vm.last_popped_stack_elem())
+}
+
+fn run_vm_tests(tests: Vec<(&str, Object)>) {
+    for (input, expected) in tests {
+        assert_eq!(run_vm(input), Ok(expected), "input: {}", input);
+    }
+}
+
+#[test]
+fn test_integer_arithmetic() {
+    run_vm_tests(vec![
+        ("1", Object::Integer(1)),
+        ("1 + 2", Object::Integer(3)),
+        ("1 - 2", Object::Integer(-1)),
+        ("4 / 2", Object::Integer(2)),
+        ("50 / 2 * 2 + 10 - 5", Object::Integer(55)),
+        ("5 * (2 + 10)", Object::Integer(60)),
+        ("-50 + 100 + -50", Object::Integer(0)),
+        ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ("-9223372036854775807 - 2", Object::Integer(i64::MAX)),
+    ]);
+}
+
+#[test]
+fn test_boolean_expressions() {
+    run_vm_tests(vec![
+        ("true", Object::Boolean(true)),
+        ("1 < 2", Object::Boolean(true)),
+        ("1 > 2", Object::Boolean(false)),
+        ("1 == 1", Object::Boolean(true)),
+        ("1 != 2", Object::Boolean(true)),
+        ("true != false", Object::Boolean(true)),
+        ("(1 < 2) == true", Object::Boolean(true)),
+        ("1 == true", Object::Boolean(false)),
+        ("!5", Object::Boolean(false)),
+        ("!!5", Object::Boolean(true)),
+        ("!(if (false) { 5; })", Object::Boolean(true)),
+    ]);
+}
+
+#[test]
+fn test_conditionals() {
+    run_vm_tests(vec![
+        ("if (true) { 10 }", Object::Integer(10)),
+        ("if (true) { 10 } else { 20 }", Object::Integer(10)),
+        ("if (false) { 10 } else { 20 } ", Object::Integer(20)),
+        ("if (1 < 2) { 10 }", Object::Integer(10)),
+        ("if (1 > 2) { 10 }", Object::Null),
+        ("if (true) { let a = 1; }", Object::Null),
+        (
+            "if ((if (false) { 10 })) { 10 } else { 20 }",
+            Object::Integer(20),
+        ),
+    ]);
+}
+
+#[test]
+fn test_global_let_statements() {
+    run_vm_tests(vec![
+        ("let one = 1; one", Object::Integer(1)),
+        (
+            "let one = 1; let two = one + one; one + two",
+            Object::Integer(3),
+        ),
+        ("let one = 1; let one = 2; one", Object::Integer(2)),
+    ]);
+}
+
+#[test]
+fn test_calling_functions() {
+    run_vm_tests(vec![
+        (
+            "let fivePlusTen = fn() { 5 + 10; }; fivePlusTen();",
+            Object::Integer(15),
+        ),
+        (
+            "let a = fn() { 1 }; let b = fn() { a() + 1 }; b() + 1",
+            Object::Integer(3),
+        ),
+        (
+            "let early = fn() { return 99; 100; }; early();",
+            Object::Integer(99),
+        ),
+        ("let noReturn = fn() { }; noReturn();", Object::Null),
+        (
+            "let sum = fn(a, b) { let c = a + b; c; }; sum(1, 2) + sum(3, 4);",
+            Object::Integer(10),
+        ),
+        (
+            "let g = 50; let f = fn(a) { let b = a * 2; g - b }; f(1) + f(2)",
+            Object::Integer(94),
+        ),
+        (
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15);",
+            Object::Integer(610),
+        ),
+        ("return 5; 6", Object::Integer(5)),
+    ]);
+}
+
+#[test]
+fn test_closures() {
+    run_vm_tests(vec![
+        (
+            "let adder = fn(x) { fn(y) { x + y } }; adder(2)(3)",
+            Object::Integer(5),
+        ),
+        (
+            "let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)",
+            Object::Integer(6),
+        ),
+        (
+            "let g = 10; let f = fn(a) { let b = a * 2; fn() { fn() { g + a + b } } }; f(1)()()",
+            Object::Integer(13),
+        ),
+        (
+            "let wrapper = fn() { let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1) }; countDown(5) }; wrapper()",
+            Object::Integer(0),
+        ),
+        (
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; let g = fib; g(10)",
+            Object::Integer(55),
+        ),
+    ]);
+}
+
+#[test]
+fn test_strings_and_arrays() {
+    run_vm_tests(vec![
+        (r#""monkey""#, Object::String("monkey".into())),
+        (
+            r#""mon" + "key" + "banana""#,
+            Object::String("monkeybanana".into()),
+        ),
+        ("[]", Object::Array(Rc::new(vec![]))),
+        (
+            "[1 + 2, 3 * 4]",
+            Object::Array(Rc::new(vec![Object::Integer(3), Object::Integer(12)])),
+        ),
+        ("[1, 2, 3][1]", Object::Integer(2)),
+        ("[[1, 1, 1]][0][0]", Object::Integer(1)),
+        ("[1, 2, 3][99]", Object::Null),
+        ("[1][-1]", Object::Null),
+        ("len(push([1], 2))", Object::Integer(2)),
+        ("let f = fn() { first }; f()([5])", Object::Integer(5)),
+    ]);
+}
+
+#[test]
+fn test_hash_literals() {
+    let hash = |pairs: Vec<(HashKey, i64)>| {
+        Object::Hash(Rc::new(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k, Object::Integer(v)))
+                .collect(),
+        ))
+    };
+
+    run_vm_tests(vec![
+        ("{}", hash(vec![])),
+        (
+            "{1: 2, 2: 3}",
+            hash(vec![(HashKey::Integer(1), 2), (HashKey::Integer(2), 3)]),
+        ),
+        (
+            r#"{"a" + "b": 2 * 2, true: 6 - 1}"#,
+            hash(vec![
+                (HashKey::String("ab".into()), 4),
+                (HashKey::Boolean(true), 5),
+            ]),
+        ),
+        ("{1: 1, 2: 2}[1]", Object::Integer(1)),
+        ("{1: 1, 2: 2}[2]", Object::Integer(2)),
+        ("{1: 1}[0]", Object::Null),
+        ("{}[0]", Object::Null),
+    ]);
+}
+
+#[test]
+fn test_loops() {
+    run_vm_tests(vec![
+        (
+            "let i = 0; let s = 0; while (i < 5) { let i = i + 1; let s = s + i; } s",
+            Object::Integer(15),
+        ),
+        (
+            "let i = 0; let s = 0; while (i < 5) { let i = i + 1; if (i == 2) { continue; } let s = s + i; } s",
+            Object::Integer(13),
+        ),
+        (
+            "let f = fn() { let i = 0; while (true) { let i = i + 1; if (i == 4) { return i; } } }; f()",
+            Object::Integer(4),
+        ),
+        (
+            "let i = 0; while (i < 3) { let i = i + 1; [1, 1 + if (i == 2) { break; } else { 0 }]; } i",
+            Object::Integer(2),
+        ),
+    ]);
+}
+
+#[test]
+fn test_for_loops() {
+    run_vm_tests(vec![
+        (
+            "let s = 0; for (i in 0..100000) { let s = s + i; } s",
+            Object::Integer(4999950000),
+        ),
+        (
+            "let s = 0; for (i in 1..=4) { let s = s * 10 + i; } s",
+            Object::Integer(1234),
+        ),
+        (
+            "let s = 0; for (i, x in [5, 6, 7]) { let s = s + i * x; } s",
+            Object::Integer(20),
+        ),
+        (
+            r#"let s = ""; for (c in "abc") { let s = c + s; } s"#,
+            Object::String("cba".into()),
+        ),
+        (
+            r#"let s = ""; for (k, v in {"a": 1, "b": 2}) { let s = s + k + str(v); } s"#,
+            Object::String("a1b2".into()),
+        ),
+        (
+            "let f = fn() { let n = 0; for (x in 0..10) { if (x == 3) { continue; } if (x > 5) { return n; } let n = n + x; } }; f()",
+            Object::Integer(12),
+        ),
+        (
+            "let n = 0; for (a in 0..3) { for (b in 0..3) { if (b > a) { break; } let n = n + 1; } } n",
+            Object::Integer(6),
+        ),
+        (
+            "let n = 0; for (x in 3..0) { let n = n + 1; } n",
+            Object::Integer(0),
+        ),
+        ("(0..3) == (0..3)", Object::Boolean(true)),
+    ]);
+}
+
+#[test]
+fn test_assign_expressions() {
+    run_vm_tests(vec![
+        ("let x = 1; x = 2; x", Object::Integer(2)),
+        ("let a = 0; let b = 0; a = b = 3; a + b", Object::Integer(6)),
+        (
+            "let x = 10; x += 5; x -= 3; x *= 2; x /= 4; x",
+            Object::Integer(6),
+        ),
+        (
+            "let a = [[1], [2]]; a[1][0] += 40; a[0][0] + a[1][0]",
+            Object::Integer(43),
+        ),
+        (
+            r#"let h = {"k": 1}; h["k"] += 1; h["n"] = 3; h["k"] + h["n"]"#,
+            Object::Integer(5),
+        ),
+        ("let a = [1]; let b = a; a[0] = 2; b[0]", Object::Integer(1)),
+        (
+            "let f = fn(n) { let s = 0; while (n > 0) { s += n; n -= 1; } s }; f(4)",
+            Object::Integer(10),
+        ),
+        (
+            "let g = 1; let f = fn() { g = 5 }; f(); g",
+            Object::Integer(5),
+        ),
+        ("let a = [0]; a[0] = 7", Object::Integer(7)),
+    ]);
+}
+
+#[test]
+fn test_match_expressions() {
+    run_vm_tests(vec![
+        ("match (2) { 1 => 10, 2 => 20, _ => 30 }", Object::Integer(20)),
+        (r#"match ("1") { 1 => 0, _ => 1 }"#, Object::Integer(1)),
+        ("match (-3) { -3 => true, _ => false }", Object::Boolean(true)),
+        (
+            "match ([1, 2, 3]) { [x, ..rest] => len(rest) * 10 + x }",
+            Object::Integer(21),
+        ),
+        ("match ([]) { [_, ..] => 1, [..] => 2 }", Object::Integer(2)),
+        (
+            r#"match ({"k": 1, "v": [4]}) { {"k": 2} => 0, {"v": [n], "k": k} => n + k }"#,
+            Object::Integer(5),
+        ),
+        (
+            "let f = fn(x) { match (x) { [h, ..t] => h + f(t), [] => 0 } }; f([1, 2, 3])",
+            Object::Integer(6),
+        ),
+        (
+            "let g = fn(n) { match (n) { n if n > 10 => 1, n if n > 3 => 2, _ => 3 } }; [g(11), g(5), g(0)]",
+            Object::Array(Rc::new(vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+            ])),
+        ),
+        // 条件の中の match が外側の値を上書きしない
+        (
+            "match ([1]) { [a] if match (2) { 2 => false } => 0, [b] => b }",
+            Object::Integer(1),
+        ),
+        (
+            "let s = 0; for (x in [[1], [2, 3], []]) { s += match (x) { [a, ..r] => a + len(r), _ => 10 } } s",
+            Object::Integer(14),
+        ),
+    ]);
+}
+
+#[test]
+fn test_destructuring() {
+    run_vm_tests(vec![
+        ("let [a, b] = [1, 2]; a * 10 + b", Object::Integer(12)),
+        (
+            "let [head, ..tail] = [1, 2, 3]; head + len(tail)",
+            Object::Integer(3),
+        ),
+        (
+            r#"let {"x": x, "y": y} = {"y": 2, "x": 1}; x - y"#,
+            Object::Integer(-1),
+        ),
+        (
+            "let f = fn([a, b], c) { let [d] = [a + b]; d * c }; f([1, 2], 3)",
+            Object::Integer(9),
+        ),
+        // 引数の形をほどいた名前も取り込める
+        (
+            r#"let f = fn({"n": n}, _) { fn() { n } }; f({"n": 4}, 0)()"#,
+            Object::Integer(4),
+        ),
+    ]);
+}
+
+#[test]
+fn test_runtime_errors() {
+    let tests = vec![
+        ("5 + true", "type mismatch: INTEGER + BOOLEAN"),
+        ("true + false", "unknown operator: BOOLEAN + BOOLEAN"),
+        ("true > false", "unknown operator: BOOLEAN > BOOLEAN"),
+        ("1 < true", "type mismatch: INTEGER < BOOLEAN"),
+        ("-true", "unknown operator: -BOOLEAN"),
+        ("1 / 0", "division by zero"),
+        ("5(1)", "not a function: INTEGER"),
+        ("fn(a) { a }()", "wrong number of arguments: want=1, got=0"),
+        ("fn() { 1 }(1)", "wrong number of arguments: want=0, got=1"),
+        ("let f = fn() { f() }; f()", "stack overflow"),
+        (r#""a" - "b""#, "unknown operator: STRING - STRING"),
+        ("[1][true]", "index operator not supported: ARRAY[BOOLEAN]"),
+        ("len(1)", "argument to `len` not supported, got INTEGER"),
+        ("{fn() {}: 1}", "unusable as hash key: CLOSURE"),
+        ("{}[[]]", "unusable as hash key: ARRAY"),
+        ("for (x in 1) {}", "not iterable: INTEGER"),
+        (
+            r#"0.."a""#,
+            "range bounds must be INTEGER, got INTEGER..STRING",
+        ),
+        ("let a = [1]; a[1] = 2", "index out of range: 1 (len 1)"),
+        (
+            "let x = 1; x[0] = 2",
+            "index assignment not supported: INTEGER[INTEGER]",
+        ),
+        ("match (3) { 1 => 1, 2 => 2 }", "no match arm for INTEGER"),
+        ("match ([1]) { [] => 0 }", "no match arm for ARRAY"),
+        (
+            "let [a, b] = [1];",
+            "pattern [a, b] does not match ARRAY of length 1",
+        ),
+        (
+            r#"fn({"k": k}) { k }(1)"#,
+            r#"pattern {"k": k} does not match INTEGER"#,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(run_vm(input), Err(expected.to_string()), "input: {}", input);
+    }
+}